@@ -1,7 +1,10 @@
 use anyhow::{anyhow, Result};
 use normeditor::png::PngDecoder;
 #[cfg(feature = "time")]
-use normeditor::util::event_log::{log_event, Event};
+use normeditor::{
+    event_log::{log_event, Event},
+    qoi::{QoiDecoder, QoiEncoder},
+};
 #[cfg(feature = "time")]
 use std::time::Instant;
 
@@ -10,13 +13,6 @@ fn main() -> Result<()> {
     let image_path = args
         .next()
         .ok_or_else(|| anyhow!("Failed to read image path"))?;
-    //
-    // let image_kind = args.next().and_then(|image_kind| {
-    //     match image_kind.as_bytes() {
-    //         b"jpeg" => {}
-    //         b"png" => {}
-    //     }
-    // });
 
     let content = std::fs::read(image_path)?;
 
@@ -24,10 +20,29 @@ fn main() -> Result<()> {
 
     #[cfg(feature = "time")]
     let a = Instant::now();
-    let _ = decoder.decode()?;
+    let _png = decoder.decode()?;
 
     #[cfg(feature = "time")]
-    log_event("", Event::TotalElapsed, Some(a.elapsed()));
+    log_event("png", Event::TotalElapsed, Some(a.elapsed()));
+
+    // Decode the same pixels as QOI to compare against. Only timed runs pay for it, so that
+    // profiles cover PNG decoding alone.
+    #[cfg(feature = "time")]
+    {
+        let mut qoi = Vec::new();
+        QoiEncoder::new(&mut qoi).encode(&_png)?;
+
+        log_event(
+            &format!("png: {} bytes, qoi: {} bytes", content.len(), qoi.len()),
+            Event::Info,
+            None,
+        );
+
+        let b = Instant::now();
+        let _ = QoiDecoder::new(&qoi).decode()?;
+
+        log_event("qoi", Event::TotalElapsed, Some(b.elapsed()));
+    }
 
     Ok(())
 }
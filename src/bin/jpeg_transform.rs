@@ -0,0 +1,84 @@
+use anyhow::{anyhow, bail, ensure, Result};
+use normeditor::{exif::grammar::Orientation, jpeg::JpegTransformer};
+use std::fs;
+
+const USAGE: &str = "Usage: norm_jpeg_transform [-rotate 90|180|270] [-flip horizontal|vertical] \
+                     [-transpose] [-transverse] [-crop WxH+X+Y] <input> <output>";
+
+/// Parses a crop region in the `WxH+X+Y` geometry syntax.
+fn parse_crop(geometry: &str) -> Result<(u32, u32, u32, u32)> {
+    let parse = || -> Option<(u32, u32, u32, u32)> {
+        let (size, offset) = geometry.split_once('+')?;
+        let (width, height) = size.split_once('x')?;
+        let (x, y) = offset.split_once('+')?;
+
+        Some((
+            x.parse().ok()?,
+            y.parse().ok()?,
+            width.parse().ok()?,
+            height.parse().ok()?,
+        ))
+    };
+
+    parse().ok_or_else(|| anyhow!("Invalid crop region: {geometry}"))
+}
+
+fn main() -> Result<()> {
+    let mut args = std::env::args().skip(1);
+
+    let mut orientation = None;
+    let mut crop = None;
+    let mut paths = Vec::new();
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| anyhow!("{arg} expects a value"));
+
+        let transform = match arg.as_str() {
+            "-rotate" => match value()?.as_str() {
+                "90" => Orientation::Rotate90,
+                "180" => Orientation::Rotate180,
+                "270" => Orientation::Rotate270,
+                foreign => bail!("Unsupported rotation: {foreign}"),
+            },
+            "-flip" => match value()?.as_str() {
+                "horizontal" => Orientation::FlipHorizontal,
+                "vertical" => Orientation::FlipVertical,
+                foreign => bail!("Unsupported flip: {foreign}"),
+            },
+            "-transpose" => Orientation::Transpose,
+            "-transverse" => Orientation::Transverse,
+            "-crop" => {
+                crop = Some(parse_crop(&value()?)?);
+                continue;
+            }
+            _ => {
+                paths.push(arg);
+                continue;
+            }
+        };
+
+        ensure!(
+            orientation.replace(transform).is_none(),
+            "Only one of -rotate, -flip, -transpose and -transverse may be given."
+        );
+    }
+
+    let [input, output] = paths.as_slice() else {
+        bail!(USAGE);
+    };
+
+    let data = fs::read(input)?;
+
+    // Transform first, so that a failure leaves any existing output untouched.
+    let mut transformed = Vec::new();
+    let mut transformer = JpegTransformer::new(&mut transformed)
+        .orientation(orientation.unwrap_or(Orientation::Normal));
+    if let Some((x, y, width, height)) = crop {
+        transformer = transformer.crop(x, y, width, height);
+    }
+
+    transformer.transform(&data)?;
+    fs::write(output, transformed)?;
+
+    Ok(())
+}
@@ -0,0 +1,599 @@
+use crate::{
+    bmp::grammar::{Bitfields, Bmp, Compression, InfoHeader, CORE_HEADER_SIZE, INFO_HEADER_SIZE},
+    image::{DynamicImageBuffer, ImageBuffer, Rgba, Rgba8},
+    impl_read_le_for_datatype, impl_read_slice,
+};
+use anyhow::{anyhow, ensure, Result};
+
+/// RLE pixels can skip over any part of the image, so their length doesn't bound its size.
+const MAX_RLE_PIXELS: u64 = 400_000_000;
+
+/// A channel of a bitfields pixel, scaled to 8 bits.
+#[derive(Debug, Clone, Copy)]
+struct Channel {
+    shift: u32,
+    max: u32,
+}
+
+impl Channel {
+    /// Channels wider than 8 bits keep their most significant 8.
+    fn new(mask: u32) -> Result<Option<Self>> {
+        if mask == 0 {
+            return Ok(None);
+        }
+
+        let mut shift = mask.trailing_zeros();
+        let mut len = (mask >> shift).trailing_ones();
+        ensure!(
+            (mask >> shift) >> len == 0,
+            "BMP channel mask is not contiguous: {mask:#010X}"
+        );
+
+        if len > 8 {
+            shift += len - 8;
+            len = 8;
+        }
+
+        Ok(Some(Self {
+            shift,
+            max: (1 << len) - 1,
+        }))
+    }
+
+    const fn read(&self, value: u32) -> u8 {
+        let value = (value >> self.shift) & self.max;
+        ((value * 255 + self.max / 2) / self.max) as u8
+    }
+}
+
+#[derive(Debug)]
+pub struct BmpDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> BmpDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Bmp> {
+        ensure!(self.read_slice(2)? == b"BM", "Expected a BMP signature.");
+        let _file_size = self.read_u32()?;
+        let _reserved = self.read_u32()?;
+        let pixel_offset = self.read_u32()? as usize;
+
+        let info_header = self.parse_info_header()?;
+        let palette = self.parse_palette(&info_header)?;
+
+        let pixels = self
+            .data
+            .get(pixel_offset..)
+            .ok_or_else(|| anyhow!("BMP pixel data starts past the end of the file."))?;
+
+        // The dimensions come straight from the header, so check them before allocating.
+        match info_header.compression {
+            Compression::Rle8 | Compression::Rle4 => ensure!(
+                info_header.width as u64 * info_header.height as u64 <= MAX_RLE_PIXELS,
+                "BMP is too large: {}x{}",
+                info_header.width,
+                info_header.height
+            ),
+            _ => {
+                uncompressed_stride(pixels, &info_header)?;
+            }
+        }
+
+        let mut image = ImageBuffer::<Rgba8>::new(info_header.width, info_header.height);
+
+        let has_alpha = match info_header.compression {
+            compression @ (Compression::Rle8 | Compression::Rle4) => {
+                decode_rle(
+                    &mut image,
+                    pixels,
+                    &palette,
+                    compression == Compression::Rle4,
+                )?;
+                false
+            }
+            _ => decode_uncompressed(&mut image, pixels, &palette, &info_header)?,
+        };
+
+        let image = if has_alpha {
+            DynamicImageBuffer::Rgba8(image)
+        } else {
+            DynamicImageBuffer::Rgb8(image.convert())
+        };
+
+        Ok(Bmp { info_header, image })
+    }
+
+    /// Decodes the bitmap of an ICO or CUR entry: a DIB without a file header, whose height
+    /// covers both the color pixels and the 1-bit AND mask that follows them. Pixels set in the
+    /// mask are transparent.
+    pub fn decode_icon(&mut self) -> Result<Bmp> {
+        let mut info_header = self.parse_info_header()?;
+        ensure!(
+            !info_header.top_down
+                && matches!(
+                    info_header.compression,
+                    Compression::Rgb | Compression::Bitfields | Compression::AlphaBitfields
+                ),
+            "Unsupported icon bitmap layout."
+        );
+
+        info_header.height /= 2;
+        ensure!(info_header.height > 0, "Invalid icon bitmap height.");
+
+        // 32-bit icons store alpha in the high byte, despite having no masks.
+        if info_header.bits_per_pixel == 32 && info_header.bitfields.is_none() {
+            info_header.bitfields = Some(Bitfields::ARGB8888);
+        }
+
+        let palette = self.parse_palette(&info_header)?;
+        let InfoHeader { width, height, .. } = info_header;
+
+        let pixels = self
+            .data
+            .get(self.cursor..)
+            .ok_or_else(|| anyhow!("Icon bitmap pixel data is missing."))?;
+        let stride = uncompressed_stride(pixels, &info_header)?;
+
+        let mut image = ImageBuffer::<Rgba8>::new(width, height);
+        let has_alpha = decode_uncompressed(&mut image, pixels, &palette, &info_header)?;
+
+        // Icons written before alpha existed leave the high byte empty.
+        if has_alpha && image.pixels().iter().all(|p| p.0[3] == 0) {
+            image.pixels_mut().iter_mut().for_each(|p| p.0[3] = 255);
+        }
+
+        let mask_stride = (width as usize).div_ceil(32) * 4;
+
+        // Some icons with alpha omit the mask.
+        let mask = pixels
+            .get(stride * height as usize..)
+            .filter(|mask| mask.len() >= mask_stride * height as usize);
+
+        if let Some(mask) = mask {
+            let rows = mask.chunks_exact(mask_stride).take(height as usize);
+
+            for (stored_row, row) in rows.enumerate() {
+                let y = height - 1 - stored_row as u32;
+
+                for (x, pixel) in image.row_mut(y).iter_mut().enumerate() {
+                    if row[x / 8] & (0x80 >> (x % 8)) != 0 {
+                        pixel.0[3] = 0;
+                    }
+                }
+            }
+        }
+
+        Ok(Bmp {
+            info_header,
+            image: DynamicImageBuffer::Rgba8(image),
+        })
+    }
+
+    fn parse_info_header(&mut self) -> Result<InfoHeader> {
+        let header_start = self.cursor;
+        let header_size = self.read_u32()?;
+        ensure!(
+            header_start + header_size as usize <= self.data.len(),
+            "BMP info header is truncated."
+        );
+
+        let (width, height, planes, bits_per_pixel, compression, colors_used) =
+            if header_size == CORE_HEADER_SIZE {
+                let width = self.read_u16()? as i32;
+                let height = self.read_u16()? as i32;
+                let planes = self.read_u16()?;
+                let bits_per_pixel = self.read_u16()?;
+
+                (width, height, planes, bits_per_pixel, Compression::Rgb, 0)
+            } else {
+                ensure!(
+                    header_size >= INFO_HEADER_SIZE,
+                    "Unsupported BMP header size: {}",
+                    header_size
+                );
+
+                let width = self.read_i32()?;
+                let height = self.read_i32()?;
+                let planes = self.read_u16()?;
+                let bits_per_pixel = self.read_u16()?;
+                let compression = Compression::try_from(self.read_u32()?)?;
+                let _image_size = self.read_u32()?;
+                let _x_pixels_per_meter = self.read_i32()?;
+                let _y_pixels_per_meter = self.read_i32()?;
+                let colors_used = self.read_u32()?;
+                let _colors_important = self.read_u32()?;
+
+                (
+                    width,
+                    height,
+                    planes,
+                    bits_per_pixel,
+                    compression,
+                    colors_used,
+                )
+            };
+
+        ensure!(width > 0, "Invalid BMP width: {}", width);
+        ensure!(height != 0 && height != i32::MIN, "Invalid BMP height.");
+        ensure!(planes == 1, "Invalid BMP plane count: {}", planes);
+
+        let valid_bits_per_pixel: &[u16] = match compression {
+            Compression::Rgb => &[1, 4, 8, 16, 24, 32],
+            Compression::Rle8 => &[8],
+            Compression::Rle4 => &[4],
+            Compression::Bitfields | Compression::AlphaBitfields => &[16, 32],
+        };
+
+        ensure!(
+            valid_bits_per_pixel.contains(&bits_per_pixel),
+            "Unsupported BMP bit depth {} for {:?} compression.",
+            bits_per_pixel,
+            compression
+        );
+
+        let top_down = height < 0;
+        ensure!(
+            !top_down || matches!(compression, Compression::Rgb | Compression::Bitfields),
+            "Compressed BMPs cannot be stored top-down."
+        );
+
+        // The masks follow the BITMAPINFOHEADER fields, either as part of a later header version
+        // or appended to a BITMAPINFOHEADER.
+        let bitfields = match compression {
+            Compression::Bitfields | Compression::AlphaBitfields => {
+                let [red, green, blue] = self.read_fixed_array(Self::read_u32)?;
+                let alpha = if compression == Compression::AlphaBitfields
+                    || header_size >= INFO_HEADER_SIZE + 16
+                {
+                    self.read_u32()?
+                } else {
+                    0
+                };
+
+                Some(Bitfields {
+                    red,
+                    green,
+                    blue,
+                    alpha,
+                })
+            }
+            _ => None,
+        };
+
+        self.cursor = self.cursor.max(header_start + header_size as usize);
+
+        Ok(InfoHeader {
+            header_size,
+            width: width as u32,
+            height: height.unsigned_abs(),
+            top_down,
+            bits_per_pixel,
+            compression,
+            colors_used,
+            bitfields,
+        })
+    }
+
+    fn parse_palette(&mut self, info_header: &InfoHeader) -> Result<Vec<Rgba8>> {
+        if info_header.bits_per_pixel > 8 {
+            return Ok(vec![]);
+        }
+
+        let max_colors = 1 << info_header.bits_per_pixel;
+        let num_colors = match info_header.colors_used {
+            0 => max_colors,
+            colors_used => colors_used.min(max_colors),
+        };
+
+        // OS/2 headers store blue, green and red; later ones pad each entry to 4 bytes.
+        let entry_size = if info_header.header_size == CORE_HEADER_SIZE {
+            3
+        } else {
+            4
+        };
+
+        (0..num_colors)
+            .map(|_| {
+                let entry = self.read_slice(entry_size)?;
+                Ok(Rgba([entry[2], entry[1], entry[0], 255]))
+            })
+            .collect()
+    }
+
+    impl_read_le_for_datatype!(read_u16, u16);
+    impl_read_le_for_datatype!(read_u32, u32);
+    impl_read_le_for_datatype!(read_i32, i32);
+
+    impl_read_slice!();
+}
+
+/// Indices outside of the palette are black, like most decoders render them.
+fn palette_color(palette: &[Rgba8], index: u8) -> Rgba8 {
+    palette
+        .get(index as usize)
+        .copied()
+        .unwrap_or(Rgba([0, 0, 0, 255]))
+}
+
+/// The length of a row of uncompressed pixels, after checking that `pixels` holds every row.
+fn uncompressed_stride(pixels: &[u8], info_header: &InfoHeader) -> Result<usize> {
+    // Rows are padded to a multiple of 4 bytes.
+    let stride =
+        (info_header.width as usize * info_header.bits_per_pixel as usize).div_ceil(32) * 4;
+    let len = stride.checked_mul(info_header.height as usize);
+    ensure!(
+        len.is_some_and(|len| pixels.len() >= len),
+        "BMP pixel data is truncated."
+    );
+
+    Ok(stride)
+}
+
+/// Returns whether the pixels have an alpha channel.
+fn decode_uncompressed(
+    image: &mut ImageBuffer<Rgba8>,
+    pixels: &[u8],
+    palette: &[Rgba8],
+    info_header: &InfoHeader,
+) -> Result<bool> {
+    let &InfoHeader {
+        height,
+        top_down,
+        bits_per_pixel,
+        bitfields,
+        ..
+    } = info_header;
+
+    let stride = uncompressed_stride(pixels, info_header)?;
+
+    let bitfields = bitfields.unwrap_or(match bits_per_pixel {
+        16 => Bitfields::RGB555,
+        _ => Bitfields::RGB888,
+    });
+
+    let red = Channel::new(bitfields.red)?;
+    let green = Channel::new(bitfields.green)?;
+    let blue = Channel::new(bitfields.blue)?;
+    let alpha = Channel::new(bitfields.alpha)?;
+
+    let read_bitfields = |value: u32| {
+        Rgba([
+            red.map_or(0, |c| c.read(value)),
+            green.map_or(0, |c| c.read(value)),
+            blue.map_or(0, |c| c.read(value)),
+            alpha.map_or(255, |c| c.read(value)),
+        ])
+    };
+
+    for (stored_row, row) in pixels
+        .chunks_exact(stride)
+        .take(height as usize)
+        .enumerate()
+    {
+        let y = if top_down {
+            stored_row as u32
+        } else {
+            height - 1 - stored_row as u32
+        };
+
+        for (x, pixel) in image.row_mut(y).iter_mut().enumerate() {
+            *pixel = match bits_per_pixel {
+                1 | 4 | 8 => {
+                    let bit = x * bits_per_pixel as usize;
+                    let byte = row[bit / 8];
+                    let index = (byte >> (8 - bits_per_pixel as usize - bit % 8))
+                        & ((1 << bits_per_pixel) - 1) as u8;
+
+                    palette_color(palette, index)
+                }
+                16 => read_bitfields(u16::from_le_bytes([row[2 * x], row[2 * x + 1]]) as u32),
+                24 => Rgba([row[3 * x + 2], row[3 * x + 1], row[3 * x], 255]),
+                32 => read_bitfields(u32::from_le_bytes(row[4 * x..4 * x + 4].try_into()?)),
+                _ => unreachable!("Validated when parsing the header."),
+            };
+        }
+    }
+
+    Ok(alpha.is_some())
+}
+
+const RLE_ESCAPE: u8 = 0;
+const RLE_END_OF_LINE: u8 = 0;
+const RLE_END_OF_BITMAP: u8 = 1;
+const RLE_DELTA: u8 = 2;
+
+/// Decodes RLE8 or RLE4 pixels. Pixels the encoding skips over stay black, like Windows
+/// renders them.
+fn decode_rle(
+    image: &mut ImageBuffer<Rgba8>,
+    pixels: &[u8],
+    palette: &[Rgba8],
+    rle4: bool,
+) -> Result<()> {
+    let (width, height) = (image.width() as usize, image.height() as usize);
+    image.pixels_mut().fill(Rgba([0, 0, 0, 255]));
+
+    // Rows are stored bottom to top, and runs that overflow a row are clipped.
+    let set_pixel = |image: &mut ImageBuffer<Rgba8>, x: usize, y: usize, index: u8| {
+        if x < width && y < height {
+            image.row_mut((height - 1 - y) as u32)[x] = palette_color(palette, index);
+        }
+    };
+
+    let nibble = |byte: u8, i: usize| {
+        if i.is_multiple_of(2) {
+            byte >> 4
+        } else {
+            byte & 0x0F
+        }
+    };
+
+    let (mut x, mut y) = (0, 0);
+    let mut cursor = 0;
+
+    // Files that end without an end of bitmap escape are accepted.
+    while let Some(&[count, value]) = pixels.get(cursor..cursor + 2) {
+        cursor += 2;
+
+        if y >= height {
+            break;
+        }
+
+        if count != RLE_ESCAPE {
+            for i in 0..count as usize {
+                let index = if rle4 { nibble(value, i) } else { value };
+                set_pixel(image, x, y, index);
+                x += 1;
+            }
+
+            continue;
+        }
+
+        match value {
+            RLE_END_OF_LINE => {
+                x = 0;
+                y += 1;
+            }
+            RLE_END_OF_BITMAP => break,
+            RLE_DELTA => {
+                let delta = pixels
+                    .get(cursor..cursor + 2)
+                    .ok_or_else(|| anyhow!("BMP RLE delta is truncated."))?;
+                cursor += 2;
+
+                x += delta[0] as usize;
+                y += delta[1] as usize;
+            }
+            count => {
+                // Absolute mode: literal indices, padded to a 16-bit boundary.
+                let count = count as usize;
+                let len = if rle4 { count.div_ceil(2) } else { count };
+
+                let indices = pixels
+                    .get(cursor..cursor + len)
+                    .ok_or_else(|| anyhow!("BMP RLE run is truncated."))?;
+                cursor += len.next_multiple_of(2);
+
+                for i in 0..count {
+                    let index = if rle4 {
+                        nibble(indices[i / 2], i)
+                    } else {
+                        indices[i]
+                    };
+                    set_pixel(image, x, y, index);
+                    x += 1;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::grammar::{ColorType, ImageExt};
+    use image::ImageReader;
+
+    fn compare_bmp(path: &str, color_type: ColorType) -> Result<()> {
+        let reference = ImageReader::open(path)?.decode()?.to_rgba8();
+
+        let content = std::fs::read(path)?;
+        let bmp = BmpDecoder::new(&content).decode()?;
+
+        assert_eq!(bmp.dimensions(), reference.dimensions(), "{path}");
+        assert_eq!(bmp.color_type(), color_type, "{path}");
+        assert_eq!(
+            bmp.rgba8().as_ref(),
+            reference.as_raw().as_slice(),
+            "{path}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_palettized() -> Result<()> {
+        compare_bmp("./tests/bmp/pal1.bmp", ColorType::RGB)?;
+        compare_bmp("./tests/bmp/pal4.bmp", ColorType::RGB)?;
+        compare_bmp("./tests/bmp/pal8.bmp", ColorType::RGB)?;
+        compare_bmp("./tests/bmp/core8.bmp", ColorType::RGB)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_truecolor() -> Result<()> {
+        compare_bmp("./tests/bmp/rgb24.bmp", ColorType::RGB)?;
+        compare_bmp("./tests/bmp/rgb32.bmp", ColorType::RGB)?;
+        compare_bmp("./tests/bmp/rgb555.bmp", ColorType::RGB)?;
+        compare_bmp("./tests/bmp/rgb565.bmp", ColorType::RGB)?;
+        compare_bmp("./tests/bmp/rgba32_v5.bmp", ColorType::RGBA)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_top_down() -> Result<()> {
+        let bottom_up = std::fs::read("./tests/bmp/rgb24.bmp")?;
+        let top_down = std::fs::read("./tests/bmp/rgb24_topdown.bmp")?;
+
+        let bottom_up = BmpDecoder::new(&bottom_up).decode()?;
+        let top_down = BmpDecoder::new(&top_down).decode()?;
+
+        assert!(top_down.info_header().top_down);
+        assert_eq!(top_down.rgb8(), bottom_up.rgb8());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_rle() -> Result<()> {
+        compare_bmp("./tests/bmp/rle4.bmp", ColorType::RGB)?;
+        // Skips pixels with a delta and an early end of line.
+        compare_bmp("./tests/bmp/rle8.bmp", ColorType::RGB)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        for data in [
+            &b"BM"[..],
+            b"PN\0\0\0\0\0\0\0\0\0\0\0\0",
+            &std::fs::read("./tests/bmp/rgb24.bmp").unwrap()[..100],
+        ] {
+            assert!(BmpDecoder::new(data).decode().is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_oversized() {
+        // Headers claiming 100000x100000 pixels, without the data to back them.
+        let header = |bits_per_pixel: u16, compression: u32| {
+            let mut data = b"BM".to_vec();
+            data.extend_from_slice(&[0; 8]);
+            data.extend_from_slice(&54u32.to_le_bytes());
+            data.extend_from_slice(&INFO_HEADER_SIZE.to_le_bytes());
+            data.extend_from_slice(&100_000i32.to_le_bytes());
+            data.extend_from_slice(&100_000i32.to_le_bytes());
+            data.extend_from_slice(&1u16.to_le_bytes());
+            data.extend_from_slice(&bits_per_pixel.to_le_bytes());
+            data.extend_from_slice(&compression.to_le_bytes());
+            data.extend_from_slice(&[0; 20]);
+            data.extend_from_slice(&[0; 1024]);
+            data.extend_from_slice(&[0, 1]);
+            data
+        };
+
+        assert!(BmpDecoder::new(&header(24, 0)).decode().is_err());
+        assert!(BmpDecoder::new(&header(8, 0)).decode().is_err());
+        assert!(BmpDecoder::new(&header(8, 1)).decode().is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use crate::{
+    bmp::grammar::{FILE_HEADER_SIZE, INFO_HEADER_SIZE, V4_HEADER_SIZE},
+    image::grammar::{ColorType, ImageEncoder, ImageExt},
+};
+use anyhow::{ensure, Result};
+use std::io::Write;
+
+/// 72 DPI.
+const PIXELS_PER_METER: i32 = 2835;
+
+/// The `LCS_sRGB` color space, the tag "sRGB" read as a big-endian integer.
+const LCS_SRGB: u32 = u32::from_be_bytes(*b"sRGB");
+
+pub struct BmpEncoder<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> BmpEncoder<W> {
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes `image` bottom-up, as 24-bit BGR, or as 32-bit BGRA with a BITMAPV4HEADER when it
+    /// has alpha.
+    pub fn encode(&mut self, image: &dyn ImageExt) -> Result<()> {
+        let (width, height) = image.dimensions();
+        ensure!(
+            width > 0 && height > 0 && width <= i32::MAX as u32 && height <= i32::MAX as u32,
+            "Invalid BMP dimensions: {}x{}",
+            width,
+            height
+        );
+
+        let has_alpha = matches!(
+            image.color_type(),
+            ColorType::RGBA | ColorType::GrayscaleAlpha
+        );
+
+        let (header_size, bits_per_pixel, compression) = if has_alpha {
+            // BI_BITFIELDS, since BI_RGB 32-bit pixels have no alpha.
+            (V4_HEADER_SIZE, 32, 3)
+        } else {
+            (INFO_HEADER_SIZE, 24, 0)
+        };
+
+        let stride = (width as usize * bits_per_pixel as usize).div_ceil(32) * 4;
+        let image_size = (stride * height as usize) as u32;
+        let pixel_offset = FILE_HEADER_SIZE + header_size;
+
+        let mut buffer = Vec::with_capacity(pixel_offset as usize + image_size as usize);
+
+        buffer.extend_from_slice(b"BM");
+        buffer.extend_from_slice(&(pixel_offset + image_size).to_le_bytes());
+        buffer.extend_from_slice(&0u32.to_le_bytes());
+        buffer.extend_from_slice(&pixel_offset.to_le_bytes());
+
+        buffer.extend_from_slice(&header_size.to_le_bytes());
+        buffer.extend_from_slice(&(width as i32).to_le_bytes());
+        buffer.extend_from_slice(&(height as i32).to_le_bytes());
+        buffer.extend_from_slice(&1u16.to_le_bytes());
+        buffer.extend_from_slice(&(bits_per_pixel as u16).to_le_bytes());
+        buffer.extend_from_slice(&(compression as u32).to_le_bytes());
+        buffer.extend_from_slice(&image_size.to_le_bytes());
+        buffer.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+        buffer.extend_from_slice(&PIXELS_PER_METER.to_le_bytes());
+        buffer.extend_from_slice(&0u32.to_le_bytes());
+        buffer.extend_from_slice(&0u32.to_le_bytes());
+
+        if has_alpha {
+            for mask in [0x00FF_0000u32, 0x0000_FF00, 0x0000_00FF, 0xFF00_0000] {
+                buffer.extend_from_slice(&mask.to_le_bytes());
+            }
+
+            buffer.extend_from_slice(&LCS_SRGB.to_le_bytes());
+            // The endpoints and gamma, unused for sRGB.
+            buffer.extend_from_slice(&[0; 48]);
+        }
+
+        let padding = stride - width as usize * bits_per_pixel as usize / 8;
+
+        if has_alpha {
+            let rgba = image.straight_rgba8();
+
+            for row in rgba.chunks_exact(width as usize * 4).rev() {
+                for p in row.chunks_exact(4) {
+                    buffer.extend_from_slice(&[p[2], p[1], p[0], p[3]]);
+                }
+            }
+        } else {
+            let rgb = image.rgb8();
+
+            for row in rgb.chunks_exact(width as usize * 3).rev() {
+                for p in row.chunks_exact(3) {
+                    buffer.extend_from_slice(&[p[2], p[1], p[0]]);
+                }
+
+                buffer.extend(std::iter::repeat_n(0, padding));
+            }
+        }
+
+        self.writer.write_all(&buffer)?;
+
+        Ok(())
+    }
+}
+
+impl<W: Write> ImageEncoder for BmpEncoder<W> {
+    fn write_image(&mut self, image: &dyn ImageExt) -> Result<()> {
+        self.encode(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        bmp::BmpDecoder,
+        image::{ImageBuffer, Rgb, Rgba},
+    };
+
+    #[test]
+    fn test_encode_round_trip() -> Result<()> {
+        let rgb = ImageBuffer::from_fn(13, 7, |x, y| Rgb([x as u8 * 19, y as u8 * 37, 200]));
+        let rgba = ImageBuffer::from_fn(13, 7, |x, y| Rgba([x as u8, y as u8, 9, x as u8 * 20]));
+
+        for (image, color_type, bits_per_pixel) in [
+            (&rgb as &dyn ImageExt, ColorType::RGB, 24),
+            (&rgba, ColorType::RGBA, 32),
+        ] {
+            let mut encoded = Vec::new();
+            BmpEncoder::new(&mut encoded).encode(image)?;
+
+            let reference = image::load_from_memory(&encoded)?.to_rgba8();
+            assert_eq!(reference.as_raw().as_slice(), image.rgba8().as_ref());
+
+            let decoded = BmpDecoder::new(&encoded).decode()?;
+            assert_eq!(decoded.info_header().bits_per_pixel, bits_per_pixel);
+            assert_eq!(decoded.color_type(), color_type);
+            assert_eq!(decoded.rgba8(), image.rgba8());
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,126 @@
+use crate::image::{
+    grammar::{ColorType, ImageExt},
+    DynamicImageBuffer,
+};
+use anyhow::bail;
+use std::borrow::Cow;
+
+pub const FILE_HEADER_SIZE: u32 = 14;
+
+pub const CORE_HEADER_SIZE: u32 = 12;
+pub const INFO_HEADER_SIZE: u32 = 40;
+pub const V4_HEADER_SIZE: u32 = 108;
+pub const V5_HEADER_SIZE: u32 = 124;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    Rgb = 0,
+    Rle8 = 1,
+    Rle4 = 2,
+    Bitfields = 3,
+    AlphaBitfields = 6,
+}
+
+impl TryFrom<u32> for Compression {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> anyhow::Result<Self, Self::Error> {
+        let compression = match value {
+            0 => Self::Rgb,
+            1 => Self::Rle8,
+            2 => Self::Rle4,
+            3 => Self::Bitfields,
+            6 => Self::AlphaBitfields,
+            foreign => bail!("Unsupported BMP compression: {}", foreign),
+        };
+
+        Ok(compression)
+    }
+}
+
+/// Where each channel lies within a 16 or 32-bit pixel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Bitfields {
+    pub red: u32,
+    pub green: u32,
+    pub blue: u32,
+    pub alpha: u32,
+}
+
+impl Bitfields {
+    /// 5 bits per channel, the layout of 16-bit pixels without explicit masks.
+    pub const RGB555: Self = Self {
+        red: 0x7C00,
+        green: 0x03E0,
+        blue: 0x001F,
+        alpha: 0,
+    };
+
+    /// 8 bits per channel with an unused high byte, the layout of 32-bit pixels without
+    /// explicit masks.
+    pub const RGB888: Self = Self {
+        red: 0x00FF_0000,
+        green: 0x0000_FF00,
+        blue: 0x0000_00FF,
+        alpha: 0,
+    };
+
+    /// 8 bits per channel with alpha in the high byte, the layout of 32-bit icon pixels.
+    pub const ARGB8888: Self = Self {
+        alpha: 0xFF00_0000,
+        ..Self::RGB888
+    };
+}
+
+/// The fields of the DIB header that describe the pixels, whichever header version stores
+/// them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InfoHeader {
+    pub header_size: u32,
+    pub width: u32,
+    pub height: u32,
+    /// Rows are stored top to bottom, rather than the usual bottom to top.
+    pub top_down: bool,
+    pub bits_per_pixel: u16,
+    pub compression: Compression,
+    pub colors_used: u32,
+    pub bitfields: Option<Bitfields>,
+}
+
+#[derive(Debug)]
+pub struct Bmp {
+    pub(crate) info_header: InfoHeader,
+    pub(crate) image: DynamicImageBuffer,
+}
+
+impl Bmp {
+    pub const fn info_header(&self) -> &InfoHeader {
+        &self.info_header
+    }
+}
+
+impl ImageExt for Bmp {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.image.color_type()
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        self.image.rgb8()
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        self.image.rgba8()
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        self.image.bitmap()
+    }
+}
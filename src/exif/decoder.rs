@@ -0,0 +1,40 @@
+use crate::exif::{
+    grammar::{Exif, Ifd, Value, EXIF_IFD_POINTER, GPS_IFD_POINTER},
+    ifd::IfdReader,
+};
+use anyhow::Result;
+
+#[derive(Debug)]
+pub struct ExifDecoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> ExifDecoder<'a> {
+    /// `data` is the TIFF structure holding the metadata, as found in a PNG eXIf chunk or after the
+    /// identifier of a JPEG APP1 segment.
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn decode(&self) -> Result<Exif> {
+        let mut reader = IfdReader::new(self.data)?;
+        let primary = reader.read_ifd(reader.next_ifd_offset())?;
+
+        let mut read_sub_ifd = |pointer| {
+            primary.get(pointer).and_then(Value::as_u32).map_or_else(
+                || Ok(Ifd::default()),
+                |offset| reader.read_ifd(offset as usize),
+            )
+        };
+
+        let exif = read_sub_ifd(EXIF_IFD_POINTER)?;
+        let gps = read_sub_ifd(GPS_IFD_POINTER)?;
+
+        Ok(Exif {
+            byte_order: reader.byte_order(),
+            primary,
+            exif,
+            gps,
+        })
+    }
+}
@@ -0,0 +1,249 @@
+use anyhow::bail;
+use std::collections::BTreeMap;
+
+pub type Tag = u16;
+
+pub const IMAGE_DESCRIPTION: Tag = 0x010E;
+pub const MAKE: Tag = 0x010F;
+pub const MODEL: Tag = 0x0110;
+pub const ORIENTATION: Tag = 0x0112;
+pub const X_RESOLUTION: Tag = 0x011A;
+pub const Y_RESOLUTION: Tag = 0x011B;
+pub const RESOLUTION_UNIT: Tag = 0x0128;
+pub const SOFTWARE: Tag = 0x0131;
+pub const DATE_TIME: Tag = 0x0132;
+pub const ARTIST: Tag = 0x013B;
+pub const COPYRIGHT: Tag = 0x8298;
+pub const EXIF_IFD_POINTER: Tag = 0x8769;
+pub const GPS_IFD_POINTER: Tag = 0x8825;
+
+pub const EXPOSURE_TIME: Tag = 0x829A;
+pub const F_NUMBER: Tag = 0x829D;
+pub const ISO_SPEED_RATINGS: Tag = 0x8827;
+pub const DATE_TIME_ORIGINAL: Tag = 0x9003;
+pub const FOCAL_LENGTH: Tag = 0x920A;
+pub const PIXEL_X_DIMENSION: Tag = 0xA002;
+pub const PIXEL_Y_DIMENSION: Tag = 0xA003;
+pub const LENS_MODEL: Tag = 0xA434;
+
+pub const GPS_LATITUDE_REF: Tag = 0x0001;
+pub const GPS_LATITUDE: Tag = 0x0002;
+pub const GPS_LONGITUDE_REF: Tag = 0x0003;
+pub const GPS_LONGITUDE: Tag = 0x0004;
+pub const GPS_ALTITUDE_REF: Tag = 0x0005;
+pub const GPS_ALTITUDE: Tag = 0x0006;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    LittleEndian,
+    BigEndian,
+}
+
+/// The value of an IFD entry, decoded according to its field type.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    /// Numerator and denominator pairs.
+    Rational(Vec<(u32, u32)>),
+    SByte(Vec<i8>),
+    Undefined(Vec<u8>),
+    SShort(Vec<i16>),
+    SLong(Vec<i32>),
+    SRational(Vec<(i32, i32)>),
+    Float(Vec<f32>),
+    Double(Vec<f64>),
+}
+
+impl Value {
+    /// The first element of an integer value, widened to u32.
+    pub fn as_u32(&self) -> Option<u32> {
+        match self {
+            Self::Byte(v) => v.first().map(|&n| n as u32),
+            Self::Short(v) => v.first().map(|&n| n as u32),
+            Self::Long(v) => v.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// Every element of an integer value, widened to u32.
+    pub fn as_u32s(&self) -> Option<Vec<u32>> {
+        match self {
+            Self::Byte(v) => Some(v.iter().map(|&n| n as u32).collect()),
+            Self::Short(v) => Some(v.iter().map(|&n| n as u32).collect()),
+            Self::Long(v) => Some(v.clone()),
+            _ => None,
+        }
+    }
+
+    /// Every element of a rational value, as a float.
+    pub fn as_f64s(&self) -> Option<Vec<f64>> {
+        match self {
+            Self::Rational(v) => Some(v.iter().map(|&(n, d)| n as f64 / d as f64).collect()),
+            Self::SRational(v) => Some(v.iter().map(|&(n, d)| n as f64 / d as f64).collect()),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::Ascii(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// An image file directory, a set of tagged fields (see TIFF 6.0, Section 2).
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Ifd {
+    pub(crate) fields: BTreeMap<Tag, Value>,
+}
+
+impl Ifd {
+    pub fn get(&self, tag: Tag) -> Option<&Value> {
+        self.fields.get(&tag)
+    }
+
+    pub fn fields(&self) -> impl Iterator<Item = (Tag, &Value)> {
+        self.fields.iter().map(|(&tag, value)| (tag, value))
+    }
+}
+
+/// How the stored pixels must be transformed to display the image upright.
+///
+/// The variant names describe the transformation applied to the stored image, in the order the
+/// Orientation tag enumerates them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Normal = 1,
+    FlipHorizontal = 2,
+    Rotate180 = 3,
+    FlipVertical = 4,
+    Transpose = 5,
+    Rotate90 = 6,
+    Transverse = 7,
+    Rotate270 = 8,
+}
+
+impl Orientation {
+    /// Whether the displayed image has its width and height swapped.
+    pub const fn swaps_dimensions(&self) -> bool {
+        matches!(
+            self,
+            Self::Transpose | Self::Rotate90 | Self::Transverse | Self::Rotate270
+        )
+    }
+
+    /// Maps a pixel of the displayed image to the pixel of the stored `width` by `height` image
+    /// it comes from.
+    pub const fn source_position(&self, x: u32, y: u32, width: u32, height: u32) -> (u32, u32) {
+        match self {
+            Self::Normal => (x, y),
+            Self::FlipHorizontal => (width - 1 - x, y),
+            Self::Rotate180 => (width - 1 - x, height - 1 - y),
+            Self::FlipVertical => (x, height - 1 - y),
+            Self::Transpose => (y, x),
+            Self::Rotate90 => (y, height - 1 - x),
+            Self::Transverse => (width - 1 - y, height - 1 - x),
+            Self::Rotate270 => (width - 1 - y, x),
+        }
+    }
+}
+
+impl TryFrom<u32> for Orientation {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> anyhow::Result<Self, Self::Error> {
+        let orientation = match value {
+            1 => Self::Normal,
+            2 => Self::FlipHorizontal,
+            3 => Self::Rotate180,
+            4 => Self::FlipVertical,
+            5 => Self::Transpose,
+            6 => Self::Rotate90,
+            7 => Self::Transverse,
+            8 => Self::Rotate270,
+            foreign => bail!("Unrecognized orientation: {}", foreign),
+        };
+
+        Ok(orientation)
+    }
+}
+
+/// EXIF metadata: the primary image's IFD (IFD0), along with the Exif and GPS IFDs it points to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Exif {
+    pub(crate) byte_order: ByteOrder,
+    pub(crate) primary: Ifd,
+    pub(crate) exif: Ifd,
+    pub(crate) gps: Ifd,
+}
+
+impl Exif {
+    pub const fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    pub const fn primary(&self) -> &Ifd {
+        &self.primary
+    }
+
+    pub const fn exif(&self) -> &Ifd {
+        &self.exif
+    }
+
+    pub const fn gps(&self) -> &Ifd {
+        &self.gps
+    }
+
+    /// Looks up `tag` in IFD0, then in the Exif IFD. GPS tags overlap with the others and are only
+    /// available through `gps`.
+    pub fn get(&self, tag: Tag) -> Option<&Value> {
+        self.primary.get(tag).or_else(|| self.exif.get(tag))
+    }
+
+    /// The Orientation tag. Missing or invalid values are treated as `Orientation::Normal`.
+    pub fn orientation(&self) -> Orientation {
+        self.primary
+            .get(ORIENTATION)
+            .and_then(Value::as_u32)
+            .and_then(|value| Orientation::try_from(value).ok())
+            .unwrap_or(Orientation::Normal)
+    }
+
+    pub fn make(&self) -> Option<&str> {
+        self.get(MAKE).and_then(Value::as_str)
+    }
+
+    pub fn model(&self) -> Option<&str> {
+        self.get(MODEL).and_then(Value::as_str)
+    }
+
+    pub fn date_time_original(&self) -> Option<&str> {
+        self.get(DATE_TIME_ORIGINAL).and_then(Value::as_str)
+    }
+
+    /// The GPS position as (latitude, longitude) in signed decimal degrees.
+    pub fn gps_coordinates(&self) -> Option<(f64, f64)> {
+        let coordinate = |value_tag: Tag, reference_tag: Tag, negative: &str| {
+            let dms = self.gps.get(value_tag)?.as_f64s()?;
+            let &[degrees, minutes, seconds] = dms.as_slice() else {
+                return None;
+            };
+
+            let decimal = degrees + minutes / 60.0 + seconds / 3600.0;
+
+            match self.gps.get(reference_tag).and_then(Value::as_str) {
+                Some(reference) if reference == negative => Some(-decimal),
+                _ => Some(decimal),
+            }
+        };
+
+        Some((
+            coordinate(GPS_LATITUDE, GPS_LATITUDE_REF, "S")?,
+            coordinate(GPS_LONGITUDE, GPS_LONGITUDE_REF, "W")?,
+        ))
+    }
+}
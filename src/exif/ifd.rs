@@ -0,0 +1,227 @@
+use crate::{
+    exif::grammar::{ByteOrder, Ifd, Tag, Value},
+    impl_read_slice,
+};
+use anyhow::{bail, ensure, Result};
+use std::collections::BTreeMap;
+
+/// Reads the image file directories of a TIFF structure, the layout EXIF metadata is stored in.
+///
+/// Offsets are relative to the start of `data`, which begins with the TIFF header.
+#[derive(Debug)]
+pub struct IfdReader<'a> {
+    cursor: usize,
+    data: &'a [u8],
+    byte_order: ByteOrder,
+}
+
+impl<'a> IfdReader<'a> {
+    /// Parses the TIFF header, leaving the reader at the offset of the first IFD.
+    pub fn new(data: &'a [u8]) -> Result<Self> {
+        let byte_order = match data.get(..2) {
+            Some(b"II") => ByteOrder::LittleEndian,
+            Some(b"MM") => ByteOrder::BigEndian,
+            _ => bail!("Invalid TIFF header: unrecognized byte order."),
+        };
+
+        let mut reader = Self {
+            cursor: 2,
+            data,
+            byte_order,
+        };
+
+        ensure!(
+            reader.read_u16()? == 42,
+            "Invalid TIFF header: expected 42."
+        );
+
+        reader.cursor = reader.read_u32()? as usize;
+
+        Ok(reader)
+    }
+
+    pub const fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    /// The offset of the IFD the reader is positioned at, or 0 if there are none left.
+    pub const fn next_ifd_offset(&self) -> usize {
+        self.cursor
+    }
+
+    /// Reads the IFD at `offset`, then positions the reader at the IFD that follows it.
+    pub fn read_ifd(&mut self, offset: usize) -> Result<Ifd> {
+        self.cursor = offset;
+
+        let num_entries = self.read_u16()?;
+        let mut fields = BTreeMap::new();
+
+        for _ in 0..num_entries {
+            let tag = self.read_u16()?;
+            let field_type = self.read_u16()?;
+            let count = self.read_u32()? as usize;
+            let value_offset = self.cursor;
+            self.cursor += 4;
+
+            // Readers should skip over fields of unknown types.
+            let Some(size) = Self::field_type_size(field_type) else {
+                continue;
+            };
+
+            let length = count
+                .checked_mul(size)
+                .ok_or_else(|| anyhow::anyhow!("IFD entry {tag:#X} is too large."))?;
+
+            // Values that fit in 4 bytes are stored in place of their offset.
+            let value_start = if length <= 4 {
+                value_offset
+            } else {
+                self.cursor = value_offset;
+                self.read_u32()? as usize
+            };
+
+            let next_entry = value_offset + 4;
+            self.cursor = value_start;
+            fields.insert(tag, self.read_value(tag, field_type, count)?);
+            self.cursor = next_entry;
+        }
+
+        self.cursor = self.read_u32()? as usize;
+
+        Ok(Ifd { fields })
+    }
+
+    /// The offset of each entry of the IFD at `offset`, by tag, for rewriting values stored in
+    /// place. Entries run for 12 bytes: the tag, field type, count, then the value or its offset.
+    pub fn entry_offsets(&mut self, offset: usize) -> Result<BTreeMap<Tag, usize>> {
+        self.cursor = offset;
+        let num_entries = self.read_u16()? as usize;
+
+        ensure!(
+            self.data.len() >= offset + 2 + num_entries * 12,
+            "IFD at {offset} is truncated."
+        );
+
+        (0..num_entries)
+            .map(|i| {
+                let entry = offset + 2 + i * 12;
+                self.cursor = entry;
+
+                Ok((self.read_u16()?, entry))
+            })
+            .collect()
+    }
+
+    /// The size in bytes of a single element of each field type.
+    const fn field_type_size(field_type: u16) -> Option<usize> {
+        match field_type {
+            1 | 2 | 6 | 7 => Some(1),
+            3 | 8 => Some(2),
+            4 | 9 | 11 => Some(4),
+            5 | 10 | 12 => Some(8),
+            _ => None,
+        }
+    }
+
+    fn read_value(&mut self, tag: Tag, field_type: u16, count: usize) -> Result<Value> {
+        let value = match field_type {
+            1 => Value::Byte(self.read_slice(count)?.to_vec()),
+            2 => {
+                // NUL-terminated, though writers do not always include the terminator.
+                let bytes = self.read_slice(count)?;
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+
+                Value::Ascii(String::from_utf8_lossy(&bytes[..end]).into_owned())
+            }
+            3 => Value::Short(self.read_vec(count, Self::read_u16)?),
+            4 => Value::Long(self.read_vec(count, Self::read_u32)?),
+            5 => Value::Rational(
+                self.read_vec(count, |this| Ok((this.read_u32()?, this.read_u32()?)))?,
+            ),
+            6 => Value::SByte(self.read_slice(count)?.iter().map(|&b| b as i8).collect()),
+            7 => Value::Undefined(self.read_slice(count)?.to_vec()),
+            8 => Value::SShort(self.read_vec(count, |this| this.read_u16().map(|n| n as i16))?),
+            9 => Value::SLong(self.read_vec(count, |this| this.read_u32().map(|n| n as i32))?),
+            10 => Value::SRational(self.read_vec(count, |this| {
+                Ok((this.read_u32()? as i32, this.read_u32()? as i32))
+            })?),
+            11 => Value::Float(self.read_vec(count, |this| this.read_u32().map(f32::from_bits))?),
+            12 => Value::Double(self.read_vec(count, |this| this.read_u64().map(f64::from_bits))?),
+            foreign => bail!("Unrecognized field type {foreign} for tag {tag:#X}"),
+        };
+
+        Ok(value)
+    }
+
+    fn read_u16(&mut self) -> Result<u16> {
+        let bytes = self.read_slice(2)?.try_into()?;
+
+        Ok(match self.byte_order {
+            ByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+        })
+    }
+
+    fn read_u32(&mut self) -> Result<u32> {
+        let bytes = self.read_slice(4)?.try_into()?;
+
+        Ok(match self.byte_order {
+            ByteOrder::LittleEndian => u32::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u32::from_be_bytes(bytes),
+        })
+    }
+
+    fn read_u64(&mut self) -> Result<u64> {
+        let bytes = self.read_slice(8)?.try_into()?;
+
+        Ok(match self.byte_order {
+            ByteOrder::LittleEndian => u64::from_le_bytes(bytes),
+            ByteOrder::BigEndian => u64::from_be_bytes(bytes),
+        })
+    }
+
+    impl_read_slice!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_ifd_in_both_byte_orders() -> Result<()> {
+        // One IFD with an inline SHORT and an out-of-line RATIONAL, followed by no further IFDs.
+        let little_endian = [
+            b'I', b'I', 42, 0, 8, 0, 0, 0, // header
+            2, 0, // number of entries
+            0x12, 0x01, 3, 0, 1, 0, 0, 0, 6, 0, 0, 0, // Orientation, SHORT, 6
+            0x1A, 0x01, 5, 0, 1, 0, 0, 0, 38, 0, 0, 0, // XResolution, RATIONAL, at 38
+            0, 0, 0, 0, // next IFD
+            72, 0, 0, 0, 1, 0, 0, 0, // 72/1
+        ];
+
+        let big_endian = [
+            b'M', b'M', 0, 42, 0, 0, 0, 8, //
+            0, 2, //
+            0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0, //
+            0x01, 0x1A, 0, 5, 0, 0, 0, 1, 0, 0, 0, 38, //
+            0, 0, 0, 0, //
+            0, 0, 0, 72, 0, 0, 0, 1,
+        ];
+
+        for (data, byte_order) in [
+            (&little_endian, ByteOrder::LittleEndian),
+            (&big_endian, ByteOrder::BigEndian),
+        ] {
+            let mut reader = IfdReader::new(data)?;
+            assert_eq!(reader.byte_order(), byte_order);
+
+            let ifd = reader.read_ifd(reader.next_ifd_offset())?;
+
+            assert_eq!(ifd.get(0x0112), Some(&Value::Short(vec![6])));
+            assert_eq!(ifd.get(0x011A), Some(&Value::Rational(vec![(72, 1)])));
+            assert_eq!(reader.next_ifd_offset(), 0);
+        }
+
+        Ok(())
+    }
+}
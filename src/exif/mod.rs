@@ -0,0 +1,6 @@
+mod decoder;
+pub(crate) mod ifd;
+
+pub mod grammar;
+
+pub use decoder::*;
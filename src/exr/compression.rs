@@ -0,0 +1,106 @@
+use crate::exr::grammar::Compression;
+use anyhow::{bail, ensure, Result};
+use flate2::read::ZlibDecoder;
+use std::{borrow::Cow, io::Read};
+
+/// Decompresses a block to the `len` bytes its pixels take. Blocks that would not shrink are
+/// stored uncompressed, whatever the image's compression.
+pub fn decompress(compression: Compression, data: &[u8], len: usize) -> Result<Cow<'_, [u8]>> {
+    if data.len() == len {
+        return Ok(Cow::Borrowed(data));
+    }
+
+    let bytes = match compression {
+        Compression::None => bail!("EXR block has {} bytes, expected {}.", data.len(), len),
+        Compression::Rle => decode_runs(data, len)?,
+        Compression::Zips | Compression::Zip => {
+            let mut output = Vec::with_capacity(len);
+            ZlibDecoder::new(data)
+                .take(len as u64)
+                .read_to_end(&mut output)?;
+
+            output
+        }
+        _ => bail!("Unsupported EXR compression: {:?}", compression),
+    };
+
+    ensure!(bytes.len() == len, "EXR block is truncated.");
+
+    Ok(Cow::Owned(deinterleave(&undo_prediction(bytes))))
+}
+
+/// Decodes runs: a negative count `n` is followed by `-n` literal bytes, any other by one byte
+/// repeated `n + 1` times.
+fn decode_runs(data: &[u8], len: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(len);
+    let mut cursor = 0;
+
+    while output.len() < len && cursor < data.len() {
+        let count = data[cursor] as i8;
+        cursor += 1;
+
+        if count < 0 {
+            let literal = data
+                .get(cursor..cursor + count.unsigned_abs() as usize)
+                .ok_or_else(|| anyhow::anyhow!("EXR run is truncated."))?;
+
+            output.extend_from_slice(literal);
+            cursor += literal.len();
+        } else {
+            let Some(&value) = data.get(cursor) else {
+                bail!("EXR run is truncated.");
+            };
+
+            output.extend(std::iter::repeat_n(value, count as usize + 1));
+            cursor += 1;
+        }
+    }
+
+    output.truncate(len);
+
+    Ok(output)
+}
+
+/// Each byte was stored as the difference from the previous one, biased by 128.
+fn undo_prediction(mut bytes: Vec<u8>) -> Vec<u8> {
+    for i in 1..bytes.len() {
+        bytes[i] = bytes[i - 1].wrapping_add(bytes[i]).wrapping_sub(128);
+    }
+
+    bytes
+}
+
+/// Compressors store the even bytes of a block, then the odd ones.
+fn deinterleave(bytes: &[u8]) -> Vec<u8> {
+    let (even, odd) = bytes.split_at(bytes.len().div_ceil(2));
+
+    let mut output = Vec::with_capacity(bytes.len());
+    for (i, &byte) in even.iter().enumerate() {
+        output.push(byte);
+        output.extend(odd.get(i));
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_runs() -> Result<()> {
+        assert_eq!(decode_runs(&[2, 7, 0xFE, 1, 2], 5)?, [7, 7, 7, 1, 2]);
+        assert!(decode_runs(&[0xFD, 1, 2], 3).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_reconstruct() {
+        // 1, 2, 3, 4 interleaved as 1, 3, 2, 4, each the difference from the previous plus 128.
+        let predicted = vec![1, 130, 127, 130];
+        assert_eq!(deinterleave(&undo_prediction(predicted)), [1, 2, 3, 4]);
+
+        assert_eq!(deinterleave(&[1, 3, 5, 2, 4]), [1, 2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,517 @@
+use crate::{
+    exr::{
+        compression::decompress,
+        grammar::{
+            Channel, Compression, DataWindow, Exr, ExrHeader, PixelType, TileDescription,
+            EXR_MAGIC, EXR_VERSION, MULTI_PART, NON_IMAGE, SINGLE_PART_TILED,
+        },
+    },
+    image::{DynamicImageBuffer, ImageBuffer},
+    impl_read_le_for_datatype, impl_read_slice,
+};
+use anyhow::{anyhow, ensure, Result};
+
+/// The most pixels an image may have.
+const MAX_PIXELS: u64 = 400_000_000;
+
+/// Names, including attribute and channel names, are at most this long without the long
+/// names flag.
+const MAX_NAME_LEN: usize = 255;
+
+/// A block of pixels: a run of scanlines, or a tile.
+#[derive(Debug, Clone, Copy)]
+struct Block {
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+#[derive(Debug)]
+pub struct ExrDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> ExrDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Exr> {
+        let header = self.parse_header()?;
+        let (width, height) = (header.data_window.width(), header.data_window.height());
+
+        // Luminance stands in for all three colors when there are none.
+        let find = |name: &str| {
+            header
+                .channels
+                .iter()
+                .position(|channel| channel.name == name)
+        };
+        let colors = match (find("R"), find("G"), find("B"), find("Y")) {
+            (Some(r), Some(g), Some(b), _) => [r, g, b],
+            (_, _, _, Some(y)) => [y; 3],
+            _ => return Err(anyhow!("EXR image has no RGB or luminance channels.")),
+        };
+        let alpha = find("A");
+
+        let num_blocks = match header.tiles {
+            Some(tiles) => {
+                width.div_ceil(tiles.width) as usize * height.div_ceil(tiles.height) as usize
+            }
+            None => height.div_ceil(header.compression.lines_per_block()) as usize,
+        };
+
+        // Mipmap levels follow the full resolution level in the offset table, and are ignored.
+        let offsets = self.read_vec(num_blocks, Self::read_u64)?;
+
+        let mut planes = header
+            .channels
+            .iter()
+            .enumerate()
+            .map(|(index, _)| {
+                (colors.contains(&index) || alpha == Some(index))
+                    .then(|| vec![0.0; width as usize * height as usize])
+            })
+            .collect::<Vec<_>>();
+
+        for offset in offsets {
+            self.cursor = usize::try_from(offset)?;
+            ensure!(
+                self.cursor < self.data.len(),
+                "EXR block offset is out of bounds."
+            );
+
+            let block = self.read_block_position(&header)?;
+
+            let size = usize::try_from(self.read_i32()?)?;
+            let bytes_per_line = header
+                .channels
+                .iter()
+                .map(|channel| channel.pixel_type.size() * block.width as usize)
+                .sum::<usize>();
+
+            let data = self.read_slice(size)?;
+            let bytes = decompress(
+                header.compression,
+                data,
+                bytes_per_line * block.height as usize,
+            )?;
+
+            // Each line of the block holds all of the line's samples of one channel, then the
+            // next channel's.
+            for (line, line_bytes) in bytes.chunks_exact(bytes_per_line).enumerate() {
+                let mut channel_bytes = line_bytes;
+
+                for (channel, plane) in header.channels.iter().zip(&mut planes) {
+                    let (samples, rest) =
+                        channel_bytes.split_at(channel.pixel_type.size() * block.width as usize);
+                    channel_bytes = rest;
+
+                    let Some(plane) = plane else {
+                        continue;
+                    };
+
+                    let start = (block.y as usize + line) * width as usize + block.x as usize;
+                    let row = &mut plane[start..start + block.width as usize];
+
+                    for (sample, bytes) in row
+                        .iter_mut()
+                        .zip(samples.chunks_exact(channel.pixel_type.size()))
+                    {
+                        *sample = read_sample(channel.pixel_type, bytes);
+                    }
+                }
+            }
+        }
+
+        let sample = |channel: usize, index: usize| {
+            planes[channel].as_ref().map_or(0.0, |plane| plane[index])
+        };
+        let num_pixels = width as usize * height as usize;
+
+        let image = match alpha {
+            Some(alpha) => {
+                let samples = (0..num_pixels)
+                    .flat_map(|index| {
+                        let alpha = sample(alpha, index);
+
+                        // Colors are premultiplied by alpha. Fully transparent pixels may
+                        // still emit light, and are kept as is.
+                        let unpremultiply =
+                            |color: f32| if alpha > 0.0 { color / alpha } else { color };
+
+                        [
+                            unpremultiply(sample(colors[0], index)),
+                            unpremultiply(sample(colors[1], index)),
+                            unpremultiply(sample(colors[2], index)),
+                            alpha,
+                        ]
+                    })
+                    .collect();
+
+                DynamicImageBuffer::Rgba32F(ImageBuffer::from_raw(width, height, samples)?)
+            }
+            None => {
+                let samples = (0..num_pixels)
+                    .flat_map(|index| colors.map(|channel| sample(channel, index)))
+                    .collect();
+
+                DynamicImageBuffer::Rgb32F(ImageBuffer::from_raw(width, height, samples)?)
+            }
+        };
+
+        Ok(Exr { header, image })
+    }
+
+    /// Reads the magic number, version and the attributes up to an empty name.
+    fn parse_header(&mut self) -> Result<ExrHeader> {
+        ensure!(
+            self.read_slice(EXR_MAGIC.len())? == EXR_MAGIC,
+            "Expected an OpenEXR magic number."
+        );
+
+        let version = self.read_u32()?;
+        ensure!(
+            version & 0xFF == EXR_VERSION,
+            "Unsupported OpenEXR version: {}",
+            version & 0xFF
+        );
+        ensure!(
+            version & (NON_IMAGE | MULTI_PART) == 0,
+            "Multi-part and deep OpenEXR images are unsupported."
+        );
+
+        let (mut channels, mut compression, mut data_window, mut tiles) = (None, None, None, None);
+
+        loop {
+            let name = self.read_name()?;
+            if name.is_empty() {
+                break;
+            }
+
+            let _type_name = self.read_name()?;
+            let size = usize::try_from(self.read_i32()?)?;
+            let mut value = ExrDecoder::new(self.read_slice(size)?);
+
+            match name {
+                "channels" => channels = Some(value.read_channels()?),
+                "compression" => compression = Some(Compression::try_from(value.read_u8()?)?),
+                "dataWindow" => {
+                    data_window = Some(DataWindow {
+                        x_min: value.read_i32()?,
+                        y_min: value.read_i32()?,
+                        x_max: value.read_i32()?,
+                        y_max: value.read_i32()?,
+                    })
+                }
+                "tiles" => {
+                    tiles = Some(TileDescription {
+                        width: value.read_u32()?,
+                        height: value.read_u32()?,
+                    })
+                }
+                // Other attributes describe how to display or interpret the pixels.
+                _ => {}
+            }
+        }
+
+        let missing = |attribute: &str| anyhow!("EXR header is missing {}.", attribute);
+
+        let channels = channels.ok_or_else(|| missing("channels"))?;
+        let data_window = data_window.ok_or_else(|| missing("dataWindow"))?;
+
+        let tiles = if version & SINGLE_PART_TILED != 0 {
+            let tiles = tiles.ok_or_else(|| missing("tiles"))?;
+            ensure!(
+                tiles.width > 0 && tiles.height > 0,
+                "Invalid EXR tile size."
+            );
+
+            Some(tiles)
+        } else {
+            None
+        };
+
+        ensure!(
+            data_window.x_min <= data_window.x_max && data_window.y_min <= data_window.y_max,
+            "Invalid EXR data window."
+        );
+        ensure!(
+            data_window.width() as u64 * data_window.height() as u64 <= MAX_PIXELS,
+            "EXR image is too large: {}x{}",
+            data_window.width(),
+            data_window.height()
+        );
+        ensure!(
+            channels
+                .iter()
+                .all(|channel| channel.x_sampling == 1 && channel.y_sampling == 1),
+            "Subsampled EXR channels are unsupported."
+        );
+
+        Ok(ExrHeader {
+            channels,
+            compression: compression.ok_or_else(|| missing("compression"))?,
+            data_window,
+            tiles,
+        })
+    }
+
+    /// Reads a channel list, terminated by an empty name.
+    fn read_channels(&mut self) -> Result<Vec<Channel>> {
+        let mut channels = Vec::new();
+
+        loop {
+            let name = self.read_name()?;
+            if name.is_empty() {
+                return Ok(channels);
+            }
+
+            let pixel_type = PixelType::try_from(self.read_i32()?)?;
+            // Whether the channel is perceptually linear, then three reserved bytes.
+            self.read_slice(4)?;
+
+            channels.push(Channel {
+                name: name.to_string(),
+                pixel_type,
+                x_sampling: self.read_i32()?,
+                y_sampling: self.read_i32()?,
+            });
+        }
+    }
+
+    /// Reads the coordinates that start a block, and clips the block to the data window.
+    fn read_block_position(&mut self, header: &ExrHeader) -> Result<Block> {
+        let (width, height) = (header.data_window.width(), header.data_window.height());
+
+        let (x, y, block_width, block_height) = match header.tiles {
+            Some(tiles) => {
+                let (tile_x, tile_y) = (self.read_i32()?, self.read_i32()?);
+                let (level_x, level_y) = (self.read_i32()?, self.read_i32()?);
+                ensure!(
+                    level_x == 0 && level_y == 0,
+                    "Expected a full resolution EXR tile."
+                );
+
+                let x = u32::try_from(tile_x)?.checked_mul(tiles.width);
+                let y = u32::try_from(tile_y)?.checked_mul(tiles.height);
+
+                match (x, y) {
+                    (Some(x), Some(y)) if x < width && y < height => {
+                        (x, y, tiles.width, tiles.height)
+                    }
+                    _ => return Err(anyhow!("EXR tile is outside the image.")),
+                }
+            }
+            None => {
+                let lines_per_block = header.compression.lines_per_block();
+                let y = self.read_i32()? as i64 - header.data_window.y_min as i64;
+
+                ensure!(
+                    (0..height as i64).contains(&y) && (y as u32).is_multiple_of(lines_per_block),
+                    "EXR scanline block is outside the image."
+                );
+
+                (0, y as u32, width, lines_per_block)
+            }
+        };
+
+        Ok(Block {
+            x,
+            y,
+            width: block_width.min(width - x),
+            height: block_height.min(height - y),
+        })
+    }
+
+    /// Reads a null-terminated name.
+    fn read_name(&mut self) -> Result<&'a str> {
+        let len = self.data[self.cursor..]
+            .iter()
+            .take(MAX_NAME_LEN + 1)
+            .position(|&b| b == 0)
+            .ok_or_else(|| anyhow!("Invalid EXR name."))?;
+
+        let name = std::str::from_utf8(self.read_slice(len)?)?;
+        self.cursor += 1;
+
+        Ok(name)
+    }
+
+    impl_read_le_for_datatype!(read_u8, u8);
+    impl_read_le_for_datatype!(read_u32, u32);
+    impl_read_le_for_datatype!(read_i32, i32);
+    impl_read_le_for_datatype!(read_u64, u64);
+
+    impl_read_slice!();
+}
+
+fn read_sample(pixel_type: PixelType, bytes: &[u8]) -> f32 {
+    match pixel_type {
+        PixelType::Uint => u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as f32,
+        PixelType::Half => half_to_f32(u16::from_le_bytes([bytes[0], bytes[1]])),
+        PixelType::Float => f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]),
+    }
+}
+
+/// Widens an IEEE 754 half precision float, which every value of is exact in single precision.
+fn half_to_f32(bits: u16) -> f32 {
+    let sign = if bits & 0x8000 != 0 { -1.0 } else { 1.0 };
+    let exponent = (bits >> 10) & 0x1F;
+    let mantissa = (bits & 0x3FF) as f32;
+
+    sign * match exponent {
+        // Subnormal.
+        0 => mantissa * (-24f32).exp2(),
+        31 if mantissa == 0.0 => f32::INFINITY,
+        31 => f32::NAN,
+        _ => (1.0 + mantissa / 1024.0) * (exponent as f32 - 15.0).exp2(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{grammar::ImageExt, ToneMapper};
+    use image::{ImageFormat, Rgb32FImage, Rgba32FImage};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_decode_scanlines_against_image_crate() -> Result<()> {
+        let path = "./tests/exr/rgb_half_zip.exr";
+        let exr = ExrDecoder::new(&std::fs::read(path)?).decode()?;
+        let reference = image::open(path)?.to_rgb32f();
+
+        assert_eq!(exr.header().compression, Compression::Zip);
+        assert_eq!(exr.header().tiles, None);
+        assert_eq!(exr.dimensions(), reference.dimensions());
+
+        let DynamicImageBuffer::Rgb32F(image) = exr.image() else {
+            panic!("Expected an RGB image.");
+        };
+        assert_eq!(image.as_raw(), reference.as_raw());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_tiles_against_image_crate() -> Result<()> {
+        // The `image` crate writes 64x64 tiles with run-length encoding, leaving partial tiles.
+        let rgb = Rgb32FImage::from_fn(100, 70, |x, y| {
+            image::Rgb([
+                x as f32 / 10.0,
+                (y % 7) as f32,
+                if x < 50 { 1.0 } else { 0.5 },
+            ])
+        });
+
+        let mut encoded = Vec::new();
+        rgb.write_to(&mut Cursor::new(&mut encoded), ImageFormat::OpenExr)?;
+
+        let exr = ExrDecoder::new(&encoded).decode()?;
+        assert!(exr.header().tiles.is_some());
+
+        let DynamicImageBuffer::Rgb32F(image) = exr.image() else {
+            panic!("Expected an RGB image.");
+        };
+        assert_eq!(image.as_raw(), rgb.as_raw());
+
+        // Alpha is stored straight by the encoder, so unpremultiplying divides colors by it.
+        let rgba = Rgba32FImage::from_fn(70, 65, |x, y| {
+            image::Rgba([x as f32, y as f32, 2.0, if x < 35 { 1.0 } else { 0.5 }])
+        });
+
+        let mut encoded = Vec::new();
+        rgba.write_to(&mut Cursor::new(&mut encoded), ImageFormat::OpenExr)?;
+
+        let exr = ExrDecoder::new(&encoded).decode()?;
+        let DynamicImageBuffer::Rgba32F(image) = exr.image() else {
+            panic!("Expected an RGBA image.");
+        };
+
+        for (pixel, reference) in image.pixels().iter().zip(rgba.pixels()) {
+            let alpha = reference.0[3];
+            assert_eq!(pixel.0[3], alpha);
+
+            for (&color, &reference) in pixel.0[..3].iter().zip(&reference.0[..3]) {
+                assert_eq!(color, reference / alpha);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_luminance() -> Result<()> {
+        let exr = ExrDecoder::new(&std::fs::read(
+            "./tests/exr/luminance_float_decreasing.exr",
+        )?)
+        .decode()?;
+        assert_eq!(exr.header().compression, Compression::None);
+        assert_eq!(
+            exr.header().data_window,
+            DataWindow {
+                x_min: 3,
+                y_min: -2,
+                x_max: 11,
+                y_max: 3
+            }
+        );
+
+        // Blocks are stored bottom up, and the luminance is repeated.
+        let DynamicImageBuffer::Rgb32F(image) = exr.image() else {
+            panic!("Expected an RGB image.");
+        };
+
+        for (index, pixel) in image.pixels().iter().enumerate() {
+            let (x, y) = (index % 9, index / 9);
+            let luminance = (2 * x + y) as f32 / 4.0;
+
+            assert_eq!(pixel.0, [luminance; 3], "({x}, {y})");
+        }
+
+        assert_eq!(
+            exr.tone_mapped_rgba8(ToneMapper::default()).as_deref(),
+            Some(exr.rgba8().as_ref())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_half_to_f32() {
+        assert_eq!(half_to_f32(0x3C00), 1.0);
+        assert_eq!(half_to_f32(0xC000), -2.0);
+        assert_eq!(half_to_f32(0x7BFF), 65504.0);
+        assert_eq!(half_to_f32(0x0001), 2f32.powi(-24));
+        assert_eq!(half_to_f32(0x7C00), f32::INFINITY);
+        assert!(half_to_f32(0x7E00).is_nan());
+    }
+
+    #[test]
+    fn test_decode_invalid() -> Result<()> {
+        let data = std::fs::read("./tests/exr/rgb_half_zip.exr")?;
+
+        assert!(ExrDecoder::new(&data[..data.len() - 10]).decode().is_err());
+        assert!(ExrDecoder::new(&data[..100]).decode().is_err());
+
+        let mut multi_part = data.clone();
+        multi_part[5] |= (MULTI_PART >> 8) as u8;
+        assert!(ExrDecoder::new(&multi_part).decode().is_err());
+
+        // PIZ compression.
+        let mut piz = data;
+        let attribute = b"compression\0compression\0\x01\0\0\0";
+        let value = piz
+            .windows(attribute.len())
+            .position(|window| window == attribute)
+            .expect("compression attribute")
+            + attribute.len();
+        piz[value] = Compression::Piz as u8;
+        assert!(ExrDecoder::new(&piz).decode().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,208 @@
+use crate::image::{
+    grammar::{ColorType, ImageExt},
+    DynamicImageBuffer, ToneMapper,
+};
+use anyhow::bail;
+use std::borrow::Cow;
+
+pub const EXR_MAGIC: [u8; 4] = [0x76, 0x2F, 0x31, 0x01];
+
+/// The file format version, in the low byte of the version field.
+pub const EXR_VERSION: u32 = 2;
+
+/// Flags of the version field. Single-part scanline images set none of them.
+pub const SINGLE_PART_TILED: u32 = 0x200;
+pub const LONG_NAMES: u32 = 0x400;
+pub const NON_IMAGE: u32 = 0x800;
+pub const MULTI_PART: u32 = 0x1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PixelType {
+    Uint = 0,
+    Half = 1,
+    Float = 2,
+}
+
+impl PixelType {
+    pub const fn size(&self) -> usize {
+        match self {
+            Self::Half => 2,
+            Self::Uint | Self::Float => 4,
+        }
+    }
+}
+
+impl TryFrom<i32> for PixelType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: i32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Uint,
+            1 => Self::Half,
+            2 => Self::Float,
+            _ => bail!("Invalid EXR pixel type: {value}"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Channel {
+    pub name: String,
+    pub pixel_type: PixelType,
+    pub x_sampling: i32,
+    pub y_sampling: i32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None = 0,
+    Rle = 1,
+    /// Zlib, one scanline per block.
+    Zips = 2,
+    /// Zlib, 16 scanlines per block.
+    Zip = 3,
+    Piz = 4,
+    Pxr24 = 5,
+    B44 = 6,
+    B44a = 7,
+    Dwaa = 8,
+    Dwab = 9,
+}
+
+impl Compression {
+    /// How many scanlines each block of a scanline image holds.
+    pub const fn lines_per_block(&self) -> u32 {
+        match self {
+            Self::None | Self::Rle | Self::Zips => 1,
+            Self::Zip | Self::Pxr24 => 16,
+            Self::Piz | Self::B44 | Self::B44a | Self::Dwaa => 32,
+            Self::Dwab => 256,
+        }
+    }
+}
+
+impl TryFrom<u8> for Compression {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::None,
+            1 => Self::Rle,
+            2 => Self::Zips,
+            3 => Self::Zip,
+            4 => Self::Piz,
+            5 => Self::Pxr24,
+            6 => Self::B44,
+            7 => Self::B44a,
+            8 => Self::Dwaa,
+            9 => Self::Dwab,
+            _ => bail!("Invalid EXR compression: {value}"),
+        })
+    }
+}
+
+/// The inclusive bounds of the pixels stored, which may start anywhere.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DataWindow {
+    pub x_min: i32,
+    pub y_min: i32,
+    pub x_max: i32,
+    pub y_max: i32,
+}
+
+impl DataWindow {
+    pub const fn width(&self) -> u32 {
+        (self.x_max as i64 - self.x_min as i64 + 1) as u32
+    }
+
+    pub const fn height(&self) -> u32 {
+        (self.y_max as i64 - self.y_min as i64 + 1) as u32
+    }
+}
+
+/// The tile size of a tiled image. Only the full resolution level is read.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TileDescription {
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExrHeader {
+    /// In the order they are stored: sorted by name.
+    pub channels: Vec<Channel>,
+    pub compression: Compression,
+    pub data_window: DataWindow,
+    pub tiles: Option<TileDescription>,
+}
+
+/// A single-part OpenEXR image, with its color channels as linear floating point RGB and alpha
+/// unpremultiplied.
+#[derive(Debug)]
+pub struct Exr {
+    pub(crate) header: ExrHeader,
+    pub(crate) image: DynamicImageBuffer,
+}
+
+impl Exr {
+    pub const fn header(&self) -> &ExrHeader {
+        &self.header
+    }
+
+    pub const fn image(&self) -> &DynamicImageBuffer {
+        &self.image
+    }
+
+    fn samples(&self) -> (&[f32], usize) {
+        match &self.image {
+            DynamicImageBuffer::Rgba32F(image) => (image.as_raw(), 4),
+            DynamicImageBuffer::Rgb32F(image) => (image.as_raw(), 3),
+            _ => unreachable!("EXR images are decoded to floating point RGB or RGBA"),
+        }
+    }
+}
+
+impl ImageExt for Exr {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.image.color_type()
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        let b = self
+            .rgba8()
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+
+    /// The pixels mapped with the default tone mapper.
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        let (samples, num_channels) = self.samples();
+        Cow::from(ToneMapper::default().map_to_rgba8(samples, num_channels))
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        let b = self
+            .rgba8()
+            .chunks_exact(4)
+            .map(|p| u32::from_be_bytes([p[3], p[0], p[1], p[2]]))
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+
+    fn tone_mapped_rgba8(&self, tone_mapper: ToneMapper) -> Option<Cow<'_, [u8]>> {
+        let (samples, num_channels) = self.samples();
+        Some(Cow::from(tone_mapper.map_to_rgba8(samples, num_channels)))
+    }
+}
@@ -0,0 +1,6 @@
+mod compression;
+mod decoder;
+
+pub mod grammar;
+
+pub use decoder::*;
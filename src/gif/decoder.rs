@@ -0,0 +1,351 @@
+use crate::{
+    gif::{
+        grammar::{
+            composite, DisposalMethod, Frame, Gif, GraphicsControl, ImageDescriptor, Repeat,
+        },
+        lzw,
+    },
+    image::{Rgba, Rgba8},
+    impl_read_le_for_datatype, impl_read_slice,
+};
+use anyhow::{bail, ensure, Result};
+
+const EXTENSION_INTRODUCER: u8 = 0x21;
+const IMAGE_SEPARATOR: u8 = 0x2C;
+const TRAILER: u8 = 0x3B;
+
+const GRAPHICS_CONTROL_LABEL: u8 = 0xF9;
+const APPLICATION_LABEL: u8 = 0xFF;
+
+/// Frames can cover any part of the canvas, so the data doesn't bound its size.
+const MAX_CANVAS_PIXELS: u64 = 400_000_000;
+
+#[derive(Debug)]
+pub struct GifDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> GifDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Gif> {
+        let signature = self.read_slice(6)?;
+        ensure!(
+            signature == b"GIF87a" || signature == b"GIF89a",
+            "Expected a GIF signature."
+        );
+
+        let width = self.read_u16()? as u32;
+        let height = self.read_u16()? as u32;
+        let flags = self.read_u8()?;
+        let _background_index = self.read_u8()?;
+        let _pixel_aspect_ratio = self.read_u8()?;
+
+        ensure!(width > 0 && height > 0, "Invalid GIF dimensions.");
+        ensure!(
+            width as u64 * height as u64 <= MAX_CANVAS_PIXELS,
+            "GIF is too large: {width}x{height}"
+        );
+
+        let global_color_table = if flags & 0x80 != 0 {
+            Some(self.parse_color_table(flags)?)
+        } else {
+            None
+        };
+
+        let mut frames = Vec::new();
+        let mut repeat = Repeat::default();
+        let mut graphics_control = None;
+
+        // Files truncated after a complete frame are accepted.
+        while self.cursor < self.data.len() {
+            match self.read_u8()? {
+                EXTENSION_INTRODUCER => match self.read_u8()? {
+                    GRAPHICS_CONTROL_LABEL => {
+                        graphics_control = Some(self.parse_graphics_control()?);
+                    }
+                    APPLICATION_LABEL => {
+                        if let Some(application_repeat) = self.parse_application_extension()? {
+                            repeat = application_repeat;
+                        }
+                    }
+                    _ => {
+                        self.read_sub_blocks()?;
+                    }
+                },
+                IMAGE_SEPARATOR => {
+                    let graphics_control = graphics_control.take().unwrap_or_default();
+                    let frame =
+                        self.parse_frame(global_color_table.as_deref(), graphics_control)?;
+
+                    frames.push(frame);
+                }
+                TRAILER => break,
+                block => bail!("Unrecognized GIF block: {:#04X}", block),
+            }
+        }
+
+        ensure!(!frames.is_empty(), "GIF has no frames.");
+
+        let mut has_alpha = false;
+        composite(width, height, &frames, |_, canvas| {
+            has_alpha |= canvas.pixels().iter().any(|p| p.0[3] != 255);
+        });
+
+        Ok(Gif {
+            width,
+            height,
+            frames,
+            repeat,
+            has_alpha,
+        })
+    }
+
+    /// Reads a color table whose size is given by the low 3 bits of `flags`.
+    fn parse_color_table(&mut self, flags: u8) -> Result<Vec<Rgba8>> {
+        let num_colors = 2 << (flags & 0x07);
+
+        self.read_slice(num_colors * 3).map(|table| {
+            table
+                .chunks_exact(3)
+                .map(|c| Rgba([c[0], c[1], c[2], 255]))
+                .collect()
+        })
+    }
+
+    fn parse_graphics_control(&mut self) -> Result<GraphicsControl> {
+        let block = self.read_sub_blocks()?;
+        ensure!(block.len() >= 4, "Graphics control extension is truncated.");
+
+        let flags = block[0];
+        let delay = u16::from_le_bytes([block[1], block[2]]);
+        let transparent_index = (flags & 0x01 != 0).then_some(block[3]);
+
+        Ok(GraphicsControl {
+            disposal: DisposalMethod::from((flags >> 2) & 0x07),
+            delay,
+            transparent_index,
+        })
+    }
+
+    /// Returns the loop count of a NETSCAPE2.0 (or ANIMEXTS1.0) extension, skipping any other
+    /// application's data.
+    fn parse_application_extension(&mut self) -> Result<Option<Repeat>> {
+        let block_size = self.read_u8()? as usize;
+        let identifier = self.read_slice(block_size)?;
+        let data = self.read_sub_blocks()?;
+
+        if identifier != b"NETSCAPE2.0" && identifier != b"ANIMEXTS1.0" {
+            return Ok(None);
+        }
+
+        let repeat = match data.as_slice() {
+            [0x01, 0, 0, ..] => Repeat::Infinite,
+            &[0x01, low, high, ..] => Repeat::Finite(u16::from_le_bytes([low, high])),
+            _ => return Ok(None),
+        };
+
+        Ok(Some(repeat))
+    }
+
+    fn parse_frame(
+        &mut self,
+        global_color_table: Option<&[Rgba8]>,
+        graphics_control: GraphicsControl,
+    ) -> Result<Frame> {
+        let left = self.read_u16()?;
+        let top = self.read_u16()?;
+        let width = self.read_u16()?;
+        let height = self.read_u16()?;
+        let flags = self.read_u8()?;
+
+        let descriptor = ImageDescriptor {
+            left,
+            top,
+            width,
+            height,
+            interlaced: flags & 0x40 != 0,
+        };
+
+        let local_color_table = if flags & 0x80 != 0 {
+            Some(self.parse_color_table(flags)?)
+        } else {
+            None
+        };
+
+        let Some(color_table) = local_color_table.or_else(|| global_color_table.map(<[_]>::to_vec))
+        else {
+            bail!("GIF frame has no color table.");
+        };
+
+        let min_code_size = self.read_u8()?;
+        let data = self.read_sub_blocks()?;
+
+        // Frames are composited when displayed, so only their indices are kept.
+        let indices = lzw::decode(min_code_size, &data, width as usize * height as usize)?;
+
+        Ok(Frame {
+            indices,
+            color_table,
+            descriptor,
+            graphics_control,
+        })
+    }
+
+    /// Concatenates a sequence of data sub-blocks, up to the empty block that terminates it.
+    fn read_sub_blocks(&mut self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+
+        loop {
+            let block_size = self.read_u8()? as usize;
+            if block_size == 0 {
+                return Ok(data);
+            }
+
+            data.extend_from_slice(self.read_slice(block_size)?);
+        }
+    }
+
+    impl_read_le_for_datatype!(read_u8, u8);
+    impl_read_le_for_datatype!(read_u16, u16);
+
+    impl_read_slice!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::grammar::{ColorType, ImageExt};
+    use image::{codecs::gif, AnimationDecoder};
+    use std::{fs::File, io::BufReader, time::Duration};
+
+    /// Decodes `path` and compares every composited frame against the `image` crate's.
+    fn compare_gif(path: &str) -> Result<Gif> {
+        let reference = gif::GifDecoder::new(BufReader::new(File::open(path)?))?
+            .into_frames()
+            .collect_frames()?;
+
+        let content = std::fs::read(path)?;
+        let gif = GifDecoder::new(&content).decode()?;
+
+        assert_eq!(gif.frames().len(), reference.len(), "{path}");
+
+        for (i, (frame, expected)) in gif.frames().iter().zip(&reference).enumerate() {
+            assert_eq!(
+                gif.composite(i).expect("frame").as_raw(),
+                expected.buffer().as_raw().as_slice(),
+                "{path}, frame {i}"
+            );
+            assert_eq!(frame.delay(), Duration::from(expected.delay()), "{path}");
+        }
+
+        Ok(gif)
+    }
+
+    #[test]
+    fn test_decode_still() -> Result<()> {
+        // 256 colors, so codes reach 12 bits and the table is cleared.
+        let gif = compare_gif("./tests/gif/still.gif")?;
+
+        assert!(!gif.is_animated());
+        assert_eq!(gif.dimensions(), (97, 61));
+        assert_eq!(gif.color_type(), ColorType::RGB);
+        assert_eq!(gif.frame_count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_interlaced() -> Result<()> {
+        let gif = compare_gif("./tests/gif/interlaced.gif")?;
+
+        assert!(gif.frames()[0].descriptor().interlaced);
+        assert_eq!(gif.color_type(), ColorType::RGBA);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_animated() -> Result<()> {
+        let gif = compare_gif("./tests/gif/animated.gif")?;
+
+        assert!(gif.is_animated());
+        assert_eq!(gif.repeat(), Repeat::Infinite);
+        assert_eq!(
+            gif.frames()
+                .iter()
+                .map(|frame| frame.disposal())
+                .collect::<Vec<_>>(),
+            [
+                DisposalMethod::Keep,
+                DisposalMethod::RestoreBackground,
+                DisposalMethod::RestorePrevious,
+                DisposalMethod::None,
+            ]
+        );
+
+        assert_eq!(gif.frame_count(), 4);
+        assert_eq!(gif.frame_rgba8(0), Some(gif.rgba8()));
+        assert_eq!(
+            gif.frame_rgba8(3).as_deref(),
+            gif.composite(3).as_ref().map(|canvas| canvas.as_raw())
+        );
+        assert_eq!(gif.frame_rgba8(4), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_interlaced_rows() {
+        let rows = |height| {
+            Frame {
+                indices: Vec::new(),
+                color_table: Vec::new(),
+                descriptor: ImageDescriptor {
+                    left: 0,
+                    top: 0,
+                    width: 1,
+                    height,
+                    interlaced: true,
+                },
+                graphics_control: GraphicsControl::default(),
+            }
+            .rows()
+            .collect::<Vec<_>>()
+        };
+
+        assert_eq!(rows(10), [0, 8, 4, 2, 6, 1, 3, 5, 7, 9]);
+        assert_eq!(rows(1), [0]);
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        for data in [
+            &b"GIF89a"[..],
+            b"GIF90a\x01\0\x01\0\0\0\0",
+            b"GIF89a\x01\0\x01\0\0\0\0\x3B",
+        ] {
+            assert!(GifDecoder::new(data).decode().is_err());
+        }
+    }
+
+    #[test]
+    fn test_decode_oversized() -> Result<()> {
+        // A 65535x65535 canvas is rejected before anything is allocated for it.
+        let mut data = b"GIF89a\xFF\xFF\xFF\xFF\x80\0\0\0\0\0\xFF\xFF\xFF".to_vec();
+        assert!(GifDecoder::new(&data).decode().is_err());
+
+        // A 65535x65535 frame on a 1x1 canvas, whose data holds a single index.
+        data[6..10].copy_from_slice(&[1, 0, 1, 0]);
+        data.extend_from_slice(b"\x2C\0\0\0\0\xFF\xFF\xFF\xFF\0\x02\x01\x44\0\x3B");
+
+        let gif = GifDecoder::new(&data).decode()?;
+        assert_eq!(gif.dimensions(), (1, 1));
+        assert_eq!(gif.rgba8().as_ref(), [0, 0, 0, 255]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,315 @@
+use crate::{
+    gif::{
+        grammar::{DisposalMethod, Repeat},
+        lzw,
+        quantize::{dither, quantize, PaletteMapper},
+    },
+    image::grammar::{ImageEncoder, ImageExt},
+};
+use anyhow::{bail, ensure, Result};
+use std::{io::Write, time::Duration};
+
+/// Pixels with less alpha are written as transparent.
+const ALPHA_THRESHOLD: u8 = 128;
+
+/// A frame of an animation and how long it is displayed.
+#[derive(Clone, Copy)]
+pub struct AnimationFrame<'a> {
+    pub image: &'a dyn ImageExt,
+    pub delay: Duration,
+}
+
+pub struct GifEncoder<W: Write> {
+    writer: W,
+    repeat: Repeat,
+    dither: bool,
+}
+
+impl<W: Write> GifEncoder<W> {
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            repeat: Repeat::Infinite,
+            dither: false,
+        }
+    }
+
+    /// How many times animations play. Animations loop forever by default.
+    pub const fn repeat(mut self, repeat: Repeat) -> Self {
+        self.repeat = repeat;
+        self
+    }
+
+    /// Whether to dither frames with more colors than fit in a palette.
+    pub const fn dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    pub fn encode(&mut self, image: &dyn ImageExt) -> Result<()> {
+        self.encode_frames(&[AnimationFrame {
+            image,
+            delay: Duration::ZERO,
+        }])
+    }
+
+    /// Writes `frames`, which must share their dimensions, as a GIF89a animation. Each frame gets
+    /// its own palette of up to 256 colors.
+    pub fn encode_frames(&mut self, frames: &[AnimationFrame]) -> Result<()> {
+        let Some(first) = frames.first() else {
+            bail!("A GIF needs at least one frame.");
+        };
+
+        let (width, height) = first.image.dimensions();
+        ensure!(
+            width > 0 && height > 0 && width <= u16::MAX as u32 && height <= u16::MAX as u32,
+            "Invalid GIF dimensions: {}x{}",
+            width,
+            height
+        );
+        ensure!(
+            frames
+                .iter()
+                .all(|frame| frame.image.dimensions() == (width, height)),
+            "GIF frames must share their dimensions."
+        );
+
+        let mut buffer = b"GIF89a".to_vec();
+        buffer.extend_from_slice(&(width as u16).to_le_bytes());
+        buffer.extend_from_slice(&(height as u16).to_le_bytes());
+        // No global color table, background color 0 and no aspect ratio.
+        buffer.extend_from_slice(&[0, 0, 0]);
+
+        // Decoders play animations without the extension once.
+        if frames.len() > 1 && self.repeat != Repeat::Finite(0) {
+            let loop_count = match self.repeat {
+                Repeat::Infinite => 0,
+                Repeat::Finite(count) => count,
+            };
+
+            buffer.extend_from_slice(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01");
+            buffer.extend_from_slice(&loop_count.to_le_bytes());
+            buffer.push(0);
+        }
+
+        for frame in frames {
+            self.write_frame(&mut buffer, frame, width as usize);
+        }
+
+        buffer.push(0x3B);
+
+        self.writer.write_all(&buffer)?;
+
+        Ok(())
+    }
+
+    fn write_frame(&self, buffer: &mut Vec<u8>, frame: &AnimationFrame, width: usize) {
+        let rgba = frame.image.straight_rgba8();
+        let pixels = rgba
+            .chunks_exact(4)
+            .map(|p| [p[0], p[1], p[2]])
+            .collect::<Vec<_>>();
+        let transparent = rgba
+            .chunks_exact(4)
+            .map(|p| p[3] < ALPHA_THRESHOLD)
+            .collect::<Vec<_>>();
+
+        let has_transparency = transparent.contains(&true);
+        let max_colors = if has_transparency { 255 } else { 256 };
+
+        let opaque_pixels = pixels
+            .iter()
+            .zip(&transparent)
+            .filter(|(_, &transparent)| !transparent)
+            .map(|(&pixel, _)| pixel);
+
+        let mut palette = quantize(opaque_pixels, max_colors);
+
+        // Transparent pixels take the last index.
+        let transparent_index = has_transparency.then(|| {
+            palette.push([0, 0, 0]);
+            (palette.len() - 1) as u8
+        });
+
+        let mut mapper = PaletteMapper::new(&palette);
+
+        let indices = if self.dither {
+            dither(
+                &pixels,
+                width,
+                &mut mapper,
+                |i| transparent[i],
+                transparent_index.unwrap_or(0),
+            )
+        } else {
+            pixels
+                .iter()
+                .zip(&transparent)
+                .map(|(&pixel, &transparent)| match transparent_index {
+                    Some(index) if transparent => index,
+                    _ => mapper.index(pixel),
+                })
+                .collect()
+        };
+
+        // Frames cover the whole canvas, so each is cleared for the next to keep transparent
+        // pixels from showing the frames before it.
+        let delay = (frame.delay.as_millis().div_ceil(10)).min(u16::MAX as u128) as u16;
+        let flags =
+            (DisposalMethod::RestoreBackground as u8) << 2 | transparent_index.is_some() as u8;
+
+        buffer.extend_from_slice(&[0x21, 0xF9, 0x04, flags]);
+        buffer.extend_from_slice(&delay.to_le_bytes());
+        buffer.extend_from_slice(&[transparent_index.unwrap_or(0), 0]);
+
+        // Color tables hold a power of 2 colors, at least 2.
+        let table_bits = (palette.len().max(2) as u32)
+            .next_power_of_two()
+            .trailing_zeros();
+
+        buffer.push(0x2C);
+        buffer.extend_from_slice(&[0, 0, 0, 0]);
+        buffer.extend_from_slice(&(width as u16).to_le_bytes());
+        buffer.extend_from_slice(&((pixels.len() / width) as u16).to_le_bytes());
+        buffer.push(0x80 | (table_bits - 1) as u8);
+
+        for i in 0..1 << table_bits {
+            buffer.extend_from_slice(palette.get(i).unwrap_or(&[0, 0, 0]));
+        }
+
+        let min_code_size = table_bits.max(2) as u8;
+        buffer.push(min_code_size);
+
+        for block in lzw::encode(min_code_size, &indices).chunks(255) {
+            buffer.push(block.len() as u8);
+            buffer.extend_from_slice(block);
+        }
+        buffer.push(0);
+    }
+}
+
+impl<W: Write> ImageEncoder for GifEncoder<W> {
+    fn write_image(&mut self, image: &dyn ImageExt) -> Result<()> {
+        self.encode(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gif::GifDecoder,
+        image::{DynamicImageBuffer, ImageBuffer, Rgb, Rgb8, Rgba},
+        png::PngDecoder,
+    };
+    use image::{codecs::gif, AnimationDecoder};
+
+    fn mean_absolute_error(a: &[u8], b: &[u8]) -> f64 {
+        assert_eq!(a.len(), b.len());
+
+        a.iter()
+            .zip(b)
+            .map(|(&a, &b)| a.abs_diff(b) as f64)
+            .sum::<f64>()
+            / a.len() as f64
+    }
+
+    #[test]
+    fn test_encode_few_colors_losslessly() -> Result<()> {
+        let image =
+            ImageBuffer::from_fn(31, 17, |x, y| Rgb([(x * 8) as u8, (y % 4 * 60) as u8, 7]));
+
+        let mut encoded = Vec::new();
+        GifEncoder::new(&mut encoded).encode(&image)?;
+
+        let decoded = GifDecoder::new(&encoded).decode()?;
+        assert_eq!(decoded.rgb8(), image.rgb8());
+
+        let reference = image::load_from_memory(&encoded)?.to_rgb8();
+        assert_eq!(reference.as_raw(), image.rgb8().as_ref());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_quantized() -> Result<()> {
+        let data = std::fs::read("./tests/obama.png")?;
+        let png = DynamicImageBuffer::try_from(PngDecoder::new(&data).decode()?)?;
+        let face = png.to::<Rgb8>().view(64, 64, 160, 160)?.to_image();
+
+        for dither in [false, true] {
+            let mut encoded = Vec::new();
+            GifEncoder::new(&mut encoded).dither(dither).encode(&face)?;
+
+            let reference = image::load_from_memory(&encoded)?.to_rgb8();
+            let error = mean_absolute_error(reference.as_raw(), &face.rgb8());
+            assert!(error < 2.0, "Mean absolute error too large: {error}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_animation() -> Result<()> {
+        let frames = (0..3)
+            .map(|i| {
+                ImageBuffer::from_fn(12, 9, move |x, y| {
+                    let alpha = if (x + y + i) % 5 == 0 { 0 } else { 255 };
+                    Rgba([(x * 20) as u8, (i * 100) as u8, (y * 25) as u8, alpha])
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let animation = frames
+            .iter()
+            .enumerate()
+            .map(|(i, image)| AnimationFrame {
+                image,
+                delay: Duration::from_millis(100 * (i as u64 + 1)),
+            })
+            .collect::<Vec<_>>();
+
+        let mut encoded = Vec::new();
+        GifEncoder::new(&mut encoded)
+            .repeat(Repeat::Finite(3))
+            .encode_frames(&animation)?;
+
+        let decoded = GifDecoder::new(&encoded).decode()?;
+        assert_eq!(decoded.repeat(), Repeat::Finite(3));
+
+        let reference = gif::GifDecoder::new(std::io::Cursor::new(&encoded))?
+            .into_frames()
+            .collect_frames()?;
+        assert_eq!(reference.len(), frames.len());
+
+        // Transparent pixels decode as transparent black.
+        for ((image, frame), expected) in frames.iter().zip(decoded.frames()).zip(&reference) {
+            let mut image = image.clone();
+            for pixel in image.pixels_mut() {
+                if pixel.0[3] == 0 {
+                    *pixel = Rgba([0; 4]);
+                }
+            }
+
+            assert_eq!(frame.image(), image);
+            assert_eq!(expected.buffer().as_raw(), image.as_raw());
+            assert_eq!(Duration::from(expected.delay()), frame.delay());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_requires_matching_frames() {
+        let small = ImageBuffer::<Rgb<u8>>::new(2, 2);
+        let large = ImageBuffer::<Rgb<u8>>::new(3, 2);
+
+        let frames = [&small, &large].map(|image| AnimationFrame {
+            image,
+            delay: Duration::ZERO,
+        });
+
+        assert!(GifEncoder::new(Vec::new()).encode_frames(&frames).is_err());
+        assert!(GifEncoder::new(Vec::new()).encode_frames(&[]).is_err());
+    }
+}
@@ -0,0 +1,297 @@
+use crate::image::{
+    grammar::{ColorType, ImageExt},
+    ImageBuffer, Rgba, Rgba8,
+};
+use std::{borrow::Cow, time::Duration};
+
+pub(crate) const TRANSPARENT: Rgba8 = Rgba([0, 0, 0, 0]);
+
+/// What happens to a frame's area before the next frame is drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DisposalMethod {
+    /// Unspecified, handled like `Keep`.
+    #[default]
+    None = 0,
+    Keep = 1,
+    /// Clears the area to transparent, which viewers use in place of the background color.
+    RestoreBackground = 2,
+    /// Restores the area to what it was before the frame was drawn.
+    RestorePrevious = 3,
+}
+
+impl From<u8> for DisposalMethod {
+    /// Reserved values are treated as unspecified.
+    fn from(value: u8) -> Self {
+        match value {
+            1 => Self::Keep,
+            2 => Self::RestoreBackground,
+            3 => Self::RestorePrevious,
+            _ => Self::None,
+        }
+    }
+}
+
+/// How many times an animation plays, from the NETSCAPE2.0 application extension.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Repeat {
+    /// Plays once, then repeats this many times.
+    Finite(u16),
+    Infinite,
+}
+
+impl Default for Repeat {
+    /// Without the extension, animations play once.
+    fn default() -> Self {
+        Self::Finite(0)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct GraphicsControl {
+    pub disposal: DisposalMethod,
+    /// In hundredths of a second.
+    pub delay: u16,
+    pub transparent_index: Option<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageDescriptor {
+    pub left: u16,
+    pub top: u16,
+    pub width: u16,
+    pub height: u16,
+    pub interlaced: bool,
+}
+
+/// A frame as stored: color table indices covering the area of its descriptor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    /// In the stored row order, and cut short if the data was truncated.
+    pub(crate) indices: Vec<u8>,
+    pub(crate) color_table: Vec<Rgba8>,
+    pub(crate) descriptor: ImageDescriptor,
+    pub(crate) graphics_control: GraphicsControl,
+}
+
+impl Frame {
+    /// The pixels the frame draws over its area, transparent where it leaves the canvas as is.
+    pub fn image(&self) -> ImageBuffer<Rgba8> {
+        let ImageDescriptor { width, height, .. } = self.descriptor;
+        let mut image = ImageBuffer::from_pixel(width as u32, height as u32, TRANSPARENT);
+
+        for (stored_row, y) in self.rows().enumerate() {
+            let row = image.row_mut(y);
+
+            for (x, pixel) in row.iter_mut().enumerate() {
+                if let Some(color) = self.color(stored_row * width as usize + x) {
+                    *pixel = color;
+                }
+            }
+        }
+
+        image
+    }
+
+    /// The area of the canvas the frame draws over.
+    pub const fn descriptor(&self) -> &ImageDescriptor {
+        &self.descriptor
+    }
+
+    pub const fn delay(&self) -> Duration {
+        Duration::from_millis(self.graphics_control.delay as u64 * 10)
+    }
+
+    pub const fn disposal(&self) -> DisposalMethod {
+        self.graphics_control.disposal
+    }
+
+    /// The frame's row for each stored row. Interlaced frames store every 8th row from 0, every
+    /// 8th from 4, every 4th from 2 and then every other row from 1.
+    pub(crate) fn rows(&self) -> impl Iterator<Item = u32> {
+        let height = self.descriptor.height as u32;
+        let passes: &[(u32, usize)] = if self.descriptor.interlaced {
+            &[(0, 8), (4, 8), (2, 4), (1, 2)]
+        } else {
+            &[(0, 1)]
+        };
+
+        passes
+            .iter()
+            .flat_map(move |&(start, step)| (start..height).step_by(step))
+    }
+
+    /// The color of the `i`th stored pixel, or `None` if it is transparent. Pixels missing from
+    /// truncated data take the first color, and indices outside of the table are black.
+    fn color(&self, i: usize) -> Option<Rgba8> {
+        let index = self.indices.get(i).copied().unwrap_or(0);
+
+        if self.graphics_control.transparent_index == Some(index) {
+            return None;
+        }
+
+        Some(
+            self.color_table
+                .get(index as usize)
+                .copied()
+                .unwrap_or(Rgba([0, 0, 0, 255])),
+        )
+    }
+
+    /// Draws the frame onto `canvas`, clipping it to the canvas.
+    fn draw(&self, canvas: &mut ImageBuffer<Rgba8>) {
+        let ImageDescriptor {
+            left, top, width, ..
+        } = self.descriptor;
+
+        for (stored_row, y) in self.rows().enumerate() {
+            let y = top as u32 + y;
+            if y >= canvas.height() {
+                continue;
+            }
+
+            let canvas_row = canvas.row_mut(y);
+
+            for x in 0..width as usize {
+                let Some(pixel) = canvas_row.get_mut(left as usize + x) else {
+                    break;
+                };
+
+                if let Some(color) = self.color(stored_row * width as usize + x) {
+                    *pixel = color;
+                }
+            }
+        }
+    }
+
+    /// Clears the frame's area of `canvas` to transparent.
+    fn clear(&self, canvas: &mut ImageBuffer<Rgba8>) {
+        let ImageDescriptor {
+            left,
+            top,
+            width,
+            height,
+            ..
+        } = self.descriptor;
+
+        let x = (left as u32).min(canvas.width());
+        let y = (top as u32).min(canvas.height());
+        let width = (width as u32).min(canvas.width() - x);
+        let height = (height as u32).min(canvas.height() - y);
+
+        if let Ok(mut view) = canvas.view_mut(x, y, width, height) {
+            view.fill(TRANSPARENT);
+        }
+    }
+}
+
+/// Draws `frames` one after another onto a transparent `width` by `height` canvas, disposing of
+/// each before drawing the next, and hands `visit` the canvas as each frame is displayed.
+pub(crate) fn composite(
+    width: u32,
+    height: u32,
+    frames: &[Frame],
+    mut visit: impl FnMut(usize, &ImageBuffer<Rgba8>),
+) {
+    let mut canvas = ImageBuffer::from_pixel(width, height, TRANSPARENT);
+
+    for (i, frame) in frames.iter().enumerate() {
+        let previous = (frame.disposal() == DisposalMethod::RestorePrevious
+            && i + 1 < frames.len())
+        .then(|| canvas.clone());
+
+        frame.draw(&mut canvas);
+        visit(i, &canvas);
+
+        match frame.disposal() {
+            DisposalMethod::None | DisposalMethod::Keep => {}
+            DisposalMethod::RestoreBackground => frame.clear(&mut canvas),
+            DisposalMethod::RestorePrevious => {
+                if let Some(previous) = previous {
+                    canvas = previous;
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Gif {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) frames: Vec<Frame>,
+    pub(crate) repeat: Repeat,
+    /// Whether any frame leaves part of the canvas transparent.
+    pub(crate) has_alpha: bool,
+}
+
+impl Gif {
+    pub fn frames(&self) -> &[Frame] {
+        &self.frames
+    }
+
+    pub const fn repeat(&self) -> Repeat {
+        self.repeat
+    }
+
+    pub const fn is_animated(&self) -> bool {
+        self.frames.len() > 1
+    }
+
+    fn first_frame(&self) -> ImageBuffer<Rgba8> {
+        self.composite(0).expect("GIFs have at least one frame")
+    }
+
+    /// The canvas as a viewer displays it while showing frame `index`.
+    pub fn composite(&self, index: usize) -> Option<ImageBuffer<Rgba8>> {
+        let frames = self.frames.get(..=index)?;
+        let mut displayed = None;
+
+        composite(self.width, self.height, frames, |i, canvas| {
+            if i == index {
+                displayed = Some(canvas.clone());
+            }
+        });
+
+        displayed
+    }
+}
+
+/// The pixels of a `Gif` are those of its first frame, composited onto the canvas.
+impl ImageExt for Gif {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn color_type(&self) -> ColorType {
+        if self.has_alpha {
+            ColorType::RGBA
+        } else {
+            ColorType::RGB
+        }
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        Cow::from(self.first_frame().rgb8().into_owned())
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        Cow::from(self.first_frame().into_raw())
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        Cow::from(self.first_frame().bitmap().into_owned())
+    }
+
+    fn frame_count(&self) -> usize {
+        self.frames.len()
+    }
+
+    fn frame_rgba8(&self, index: usize) -> Option<Cow<'_, [u8]>> {
+        self.composite(index)
+            .map(|canvas| Cow::from(canvas.into_raw()))
+    }
+}
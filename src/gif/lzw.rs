@@ -0,0 +1,253 @@
+use anyhow::{bail, ensure, Result};
+use std::collections::HashMap;
+
+/// Codes are at most 12 bits wide.
+const MAX_CODES: usize = 1 << 12;
+
+/// Reads codes packed least significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    buffer: u32,
+    bits: u8,
+}
+
+impl<'a> BitReader<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            buffer: 0,
+            bits: 0,
+        }
+    }
+
+    fn read(&mut self, width: u8) -> Option<u16> {
+        while self.bits < width {
+            let &byte = self.data.get(self.cursor)?;
+            self.cursor += 1;
+
+            self.buffer |= (byte as u32) << self.bits;
+            self.bits += 8;
+        }
+
+        let code = (self.buffer & ((1 << width) - 1)) as u16;
+        self.buffer >>= width;
+        self.bits -= width;
+
+        Some(code)
+    }
+}
+
+/// Decodes GIF's variable-width LZW, stopping after `max_len` indices. Data that ends without
+/// an end of information code is accepted, as many encoders omit it.
+pub fn decode(min_code_size: u8, data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    ensure!(
+        (1..=11).contains(&min_code_size),
+        "Invalid LZW minimum code size: {}",
+        min_code_size
+    );
+
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+
+    // Each entry is an earlier entry followed by one index. Entries below the clear code are the
+    // indices themselves.
+    let mut prefixes = [0u16; MAX_CODES];
+    let mut suffixes = [0u8; MAX_CODES];
+    let mut lengths = [0u16; MAX_CODES];
+
+    for code in 0..clear_code {
+        suffixes[code as usize] = code as u8;
+        lengths[code as usize] = 1;
+    }
+
+    let mut next_code = end_code + 1;
+    let mut code_size = min_code_size + 1;
+    let mut previous: Option<u16> = None;
+
+    // `max_len` comes from the frame's dimensions, which the data may not live up to.
+    let mut output = Vec::with_capacity(max_len.min(data.len() * 8));
+    let mut reader = BitReader::new(data);
+
+    while output.len() < max_len {
+        let Some(code) = reader.read(code_size) else {
+            break;
+        };
+
+        if code == clear_code {
+            next_code = end_code + 1;
+            code_size = min_code_size + 1;
+            previous = None;
+            continue;
+        }
+
+        if code == end_code {
+            break;
+        }
+
+        let Some(previous_code) = previous else {
+            ensure!(code < clear_code, "Invalid first LZW code: {}", code);
+            output.push(code as u8);
+            previous = Some(code);
+            continue;
+        };
+
+        // A code one past the table is the previous entry followed by its own first index.
+        let entry = match code {
+            code if code < next_code => code,
+            code if code == next_code => previous_code,
+            code => bail!("Invalid LZW code: {}", code),
+        };
+
+        let start = output.len();
+        let length = lengths[entry as usize] as usize;
+        output.resize(start + length, 0);
+
+        let mut walk = entry;
+        for i in (start..start + length).rev() {
+            output[i] = suffixes[walk as usize];
+            walk = prefixes[walk as usize];
+        }
+
+        let first = output[start];
+        if code == next_code {
+            output.push(first);
+        }
+
+        // Once the table is full, codes keep their width until the next clear code.
+        if (next_code as usize) < MAX_CODES {
+            prefixes[next_code as usize] = previous_code;
+            suffixes[next_code as usize] = first;
+            lengths[next_code as usize] = lengths[previous_code as usize] + 1;
+            next_code += 1;
+
+            if next_code == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        }
+
+        previous = Some(code);
+    }
+
+    output.truncate(max_len);
+
+    Ok(output)
+}
+
+/// Packs codes least significant bit first.
+struct BitWriter {
+    output: Vec<u8>,
+    buffer: u32,
+    bits: u8,
+}
+
+impl BitWriter {
+    const fn new() -> Self {
+        Self {
+            output: Vec::new(),
+            buffer: 0,
+            bits: 0,
+        }
+    }
+
+    fn write(&mut self, code: u16, width: u8) {
+        self.buffer |= (code as u32) << self.bits;
+        self.bits += width;
+
+        while self.bits >= 8 {
+            self.output.push(self.buffer as u8);
+            self.buffer >>= 8;
+            self.bits -= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.bits > 0 {
+            self.output.push(self.buffer as u8);
+        }
+
+        self.output
+    }
+}
+
+/// Encodes `indices`, each below `1 << min_code_size`, with GIF's variable-width LZW. The table
+/// is cleared whenever it fills up.
+pub fn encode(min_code_size: u8, indices: &[u8]) -> Vec<u8> {
+    let clear_code = 1u16 << min_code_size;
+    let end_code = clear_code + 1;
+
+    // Maps an entry and the index that follows it to the entry for both.
+    let mut table = HashMap::<(u16, u8), u16>::new();
+    let mut next_code = end_code + 1;
+    let mut code_size = min_code_size + 1;
+
+    let mut writer = BitWriter::new();
+    writer.write(clear_code, code_size);
+
+    let mut indices = indices.iter().copied();
+    let Some(first) = indices.next() else {
+        writer.write(end_code, code_size);
+        return writer.finish();
+    };
+
+    let mut entry = first as u16;
+
+    for index in indices {
+        if let Some(&code) = table.get(&(entry, index)) {
+            entry = code;
+            continue;
+        }
+
+        writer.write(entry, code_size);
+
+        if (next_code as usize) < MAX_CODES {
+            table.insert((entry, index), next_code);
+            next_code += 1;
+
+            // The decoder adds each entry a code later, so widens a code later too.
+            if next_code > 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        } else {
+            writer.write(clear_code, code_size);
+
+            table.clear();
+            next_code = end_code + 1;
+            code_size = min_code_size + 1;
+        }
+
+        entry = index as u16;
+    }
+
+    writer.write(entry, code_size);
+    writer.write(end_code, code_size);
+
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_round_trip() -> Result<()> {
+        let patterns: [Vec<u8>; 4] = [
+            vec![],
+            vec![3],
+            vec![1; 10_000],
+            (0..50_000u32)
+                .map(|i| ((i * i / 7 + i / 3) % 256) as u8)
+                .collect(),
+        ];
+
+        for indices in patterns {
+            let min_code_size = if indices.iter().all(|&i| i < 4) { 2 } else { 8 };
+            let encoded = encode(min_code_size, &indices);
+
+            assert_eq!(decode(min_code_size, &encoded, indices.len())?, indices);
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,9 @@
+mod decoder;
+mod encoder;
+mod lzw;
+mod quantize;
+
+pub mod grammar;
+
+pub use decoder::*;
+pub use encoder::*;
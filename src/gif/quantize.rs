@@ -0,0 +1,227 @@
+use std::collections::HashMap;
+
+/// A color and the number of pixels with it.
+type Bin = ([u8; 3], u32);
+
+/// Picks at most `max_colors` colors to represent `pixels` by median cut: the box of colors
+/// whose widest channel range times pixel count is largest is repeatedly split at the median
+/// pixel along that channel, and each box is replaced by its average. Images with few enough
+/// colors keep them all.
+pub fn quantize(pixels: impl Iterator<Item = [u8; 3]>, max_colors: usize) -> Vec<[u8; 3]> {
+    let mut histogram = HashMap::<[u8; 3], u32>::new();
+    for pixel in pixels {
+        *histogram.entry(pixel).or_default() += 1;
+    }
+
+    let mut bins = histogram.into_iter().collect::<Vec<Bin>>();
+    bins.sort_unstable();
+
+    if bins.len() <= max_colors {
+        return bins.into_iter().map(|(color, _)| color).collect();
+    }
+
+    let mut boxes = vec![bins];
+
+    while boxes.len() < max_colors {
+        let widest = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, bins)| bins.len() > 1)
+            .map(|(i, bins)| (i, widest_channel(bins)))
+            .max_by_key(|&(i, (_, range))| range as u64 * population(&boxes[i]));
+
+        let Some((i, (channel, _))) = widest else {
+            break;
+        };
+
+        let mut bins = boxes.swap_remove(i);
+        bins.sort_unstable_by_key(|(color, _)| color[channel]);
+
+        // Split at the median pixel, keeping at least one color on each side.
+        let total = population(&bins);
+        let mut seen = 0;
+        let median = bins
+            .iter()
+            .position(|&(_, count)| {
+                seen += count as u64;
+                seen * 2 >= total
+            })
+            .unwrap_or(0)
+            .clamp(0, bins.len() - 2);
+
+        let upper = bins.split_off(median + 1);
+        boxes.push(bins);
+        boxes.push(upper);
+    }
+
+    boxes.iter().map(|bins| average(bins)).collect()
+}
+
+/// The channel whose values span the widest range, and that range.
+fn widest_channel(bins: &[Bin]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = bins
+                .iter()
+                .fold((u8::MAX, u8::MIN), |(min, max), (color, _)| {
+                    (min.min(color[channel]), max.max(color[channel]))
+                });
+
+            (channel, max - min)
+        })
+        .max_by_key(|&(_, range)| range)
+        .unwrap_or((0, 0))
+}
+
+fn population(bins: &[Bin]) -> u64 {
+    bins.iter().map(|&(_, count)| count as u64).sum()
+}
+
+fn average(bins: &[Bin]) -> [u8; 3] {
+    let total = population(bins).max(1);
+
+    [0, 1, 2].map(|channel| {
+        let sum = bins
+            .iter()
+            .map(|&(color, count)| color[channel] as u64 * count as u64)
+            .sum::<u64>();
+
+        ((sum + total / 2) / total) as u8
+    })
+}
+
+/// Maps colors to the index of the nearest palette color, remembering earlier lookups.
+#[derive(Debug)]
+pub struct PaletteMapper<'a> {
+    palette: &'a [[u8; 3]],
+    cache: HashMap<[u8; 3], u8>,
+}
+
+impl<'a> PaletteMapper<'a> {
+    pub fn new(palette: &'a [[u8; 3]]) -> Self {
+        Self {
+            palette,
+            cache: HashMap::new(),
+        }
+    }
+
+    pub fn index(&mut self, color: [u8; 3]) -> u8 {
+        let palette = self.palette;
+
+        *self.cache.entry(color).or_insert_with(|| {
+            let distance = |candidate: &[u8; 3]| {
+                (0..3)
+                    .map(|c| (candidate[c] as i32 - color[c] as i32).pow(2))
+                    .sum::<i32>()
+            };
+
+            palette
+                .iter()
+                .enumerate()
+                .min_by_key(|(_, candidate)| distance(candidate))
+                .map_or(0, |(i, _)| i as u8)
+        })
+    }
+}
+
+/// Maps `pixels`, a `width` wide image, to palette indices with Floyd-Steinberg dithering: the
+/// error of each pixel is spread over its unvisited neighbors. Pixels for which `skip` returns
+/// true are left as `skip_index`, and neither take nor spread error.
+pub fn dither(
+    pixels: &[[u8; 3]],
+    width: usize,
+    mapper: &mut PaletteMapper,
+    skip: impl Fn(usize) -> bool,
+    skip_index: u8,
+) -> Vec<u8> {
+    let palette = mapper.palette;
+    let mut errors = vec![[0i16; 3]; pixels.len()];
+    let mut indices = vec![skip_index; pixels.len()];
+
+    for (i, pixel) in pixels.iter().enumerate() {
+        if skip(i) {
+            continue;
+        }
+
+        let color = [0, 1, 2].map(|c| (pixel[c] as i16 + errors[i][c]).clamp(0, 255) as u8);
+        let index = mapper.index(color);
+        indices[i] = index;
+
+        let error = [0, 1, 2].map(|c| color[c] as i16 - palette[index as usize][c] as i16);
+
+        let x = i % width;
+        let mut spread = |neighbor: usize, weight: i16| {
+            if neighbor < pixels.len() {
+                for c in 0..3 {
+                    errors[neighbor][c] += error[c] * weight / 16;
+                }
+            }
+        };
+
+        if x + 1 < width {
+            spread(i + 1, 7);
+            spread(i + width + 1, 1);
+        }
+
+        if x > 0 {
+            spread(i + width - 1, 3);
+        }
+
+        spread(i + width, 5);
+    }
+
+    indices
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quantize_keeps_few_colors() {
+        let pixels = [[1, 2, 3], [200, 0, 0], [1, 2, 3], [0, 0, 255]];
+        let palette = quantize(pixels.into_iter(), 4);
+
+        assert_eq!(palette.len(), 3);
+
+        let mut mapper = PaletteMapper::new(&palette);
+        for pixel in pixels {
+            assert_eq!(palette[mapper.index(pixel) as usize], pixel);
+        }
+    }
+
+    #[test]
+    fn test_quantize_gradient() {
+        let pixels = (0..=255).flat_map(|r| (0..=255).step_by(5).map(move |g| [r, g, 128]));
+        let palette = quantize(pixels.clone(), 64);
+
+        assert_eq!(palette.len(), 64);
+
+        let mut mapper = PaletteMapper::new(&palette);
+        let worst = pixels
+            .map(|pixel| {
+                let nearest = palette[mapper.index(pixel) as usize];
+                (0..3).map(|c| pixel[c].abs_diff(nearest[c])).max().unwrap()
+            })
+            .max()
+            .unwrap();
+
+        assert!(worst <= 24, "Worst channel error: {worst}");
+    }
+
+    #[test]
+    fn test_dither_preserves_mean() {
+        // A flat color between two palette entries dithers to a mix of both.
+        let palette = [[0, 0, 0], [255, 255, 255]];
+        let pixels = vec![[64, 64, 64]; 64 * 64];
+
+        let mut mapper = PaletteMapper::new(&palette);
+        let indices = dither(&pixels, 64, &mut mapper, |_| false, 0);
+
+        let white = indices.iter().filter(|&&i| i == 1).count() as f64 / indices.len() as f64;
+        assert!(
+            (white - 64.0 / 255.0).abs() < 0.02,
+            "White fraction: {white}"
+        );
+    }
+}
@@ -0,0 +1,359 @@
+use crate::{
+    hdr::grammar::{
+        Axis, AxisOrder, Hdr, HdrHeader, RGBE_FORMAT, RLE_SCANLINE_LENGTHS, SIGNATURES,
+    },
+    image::ImageBuffer,
+    impl_read_for_datatype, impl_read_slice,
+};
+use anyhow::{anyhow, bail, ensure, Result};
+
+/// The most pixels an image may have.
+const MAX_PIXELS: u64 = 400_000_000;
+
+#[derive(Debug)]
+pub struct HdrDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> HdrDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Hdr> {
+        let header = self.parse_header()?;
+        let [major, minor] = header.scan_order;
+
+        // Scanlines are decoded in storage order before being placed, so that truncated data
+        // fails before the whole image is allocated.
+        let mut rgbes = Vec::new();
+        for _ in 0..major.len {
+            self.read_scanline(minor.len as usize, &mut rgbes)?;
+        }
+
+        let mut samples = vec![0.0; rgbes.len() * 3];
+
+        for (index, rgbe) in rgbes.into_iter().enumerate() {
+            let (major_index, minor_index) = (index as u32 / minor.len, index as u32 % minor.len);
+            let (x, y) = match major.axis {
+                Axis::Y => (minor.position(minor_index), major.position(major_index)),
+                Axis::X => (major.position(major_index), minor.position(minor_index)),
+            };
+
+            let offset = (y as usize * header.width as usize + x as usize) * 3;
+            samples[offset..offset + 3].copy_from_slice(&rgbe_to_rgb(rgbe));
+        }
+
+        let image = ImageBuffer::from_raw(header.width, header.height, samples)?;
+
+        Ok(Hdr { header, image })
+    }
+
+    /// Reads the signature, the `KEY=value` lines up to an empty line, then the resolution
+    /// line.
+    fn parse_header(&mut self) -> Result<HdrHeader> {
+        let signature = self.read_line()?;
+        ensure!(
+            SIGNATURES.contains(&signature.as_bytes()),
+            "Expected a Radiance HDR signature."
+        );
+
+        let mut exposure = 1.0;
+
+        loop {
+            let line = self.read_line()?;
+
+            if line.is_empty() {
+                break;
+            }
+
+            match line.split_once('=') {
+                Some(("FORMAT", format)) => ensure!(
+                    format.trim() == RGBE_FORMAT,
+                    "Unsupported HDR pixel format: {}",
+                    format
+                ),
+                Some(("EXPOSURE", value)) => {
+                    exposure *= value
+                        .trim()
+                        .parse::<f32>()
+                        .map_err(|_| anyhow!("Invalid HDR exposure: {}", value))?;
+                }
+                // Comments, commands and other variables describe how the image was made.
+                _ => {}
+            }
+        }
+
+        let resolution = self.read_line()?;
+        let [major, minor] = match resolution.split_whitespace().collect::<Vec<_>>()[..] {
+            [major_axis, major_len, minor_axis, minor_len] => [
+                AxisOrder::try_from((major_axis, major_len))?,
+                AxisOrder::try_from((minor_axis, minor_len))?,
+            ],
+            _ => bail!("Invalid HDR resolution line: {}", resolution),
+        };
+
+        ensure!(
+            major.axis != minor.axis,
+            "Invalid HDR resolution line: {}",
+            resolution
+        );
+
+        let (width, height) = match major.axis {
+            Axis::Y => (minor.len, major.len),
+            Axis::X => (major.len, minor.len),
+        };
+
+        ensure!(
+            width as u64 * height as u64 <= MAX_PIXELS,
+            "HDR image is too large: {}x{}",
+            width,
+            height
+        );
+
+        Ok(HdrHeader {
+            width,
+            height,
+            exposure,
+            scan_order: [major, minor],
+        })
+    }
+
+    /// Appends the `len` RGBE pixels of the next scanline to `rgbes`.
+    fn read_scanline(&mut self, len: usize, rgbes: &mut Vec<[u8; 4]>) -> Result<()> {
+        let is_run_length_encoded = RLE_SCANLINE_LENGTHS.contains(&len)
+            && matches!(self.peek_slice(4), Ok(&[2, 2, high, _]) if high & 0x80 == 0);
+
+        if !is_run_length_encoded {
+            return self.read_flat_scanline(len, rgbes);
+        }
+
+        let encoded_len = u16::from_be_bytes(self.read_slice(4)?[2..].try_into()?) as usize;
+        ensure!(
+            encoded_len == len,
+            "HDR scanline has {} pixels, expected {}.",
+            encoded_len,
+            len
+        );
+
+        let start = rgbes.len();
+        rgbes.resize(start + len, [0; 4]);
+        let scanline = &mut rgbes[start..];
+
+        // Each component is stored separately, as runs and literal spans.
+        for component in 0..4 {
+            let mut x = 0;
+
+            while x < len {
+                let count = self.read_u8()? as usize;
+
+                if count > 128 {
+                    let run = count - 128;
+                    ensure!(x + run <= len, "HDR run overflows its scanline.");
+
+                    let value = self.read_u8()?;
+                    for rgbe in &mut scanline[x..x + run] {
+                        rgbe[component] = value;
+                    }
+
+                    x += run;
+                } else {
+                    ensure!(
+                        count > 0 && x + count <= len,
+                        "Invalid HDR literal span length: {}",
+                        count
+                    );
+
+                    for (rgbe, &value) in scanline[x..x + count]
+                        .iter_mut()
+                        .zip(self.read_slice(count)?)
+                    {
+                        rgbe[component] = value;
+                    }
+
+                    x += count;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads a scanline of whole RGBE pixels, where a pixel of `1, 1, 1, count` repeats the
+    /// previous one `count` times. Consecutive repeats hold higher bytes of the count.
+    fn read_flat_scanline(&mut self, len: usize, rgbes: &mut Vec<[u8; 4]>) -> Result<()> {
+        let end = rgbes.len() + len;
+        let mut shift = 0;
+
+        while rgbes.len() < end {
+            let rgbe: [u8; 4] = self.read_slice(4)?.try_into()?;
+
+            match rgbe {
+                [1, 1, 1, count] => {
+                    ensure!(shift <= 24, "HDR run is too long.");
+
+                    let &previous = rgbes
+                        .last()
+                        .ok_or_else(|| anyhow!("HDR run has no pixel to repeat."))?;
+                    let count = (count as usize) << shift;
+                    ensure!(
+                        rgbes.len() + count <= end,
+                        "HDR run overflows its scanline."
+                    );
+
+                    rgbes.extend(std::iter::repeat_n(previous, count));
+                    shift += 8;
+                }
+                _ => {
+                    rgbes.push(rgbe);
+                    shift = 0;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reads up to and including the next line feed, returning the line without it.
+    fn read_line(&mut self) -> Result<&'a str> {
+        let start = self.cursor;
+        let len = self.data[start..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .ok_or_else(|| anyhow!("HDR header is truncated."))?;
+
+        let line = std::str::from_utf8(self.read_slice(len)?)?;
+        self.cursor += 1;
+
+        Ok(line)
+    }
+
+    impl_read_for_datatype!(read_u8, u8);
+
+    impl_read_slice!();
+}
+
+/// Expands a pixel whose components share the exponent `e`, biased by 128, to linear RGB.
+fn rgbe_to_rgb([r, g, b, e]: [u8; 4]) -> [f32; 3] {
+    if e == 0 {
+        return [0.0; 3];
+    }
+
+    // The mantissas are fractions of 256.
+    let scale = (e as f32 - (128.0 + 8.0)).exp2();
+    [r as f32 * scale, g as f32 * scale, b as f32 * scale]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{grammar::ImageExt, ToneMapOperator, ToneMapper};
+
+    #[test]
+    fn test_decode_against_image_crate() -> Result<()> {
+        for path in ["./tests/hdr/ramp_rle.hdr", "./tests/hdr/flat_old_rle.hdr"] {
+            let hdr = HdrDecoder::new(&std::fs::read(path)?).decode()?;
+            let reference = image::open(path)?.to_rgb32f();
+
+            assert_eq!(hdr.dimensions(), reference.dimensions(), "{path}");
+            assert_eq!(hdr.image().as_raw(), reference.as_raw(), "{path}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_header() -> Result<()> {
+        let hdr = HdrDecoder::new(&std::fs::read("./tests/hdr/ramp_rle.hdr")?).decode()?;
+        let header = hdr.header();
+
+        assert_eq!((header.width, header.height), (64, 32));
+        assert_eq!(header.exposure, 1.0, "EXPOSURE lines multiply");
+        assert_eq!(
+            header.scan_order,
+            [
+                AxisOrder {
+                    axis: Axis::Y,
+                    decreasing: true,
+                    len: 32
+                },
+                AxisOrder {
+                    axis: Axis::X,
+                    decreasing: false,
+                    len: 64
+                }
+            ]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_scan_orders() -> Result<()> {
+        let top_down = HdrDecoder::new(&std::fs::read("./tests/hdr/ramp_rle.hdr")?).decode()?;
+        let bottom_up =
+            HdrDecoder::new(&std::fs::read("./tests/hdr/ramp_bottom_up.hdr")?).decode()?;
+        assert_eq!(bottom_up.image().as_raw(), top_down.image().as_raw());
+
+        // 2x3 pixels stored column by column, from the right.
+        let mut data = b"#?RGBE\nFORMAT=32-bit_rle_rgbe\n\n-X 2 -Y 3\n".to_vec();
+        for value in 1..=6 {
+            data.extend_from_slice(&[value, 0, 0, 136]);
+        }
+
+        let columns = HdrDecoder::new(&data).decode()?;
+        let reds = columns.image().pixels().iter().map(|p| p.0[0]);
+        assert_eq!(reds.collect::<Vec<_>>(), [4.0, 1.0, 5.0, 2.0, 6.0, 3.0]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tone_mapping() -> Result<()> {
+        let hdr = HdrDecoder::new(&std::fs::read("./tests/hdr/flat_old_rle.hdr")?).decode()?;
+
+        assert_eq!(
+            hdr.tone_mapped_rgba8(ToneMapper::default()).as_deref(),
+            Some(hdr.rgba8().as_ref())
+        );
+
+        // A bright pixel saturates, and lowering the exposure brings it back.
+        let reinhard = ToneMapper::new(ToneMapOperator::Reinhard);
+        let bright = &hdr.tone_mapped_rgba8(reinhard).unwrap()[28..32];
+        assert_eq!(bright, [0, 254, 0, 255]);
+
+        let darker = &hdr.tone_mapped_rgba8(reinhard.exposure(-6.0)).unwrap()[28..32];
+        assert!(darker[1] < 240);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_invalid() -> Result<()> {
+        let data = std::fs::read("./tests/hdr/ramp_rle.hdr")?;
+
+        assert!(HdrDecoder::new(&data[..data.len() - 1]).decode().is_err());
+        assert!(HdrDecoder::new(b"#?RADIANCE\n\n-Y 1 +X 1\n")
+            .decode()
+            .is_err());
+        assert!(HdrDecoder::new(b"#?RADIANCE\n\n-Y 1 +Y 1\n\0\0\0\0")
+            .decode()
+            .is_err());
+        assert!(
+            HdrDecoder::new(b"#?RADIANCE\nFORMAT=32-bit_rle_xyze\n\n-Y 1 +X 1\n\0\0\0\0")
+                .decode()
+                .is_err()
+        );
+        assert!(HdrDecoder::new(b"P6\n1 1\n255\n\0\0\0").decode().is_err());
+
+        // A run before any pixel.
+        assert!(
+            HdrDecoder::new(b"#?RADIANCE\n\n-Y 1 +X 2\n\x01\x01\x01\x01\0\0\0\0")
+                .decode()
+                .is_err()
+        );
+
+        Ok(())
+    }
+}
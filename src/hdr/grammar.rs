@@ -0,0 +1,142 @@
+use crate::image::{
+    grammar::{ColorType, ImageExt},
+    ImageBuffer, Rgb32F, ToneMapper,
+};
+use anyhow::bail;
+use std::borrow::Cow;
+
+/// The first line of a Radiance file, either of which programs write.
+pub const SIGNATURES: [&[u8]; 2] = [b"#?RADIANCE", b"#?RGBE"];
+
+/// The only pixel format supported, as named by the `FORMAT` header line.
+pub const RGBE_FORMAT: &str = "32-bit_rle_rgbe";
+
+/// Scanlines this long may be run-length encoded per component, which a scanline marks by
+/// starting with 2, 2 and its length.
+pub const RLE_SCANLINE_LENGTHS: std::ops::RangeInclusive<usize> = 8..=0x7FFF;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Axis {
+    X,
+    Y,
+}
+
+/// One half of the resolution line, such as `-Y 480`. Radiance's Y axis points up, so `-Y`
+/// runs from the top of the image down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AxisOrder {
+    pub axis: Axis,
+    pub decreasing: bool,
+    pub len: u32,
+}
+
+impl AxisOrder {
+    /// The row or column of the `index`-th position along the axis.
+    pub const fn position(&self, index: u32) -> u32 {
+        let forward = match self.axis {
+            Axis::X => !self.decreasing,
+            Axis::Y => self.decreasing,
+        };
+
+        if forward {
+            index
+        } else {
+            self.len - 1 - index
+        }
+    }
+}
+
+impl TryFrom<(&str, &str)> for AxisOrder {
+    type Error = anyhow::Error;
+
+    fn try_from((sign_and_axis, len): (&str, &str)) -> Result<Self, Self::Error> {
+        let (decreasing, axis) = match sign_and_axis {
+            "-X" => (true, Axis::X),
+            "+X" => (false, Axis::X),
+            "-Y" => (true, Axis::Y),
+            "+Y" => (false, Axis::Y),
+            _ => bail!("Invalid HDR resolution axis: {sign_and_axis}"),
+        };
+
+        let Ok(len @ 1..) = len.parse() else {
+            bail!("Invalid HDR resolution: {len}");
+        };
+
+        Ok(Self {
+            axis,
+            decreasing,
+            len,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HdrHeader {
+    pub width: u32,
+    pub height: u32,
+    /// The product of the `EXPOSURE` lines. Pixel values are radiance times this.
+    pub exposure: f32,
+    /// The axis scanlines are stored along, followed by the axis pixels within a scanline are
+    /// stored along.
+    pub scan_order: [AxisOrder; 2],
+}
+
+/// A Radiance RGBE image, with its pixels expanded to linear floating point RGB.
+#[derive(Debug)]
+pub struct Hdr {
+    pub(crate) header: HdrHeader,
+    pub(crate) image: ImageBuffer<Rgb32F>,
+}
+
+impl Hdr {
+    pub const fn header(&self) -> &HdrHeader {
+        &self.header
+    }
+
+    pub const fn image(&self) -> &ImageBuffer<Rgb32F> {
+        &self.image
+    }
+}
+
+impl ImageExt for Hdr {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn color_type(&self) -> ColorType {
+        ColorType::RGB
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        let b = self
+            .rgba8()
+            .chunks_exact(4)
+            .flat_map(|p| [p[0], p[1], p[2]])
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+
+    /// The pixels mapped with the default tone mapper.
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        Cow::from(ToneMapper::default().map_to_rgba8(self.image.as_raw(), 3))
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        let b = self
+            .rgba8()
+            .chunks_exact(4)
+            .map(|p| u32::from_be_bytes([p[3], p[0], p[1], p[2]]))
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+
+    fn tone_mapped_rgba8(&self, tone_mapper: ToneMapper) -> Option<Cow<'_, [u8]>> {
+        Some(Cow::from(tone_mapper.map_to_rgba8(self.image.as_raw(), 3)))
+    }
+}
@@ -0,0 +1,502 @@
+use crate::{
+    icc::grammar::{Clut, ColorModel, Curve, IccProfile, Lut, PcsEncoding, ProfileColorSpace},
+    impl_read_for_datatype, impl_read_slice,
+};
+use anyhow::{bail, ensure, Result};
+use std::collections::BTreeMap;
+
+type Signature = [u8; 4];
+
+/// Parses ICC profiles of version 2 and 4 (ICC.1:2001-04 and ICC.1:2022).
+#[derive(Debug)]
+pub struct IccDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> IccDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<IccProfile> {
+        ensure!(self.data.len() >= 132, "ICC profile is too short.");
+        ensure!(
+            &self.data[36..40] == b"acsp",
+            "Invalid ICC profile: missing profile file signature."
+        );
+
+        self.cursor = 8;
+        let version = (self.read_u8()?, self.read_u8()? >> 4);
+
+        self.cursor = 12;
+        let device_class = self.read_signature()?;
+        let color_space = ProfileColorSpace::from(self.read_signature()?);
+        let connection_space = ProfileColorSpace::from(self.read_signature()?);
+
+        self.cursor = 128;
+        let num_tags = self.read_u32()?;
+        let mut tags = BTreeMap::new();
+
+        for _ in 0..num_tags {
+            let signature = self.read_signature()?;
+            let offset = self.read_u32()? as usize;
+            let size = self.read_u32()? as usize;
+
+            ensure!(
+                offset
+                    .checked_add(size)
+                    .is_some_and(|end| end <= self.data.len()),
+                "ICC tag {} lies outside the profile.",
+                String::from_utf8_lossy(&signature)
+            );
+
+            tags.insert(signature, offset);
+        }
+
+        let description = tags
+            .get(b"desc")
+            .and_then(|&offset| self.parse_text(offset).ok());
+
+        // Unsupported or malformed transforms leave the profile without a color model, but the
+        // profile itself is still worth keeping around for re-encoding.
+        let color_model = self
+            .parse_color_model(&tags, color_space, connection_space)
+            .ok()
+            .flatten();
+
+        Ok(IccProfile {
+            version,
+            device_class,
+            color_space,
+            connection_space,
+            description,
+            color_model,
+            data: self.data.to_vec(),
+        })
+    }
+
+    /// Picks the perceptual A2B0 lookup table if present, falling back to the matrix and tone
+    /// curves of display profiles.
+    fn parse_color_model(
+        &mut self,
+        tags: &BTreeMap<Signature, usize>,
+        color_space: ProfileColorSpace,
+        connection_space: ProfileColorSpace,
+    ) -> Result<Option<ColorModel>> {
+        let num_channels = match color_space {
+            ProfileColorSpace::RGB => 3,
+            ProfileColorSpace::Gray => 1,
+            _ => return Ok(None),
+        };
+
+        ensure!(
+            matches!(
+                connection_space,
+                ProfileColorSpace::XYZ | ProfileColorSpace::Lab
+            ),
+            "Unsupported profile connection space: {connection_space:?}"
+        );
+
+        if let Some(&offset) = tags.get(b"A2B0") {
+            let lut = self.parse_lut(offset)?;
+
+            ensure!(
+                lut.input_channels == num_channels,
+                "A2B0 expects {} channels, the profile's color space has {num_channels}.",
+                lut.input_channels
+            );
+
+            return Ok(Some(ColorModel::Lut(lut)));
+        }
+
+        if color_space == ProfileColorSpace::Gray {
+            let Some(&offset) = tags.get(b"kTRC") else {
+                return Ok(None);
+            };
+
+            return Ok(Some(ColorModel::GrayTrc(self.parse_curve(offset)?)));
+        }
+
+        let mut colorants = [[0.0; 3]; 3];
+        let mut curves = [Curve::Identity, Curve::Identity, Curve::Identity];
+
+        for (channel, (colorant, curve)) in
+            [(b"rXYZ", b"rTRC"), (b"gXYZ", b"gTRC"), (b"bXYZ", b"bTRC")]
+                .into_iter()
+                .enumerate()
+        {
+            let (Some(&colorant), Some(&curve)) = (tags.get(colorant), tags.get(curve)) else {
+                return Ok(None);
+            };
+
+            let xyz = self.parse_xyz(colorant)?;
+            for (row, value) in xyz.into_iter().enumerate() {
+                colorants[row][channel] = value;
+            }
+
+            curves[channel] = self.parse_curve(curve)?;
+        }
+
+        Ok(Some(ColorModel::MatrixTrc { colorants, curves }))
+    }
+
+    /// Parses a `desc` tag, either a version 2 `textDescriptionType` or a version 4
+    /// `multiLocalizedUnicodeType`, of which the first record is used.
+    fn parse_text(&mut self, offset: usize) -> Result<String> {
+        self.cursor = offset;
+
+        let text = match &self.read_signature()? {
+            b"desc" => {
+                self.cursor += 4;
+                let length = self.read_u32()? as usize;
+                let bytes = self.read_slice(length)?;
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+
+                String::from_utf8_lossy(&bytes[..end]).into_owned()
+            }
+            b"mluc" => {
+                self.cursor += 4;
+                ensure!(self.read_u32()? > 0, "mluc tag has no records.");
+                self.cursor += 8;
+
+                let length = self.read_u32()? as usize;
+                self.cursor = offset + self.read_u32()? as usize;
+
+                let units = self.read_vec(length / 2, Self::read_u16)?;
+                String::from_utf16_lossy(&units)
+            }
+            b"text" => {
+                self.cursor += 4;
+                let bytes = self.data.get(self.cursor..).unwrap_or_default();
+                let end = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+
+                String::from_utf8_lossy(&bytes[..end]).into_owned()
+            }
+            foreign => bail!(
+                "Unsupported text type: {}",
+                String::from_utf8_lossy(foreign)
+            ),
+        };
+
+        Ok(text)
+    }
+
+    fn parse_xyz(&mut self, offset: usize) -> Result<[f32; 3]> {
+        self.cursor = offset;
+        ensure!(&self.read_signature()? == b"XYZ ", "Expected an XYZ tag.");
+        self.cursor += 4;
+
+        Ok([
+            self.read_s15_fixed16()?,
+            self.read_s15_fixed16()?,
+            self.read_s15_fixed16()?,
+        ])
+    }
+
+    /// Parses a `curv` or `para` curve at `offset`, leaving the cursor after it.
+    fn parse_curve(&mut self, offset: usize) -> Result<Curve> {
+        self.cursor = offset;
+
+        let curve =
+            match &self.read_signature()? {
+                b"curv" => {
+                    self.cursor += 4;
+
+                    match self.read_u32()? {
+                        0 => Curve::Identity,
+                        1 => Curve::Gamma(self.read_u16()? as f32 / 256.0),
+                        count => Curve::Table(self.read_vec(count as usize, |this| {
+                            Ok(this.read_u16()? as f32 / 65535.0)
+                        })?),
+                    }
+                }
+                b"para" => {
+                    self.cursor += 4;
+
+                    let function = self.read_u16()?;
+                    self.cursor += 2;
+
+                    let num_parameters = match function {
+                        0 => 1,
+                        1 => 3,
+                        2 => 4,
+                        3 => 5,
+                        4 => 7,
+                        foreign => bail!("Unsupported parametric curve function: {foreign}"),
+                    };
+
+                    let mut parameters = [0.0; 7];
+                    for parameter in &mut parameters[..num_parameters] {
+                        *parameter = self.read_s15_fixed16()?;
+                    }
+
+                    Curve::Parametric(function, parameters)
+                }
+                foreign => bail!(
+                    "Unsupported curve type: {}",
+                    String::from_utf8_lossy(foreign)
+                ),
+            };
+
+        Ok(curve)
+    }
+
+    /// Parses `count` curves stored back to back, each padded to a multiple of 4 bytes.
+    fn parse_curves(&mut self, offset: usize, count: usize) -> Result<Vec<Curve>> {
+        let mut curves = Vec::with_capacity(count);
+        let mut offset = offset;
+
+        for _ in 0..count {
+            curves.push(self.parse_curve(offset)?);
+            offset = self.cursor.next_multiple_of(4);
+        }
+
+        Ok(curves)
+    }
+
+    fn parse_lut(&mut self, offset: usize) -> Result<Lut> {
+        self.cursor = offset;
+
+        let signature = self.read_signature()?;
+        self.cursor += 4;
+
+        let input_channels = self.read_u8()? as usize;
+        let output_channels = self.read_u8()? as usize;
+
+        ensure!(
+            (1..=8).contains(&input_channels) && output_channels == 3,
+            "Unsupported lookup table with {input_channels} inputs and {output_channels} outputs."
+        );
+
+        match &signature {
+            b"mft1" | b"mft2" => {
+                let grid_points = self.read_u8()? as usize;
+                // The matrix only applies to XYZ input, which is not a device color space.
+                self.cursor += 1 + 36;
+
+                let sixteen_bit = &signature == b"mft2";
+                let (input_entries, output_entries) = if sixteen_bit {
+                    (self.read_u16()? as usize, self.read_u16()? as usize)
+                } else {
+                    (256, 256)
+                };
+
+                let read_values = |this: &mut Self, count: usize| -> Result<Vec<f32>> {
+                    if sixteen_bit {
+                        this.read_vec(count, |this| Ok(this.read_u16()? as f32 / 65535.0))
+                    } else {
+                        let bytes = this.read_slice(count)?;
+                        Ok(bytes.iter().map(|&b| b as f32 / 255.0).collect())
+                    }
+                };
+
+                let a_curves = (0..input_channels)
+                    .map(|_| read_values(self, input_entries).map(Curve::Table))
+                    .collect::<Result<Vec<_>>>()?;
+
+                let clut_size = grid_points.pow(input_channels as u32) * output_channels;
+                let clut = Clut {
+                    grid_points: vec![grid_points; input_channels],
+                    output_channels,
+                    values: read_values(self, clut_size)?,
+                };
+
+                let b_curves = (0..output_channels)
+                    .map(|_| read_values(self, output_entries).map(Curve::Table))
+                    .collect::<Result<Vec<_>>>()?;
+
+                Ok(Lut {
+                    input_channels,
+                    a_curves,
+                    clut: Some(clut),
+                    m_curves: Vec::new(),
+                    matrix: None,
+                    b_curves,
+                    pcs_encoding: if sixteen_bit {
+                        PcsEncoding::Lut16
+                    } else {
+                        PcsEncoding::Lut8
+                    },
+                })
+            }
+            b"mAB " => {
+                self.cursor += 2;
+
+                let [b_curves, matrix, m_curves, clut, a_curves] =
+                    self.read_fixed_array::<5, _>(|this| Ok(this.read_u32()? as usize))?;
+
+                let at = |relative: usize| (relative != 0).then_some(offset + relative);
+
+                let b_curves = match at(b_curves) {
+                    Some(b_curves) => self.parse_curves(b_curves, output_channels)?,
+                    None => bail!("lutAToBType requires B curves."),
+                };
+
+                let m_curves = at(m_curves).map_or_else(
+                    || Ok(Vec::new()),
+                    |m_curves| self.parse_curves(m_curves, output_channels),
+                )?;
+
+                let a_curves = at(a_curves).map_or_else(
+                    || Ok(Vec::new()),
+                    |a_curves| self.parse_curves(a_curves, input_channels),
+                )?;
+
+                let matrix = match at(matrix) {
+                    Some(matrix) => {
+                        self.cursor = matrix;
+                        Some(self.read_fixed_array::<12, _>(Self::read_s15_fixed16)?)
+                    }
+                    None => None,
+                };
+
+                let clut = match at(clut) {
+                    Some(clut) => {
+                        self.cursor = clut;
+
+                        let grid_points = self.read_slice(16)?[..input_channels]
+                            .iter()
+                            .map(|&n| n as usize)
+                            .collect::<Vec<_>>();
+
+                        let precision = self.read_u8()?;
+                        self.cursor += 3;
+
+                        let size = grid_points.iter().product::<usize>() * output_channels;
+                        let values = match precision {
+                            1 => self
+                                .read_slice(size)?
+                                .iter()
+                                .map(|&b| b as f32 / 255.0)
+                                .collect(),
+                            2 => {
+                                self.read_vec(size, |this| Ok(this.read_u16()? as f32 / 65535.0))?
+                            }
+                            foreign => bail!("Invalid CLUT precision: {foreign}"),
+                        };
+
+                        Some(Clut {
+                            grid_points,
+                            output_channels,
+                            values,
+                        })
+                    }
+                    None => None,
+                };
+
+                Ok(Lut {
+                    input_channels,
+                    a_curves,
+                    clut,
+                    m_curves,
+                    matrix,
+                    b_curves,
+                    pcs_encoding: PcsEncoding::LutAToB,
+                })
+            }
+            foreign => bail!(
+                "Unsupported lookup table type: {}",
+                String::from_utf8_lossy(foreign)
+            ),
+        }
+    }
+
+    fn read_signature(&mut self) -> Result<Signature> {
+        Ok(self.read_slice(4)?.try_into()?)
+    }
+
+    fn read_s15_fixed16(&mut self) -> Result<f32> {
+        Ok(self.read_i32()? as f32 / 65536.0)
+    }
+
+    impl_read_for_datatype!(read_u8, u8);
+    impl_read_for_datatype!(read_u16, u16);
+    impl_read_for_datatype!(read_u32, u32);
+    impl_read_for_datatype!(read_i32, i32);
+    impl_read_slice!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image::grammar::ImageExt, png::PngDecoder};
+
+    #[test]
+    fn test_decode_matrix_trc() -> Result<()> {
+        let content = std::fs::read("./tests/display_p3.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+        let profile = png.icc_profile().expect("iCCP chunk");
+
+        assert_eq!(profile.version(), (4, 3));
+        assert_eq!(profile.device_class(), b"mntr");
+        assert_eq!(profile.color_space(), ProfileColorSpace::RGB);
+        assert_eq!(profile.connection_space(), ProfileColorSpace::XYZ);
+        assert_eq!(profile.description(), Some("Display P3"));
+
+        let Some(ColorModel::MatrixTrc { colorants, curves }) = profile.color_model() else {
+            panic!("expected a matrix/TRC profile");
+        };
+
+        // The green colorant, to s15Fixed16 precision.
+        assert!((colorants[0][1] - 0.291_965).abs() < 1e-4);
+        assert!((colorants[1][1] - 0.692_236).abs() < 1e-4);
+        assert!((colorants[2][1] - 0.041_882).abs() < 1e-4);
+
+        assert!(matches!(curves[0], Curve::Parametric(3, _)));
+        assert!((curves[0].evaluate(0.5) - 0.214_041).abs() < 1e-4);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_lut16() -> Result<()> {
+        let content = std::fs::read("./tests/srgb_lut16.icc")?;
+        let profile = IccDecoder::new(&content).decode()?;
+
+        assert_eq!(profile.version(), (2, 1));
+        assert_eq!(profile.connection_space(), ProfileColorSpace::Lab);
+        assert_eq!(profile.description(), Some("sRGB lut16"));
+        assert_eq!(profile.data(), content);
+
+        let Some(ColorModel::Lut(lut)) = profile.color_model() else {
+            panic!("expected a lookup table profile");
+        };
+
+        assert_eq!(lut.pcs_encoding, PcsEncoding::Lut16);
+        assert_eq!(lut.a_curves.len(), 3);
+        assert_eq!(lut.b_curves.len(), 3);
+
+        let clut = lut.clut.as_ref().expect("CLUT");
+        assert_eq!(clut.grid_points, vec![17; 3]);
+        assert_eq!(clut.values.len(), 17 * 17 * 17 * 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_lut_a_to_b() -> Result<()> {
+        let content = std::fs::read("./tests/srgb_lut_a_to_b.icc")?;
+        let profile = IccDecoder::new(&content).decode()?;
+
+        assert_eq!(profile.description(), Some("sRGB lutAToB"));
+
+        let Some(ColorModel::Lut(lut)) = profile.color_model() else {
+            panic!("expected a lookup table profile");
+        };
+
+        assert_eq!(lut.pcs_encoding, PcsEncoding::LutAToB);
+        assert!(lut.a_curves.is_empty() && lut.clut.is_none());
+        assert_eq!(lut.m_curves.len(), 3);
+        assert_eq!(lut.b_curves, vec![Curve::Identity; 3]);
+        assert!(lut.matrix.is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_rejects_invalid_profiles() {
+        assert!(IccDecoder::new(&[0; 64]).decode().is_err());
+        assert!(IccDecoder::new(&[0; 256]).decode().is_err());
+    }
+}
@@ -0,0 +1,224 @@
+#![allow(clippy::suboptimal_flops)]
+
+/// The color space of the data a profile describes, or of its profile connection space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProfileColorSpace {
+    XYZ,
+    Lab,
+    RGB,
+    Gray,
+    CMYK,
+    Other([u8; 4]),
+}
+
+impl From<[u8; 4]> for ProfileColorSpace {
+    fn from(signature: [u8; 4]) -> Self {
+        match &signature {
+            b"XYZ " => Self::XYZ,
+            b"Lab " => Self::Lab,
+            b"RGB " => Self::RGB,
+            b"GRAY" => Self::Gray,
+            b"CMYK" => Self::CMYK,
+            _ => Self::Other(signature),
+        }
+    }
+}
+
+/// A one-dimensional transfer function, mapping normalized values in [0, 1].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Curve {
+    Identity,
+    Gamma(f32),
+    /// Equally spaced samples, linearly interpolated.
+    Table(Vec<f32>),
+    /// One of the five parametric curve functions of a `para` tag, along with its parameters
+    /// g, a, b, c, d, e and f.
+    Parametric(u16, [f32; 7]),
+}
+
+impl Curve {
+    pub fn evaluate(&self, x: f32) -> f32 {
+        let x = x.clamp(0.0, 1.0);
+
+        match self {
+            Self::Identity => x,
+            Self::Gamma(gamma) => x.powf(*gamma),
+            Self::Table(table) => match table.as_slice() {
+                [] => x,
+                [only] => *only,
+                table => {
+                    let position = x * (table.len() - 1) as f32;
+                    let index = (position as usize).min(table.len() - 2);
+                    let fraction = position - index as f32;
+
+                    table[index] + (table[index + 1] - table[index]) * fraction
+                }
+            },
+            &Self::Parametric(function, [g, a, b, c, d, e, f]) => {
+                let y = match function {
+                    0 => x.powf(g),
+                    1 if x >= -b / a => (a * x + b).powf(g),
+                    1 => 0.0,
+                    2 if x >= -b / a => (a * x + b).powf(g) + c,
+                    2 => c,
+                    3 if x >= d => (a * x + b).powf(g),
+                    3 => c * x,
+                    4 if x >= d => (a * x + b).powf(g) + e,
+                    4 => c * x + f,
+                    _ => x,
+                };
+
+                y.clamp(0.0, 1.0)
+            }
+        }
+    }
+}
+
+/// A multidimensional color lookup table with `grid_points[i]` samples along input dimension
+/// `i`. Samples are normalized to [0, 1] and stored with the first input dimension varying
+/// slowest.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clut {
+    pub(crate) grid_points: Vec<usize>,
+    pub(crate) output_channels: usize,
+    pub(crate) values: Vec<f32>,
+}
+
+impl Clut {
+    /// Interpolates the table at `input` multilinearly, writing `output_channels` values to
+    /// `output`.
+    pub fn evaluate(&self, input: &[f32], output: &mut [f32]) {
+        output.fill(0.0);
+
+        let dimensions = self.grid_points.len();
+
+        // The lower grid index and the fraction towards the next one, per input dimension.
+        let cells = input
+            .iter()
+            .zip(&self.grid_points)
+            .map(|(&x, &n)| {
+                if n < 2 {
+                    return (0, 0.0);
+                }
+
+                let position = x.clamp(0.0, 1.0) * (n - 1) as f32;
+                let index = (position as usize).min(n - 2);
+
+                (index, position - index as f32)
+            })
+            .collect::<Vec<_>>();
+
+        // Visit the 2^dimensions corners of the enclosing cell.
+        for corner in 0..1_usize << dimensions {
+            let mut weight = 1.0;
+            let mut offset = 0;
+
+            for (dimension, &(index, fraction)) in cells.iter().enumerate() {
+                let upper = (corner >> (dimensions - 1 - dimension)) & 1 == 1;
+                let n = self.grid_points[dimension];
+
+                let index = if upper { (index + 1).min(n - 1) } else { index };
+                weight *= if upper { fraction } else { 1.0 - fraction };
+                offset = offset * n + index;
+            }
+
+            if weight == 0.0 {
+                continue;
+            }
+
+            let values = &self.values[offset * self.output_channels..][..self.output_channels];
+            for (output, &value) in output.iter_mut().zip(values) {
+                *output += weight * value;
+            }
+        }
+    }
+}
+
+/// How the output of a `Lut` encodes the profile connection space.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PcsEncoding {
+    /// `lut8Type`.
+    Lut8,
+    /// `lut16Type`, which keeps the version 2 Lab encoding.
+    Lut16,
+    /// `lutAToBType`.
+    LutAToB,
+}
+
+/// A transform from device colors to the profile connection space built from lookup tables,
+/// from a `lut8Type`, `lut16Type` or `lutAToBType` tag.
+///
+/// Stages are applied in order: A curves, CLUT, M curves, matrix and B curves. The lut8 and lut16
+/// types are expressed with the input tables as A curves and the output tables as B curves.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lut {
+    pub(crate) input_channels: usize,
+    pub(crate) a_curves: Vec<Curve>,
+    pub(crate) clut: Option<Clut>,
+    pub(crate) m_curves: Vec<Curve>,
+    /// A 3x3 matrix in row-major order followed by an offset per row.
+    pub(crate) matrix: Option<[f32; 12]>,
+    pub(crate) b_curves: Vec<Curve>,
+    pub(crate) pcs_encoding: PcsEncoding,
+}
+
+/// How a profile maps device colors to the profile connection space.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorModel {
+    /// Per-channel tone curves followed by a matrix whose columns are the XYZ values of the red,
+    /// green and blue colorants.
+    MatrixTrc {
+        colorants: [[f32; 3]; 3],
+        curves: [Curve; 3],
+    },
+    /// A tone curve mapping gray to luminance.
+    GrayTrc(Curve),
+    Lut(Lut),
+}
+
+/// An ICC profile, as embedded in an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IccProfile {
+    pub(crate) version: (u8, u8),
+    pub(crate) device_class: [u8; 4],
+    pub(crate) color_space: ProfileColorSpace,
+    pub(crate) connection_space: ProfileColorSpace,
+    pub(crate) description: Option<String>,
+    /// None if the profile's transform is not supported, in which case colors are passed
+    /// through unmanaged.
+    pub(crate) color_model: Option<ColorModel>,
+    /// The profile as stored, so it can be embedded again when re-encoding.
+    pub(crate) data: Vec<u8>,
+}
+
+impl IccProfile {
+    /// The major and minor version of the profile format.
+    pub const fn version(&self) -> (u8, u8) {
+        self.version
+    }
+
+    /// The profile class, such as `mntr` for displays or `scnr` for input devices.
+    pub const fn device_class(&self) -> &[u8; 4] {
+        &self.device_class
+    }
+
+    pub const fn color_space(&self) -> ProfileColorSpace {
+        self.color_space
+    }
+
+    pub const fn connection_space(&self) -> ProfileColorSpace {
+        self.connection_space
+    }
+
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    pub const fn color_model(&self) -> Option<&ColorModel> {
+        self.color_model.as_ref()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+}
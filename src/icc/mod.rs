@@ -0,0 +1,7 @@
+mod decoder;
+mod transform;
+
+pub mod grammar;
+
+pub use decoder::*;
+pub use transform::*;
@@ -0,0 +1,277 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::icc::grammar::{ColorModel, IccProfile, Lut, PcsEncoding, ProfileColorSpace};
+
+/// Maps XYZ relative to the D50 illuminant of the profile connection space to linear sRGB,
+/// adapting the white point to D65 with the Bradford transform.
+const XYZ_D50_TO_LINEAR_SRGB: [[f32; 3]; 3] = [
+    [3.133_856, -1.616_867, -0.490_615],
+    [-0.978_768, 1.916_142, 0.033_454],
+    [0.071_945, -0.228_991, 1.405_243],
+];
+
+/// The D50 illuminant, in XYZ.
+const D50: [f32; 3] = [0.9642, 1.0, 0.8249];
+
+/// The number of entries of the table used to encode linear values with the sRGB transfer
+/// function.
+const ENCODING_TABLE_SIZE: usize = 4096;
+
+fn multiply(matrix: &[[f32; 3]; 3], vector: [f32; 3]) -> [f32; 3] {
+    matrix.map(|row| row[0] * vector[0] + row[1] * vector[1] + row[2] * vector[2])
+}
+
+fn multiply_matrices(a: &[[f32; 3]; 3], b: &[[f32; 3]; 3]) -> [[f32; 3]; 3] {
+    std::array::from_fn(|row| {
+        std::array::from_fn(|column| (0..3).map(|k| a[row][k] * b[k][column]).sum())
+    })
+}
+
+/// Converts CIE L*a*b* relative to D50 to XYZ (see CIE 15).
+fn lab_to_xyz([l, a, b]: [f32; 3]) -> [f32; 3] {
+    let fy = (l + 16.0) / 116.0;
+    let fx = fy + a / 500.0;
+    let fz = fy - b / 200.0;
+
+    let inverse = |t: f32| {
+        if t > 6.0 / 29.0 {
+            t * t * t
+        } else {
+            3.0 * (6.0_f32 / 29.0).powi(2) * (t - 4.0 / 29.0)
+        }
+    };
+
+    [
+        D50[0] * inverse(fx),
+        D50[1] * inverse(fy),
+        D50[2] * inverse(fz),
+    ]
+}
+
+/// Evaluates a lookup table on normalized device values, returning XYZ relative to D50.
+fn evaluate_lut(lut: &Lut, connection_space: ProfileColorSpace, input: &[f32]) -> [f32; 3] {
+    let mut values = input.to_vec();
+
+    for (value, curve) in values.iter_mut().zip(&lut.a_curves) {
+        *value = curve.evaluate(*value);
+    }
+
+    if let Some(clut) = &lut.clut {
+        let mut output = vec![0.0; clut.output_channels];
+        clut.evaluate(&values, &mut output);
+        values = output;
+    }
+
+    for (value, curve) in values.iter_mut().zip(&lut.m_curves) {
+        *value = curve.evaluate(*value);
+    }
+
+    if let Some(m) = &lut.matrix {
+        let [x, y, z] = [values[0], values[1], values[2]];
+
+        values = (0..3)
+            .map(|row| m[row * 3] * x + m[row * 3 + 1] * y + m[row * 3 + 2] * z + m[9 + row])
+            .collect();
+    }
+
+    for (value, curve) in values.iter_mut().zip(&lut.b_curves) {
+        *value = curve.evaluate(*value);
+    }
+
+    let pcs = [values[0], values[1], values[2]];
+
+    match (connection_space, lut.pcs_encoding) {
+        // 1.0 is encoded as 0x8000.
+        (ProfileColorSpace::XYZ, _) => pcs.map(|v| v * 65535.0 / 32768.0),
+        // Version 2 encodes L* = 100 as 0xFF00.
+        (_, PcsEncoding::Lut16) => {
+            let [l, a, b] = pcs.map(|v| v * 65535.0 / 65280.0);
+            lab_to_xyz([l * 100.0, a * 255.0 - 128.0, b * 255.0 - 128.0])
+        }
+        _ => lab_to_xyz([
+            pcs[0] * 100.0,
+            pcs[1] * 255.0 - 128.0,
+            pcs[2] * 255.0 - 128.0,
+        ]),
+    }
+}
+
+enum Stages {
+    /// Linearization tables for each 8-bit input channel followed by a matrix to linear sRGB.
+    Matrix {
+        linearize: Box<[[f32; 256]; 3]>,
+        matrix: [[f32; 3]; 3],
+    },
+    /// The sRGB encoded value of each 8-bit gray level.
+    Gray([u8; 256]),
+    Lut {
+        lut: Lut,
+        connection_space: ProfileColorSpace,
+    },
+}
+
+/// Converts 8-bit colors described by an ICC profile to sRGB, for display.
+pub struct SrgbTransform {
+    stages: Stages,
+    /// The sRGB transfer function, indexed by linear values in [0, 1].
+    encode: Vec<u8>,
+}
+
+impl SrgbTransform {
+    /// Builds the transform for `profile`, or returns None if its color model is not supported.
+    pub fn new(profile: &IccProfile) -> Option<Self> {
+        let encode = (0..ENCODING_TABLE_SIZE)
+            .map(|i| {
+                let linear = i as f32 / (ENCODING_TABLE_SIZE - 1) as f32;
+
+                let encoded = if linear <= 0.003_130_8 {
+                    12.92 * linear
+                } else {
+                    1.055 * linear.powf(1.0 / 2.4) - 0.055
+                };
+
+                (encoded * 255.0).round() as u8
+            })
+            .collect::<Vec<_>>();
+
+        let stages = match profile.color_model()? {
+            ColorModel::MatrixTrc { colorants, curves } => Stages::Matrix {
+                linearize: Box::new(std::array::from_fn(|channel| {
+                    std::array::from_fn(|i| curves[channel].evaluate(i as f32 / 255.0))
+                })),
+                matrix: multiply_matrices(&XYZ_D50_TO_LINEAR_SRGB, colorants),
+            },
+            ColorModel::GrayTrc(curve) => {
+                // Gray maps to a fraction of the D50 white point, which maps to the sRGB white
+                // point.
+                let mut gray = [0; 256];
+                for (i, gray) in gray.iter_mut().enumerate() {
+                    *gray = Self::encode_with(&encode, curve.evaluate(i as f32 / 255.0));
+                }
+
+                Stages::Gray(gray)
+            }
+            ColorModel::Lut(lut) => Stages::Lut {
+                lut: lut.clone(),
+                connection_space: profile.connection_space(),
+            },
+        };
+
+        Some(Self { stages, encode })
+    }
+
+    fn encode_with(encode: &[u8], linear: f32) -> u8 {
+        let index = (linear.clamp(0.0, 1.0) * (encode.len() - 1) as f32).round();
+        encode[index as usize]
+    }
+
+    /// Converts a single pixel. Gray profiles read the first channel only.
+    pub fn convert(&self, [r, g, b]: [u8; 3]) -> [u8; 3] {
+        let linear = match &self.stages {
+            Stages::Matrix { linearize, matrix } => multiply(
+                matrix,
+                [
+                    linearize[0][r as usize],
+                    linearize[1][g as usize],
+                    linearize[2][b as usize],
+                ],
+            ),
+            Stages::Gray(gray) => return [gray[r as usize]; 3],
+            Stages::Lut {
+                lut,
+                connection_space,
+            } => {
+                let input = [r, g, b].map(|c| c as f32 / 255.0);
+                let xyz = evaluate_lut(lut, *connection_space, &input[..lut.input_channels]);
+
+                multiply(&XYZ_D50_TO_LINEAR_SRGB, xyz)
+            }
+        };
+
+        linear.map(|c| Self::encode_with(&self.encode, c))
+    }
+
+    /// Converts packed pixels of `num_channels` channels in place, leaving any channels past the
+    /// third, such as alpha, untouched.
+    pub fn convert_pixels(&self, pixels: &mut [u8], num_channels: usize) {
+        // Images tend to repeat colors, and lookup table transforms are comparatively slow.
+        let mut last = None;
+
+        for pixel in pixels.chunks_exact_mut(num_channels) {
+            let color = [pixel[0], pixel[1], pixel[2]];
+
+            let converted = match last {
+                Some((previous, converted)) if previous == color => converted,
+                _ => {
+                    let converted = self.convert(color);
+                    last = Some((color, converted));
+                    converted
+                }
+            };
+
+            pixel[..3].copy_from_slice(&converted);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{icc::IccDecoder, image::grammar::ImageExt, png::PngDecoder};
+    use anyhow::Result;
+
+    #[test]
+    fn test_display_p3_to_srgb() -> Result<()> {
+        let content = std::fs::read("./tests/display_p3.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+        let transform = SrgbTransform::new(png.icc_profile().unwrap()).unwrap();
+
+        // Computed in double precision from the profile's colorants and the sRGB primaries.
+        // Colors outside the sRGB gamut clip.
+        let expected = [
+            [255, 0, 0],
+            [0, 255, 0],
+            [0, 0, 255],
+            [255, 255, 255],
+            [128, 128, 128],
+            [215, 93, 31],
+            [0, 0, 0],
+            [0, 204, 180],
+        ];
+
+        let mut pixels = png.rgb8().into_owned();
+        transform.convert_pixels(&mut pixels, 3);
+
+        for (converted, expected) in pixels.chunks_exact(3).zip(expected) {
+            for (&c, e) in converted.iter().zip(expected) {
+                assert!(c.abs_diff(e) <= 1, "{converted:?} != {expected:?}");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_lut_profiles_describe_srgb() -> Result<()> {
+        for path in ["./tests/srgb_lut16.icc", "./tests/srgb_lut_a_to_b.icc"] {
+            let content = std::fs::read(path)?;
+            let profile = IccDecoder::new(&content).decode()?;
+            let transform = SrgbTransform::new(&profile).unwrap();
+
+            for r in (0..=255).step_by(15) {
+                for g in (0..=255).step_by(15) {
+                    for b in (0..=255).step_by(15) {
+                        let color = [r as u8, g as u8, b as u8];
+                        let converted = transform.convert(color);
+
+                        for (c, e) in converted.into_iter().zip(color) {
+                            assert!(c.abs_diff(e) <= 2, "{path}: {color:?} -> {converted:?}");
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
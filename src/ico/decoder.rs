@@ -0,0 +1,217 @@
+use crate::{
+    bmp::BmpDecoder,
+    ico::grammar::{
+        Ico, IconDirEntry, IconImage, ImageFormat, ResourceType, ENTRY_SIZE, HEADER_SIZE,
+        PNG_SIGNATURE,
+    },
+    image::{grammar::ImageExt, DynamicImageBuffer},
+    impl_read_le_for_datatype, impl_read_slice,
+    png::PngDecoder,
+};
+use anyhow::{anyhow, ensure, Context, Result};
+
+#[derive(Debug)]
+pub struct IcoDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> IcoDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    /// Decodes every image of an icon or cursor.
+    pub fn decode(&mut self) -> Result<Ico> {
+        ensure!(self.read_u16()? == 0, "Expected an ICO header.");
+        let resource_type = ResourceType::try_from(self.read_u16()?)?;
+        let count = self.read_u16()? as usize;
+
+        ensure!(count > 0, "ICO has no images.");
+        ensure!(
+            self.data.len() >= HEADER_SIZE + count * ENTRY_SIZE,
+            "ICO directory is truncated."
+        );
+
+        let entries = self.read_vec(count, Self::parse_entry)?;
+
+        let images = entries
+            .into_iter()
+            .enumerate()
+            .map(|(index, entry)| {
+                self.decode_image(entry)
+                    .with_context(|| format!("Invalid ICO image {index}."))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Ico {
+            resource_type,
+            images,
+        })
+    }
+
+    fn parse_entry(&mut self) -> Result<IconDirEntry> {
+        let [width, height, color_count, _reserved] = self.read_fixed_array(Self::read_u8)?;
+
+        // Sizes of 256 don't fit in a byte.
+        let size = |stored: u8| match stored {
+            0 => 256,
+            size => size as u32,
+        };
+
+        Ok(IconDirEntry {
+            width: size(width),
+            height: size(height),
+            color_count,
+            planes: self.read_u16()?,
+            bits_per_pixel: self.read_u16()?,
+            size: self.read_u32()?,
+            offset: self.read_u32()?,
+        })
+    }
+
+    fn decode_image(&self, entry: IconDirEntry) -> Result<IconImage> {
+        let start = entry.offset as usize;
+        let data = self
+            .data
+            .get(start..start + entry.size as usize)
+            .ok_or_else(|| anyhow!("ICO image data lies past the end of the file."))?;
+
+        let (format, image) = if data.starts_with(&PNG_SIGNATURE) {
+            let png = PngDecoder::new(data).decode()?;
+            (
+                ImageFormat::Png,
+                DynamicImageBuffer::try_from(&png as &dyn ImageExt)?,
+            )
+        } else {
+            let bmp = BmpDecoder::new(data).decode_icon()?;
+            (ImageFormat::Bmp, bmp.image)
+        };
+
+        // Images of 256 pixels and more all list 256.
+        let (width, height) = image.dimensions();
+        ensure!(
+            (width.min(256), height.min(256)) == (entry.width, entry.height),
+            "ICO entry is {}x{}, but its image is {}x{}.",
+            entry.width,
+            entry.height,
+            width,
+            height
+        );
+
+        Ok(IconImage {
+            entry,
+            format,
+            image,
+        })
+    }
+
+    impl_read_le_for_datatype!(read_u8, u8);
+    impl_read_le_for_datatype!(read_u16, u16);
+    impl_read_le_for_datatype!(read_u32, u32);
+
+    impl_read_slice!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::grammar::ColorType;
+
+    #[test]
+    fn test_decode_against_image_crate() -> Result<()> {
+        for path in [
+            "./tests/ico/pal8_mask.ico",
+            "./tests/ico/rgba32.ico",
+            "./tests/ico/multiple.ico",
+        ] {
+            let ico = IcoDecoder::new(&std::fs::read(path)?).decode()?;
+            let reference = image::open(path)?.to_rgba8();
+
+            assert_eq!(ico.dimensions(), reference.dimensions(), "{path}");
+            assert_eq!(
+                ico.rgba8().as_ref(),
+                reference.as_raw().as_slice(),
+                "{path}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_entries() -> Result<()> {
+        let ico = IcoDecoder::new(&std::fs::read("./tests/ico/multiple.ico")?).decode()?;
+        assert_eq!(ico.resource_type(), ResourceType::Icon);
+
+        let formats = ico.images().iter().map(|image| image.format());
+        assert_eq!(
+            formats.collect::<Vec<_>>(),
+            [ImageFormat::Bmp, ImageFormat::Bmp, ImageFormat::Png]
+        );
+
+        let [pal8, rgba32, png] = ico.images() else {
+            panic!("expected 3 images");
+        };
+
+        assert_eq!(pal8.entry().color_count, 4);
+        assert_eq!(rgba32.entry().bits_per_pixel, 32);
+        assert_eq!(png.image().color_type(), ColorType::RGBA);
+        assert!(std::ptr::eq(ico.best_image(), png));
+        assert_eq!(ico.hotspot(png), None);
+
+        // The same bitmaps on their own.
+        let single = IcoDecoder::new(&std::fs::read("./tests/ico/pal8_mask.ico")?).decode()?;
+        assert_eq!(single.rgba8(), pal8.image().rgba8());
+
+        // The AND mask makes the border of the palettized image transparent.
+        let pixels = pal8.image().rgba8();
+        assert_eq!(&pixels[..4], [255, 0, 0, 0]);
+        assert_eq!(&pixels[17 * 4..18 * 4], [255, 0, 0, 255]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_cursor() -> Result<()> {
+        let cur = IcoDecoder::new(&std::fs::read("./tests/ico/pointer.cur")?).decode()?;
+        assert_eq!(cur.resource_type(), ResourceType::Cursor);
+        assert_eq!(cur.hotspot(cur.best_image()), Some((5, 7)));
+        assert_eq!(cur.dimensions(), (12, 10));
+
+        // A checkerboard of black and white, with the last three columns masked out.
+        let pixels = cur.rgba8();
+        for (i, pixel) in pixels.chunks_exact(4).enumerate() {
+            let (x, y) = (i % 12, i / 12);
+            let level = if (x + y) % 2 == 0 { 0 } else { 255 };
+            let alpha = if x >= 9 { 0 } else { 255 };
+
+            assert_eq!(pixel, [level, level, level, alpha], "({x}, {y})");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_invalid() -> Result<()> {
+        let data = std::fs::read("./tests/ico/rgba32.ico")?;
+
+        assert!(IcoDecoder::new(&data[..data.len() - 1]).decode().is_err());
+        assert!(IcoDecoder::new(&data[..20]).decode().is_err());
+        assert!(IcoDecoder::new(&[0, 0, 1, 0, 0, 0]).decode().is_err());
+        assert!(IcoDecoder::new(&[0, 0, 3, 0, 1, 0]).decode().is_err());
+
+        // The bitmap's info header claims to run past the end of the entry.
+        let mut overrun = data.clone();
+        let offset = u32::from_le_bytes(data[18..22].try_into()?) as usize;
+        overrun[offset..offset + 4].copy_from_slice(&32552u32.to_le_bytes());
+        assert!(IcoDecoder::new(&overrun).decode().is_err());
+
+        // The entry claims 16x16 pixels.
+        let mut resized = data;
+        resized[6..8].copy_from_slice(&[16, 16]);
+        assert!(IcoDecoder::new(&resized).decode().is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use crate::{
+    ico::grammar::{ResourceType, ENTRY_SIZE, HEADER_SIZE},
+    image::{
+        grammar::{ImageEncoder, ImageExt},
+        ImageBuffer, Rgba, Rgba8,
+    },
+    png::PngEncoder,
+};
+use anyhow::{ensure, Result};
+use std::io::Write;
+
+/// The sizes Windows draws icons at, from small list views to large thumbnails.
+pub const DEFAULT_SIZES: [u32; 5] = [16, 24, 32, 48, 256];
+
+/// Images this large are stored as PNG, which Windows reads since Vista. Smaller ones are
+/// bitmaps, which every reader supports.
+const MIN_PNG_SIZE: u32 = 256;
+
+/// The size of the BITMAPINFOHEADER of bitmap entries.
+const INFO_HEADER_SIZE: u32 = 40;
+
+/// Writes an icon, or a cursor, holding a source image scaled to each of a set of square
+/// sizes.
+pub struct IcoEncoder<W: Write> {
+    writer: W,
+    sizes: Vec<u32>,
+    hotspot: Option<(u32, u32)>,
+}
+
+impl<W: Write> IcoEncoder<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            sizes: DEFAULT_SIZES.to_vec(),
+            hotspot: None,
+        }
+    }
+
+    /// The widths and heights of the images to write, from 1 to 256.
+    pub fn sizes(mut self, sizes: &[u32]) -> Self {
+        self.sizes = sizes.to_vec();
+        self
+    }
+
+    /// Writes a cursor that clicks at (`x`, `y`) of the source image, rather than an icon.
+    pub const fn hotspot(mut self, x: u32, y: u32) -> Self {
+        self.hotspot = Some((x, y));
+        self
+    }
+
+    /// Scales `image` to fit each size, keeping its aspect ratio and centering it on
+    /// transparent pixels.
+    pub fn encode(&mut self, image: &dyn ImageExt) -> Result<()> {
+        let (width, height) = image.dimensions();
+        ensure!(
+            width > 0 && height > 0,
+            "Invalid ICO source dimensions: {}x{}",
+            width,
+            height
+        );
+        ensure!(
+            !self.sizes.is_empty() && self.sizes.len() <= u16::MAX as usize,
+            "Invalid ICO image count: {}",
+            self.sizes.len()
+        );
+
+        let source =
+            ImageBuffer::<Rgba8>::from_raw(width, height, image.straight_rgba8().into_owned())?;
+
+        let resource_type = match self.hotspot {
+            Some(_) => ResourceType::Cursor,
+            None => ResourceType::Icon,
+        };
+
+        let mut directory = Vec::with_capacity(HEADER_SIZE + self.sizes.len() * ENTRY_SIZE);
+        directory.extend_from_slice(&0u16.to_le_bytes());
+        directory.extend_from_slice(&(resource_type as u16).to_le_bytes());
+        directory.extend_from_slice(&(self.sizes.len() as u16).to_le_bytes());
+
+        let mut images = Vec::new();
+        let mut offset = (HEADER_SIZE + self.sizes.len() * ENTRY_SIZE) as u32;
+
+        for &size in &self.sizes {
+            ensure!((1..=256).contains(&size), "Invalid ICO size: {}", size);
+
+            let scale = size as f32 / width.max(height) as f32;
+            let scaled_width = ((width as f32 * scale).round() as u32).clamp(1, size);
+            let scaled_height = ((height as f32 * scale).round() as u32).clamp(1, size);
+            let (left, top) = ((size - scaled_width) / 2, (size - scaled_height) / 2);
+
+            let mut icon = ImageBuffer::<Rgba8>::new(size, size);
+            icon.view_mut(left, top, scaled_width, scaled_height)?
+                .copy_from(&source.resize(scaled_width, scaled_height))?;
+
+            let data = if size >= MIN_PNG_SIZE {
+                let mut data = Vec::new();
+                PngEncoder::new(&mut data).write_image(&icon)?;
+                data
+            } else {
+                encode_bitmap(&icon)
+            };
+
+            // Cursors store their hotspot where icons store their planes and bit depth.
+            let (planes, bits_per_pixel) = match self.hotspot {
+                Some((x, y)) => {
+                    let scale_position = |position: u32, start: u32| {
+                        let scaled = ((position as f32 + 0.5) * scale) as u32 + start;
+                        scaled.min(size - 1) as u16
+                    };
+
+                    (scale_position(x, left), scale_position(y, top))
+                }
+                None => (1, 32),
+            };
+
+            // 256 is stored as 0.
+            directory.extend_from_slice(&[size as u8, size as u8, 0, 0]);
+            directory.extend_from_slice(&planes.to_le_bytes());
+            directory.extend_from_slice(&bits_per_pixel.to_le_bytes());
+            directory.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            directory.extend_from_slice(&offset.to_le_bytes());
+
+            offset += data.len() as u32;
+            images.push(data);
+        }
+
+        self.writer.write_all(&directory)?;
+        for data in images {
+            self.writer.write_all(&data)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<W: Write> ImageEncoder for IcoEncoder<W> {
+    fn write_image(&mut self, image: &dyn ImageExt) -> Result<()> {
+        self.encode(image)
+    }
+}
+
+/// Stores `icon` as a 32-bit BGRA bitmap, bottom-up, followed by an AND mask that hides its
+/// transparent pixels from readers that ignore alpha.
+fn encode_bitmap(icon: &ImageBuffer<Rgba8>) -> Vec<u8> {
+    let (width, height) = icon.dimensions();
+    let mask_stride = width.div_ceil(32) as usize * 4;
+    let image_size = (width * height * 4) as usize + mask_stride * height as usize;
+
+    let mut data = Vec::with_capacity(INFO_HEADER_SIZE as usize + image_size);
+    data.extend_from_slice(&INFO_HEADER_SIZE.to_le_bytes());
+    data.extend_from_slice(&(width as i32).to_le_bytes());
+    // The height covers both the pixels and the mask.
+    data.extend_from_slice(&(2 * height as i32).to_le_bytes());
+    data.extend_from_slice(&1u16.to_le_bytes());
+    data.extend_from_slice(&32u16.to_le_bytes());
+    data.extend_from_slice(&0u32.to_le_bytes());
+    data.extend_from_slice(&(image_size as u32).to_le_bytes());
+    data.extend_from_slice(&[0; 16]);
+
+    for row in (0..height).rev().map(|y| icon.row(y)) {
+        for &Rgba([r, g, b, a]) in row {
+            data.extend_from_slice(&[b, g, r, a]);
+        }
+    }
+
+    for row in (0..height).rev().map(|y| icon.row(y)) {
+        let mut mask = vec![0; mask_stride];
+        for (x, pixel) in row.iter().enumerate() {
+            if pixel.0[3] == 0 {
+                mask[x / 8] |= 0x80 >> (x % 8);
+            }
+        }
+
+        data.extend_from_slice(&mask);
+    }
+
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        ico::{grammar::ImageFormat, IcoDecoder},
+        png::PngDecoder,
+    };
+
+    #[test]
+    fn test_encode_sizes() -> Result<()> {
+        let data = std::fs::read("./tests/obama.png")?;
+        let source = PngDecoder::new(&data).decode()?;
+
+        let mut encoded = Vec::new();
+        IcoEncoder::new(&mut encoded).encode(&source)?;
+
+        let ico = IcoDecoder::new(&encoded).decode()?;
+        assert_eq!(ico.resource_type(), ResourceType::Icon);
+
+        let sizes = ico.images().iter().map(|image| image.image().width());
+        assert_eq!(sizes.collect::<Vec<_>>(), DEFAULT_SIZES);
+
+        let formats = ico.images().iter().map(|image| image.format());
+        assert_eq!(
+            formats.collect::<Vec<_>>(),
+            [
+                ImageFormat::Bmp,
+                ImageFormat::Bmp,
+                ImageFormat::Bmp,
+                ImageFormat::Bmp,
+                ImageFormat::Png
+            ]
+        );
+
+        // The image crate reads the largest image.
+        let reference = image::load_from_memory(&encoded)?.to_rgba8();
+        assert_eq!(ico.dimensions(), (256, 256));
+        assert_eq!(ico.rgba8().as_ref(), reference.as_raw().as_slice());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_png_entry_round_trip() -> Result<()> {
+        let source = ImageBuffer::<Rgba8>::from_fn(256, 256, |x, y| {
+            Rgba([
+                ((x * 7) ^ y) as u8,
+                (x + y * 3) as u8,
+                (x * y) as u8,
+                (y * 5) as u8,
+            ])
+        });
+
+        let mut encoded = Vec::new();
+        IcoEncoder::new(&mut encoded)
+            .sizes(&[256])
+            .encode(&source)?;
+
+        let ico = IcoDecoder::new(&encoded).decode()?;
+        assert_eq!(ico.images()[0].format(), ImageFormat::Png);
+        assert_eq!(ico.rgba8(), source.rgba8());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_letterboxed_cursor() -> Result<()> {
+        // A 4x2 opaque image, with one transparent pixel.
+        let source = ImageBuffer::<Rgba8>::from_fn(4, 2, |x, y| {
+            if (x, y) == (3, 1) {
+                Rgba([0, 0, 0, 0])
+            } else {
+                Rgba([200, 100, 50, 255])
+            }
+        });
+
+        let mut encoded = Vec::new();
+        IcoEncoder::new(&mut encoded)
+            .sizes(&[4, 8])
+            .hotspot(1, 1)
+            .encode(&source)?;
+
+        let cur = IcoDecoder::new(&encoded).decode()?;
+        assert_eq!(cur.resource_type(), ResourceType::Cursor);
+
+        let [small, large] = cur.images() else {
+            panic!("expected 2 images");
+        };
+
+        // Centered vertically, between transparent rows.
+        assert_eq!(cur.hotspot(small), Some((1, 2)));
+        let pixels = small.image().rgba8();
+        assert!(pixels[..16].chunks_exact(4).all(|p| p[3] == 0));
+        assert_eq!(&pixels[16..20], [200, 100, 50, 255]);
+        assert_eq!(&pixels[44..48], [0, 0, 0, 0]);
+        assert!(pixels[48..].chunks_exact(4).all(|p| p[3] == 0));
+
+        assert_eq!(cur.hotspot(large), Some((3, 5)));
+        assert_eq!(large.image().dimensions(), (8, 8));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_invalid() {
+        let source = ImageBuffer::<Rgba8>::from_pixel(2, 2, Rgba([1, 2, 3, 255]));
+
+        for sizes in [&[][..], &[0], &[257]] {
+            let mut encoded = Vec::new();
+            assert!(IcoEncoder::new(&mut encoded)
+                .sizes(sizes)
+                .encode(&source)
+                .is_err());
+            assert!(encoded.is_empty());
+        }
+    }
+}
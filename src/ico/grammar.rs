@@ -0,0 +1,147 @@
+use crate::image::{
+    grammar::{ColorType, ImageExt},
+    DynamicImageBuffer,
+};
+use anyhow::bail;
+use std::borrow::Cow;
+
+/// The size of the ICONDIR header: reserved, resource type and image count.
+pub const HEADER_SIZE: usize = 6;
+
+/// The size of an ICONDIRENTRY.
+pub const ENTRY_SIZE: usize = 16;
+
+/// Entries store their images as PNG files, or as bitmaps without a file header.
+pub const PNG_SIGNATURE: [u8; 8] = *b"\x89PNG\r\n\x1A\n";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResourceType {
+    Icon = 1,
+    Cursor = 2,
+}
+
+impl TryFrom<u16> for ResourceType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => Self::Icon,
+            2 => Self::Cursor,
+            _ => bail!("Invalid ICO resource type: {value}"),
+        })
+    }
+}
+
+/// How an entry's image is stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Bmp,
+    Png,
+}
+
+/// An ICONDIRENTRY, describing one image of the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IconDirEntry {
+    /// Widths of 256 and more are stored as 0.
+    pub width: u32,
+    pub height: u32,
+    /// The number of palette colors, or 0 without a palette.
+    pub color_count: u8,
+    /// The color planes of an icon, or the hotspot's x of a cursor.
+    pub planes: u16,
+    /// The bit depth of an icon, or the hotspot's y of a cursor.
+    pub bits_per_pixel: u16,
+    pub size: u32,
+    pub offset: u32,
+}
+
+/// One of the sizes an icon or cursor is drawn at.
+#[derive(Debug)]
+pub struct IconImage {
+    pub(crate) entry: IconDirEntry,
+    pub(crate) format: ImageFormat,
+    pub(crate) image: DynamicImageBuffer,
+}
+
+impl IconImage {
+    pub const fn entry(&self) -> &IconDirEntry {
+        &self.entry
+    }
+
+    pub const fn format(&self) -> ImageFormat {
+        self.format
+    }
+
+    pub const fn image(&self) -> &DynamicImageBuffer {
+        &self.image
+    }
+}
+
+#[derive(Debug)]
+pub struct Ico {
+    pub(crate) resource_type: ResourceType,
+    pub(crate) images: Vec<IconImage>,
+}
+
+impl Ico {
+    pub const fn resource_type(&self) -> ResourceType {
+        self.resource_type
+    }
+
+    /// The images in the order of the directory. There is at least one.
+    pub fn images(&self) -> &[IconImage] {
+        &self.images
+    }
+
+    /// The point of a cursor's image that clicks, from its top left corner.
+    pub const fn hotspot(&self, image: &IconImage) -> Option<(u16, u16)> {
+        match self.resource_type {
+            ResourceType::Icon => None,
+            ResourceType::Cursor => Some((image.entry.planes, image.entry.bits_per_pixel)),
+        }
+    }
+
+    /// The image shown as the icon: the largest, then the deepest, then the last listed.
+    pub fn best_image(&self) -> &IconImage {
+        self.images
+            .iter()
+            .max_by_key(|image| {
+                let bits_per_pixel = match self.resource_type {
+                    ResourceType::Icon => image.entry.bits_per_pixel,
+                    ResourceType::Cursor => 0,
+                };
+
+                (
+                    image.image.width() as u64 * image.image.height() as u64,
+                    bits_per_pixel,
+                )
+            })
+            .expect("decoded icons have an image")
+    }
+}
+
+impl ImageExt for Ico {
+    fn width(&self) -> u32 {
+        self.best_image().image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.best_image().image.height()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.best_image().image.color_type()
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        self.best_image().image.rgb8()
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        self.best_image().image.rgba8()
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        self.best_image().image.bitmap()
+    }
+}
@@ -0,0 +1,834 @@
+use crate::{
+    image::{
+        grammar::{ColorType, ImageExt},
+        pixel::{
+            Luma16, Luma8, LumaA16, LumaA8, Pixel, Rgb16, Rgb32F, Rgb8, Rgba16, Rgba32F, Rgba8,
+        },
+    },
+    jpeg::grammar::Jpeg,
+    png::grammar::Png,
+};
+use anyhow::{ensure, Result};
+use std::{
+    any::Any,
+    borrow::Cow,
+    ops::{Index, IndexMut},
+};
+
+/// Checks that the `width` by `height` rectangle at (`x`, `y`) lies within `bounds`.
+fn check_view(bounds: (u32, u32), x: u32, y: u32, width: u32, height: u32) -> Result<()> {
+    ensure!(
+        x.checked_add(width).is_some_and(|right| right <= bounds.0)
+            && y.checked_add(height)
+                .is_some_and(|bottom| bottom <= bounds.1),
+        "The {width}x{height} view at ({x}, {y}) exceeds the {}x{} image.",
+        bounds.0,
+        bounds.1
+    );
+
+    Ok(())
+}
+
+/// An image held in memory as rows of pixels of type `P`, top to bottom.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImageBuffer<P: Pixel> {
+    width: u32,
+    height: u32,
+    pixels: Vec<P>,
+}
+
+impl<P: Pixel> ImageBuffer<P> {
+    /// Creates an image with every channel set to zero.
+    pub fn new(width: u32, height: u32) -> Self {
+        Self::from_pixel(width, height, P::default())
+    }
+
+    pub fn from_pixel(width: u32, height: u32, pixel: P) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![pixel; width as usize * height as usize],
+        }
+    }
+
+    /// Creates an image by calling `f` with the coordinates of each pixel.
+    pub fn from_fn(width: u32, height: u32, mut f: impl FnMut(u32, u32) -> P) -> Self {
+        let pixels = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| f(x, y))
+            .collect();
+
+        Self {
+            width,
+            height,
+            pixels,
+        }
+    }
+
+    /// Creates an image from interleaved channels, such as `[r, g, b, r, g, b, ..]` for `Rgb`.
+    pub fn from_raw(width: u32, height: u32, channels: Vec<P::Subpixel>) -> Result<Self> {
+        let expected = width as usize * height as usize * P::CHANNEL_COUNT;
+        ensure!(
+            channels.len() == expected,
+            "A {width}x{height} image needs {expected} channels, got {}.",
+            channels.len()
+        );
+
+        let pixels = channels
+            .chunks_exact(P::CHANNEL_COUNT)
+            .map(P::from_channels)
+            .collect();
+
+        Ok(Self {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub fn pixels(&self) -> &[P] {
+        &self.pixels
+    }
+
+    pub fn pixels_mut(&mut self) -> &mut [P] {
+        &mut self.pixels
+    }
+
+    /// The interleaved channels of every pixel.
+    pub fn as_raw(&self) -> &[P::Subpixel] {
+        bytemuck::cast_slice(&self.pixels)
+    }
+
+    pub fn as_raw_mut(&mut self) -> &mut [P::Subpixel] {
+        bytemuck::cast_slice_mut(&mut self.pixels)
+    }
+
+    pub fn into_raw(self) -> Vec<P::Subpixel> {
+        self.as_raw().to_vec()
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<&P> {
+        (x < self.width && y < self.height)
+            .then(|| &self.pixels[y as usize * self.width as usize + x as usize])
+    }
+
+    pub fn get_pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut P> {
+        (x < self.width && y < self.height)
+            .then(|| &mut self.pixels[y as usize * self.width as usize + x as usize])
+    }
+
+    /// Panics if `y` is out of bounds.
+    pub fn row(&self, y: u32) -> &[P] {
+        assert!(y < self.height, "Row {y} is out of bounds.");
+
+        let width = self.width as usize;
+        &self.pixels[y as usize * width..][..width]
+    }
+
+    /// Panics if `y` is out of bounds.
+    pub fn row_mut(&mut self, y: u32) -> &mut [P] {
+        assert!(y < self.height, "Row {y} is out of bounds.");
+
+        let width = self.width as usize;
+        &mut self.pixels[y as usize * width..][..width]
+    }
+
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = &[P]> {
+        // Chunks must not be empty, and an image without columns has no pixels to chunk.
+        self.pixels.chunks_exact(self.width.max(1) as usize)
+    }
+
+    pub fn rows_mut(&mut self) -> impl ExactSizeIterator<Item = &mut [P]> {
+        self.pixels.chunks_exact_mut(self.width.max(1) as usize)
+    }
+
+    /// Borrows the `width` by `height` rectangle at (`x`, `y`).
+    pub fn view(&self, x: u32, y: u32, width: u32, height: u32) -> Result<SubImage<'_, P>> {
+        check_view(self.dimensions(), x, y, width, height)?;
+
+        Ok(SubImage {
+            image: self,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Mutably borrows the `width` by `height` rectangle at (`x`, `y`).
+    pub fn view_mut(
+        &mut self,
+        x: u32,
+        y: u32,
+        width: u32,
+        height: u32,
+    ) -> Result<SubImageMut<'_, P>> {
+        check_view(self.dimensions(), x, y, width, height)?;
+
+        Ok(SubImageMut {
+            image: self,
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    /// Converts every pixel to `Q`.
+    pub fn convert<Q: Pixel>(&self) -> ImageBuffer<Q> {
+        ImageBuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self.pixels.iter().map(P::convert).collect(),
+        }
+    }
+}
+
+impl<P: Pixel> Index<(u32, u32)> for ImageBuffer<P> {
+    type Output = P;
+
+    fn index(&self, (x, y): (u32, u32)) -> &P {
+        self.get_pixel(x, y)
+            .unwrap_or_else(|| panic!("Pixel ({x}, {y}) is out of bounds."))
+    }
+}
+
+impl<P: Pixel> IndexMut<(u32, u32)> for ImageBuffer<P> {
+    fn index_mut(&mut self, (x, y): (u32, u32)) -> &mut P {
+        self.get_pixel_mut(x, y)
+            .unwrap_or_else(|| panic!("Pixel ({x}, {y}) is out of bounds."))
+    }
+}
+
+/// A rectangular region of an `ImageBuffer`.
+#[derive(Debug, Clone, Copy)]
+pub struct SubImage<'a, P: Pixel> {
+    image: &'a ImageBuffer<P>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl<'a, P: Pixel> SubImage<'a, P> {
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    /// The position of the view within the underlying image.
+    pub const fn offsets(&self) -> (u32, u32) {
+        (self.x, self.y)
+    }
+
+    /// Coordinates are relative to the view.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<&'a P> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.image.get_pixel(self.x + x, self.y + y)
+    }
+
+    /// Panics if `y` is out of bounds.
+    pub fn row(&self, y: u32) -> &'a [P] {
+        assert!(y < self.height, "Row {y} is out of bounds.");
+
+        let (x, width) = (self.x as usize, self.width as usize);
+        &self.image.row(self.y + y)[x..][..width]
+    }
+
+    pub fn rows(&self) -> impl ExactSizeIterator<Item = &'a [P]> + '_ {
+        (0..self.height).map(|y| self.row(y))
+    }
+
+    /// Borrows a rectangle of this view, relative to it.
+    pub fn view(&self, x: u32, y: u32, width: u32, height: u32) -> Result<Self> {
+        check_view(self.dimensions(), x, y, width, height)?;
+
+        Ok(Self {
+            image: self.image,
+            x: self.x + x,
+            y: self.y + y,
+            width,
+            height,
+        })
+    }
+
+    /// Copies the view into an image of its own.
+    pub fn to_image(&self) -> ImageBuffer<P> {
+        ImageBuffer {
+            width: self.width,
+            height: self.height,
+            pixels: self.rows().flatten().copied().collect(),
+        }
+    }
+}
+
+/// A mutable rectangular region of an `ImageBuffer`.
+#[derive(Debug)]
+pub struct SubImageMut<'a, P: Pixel> {
+    image: &'a mut ImageBuffer<P>,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+}
+
+impl<P: Pixel> SubImageMut<'_, P> {
+    pub const fn width(&self) -> u32 {
+        self.width
+    }
+
+    pub const fn height(&self) -> u32 {
+        self.height
+    }
+
+    pub const fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    pub const fn offsets(&self) -> (u32, u32) {
+        (self.x, self.y)
+    }
+
+    /// Reborrows the view immutably.
+    pub const fn as_view(&self) -> SubImage<'_, P> {
+        SubImage {
+            image: self.image,
+            x: self.x,
+            y: self.y,
+            width: self.width,
+            height: self.height,
+        }
+    }
+
+    /// Coordinates are relative to the view.
+    pub fn get_pixel_mut(&mut self, x: u32, y: u32) -> Option<&mut P> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+
+        self.image.get_pixel_mut(self.x + x, self.y + y)
+    }
+
+    /// Panics if `y` is out of bounds.
+    pub fn row_mut(&mut self, y: u32) -> &mut [P] {
+        assert!(y < self.height, "Row {y} is out of bounds.");
+
+        let (x, width) = (self.x as usize, self.width as usize);
+        &mut self.image.row_mut(self.y + y)[x..][..width]
+    }
+
+    pub fn rows_mut(&mut self) -> impl ExactSizeIterator<Item = &mut [P]> {
+        let (x, width) = (self.x as usize, self.width as usize);
+
+        self.image
+            .rows_mut()
+            .skip(self.y as usize)
+            .take(self.height as usize)
+            .map(move |row| &mut row[x..][..width])
+    }
+
+    pub fn fill(&mut self, pixel: P) {
+        for row in self.rows_mut() {
+            row.fill(pixel);
+        }
+    }
+
+    /// Overwrites the view with `source`, which must have the same dimensions.
+    pub fn copy_from(&mut self, source: &ImageBuffer<P>) -> Result<()> {
+        ensure!(
+            source.dimensions() == self.dimensions(),
+            "Cannot copy a {}x{} image into a {}x{} view.",
+            source.width,
+            source.height,
+            self.width,
+            self.height
+        );
+
+        for (row, source) in self.rows_mut().zip(source.rows()) {
+            row.copy_from_slice(source);
+        }
+
+        Ok(())
+    }
+}
+
+impl<P: Pixel> ImageBuffer<P> {
+    /// Borrows the image as `ImageBuffer<Q>` if `P` is `Q`, to skip a conversion.
+    fn downcast<Q: Pixel>(&self) -> Option<&ImageBuffer<Q>> {
+        (self as &dyn Any).downcast_ref::<ImageBuffer<Q>>()
+    }
+}
+
+impl<P: Pixel> ImageExt for ImageBuffer<P> {
+    fn width(&self) -> u32 {
+        self.width
+    }
+
+    fn height(&self) -> u32 {
+        self.height
+    }
+
+    fn color_type(&self) -> ColorType {
+        P::COLOR_TYPE
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        self.downcast::<Rgb8>().map_or_else(
+            || Cow::from(self.convert::<Rgb8>().into_raw()),
+            |image| Cow::from(image.as_raw()),
+        )
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        self.downcast::<Rgba8>().map_or_else(
+            || Cow::from(self.convert::<Rgba8>().into_raw()),
+            |image| Cow::from(image.as_raw()),
+        )
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        let b = self
+            .rgba8()
+            .chunks_exact(4)
+            .map(|b| u32::from_be_bytes([b[3], b[0], b[1], b[2]]))
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+}
+
+/// An `ImageBuffer` whose pixel type is only known at runtime, such as a decoded image at its
+/// stored precision.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DynamicImageBuffer {
+    Luma8(ImageBuffer<Luma8>),
+    LumaA8(ImageBuffer<LumaA8>),
+    Rgb8(ImageBuffer<Rgb8>),
+    Rgba8(ImageBuffer<Rgba8>),
+    Luma16(ImageBuffer<Luma16>),
+    LumaA16(ImageBuffer<LumaA16>),
+    Rgb16(ImageBuffer<Rgb16>),
+    Rgba16(ImageBuffer<Rgba16>),
+    Rgb32F(ImageBuffer<Rgb32F>),
+    Rgba32F(ImageBuffer<Rgba32F>),
+}
+
+macro_rules! dynamic_map {
+    ($dynamic:expr, |$image:ident| $body:expr) => {
+        match $dynamic {
+            DynamicImageBuffer::Luma8($image) => $body,
+            DynamicImageBuffer::LumaA8($image) => $body,
+            DynamicImageBuffer::Rgb8($image) => $body,
+            DynamicImageBuffer::Rgba8($image) => $body,
+            DynamicImageBuffer::Luma16($image) => $body,
+            DynamicImageBuffer::LumaA16($image) => $body,
+            DynamicImageBuffer::Rgb16($image) => $body,
+            DynamicImageBuffer::Rgba16($image) => $body,
+            DynamicImageBuffer::Rgb32F($image) => $body,
+            DynamicImageBuffer::Rgba32F($image) => $body,
+        }
+    };
+}
+
+impl DynamicImageBuffer {
+    /// Converts the image to pixels of type `P`.
+    pub fn to<P: Pixel>(&self) -> ImageBuffer<P> {
+        dynamic_map!(self, |image| image.convert())
+    }
+
+    /// The number of bits per channel.
+    pub const fn bit_depth(&self) -> u8 {
+        match self {
+            Self::Luma8(_) | Self::LumaA8(_) | Self::Rgb8(_) | Self::Rgba8(_) => 8,
+            Self::Luma16(_) | Self::LumaA16(_) | Self::Rgb16(_) | Self::Rgba16(_) => 16,
+            Self::Rgb32F(_) | Self::Rgba32F(_) => 32,
+        }
+    }
+}
+
+impl ImageExt for DynamicImageBuffer {
+    fn width(&self) -> u32 {
+        dynamic_map!(self, |image| image.width)
+    }
+
+    fn height(&self) -> u32 {
+        dynamic_map!(self, |image| image.height)
+    }
+
+    fn color_type(&self) -> ColorType {
+        dynamic_map!(self, |image| image.color_type())
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        dynamic_map!(self, |image| image.rgb8())
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        dynamic_map!(self, |image| image.rgba8())
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        dynamic_map!(self, |image| image.bitmap())
+    }
+}
+
+impl TryFrom<&dyn ImageExt> for DynamicImageBuffer {
+    type Error = anyhow::Error;
+
+    /// Copies any image with 8 bits per channel, keeping its color type. Palette images become
+    /// RGB, and alpha is straight. Fails if the image doesn't hold a pixel per coordinate.
+    fn try_from(image: &dyn ImageExt) -> Result<Self, Self::Error> {
+        let (width, height) = image.dimensions();
+
+        match image.color_type() {
+            ColorType::Grayscale => {
+                let samples = image.rgb8().iter().step_by(3).copied().collect();
+                ImageBuffer::from_raw(width, height, samples).map(Self::Luma8)
+            }
+            ColorType::GrayscaleAlpha => {
+                let samples = image
+                    .straight_rgba8()
+                    .chunks_exact(4)
+                    .flat_map(|p| [p[0], p[3]])
+                    .collect();
+                ImageBuffer::from_raw(width, height, samples).map(Self::LumaA8)
+            }
+            ColorType::RGB | ColorType::Palette => {
+                ImageBuffer::from_raw(width, height, image.rgb8().into_owned()).map(Self::Rgb8)
+            }
+            ColorType::RGBA => {
+                let samples = image.straight_rgba8().into_owned();
+                ImageBuffer::from_raw(width, height, samples).map(Self::Rgba8)
+            }
+        }
+    }
+}
+
+impl TryFrom<Png> for DynamicImageBuffer {
+    type Error = anyhow::Error;
+
+    /// Keeps the samples of 8 and 16-bit grayscale and truecolor PNGs. Palette and lower bit
+    /// depth PNGs are not supported.
+    fn try_from(png: Png) -> Result<Self, Self::Error> {
+        let (width, height) = png.dimensions();
+        let color_type = png.color_type();
+        let bit_depth = png.image_header.bit_depth;
+
+        ensure!(
+            color_type != ColorType::Palette && matches!(bit_depth, 8 | 16),
+            "Unsupported PNG for an image buffer: {:?} at {} bits.",
+            color_type,
+            bit_depth
+        );
+
+        if bit_depth == 16 {
+            let samples = png
+                .pixel_buffer
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect::<Vec<_>>();
+
+            return match color_type {
+                ColorType::Grayscale => {
+                    ImageBuffer::from_raw(width, height, samples).map(Self::Luma16)
+                }
+                ColorType::GrayscaleAlpha => {
+                    ImageBuffer::from_raw(width, height, samples).map(Self::LumaA16)
+                }
+                ColorType::RGB => ImageBuffer::from_raw(width, height, samples).map(Self::Rgb16),
+                _ => ImageBuffer::from_raw(width, height, samples).map(Self::Rgba16),
+            };
+        }
+
+        let samples = png.pixel_buffer;
+
+        match color_type {
+            ColorType::Grayscale => ImageBuffer::from_raw(width, height, samples).map(Self::Luma8),
+            ColorType::GrayscaleAlpha => {
+                ImageBuffer::from_raw(width, height, samples).map(Self::LumaA8)
+            }
+            ColorType::RGB => ImageBuffer::from_raw(width, height, samples).map(Self::Rgb8),
+            _ => ImageBuffer::from_raw(width, height, samples).map(Self::Rgba8),
+        }
+    }
+}
+
+impl From<Jpeg> for DynamicImageBuffer {
+    /// 8-bit JPEGs keep their samples, higher precisions are scaled to 16 bits.
+    fn from(jpeg: Jpeg) -> Self {
+        let (width, height) = jpeg.dimensions();
+
+        if jpeg.sample_precision == 8 {
+            let samples = jpeg.pixel_buffer;
+
+            return match jpeg.color_type {
+                ColorType::Grayscale => {
+                    ImageBuffer::from_raw(width, height, samples).map(Self::Luma8)
+                }
+                ColorType::RGB => ImageBuffer::from_raw(width, height, samples).map(Self::Rgb8),
+                foreign => unreachable!("Jpeg does not decode into {:?}", foreign),
+            }
+            .expect("decoded JPEGs hold a sample per channel");
+        }
+
+        let max = ((1_u32 << jpeg.sample_precision) - 1) as f32;
+        let samples = jpeg
+            .samples()
+            .into_iter()
+            .map(|sample| (sample as f32 * 65535.0 / max).round() as u16)
+            .collect::<Vec<_>>();
+
+        match jpeg.color_type {
+            ColorType::Grayscale => ImageBuffer::from_raw(width, height, samples).map(Self::Luma16),
+            ColorType::RGB => ImageBuffer::from_raw(width, height, samples).map(Self::Rgb16),
+            foreign => unreachable!("Jpeg does not decode into {:?}", foreign),
+        }
+        .expect("decoded JPEGs hold a sample per channel")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::pixel::{Luma, Rgb, Rgba},
+        jpeg::JpegDecoder,
+        png::PngDecoder,
+    };
+    use image::ImageReader;
+
+    fn gradient() -> ImageBuffer<Rgb8> {
+        ImageBuffer::from_fn(4, 3, |x, y| Rgb([x as u8 * 60, y as u8 * 100, 7]))
+    }
+
+    #[test]
+    fn test_raw_round_trip() -> Result<()> {
+        let image = gradient();
+        assert_eq!(image.dimensions(), (4, 3));
+        assert_eq!(image.as_raw().len(), 4 * 3 * 3);
+        assert_eq!(&image.as_raw()[..6], &[0, 0, 7, 60, 0, 7]);
+
+        let raw = image.clone().into_raw();
+        assert_eq!(ImageBuffer::<Rgb8>::from_raw(4, 3, raw)?, image);
+
+        assert!(ImageBuffer::<Rgb8>::from_raw(4, 3, vec![0; 35]).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_pixel_and_row_access() {
+        let mut image = gradient();
+
+        assert_eq!(image[(3, 2)], Rgb([180, 200, 7]));
+        assert_eq!(image.get_pixel(4, 0), None);
+        assert_eq!(image.get_pixel(0, 3), None);
+
+        image[(1, 1)] = Rgb([1, 2, 3]);
+        assert_eq!(image.row(1)[1], Rgb([1, 2, 3]));
+
+        image.row_mut(2).fill(Rgb([9, 9, 9]));
+        assert_eq!(image.rows().len(), 3);
+        assert!(image
+            .rows()
+            .last()
+            .unwrap()
+            .iter()
+            .all(|&p| p == Rgb([9, 9, 9])));
+
+        for row in image.rows_mut() {
+            row[0] = Rgb([0, 0, 0]);
+        }
+        assert!(image.rows().all(|row| row[0] == Rgb([0, 0, 0])));
+
+        let empty = ImageBuffer::<Rgb8>::new(0, 5);
+        assert_eq!(empty.rows().len(), 0);
+    }
+
+    #[test]
+    fn test_sub_images() -> Result<()> {
+        let mut image = gradient();
+
+        let view = image.view(1, 1, 2, 2)?;
+        assert_eq!(view.offsets(), (1, 1));
+        assert_eq!(view.get_pixel(0, 0), Some(&Rgb([60, 100, 7])));
+        assert_eq!(view.get_pixel(2, 0), None);
+        assert_eq!(view.row(1), &[Rgb([60, 200, 7]), Rgb([120, 200, 7])]);
+
+        let nested = view.view(1, 0, 1, 2)?;
+        assert_eq!(nested.offsets(), (2, 1));
+        assert_eq!(
+            nested.to_image(),
+            ImageBuffer::from_raw(1, 2, vec![120, 100, 7, 120, 200, 7])?
+        );
+
+        assert!(view.view(1, 1, 2, 1).is_err());
+        assert!(image.view(3, 0, 2, 1).is_err());
+        assert!(image.view(u32::MAX, 0, 2, 1).is_err());
+
+        let mut view = image.view_mut(0, 1, 3, 2)?;
+        view.fill(Rgb([255, 255, 255]));
+        *view.get_pixel_mut(2, 1).unwrap() = Rgb([1, 1, 1]);
+        view.row_mut(0)[0] = Rgb([2, 2, 2]);
+        assert!(view.copy_from(&ImageBuffer::new(2, 2)).is_err());
+
+        assert_eq!(image.row(0), gradient().row(0));
+        assert_eq!(
+            image.row(1),
+            &[
+                Rgb([2, 2, 2]),
+                Rgb([255, 255, 255]),
+                Rgb([255, 255, 255]),
+                Rgb([180, 100, 7])
+            ]
+        );
+        assert_eq!(image[(2, 2)], Rgb([1, 1, 1]));
+
+        let mut target = ImageBuffer::<Rgb8>::new(6, 5);
+        target.view_mut(2, 1, 4, 3)?.copy_from(&image)?;
+        assert_eq!(target.view(2, 1, 4, 3)?.to_image(), image);
+        assert_eq!(target[(1, 1)], Rgb([0, 0, 0]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_conversions() {
+        let image = gradient();
+
+        let rgba = image.convert::<Rgba8>();
+        assert_eq!(rgba[(3, 2)], Rgba([180, 200, 7, 255]));
+        assert_eq!(rgba.convert::<Rgb8>(), image);
+
+        let rgb16 = image.convert::<Rgb16>();
+        assert_eq!(rgb16[(3, 2)], Rgb([180 * 257, 200 * 257, 7 * 257]));
+        assert_eq!(rgb16.convert::<Rgb8>(), image);
+
+        let rgb32f = image.convert::<Rgb32F>();
+        assert_eq!(rgb32f[(0, 0)], Rgb([0.0, 0.0, 7.0 / 255.0]));
+        assert_eq!(rgb32f.convert::<Rgb8>(), image);
+
+        let gray = ImageBuffer::from_pixel(1, 1, Rgb8::from_rgba([1.0, 0.5, 0.0, 1.0]));
+        assert_eq!(gray.convert::<Luma8>()[(0, 0)], Luma([151]));
+        assert_eq!(
+            gray.convert::<Luma8>().convert::<Rgba8>()[(0, 0)],
+            Rgba([151, 151, 151, 255])
+        );
+
+        let translucent = ImageBuffer::from_pixel(1, 1, Rgba([10_u8, 20, 30, 40]));
+        assert_eq!(
+            translucent.convert::<LumaA16>()[(0, 0)].channels()[1],
+            40 * 257
+        );
+    }
+
+    #[test]
+    fn test_image_ext() {
+        let image = gradient();
+
+        assert_eq!(image.color_type(), ColorType::RGB);
+        assert!(matches!(image.rgb8(), Cow::Borrowed(_)));
+        assert_eq!(&image.rgba8()[..8], &[0, 0, 7, 255, 60, 0, 7, 255]);
+        assert_eq!(image.bitmap()[1], 0xFF3C_0007);
+    }
+
+    #[test]
+    fn test_from_png() -> Result<()> {
+        let content = std::fs::read("./tests/obama.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+        let rgb8 = png.rgb8().into_owned();
+
+        let image = DynamicImageBuffer::try_from(png)?;
+        assert!(matches!(image, DynamicImageBuffer::Rgb8(_)));
+        assert_eq!(image.to::<Rgb8>().into_raw(), rgb8);
+
+        for path in ["./test_suite/basn2c16.png", "./test_suite/basn0g16.png"] {
+            let content = std::fs::read(path)?;
+            let image = DynamicImageBuffer::try_from(PngDecoder::new(&content).decode()?)?;
+            assert_eq!(image.bit_depth(), 16);
+
+            let reference = ImageReader::open(path)?.decode()?.to_rgb16();
+            assert_eq!(
+                image.to::<Rgb16>().into_raw(),
+                reference.into_raw(),
+                "{path}"
+            );
+        }
+
+        // Palette and lower bit depth PNGs are rejected rather than misread.
+        let content = std::fs::read("./test_suite/basn3p08.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+        assert!(DynamicImageBuffer::try_from(png).is_err());
+
+        let content = std::fs::read("./test_suite/basn0g08.png")?;
+        let mut png = PngDecoder::new(&content).decode()?;
+        png.image_header.bit_depth = 4;
+        assert!(DynamicImageBuffer::try_from(png).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_image_ext() -> Result<()> {
+        let content = std::fs::read("./test_suite/basn6a16.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+
+        let image = DynamicImageBuffer::try_from(&png as &dyn ImageExt)?;
+        assert!(matches!(image, DynamicImageBuffer::Rgba8(_)));
+        assert_eq!(image.rgba8(), png.rgba8());
+
+        // An image whose pixels don't cover its dimensions.
+        let mut png = png;
+        png.image_header.width += 1;
+        assert!(DynamicImageBuffer::try_from(&png as &dyn ImageExt).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_from_jpeg() -> Result<()> {
+        let content = std::fs::read("./tests/extended_12bit.jpg")?;
+        let jpeg = JpegDecoder::new(&content).decode()?;
+        let samples = jpeg.samples();
+
+        let image = DynamicImageBuffer::from(jpeg);
+        let DynamicImageBuffer::Rgb16(image) = image else {
+            panic!("12-bit JPEGs convert to 16 bits");
+        };
+
+        for (&converted, &sample) in image.as_raw().iter().zip(&samples) {
+            assert_eq!(converted, (sample as f32 * 65535.0 / 4095.0).round() as u16);
+        }
+
+        let content = std::fs::read("./tests/taxi_zone_map_manhattan.jpg")?;
+        let jpeg = JpegDecoder::new(&content).decode()?;
+        let rgb8 = jpeg.rgb8().into_owned();
+
+        let image = DynamicImageBuffer::from(jpeg);
+        assert_eq!(image.rgb8(), rgb8);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{
+    exif::grammar::Exif,
+    icc::{grammar::ProfileColorSpace, SrgbTransform},
+    image::{
+        grammar::{AlphaMode, ColorType, Gamma, Image, ImageExt},
+        ToneMapper,
+    },
+};
+use std::borrow::Cow;
+
+/// Presents an image converted from the color space of its embedded ICC profile to sRGB, so it
+/// displays with the intended colors.
+pub struct SrgbImage {
+    image: Image,
+    transform: SrgbTransform,
+}
+
+impl SrgbImage {
+    /// Wraps `image` if it carries a supported profile matching its color type, otherwise
+    /// returns it as is.
+    pub fn wrap(image: Image) -> Image {
+        let Some(profile) = image.icc_profile() else {
+            return image;
+        };
+
+        let matches_color_type = match profile.color_space() {
+            ProfileColorSpace::RGB => {
+                matches!(image.color_type(), ColorType::RGB | ColorType::RGBA)
+            }
+            ProfileColorSpace::Gray => matches!(
+                image.color_type(),
+                ColorType::Grayscale | ColorType::GrayscaleAlpha
+            ),
+            _ => false,
+        };
+
+        match SrgbTransform::new(profile).filter(|_| matches_color_type) {
+            Some(transform) => Box::new(Self { image, transform }),
+            None => image,
+        }
+    }
+}
+
+impl ImageExt for SrgbImage {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn gamma(&self) -> Option<Gamma> {
+        self.image.gamma()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.image.alpha_mode()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.image.color_type()
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        let mut b = self.image.rgb8().into_owned();
+        self.transform.convert_pixels(&mut b, 3);
+
+        Cow::from(b)
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        let mut b = self.image.rgba8().into_owned();
+        self.transform.convert_pixels(&mut b, 4);
+
+        Cow::from(b)
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        let b = self
+            .image
+            .bitmap()
+            .iter()
+            .map(|&pixel| {
+                let [a, r, g, b] = pixel.to_be_bytes();
+                let [r, g, b] = self.transform.convert([r, g, b]);
+
+                u32::from_be_bytes([a, r, g, b])
+            })
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+
+    fn frame_count(&self) -> usize {
+        self.image.frame_count()
+    }
+
+    fn frame_rgba8(&self, index: usize) -> Option<Cow<'_, [u8]>> {
+        let mut b = self.image.frame_rgba8(index)?.into_owned();
+        self.transform.convert_pixels(&mut b, 4);
+
+        Some(Cow::from(b))
+    }
+
+    fn tone_mapped_rgba8(&self, tone_mapper: ToneMapper) -> Option<Cow<'_, [u8]>> {
+        let mut b = self.image.tone_mapped_rgba8(tone_mapper)?.into_owned();
+        self.transform.convert_pixels(&mut b, 4);
+
+        Some(Cow::from(b))
+    }
+
+    fn exif(&self) -> Option<&Exif> {
+        self.image.exif()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{gif::GifDecoder, png::PngDecoder};
+    use anyhow::Result;
+
+    #[test]
+    fn test_forwards_frames() -> Result<()> {
+        let content = std::fs::read("./tests/display_p3.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+        let profile = png.icc_profile().expect("ICC profile");
+
+        let content = std::fs::read("./tests/gif/animated.gif")?;
+        let gif = GifDecoder::new(&content).decode()?;
+        let frames = (0..gif.frame_count())
+            .map(|i| gif.frame_rgba8(i).map(Cow::into_owned))
+            .collect::<Vec<_>>();
+
+        let transform = SrgbTransform::new(profile).expect("supported profile");
+        let srgb = SrgbImage {
+            image: Box::new(gif),
+            transform: SrgbTransform::new(profile).expect("supported profile"),
+        };
+        assert_eq!(srgb.frame_count(), frames.len());
+
+        for (i, frame) in frames.into_iter().enumerate() {
+            let mut expected = frame.expect("frame");
+            transform.convert_pixels(&mut expected, 4);
+
+            assert_eq!(srgb.frame_rgba8(i).as_deref(), Some(expected.as_slice()));
+        }
+
+        Ok(())
+    }
+}
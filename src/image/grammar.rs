@@ -1,10 +1,115 @@
-use anyhow::bail;
-use std::borrow::Cow;
+use crate::{exif::grammar::Exif, exr, hdr, icc::grammar::IccProfile, image::ToneMapper, tga};
+use anyhow::{bail, Result};
+use std::{borrow::Cow, path::Path};
 
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum ImageKind {
     Png,
     Jpeg,
+    Bmp,
+    Gif,
+    Pnm,
+    Qoi,
+    Tiff,
+    Webp,
+    Hdr,
+    Exr,
+    Ico,
+    Tga,
+}
+
+impl ImageKind {
+    /// Detects the format from the signature at the start of `data`.
+    pub fn from_magic_bytes(data: &[u8]) -> Option<Self> {
+        if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+            return Some(Self::Png);
+        }
+
+        // SOI, followed by the start of any marker.
+        if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            return Some(Self::Jpeg);
+        }
+
+        if data.starts_with(b"BM") {
+            return Some(Self::Bmp);
+        }
+
+        if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            return Some(Self::Gif);
+        }
+
+        // P1 through P7, followed by whitespace.
+        if let [b'P', b'1'..=b'7', separator, ..] = data {
+            if separator.is_ascii_whitespace() {
+                return Some(Self::Pnm);
+            }
+        }
+
+        if data.starts_with(b"qoif") {
+            return Some(Self::Qoi);
+        }
+
+        // The byte order, then 42 in that order.
+        if data.starts_with(b"II*\0") || data.starts_with(b"MM\0*") {
+            return Some(Self::Tiff);
+        }
+
+        // A RIFF container, whose form type follows the chunk size.
+        if let [b'R', b'I', b'F', b'F', _, _, _, _, b'W', b'E', b'B', b'P', ..] = data {
+            return Some(Self::Webp);
+        }
+
+        if hdr::grammar::SIGNATURES
+            .iter()
+            .any(|signature| data.starts_with(signature))
+        {
+            return Some(Self::Hdr);
+        }
+
+        if data.starts_with(&exr::grammar::EXR_MAGIC) {
+            return Some(Self::Exr);
+        }
+
+        // Reserved, an icon or cursor resource type, then at least one image.
+        if let [0, 0, 1 | 2, 0, count_low, count_high, ..] = data {
+            if (*count_low, *count_high) != (0, 0) {
+                return Some(Self::Ico);
+            }
+        }
+
+        // Only TGA 2.0 files are marked, by their footer.
+        if data.ends_with(&tga::grammar::FOOTER_SIGNATURE) {
+            return Some(Self::Tga);
+        }
+
+        None
+    }
+
+    /// Guesses the format from a file extension, ignoring case.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "png" => Some(Self::Png),
+            "jpg" | "jpeg" | "jpe" | "jfif" => Some(Self::Jpeg),
+            "bmp" | "dib" => Some(Self::Bmp),
+            "gif" => Some(Self::Gif),
+            "pbm" | "pgm" | "ppm" | "pnm" | "pam" => Some(Self::Pnm),
+            "qoi" => Some(Self::Qoi),
+            "tif" | "tiff" => Some(Self::Tiff),
+            "webp" => Some(Self::Webp),
+            "hdr" | "rgbe" => Some(Self::Hdr),
+            "exr" => Some(Self::Exr),
+            "ico" | "cur" => Some(Self::Ico),
+            "tga" | "icb" | "vda" | "vst" => Some(Self::Tga),
+            _ => None,
+        }
+    }
+
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Self> {
+        path.as_ref()
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .and_then(Self::from_extension)
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -45,6 +150,35 @@ impl TryFrom<u8> for ColorType {
     }
 }
 
+/// The gamma an image was encoded with, stored like a PNG gAMA chunk: times 100000.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gamma(pub(crate) u32);
+
+impl Gamma {
+    pub const fn new(scaled: u32) -> Self {
+        Self(scaled)
+    }
+
+    /// The gamma times 100000, e.g. 45455 for 1/2.2.
+    pub const fn scaled(&self) -> u32 {
+        self.0
+    }
+
+    pub fn value(&self) -> f32 {
+        self.0 as f32 / 100_000.0
+    }
+}
+
+/// How color channels relate to the alpha channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlphaMode {
+    /// Color channels are independent of alpha, as stored by PNG.
+    #[default]
+    Straight,
+    /// Color channels have been multiplied by alpha.
+    Premultiplied,
+}
+
 pub type Image = Box<dyn ImageExt>;
 
 pub trait ImageExt: Send + Sync {
@@ -56,13 +190,286 @@ pub trait ImageExt: Send + Sync {
         (self.width(), self.height())
     }
 
-    fn gamma(&self) -> u32;
+    /// The gamma the image declares it was encoded with, if any.
+    fn gamma(&self) -> Option<Gamma> {
+        None
+    }
 
+    /// The color type of the stored pixels. Conversions below never fail for it.
     fn color_type(&self) -> ColorType;
 
+    /// How the color channels of `rgba8` and `bitmap` relate to alpha.
+    fn alpha_mode(&self) -> AlphaMode {
+        AlphaMode::Straight
+    }
+
+    /// Red, green and blue, dropping any alpha. Grayscale repeats the gray level.
     fn rgb8(&self) -> Cow<'_, [u8]>;
 
+    /// Red, green, blue and alpha. Images without alpha are opaque: alpha is 255.
     fn rgba8(&self) -> Cow<'_, [u8]>;
 
+    /// Pixels packed as `0xAARRGGBB`, with the same alpha as `rgba8`.
     fn bitmap(&self) -> Cow<'_, [u32]>;
+
+    /// `rgba8` with straight alpha, whatever the image's alpha mode. Fully transparent pixels
+    /// of premultiplied images become transparent black.
+    fn straight_rgba8(&self) -> Cow<'_, [u8]> {
+        let rgba = self.rgba8();
+
+        if self.alpha_mode() == AlphaMode::Straight {
+            return rgba;
+        }
+
+        let b = rgba
+            .chunks_exact(4)
+            .flat_map(|p| {
+                let alpha = p[3] as u32;
+                let unpremultiply = |c: u8| match alpha {
+                    0 => 0,
+                    alpha => ((c as u32 * 255 + alpha / 2) / alpha).min(255) as u8,
+                };
+
+                [
+                    unpremultiply(p[0]),
+                    unpremultiply(p[1]),
+                    unpremultiply(p[2]),
+                    p[3],
+                ]
+            })
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+
+    /// The number of frames of an animated image. Still images have one.
+    fn frame_count(&self) -> usize {
+        1
+    }
+
+    /// Frame `index` of an animated image as displayed, with the same layout as
+    /// `straight_rgba8`. The first frame holds the image's own pixels.
+    fn frame_rgba8(&self, index: usize) -> Option<Cow<'_, [u8]>> {
+        (index == 0).then(|| self.straight_rgba8())
+    }
+
+    /// The pixels of a high dynamic range image mapped to 8-bit RGBA by `tone_mapper`, with the
+    /// same layout as `rgba8`. Other images return None.
+    fn tone_mapped_rgba8(&self, _tone_mapper: ToneMapper) -> Option<Cow<'_, [u8]>> {
+        None
+    }
+
+    fn exif(&self) -> Option<&Exif> {
+        None
+    }
+
+    /// The embedded ICC profile describing the color space of the pixels, if any.
+    fn icc_profile(&self) -> Option<&IccProfile> {
+        None
+    }
+}
+
+/// An encoder that writes any image in its format.
+pub trait ImageEncoder {
+    fn write_image(&mut self, image: &dyn ImageExt) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exif::grammar::Orientation,
+        image::{
+            buffer::ImageBuffer,
+            orientation::OrientedImage,
+            pixel::{Luma, LumaA, Rgb, Rgba},
+        },
+        jpeg::JpegDecoder,
+        png::{PngDecoder, PngEncoder},
+    };
+
+    /// Checks that the conversions of `image` agree with each other and with its color type.
+    fn assert_conforms(image: &dyn ImageExt, name: &str) {
+        let num_pixels = (image.width() * image.height()) as usize;
+        let (rgb, rgba, bitmap) = (image.rgb8(), image.rgba8(), image.bitmap());
+
+        assert_eq!(rgb.len(), num_pixels * 3, "{name}");
+        assert_eq!(rgba.len(), num_pixels * 4, "{name}");
+        assert_eq!(bitmap.len(), num_pixels, "{name}");
+
+        let has_alpha = matches!(
+            image.color_type(),
+            ColorType::GrayscaleAlpha | ColorType::RGBA
+        );
+
+        let grayscale = matches!(
+            image.color_type(),
+            ColorType::Grayscale | ColorType::GrayscaleAlpha
+        );
+
+        for ((rgb, rgba), &argb) in rgb.chunks_exact(3).zip(rgba.chunks_exact(4)).zip(&*bitmap) {
+            assert_eq!(rgb, &rgba[..3], "{name}");
+            assert_eq!(
+                argb,
+                u32::from_be_bytes([rgba[3], rgba[0], rgba[1], rgba[2]]),
+                "{name}"
+            );
+
+            if !has_alpha {
+                assert_eq!(rgba[3], 0xFF, "{name}: images without alpha are opaque");
+            }
+
+            if grayscale {
+                assert!(rgb[0] == rgb[1] && rgb[1] == rgb[2], "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_png_color_types_conform() -> Result<()> {
+        for (path, color_type) in [
+            ("./test_suite/basn0g08.png", ColorType::Grayscale),
+            ("./test_suite/basn4a08.png", ColorType::GrayscaleAlpha),
+            ("./test_suite/basn3p08.png", ColorType::Palette),
+            ("./test_suite/basn2c08.png", ColorType::RGB),
+            ("./test_suite/basn6a08.png", ColorType::RGBA),
+            ("./test_suite/basn0g16.png", ColorType::Grayscale),
+            ("./test_suite/basn4a16.png", ColorType::GrayscaleAlpha),
+            ("./test_suite/basn2c16.png", ColorType::RGB),
+            ("./test_suite/basn6a16.png", ColorType::RGBA),
+        ] {
+            let content = std::fs::read(path)?;
+            let png = PngDecoder::new(&content).decode()?;
+            assert_eq!(png.color_type(), color_type, "{path}");
+
+            assert_conforms(&png, path);
+
+            let reference = image::open(path)?.to_rgba8().into_raw();
+            assert_eq!(png.rgba8(), reference, "{path}");
+            assert_eq!(png.alpha_mode(), AlphaMode::Straight);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_jpeg_color_types_conform() -> Result<()> {
+        for (path, color_type) in [
+            ("./tests/lossless_gray16.jpg", ColorType::Grayscale),
+            ("./tests/extended_12bit.jpg", ColorType::RGB),
+            ("./tests/taxi_zone_map_manhattan.jpg", ColorType::RGB),
+        ] {
+            let content = std::fs::read(path)?;
+            let jpeg = JpegDecoder::new(&content).decode()?;
+            assert_eq!(jpeg.color_type(), color_type, "{path}");
+            assert_eq!(jpeg.gamma(), None);
+
+            assert_conforms(&jpeg, path);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_image_buffer_and_wrappers_conform() -> Result<()> {
+        let images: Vec<(Image, &str)> = vec![
+            (
+                Box::new(ImageBuffer::from_fn(3, 2, |x, _| Luma([x as u8 * 90]))),
+                "Luma8",
+            ),
+            (
+                Box::new(ImageBuffer::from_fn(3, 2, |x, y| {
+                    LumaA([x as u16 * 900, y as u16 * 60000])
+                })),
+                "LumaA16",
+            ),
+            (
+                Box::new(ImageBuffer::from_fn(3, 2, |x, y| {
+                    Rgb([x as f32 / 2.0, y as f32, 0.25])
+                })),
+                "Rgb32F",
+            ),
+            (
+                Box::new(ImageBuffer::from_fn(3, 2, |x, y| {
+                    Rgba([x as u8, y as u8, 3, x as u8 * 100])
+                })),
+                "Rgba8",
+            ),
+        ];
+
+        for (image, name) in images {
+            assert_conforms(image.as_ref(), name);
+
+            let oriented = OrientedImage::new(image, Orientation::Rotate90);
+            assert_conforms(&oriented, name);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_gamma() -> Result<()> {
+        let content = std::fs::read("./test_suite/g03n2c08.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+
+        let gamma = png.gamma().expect("gAMA chunk");
+        assert_eq!(gamma, Gamma::new(35000));
+        assert!((gamma.value() - 0.35).abs() < 1e-6);
+
+        let mut encoded = Vec::new();
+        PngEncoder::new(&mut encoded).encode(&png)?;
+        assert_eq!(PngDecoder::new(&encoded).decode()?.gamma(), Some(gamma));
+
+        let content = std::fs::read("./tests/obama.png")?;
+        assert_eq!(PngDecoder::new(&content).decode()?.gamma(), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_straight_rgba8() {
+        struct Premultiplied(ImageBuffer<Rgba<u8>>);
+
+        impl ImageExt for Premultiplied {
+            fn width(&self) -> u32 {
+                self.0.width()
+            }
+
+            fn height(&self) -> u32 {
+                self.0.height()
+            }
+
+            fn color_type(&self) -> ColorType {
+                ColorType::RGBA
+            }
+
+            fn alpha_mode(&self) -> AlphaMode {
+                AlphaMode::Premultiplied
+            }
+
+            fn rgb8(&self) -> Cow<'_, [u8]> {
+                self.0.rgb8()
+            }
+
+            fn rgba8(&self) -> Cow<'_, [u8]> {
+                self.0.rgba8()
+            }
+
+            fn bitmap(&self) -> Cow<'_, [u32]> {
+                self.0.bitmap()
+            }
+        }
+
+        let pixels = vec![50, 100, 0, 128, 255, 255, 255, 255, 9, 9, 9, 0];
+        let image = ImageBuffer::from_raw(3, 1, pixels.clone()).unwrap();
+
+        assert_eq!(image.straight_rgba8(), pixels);
+
+        let premultiplied = Premultiplied(image);
+        assert_conforms(&premultiplied, "premultiplied");
+        assert_eq!(
+            premultiplied.straight_rgba8(),
+            vec![100, 199, 0, 128, 255, 255, 255, 255, 0, 0, 0, 0]
+        );
+    }
 }
@@ -1,5 +1,18 @@
 pub mod grammar;
 
+pub use buffer::*;
+pub use color_management::*;
+pub use orientation::*;
+pub use pixel::*;
 pub use reader::*;
+pub use tone_map::*;
+pub use writer::*;
 
+mod buffer;
+mod color_management;
+mod orientation;
+mod pixel;
 mod reader;
+mod resize;
+mod tone_map;
+mod writer;
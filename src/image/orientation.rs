@@ -0,0 +1,210 @@
+use crate::{
+    exif::grammar::{Exif, Orientation},
+    icc::grammar::IccProfile,
+    image::{
+        grammar::{AlphaMode, ColorType, Gamma, Image, ImageExt},
+        ToneMapper,
+    },
+};
+use std::borrow::Cow;
+
+/// Presents an image transformed according to an EXIF orientation, so it displays upright.
+pub struct OrientedImage {
+    image: Image,
+    orientation: Orientation,
+}
+
+impl OrientedImage {
+    pub fn new(image: Image, orientation: Orientation) -> Self {
+        Self { image, orientation }
+    }
+
+    fn reorient<T: Copy>(&self, pixels: &[T], num_channels: usize) -> Vec<T> {
+        let (width, height) = self.image.dimensions();
+        let mut reoriented = Vec::with_capacity(pixels.len());
+
+        for y in 0..self.height() {
+            for x in 0..self.width() {
+                let (source_x, source_y) = self.orientation.source_position(x, y, width, height);
+
+                let offset =
+                    (source_y as usize * width as usize + source_x as usize) * num_channels;
+                reoriented.extend_from_slice(&pixels[offset..offset + num_channels]);
+            }
+        }
+
+        reoriented
+    }
+}
+
+impl ImageExt for OrientedImage {
+    fn width(&self) -> u32 {
+        if self.orientation.swaps_dimensions() {
+            self.image.height()
+        } else {
+            self.image.width()
+        }
+    }
+
+    fn height(&self) -> u32 {
+        if self.orientation.swaps_dimensions() {
+            self.image.width()
+        } else {
+            self.image.height()
+        }
+    }
+
+    fn gamma(&self) -> Option<Gamma> {
+        self.image.gamma()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.image.alpha_mode()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.image.color_type()
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        Cow::from(self.reorient(&self.image.rgb8(), 3))
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        Cow::from(self.reorient(&self.image.rgba8(), 4))
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        Cow::from(self.reorient(&self.image.bitmap(), 1))
+    }
+
+    fn frame_count(&self) -> usize {
+        self.image.frame_count()
+    }
+
+    fn frame_rgba8(&self, index: usize) -> Option<Cow<'_, [u8]>> {
+        let frame = self.image.frame_rgba8(index)?;
+
+        Some(Cow::from(self.reorient(&frame, 4)))
+    }
+
+    fn tone_mapped_rgba8(&self, tone_mapper: ToneMapper) -> Option<Cow<'_, [u8]>> {
+        let b = self.image.tone_mapped_rgba8(tone_mapper)?;
+
+        Some(Cow::from(self.reorient(&b, 4)))
+    }
+
+    fn exif(&self) -> Option<&Exif> {
+        self.image.exif()
+    }
+
+    fn icc_profile(&self) -> Option<&IccProfile> {
+        self.image.icc_profile()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        gif::GifDecoder,
+        hdr::HdrDecoder,
+        png::grammar::{ImageHeader, Png},
+    };
+    use anyhow::Result;
+    use image::{metadata::Orientation as ReferenceOrientation, DynamicImage, RgbImage};
+
+    #[test]
+    fn test_orientations_match_reference() -> Result<()> {
+        let (width, height) = (3, 2);
+        let pixel_buffer = (0..width * height * 3).map(|i| i as u8).collect::<Vec<_>>();
+
+        for value in 1..=8 {
+            let png = Png {
+                image_header: ImageHeader {
+                    width,
+                    height,
+                    bit_depth: 8,
+                    color_type: ColorType::RGB,
+                    compression_method: 0,
+                    filter_method: 0,
+                    interlace_method: false,
+                },
+                gamma: None,
+                exif: None,
+                icc_profile: None,
+                palette: Vec::new(),
+                pixel_buffer: pixel_buffer.clone(),
+            };
+
+            let oriented = OrientedImage::new(Box::new(png), Orientation::try_from(value)?);
+
+            let mut reference = DynamicImage::ImageRgb8(
+                RgbImage::from_raw(width, height, pixel_buffer.clone()).unwrap(),
+            );
+            reference.apply_orientation(ReferenceOrientation::from_exif(value as u8).unwrap());
+
+            assert_eq!(
+                oriented.dimensions(),
+                (reference.width(), reference.height())
+            );
+            assert_eq!(
+                oriented.rgb8().to_vec(),
+                reference.to_rgb8().to_vec(),
+                "orientation {value}"
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `oriented` holds the `width` by `height` RGBA `source` rotated by 90 degrees.
+    fn assert_rotated(oriented: &[u8], source: &[u8], width: u32, height: u32) {
+        assert_eq!(oriented.len(), source.len());
+
+        for (i, pixel) in oriented.chunks_exact(4).enumerate() {
+            let (x, y) = (i as u32 % height, i as u32 / height);
+            let (source_x, source_y) = Orientation::Rotate90.source_position(x, y, width, height);
+            let offset = (source_y * width + source_x) as usize * 4;
+
+            assert_eq!(pixel, &source[offset..offset + 4], "pixel ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn test_forwards_frames_and_tone_mapping() -> Result<()> {
+        let content = std::fs::read("./tests/gif/animated.gif")?;
+        let gif = GifDecoder::new(&content).decode()?;
+        let frames = (0..gif.frame_count())
+            .map(|i| gif.frame_rgba8(i).map(Cow::into_owned))
+            .collect::<Vec<_>>();
+
+        let (width, height) = gif.dimensions();
+        let oriented = OrientedImage::new(Box::new(gif), Orientation::Rotate90);
+        assert_eq!(oriented.frame_count(), frames.len());
+
+        for (i, frame) in frames.iter().enumerate() {
+            let frame = frame.as_deref().expect("frame");
+            let oriented_frame = oriented.frame_rgba8(i).expect("oriented frame");
+            assert_rotated(&oriented_frame, frame, width, height);
+        }
+
+        assert_eq!(oriented.frame_rgba8(frames.len()), None);
+
+        let content = std::fs::read("./tests/hdr/ramp_rle.hdr")?;
+        let hdr = HdrDecoder::new(&content).decode()?;
+        let tone_mapped = hdr
+            .tone_mapped_rgba8(ToneMapper::default())
+            .expect("tone mapped")
+            .into_owned();
+
+        let (width, height) = hdr.dimensions();
+        let oriented = OrientedImage::new(Box::new(hdr), Orientation::Rotate90);
+        let oriented_tone_mapped = oriented
+            .tone_mapped_rgba8(ToneMapper::default())
+            .expect("oriented tone mapped");
+        assert_rotated(&oriented_tone_mapped, &tone_mapped, width, height);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,188 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::image::grammar::ColorType;
+use bytemuck::{Pod, Zeroable};
+use std::fmt::Debug;
+
+/// The type of a single channel.
+pub trait Primitive: Pod + Default + Debug + PartialEq + PartialOrd + Send + Sync {
+    /// Full intensity: 255 for `u8`, 65535 for `u16` and 1.0 for `f32`.
+    const MAX: Self;
+
+    /// Maps the channel to [0, 1], where 1 is full intensity.
+    fn to_normalized(self) -> f32;
+
+    /// The inverse of `to_normalized`. Integer channels round and clamp, floating point channels
+    /// keep values outside of [0, 1].
+    fn from_normalized(value: f32) -> Self;
+}
+
+impl Primitive for u8 {
+    const MAX: Self = Self::MAX;
+
+    fn to_normalized(self) -> f32 {
+        self as f32 / 255.0
+    }
+
+    fn from_normalized(value: f32) -> Self {
+        (value * 255.0).round().clamp(0.0, 255.0) as Self
+    }
+}
+
+impl Primitive for u16 {
+    const MAX: Self = Self::MAX;
+
+    fn to_normalized(self) -> f32 {
+        self as f32 / 65535.0
+    }
+
+    fn from_normalized(value: f32) -> Self {
+        (value * 65535.0).round().clamp(0.0, 65535.0) as Self
+    }
+}
+
+impl Primitive for f32 {
+    const MAX: Self = 1.0;
+
+    fn to_normalized(self) -> f32 {
+        self
+    }
+
+    fn from_normalized(value: f32) -> Self {
+        value
+    }
+}
+
+/// A pixel of an `ImageBuffer`: a fixed number of channels of one primitive type.
+pub trait Pixel: Pod + Default + Debug + PartialEq + Send + Sync {
+    type Subpixel: Primitive;
+
+    const CHANNEL_COUNT: usize;
+
+    /// The color type of images made of this pixel, regardless of the channel type.
+    const COLOR_TYPE: ColorType;
+
+    fn channels(&self) -> &[Self::Subpixel];
+
+    fn channels_mut(&mut self) -> &mut [Self::Subpixel];
+
+    /// Builds a pixel from the first `CHANNEL_COUNT` values of `channels`.
+    fn from_channels(channels: &[Self::Subpixel]) -> Self;
+
+    /// Normalized red, green, blue and alpha. Pixels without alpha are opaque.
+    fn to_rgba(&self) -> [f32; 4];
+
+    /// Builds a pixel from normalized red, green, blue and alpha. Grayscale pixels take the luma
+    /// of the color (Rec. 601, like JFIF), and pixels without alpha drop it.
+    fn from_rgba(rgba: [f32; 4]) -> Self;
+
+    /// Converts between pixel types through normalized RGBA.
+    fn convert<Q: Pixel>(&self) -> Q {
+        Q::from_rgba(self.to_rgba())
+    }
+}
+
+fn luma([r, g, b, _]: [f32; 4]) -> f32 {
+    0.299 * r + 0.587 * g + 0.114 * b
+}
+
+macro_rules! define_pixel {
+    (
+        $(#[$attr:meta])*
+        $name:ident,
+        $channel_count:literal,
+        $color_type:ident,
+        |$pixel:ident| $to_rgba:expr,
+        |$rgba:ident| $from_rgba:expr
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+        #[repr(transparent)]
+        pub struct $name<T: Primitive>(pub [T; $channel_count]);
+
+        // SAFETY: The pixel is a transparent wrapper around an array of plain old data.
+        unsafe impl<T: Primitive> Zeroable for $name<T> {}
+        unsafe impl<T: Primitive> Pod for $name<T> {}
+
+        impl<T: Primitive> Pixel for $name<T> {
+            type Subpixel = T;
+
+            const CHANNEL_COUNT: usize = $channel_count;
+            const COLOR_TYPE: ColorType = ColorType::$color_type;
+
+            fn channels(&self) -> &[T] {
+                &self.0
+            }
+
+            fn channels_mut(&mut self) -> &mut [T] {
+                &mut self.0
+            }
+
+            fn from_channels(channels: &[T]) -> Self {
+                let mut pixel = Self::default();
+                pixel.0.copy_from_slice(&channels[..$channel_count]);
+                pixel
+            }
+
+            fn to_rgba(&self) -> [f32; 4] {
+                let $pixel = self.0.map(T::to_normalized);
+                $to_rgba
+            }
+
+            fn from_rgba($rgba: [f32; 4]) -> Self {
+                Self($from_rgba.map(T::from_normalized))
+            }
+        }
+    };
+}
+
+define_pixel!(
+    /// A grayscale pixel.
+    Luma,
+    1,
+    Grayscale,
+    |p| [p[0], p[0], p[0], 1.0],
+    |rgba| [luma(rgba)]
+);
+
+define_pixel!(
+    /// A grayscale pixel with alpha.
+    LumaA,
+    2,
+    GrayscaleAlpha,
+    |p| [p[0], p[0], p[0], p[1]],
+    |rgba| [luma(rgba), rgba[3]]
+);
+
+define_pixel!(
+    /// A red, green and blue pixel.
+    Rgb,
+    3,
+    RGB,
+    |p| [p[0], p[1], p[2], 1.0],
+    |rgba| [rgba[0], rgba[1], rgba[2]]
+);
+
+define_pixel!(
+    /// A red, green and blue pixel with alpha.
+    Rgba,
+    4,
+    RGBA,
+    |p| p,
+    |rgba| rgba
+);
+
+pub type Luma8 = Luma<u8>;
+pub type LumaA8 = LumaA<u8>;
+pub type Rgb8 = Rgb<u8>;
+pub type Rgba8 = Rgba<u8>;
+
+pub type Luma16 = Luma<u16>;
+pub type LumaA16 = LumaA<u16>;
+pub type Rgb16 = Rgb<u16>;
+pub type Rgba16 = Rgba<u16>;
+
+pub type Luma32F = Luma<f32>;
+pub type LumaA32F = LumaA<f32>;
+pub type Rgb32F = Rgb<f32>;
+pub type Rgba32F = Rgba<f32>;
@@ -1,25 +1,251 @@
 use crate::{
-    image::grammar::{Image, ImageExt, ImageKind},
+    bmp::BmpDecoder,
+    exif::grammar::Orientation,
+    exr::ExrDecoder,
+    gif::GifDecoder,
+    hdr::HdrDecoder,
+    ico::IcoDecoder,
+    image::{
+        color_management::SrgbImage,
+        grammar::{Image, ImageExt, ImageKind},
+        orientation::OrientedImage,
+    },
     jpeg::JpegDecoder,
     png::PngDecoder,
+    pnm::PnmDecoder,
+    qoi::QoiDecoder,
+    tga::TgaDecoder,
+    tiff::TiffDecoder,
+    webp::WebpDecoder,
 };
-use anyhow::Result;
-use std::path::Path;
+use anyhow::{anyhow, Result};
+use std::{io::Read, path::Path};
 
-#[derive(Debug)]
-pub struct ImageReader;
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ImageReader {
+    apply_orientation: bool,
+    apply_color_profile: bool,
+}
 
 impl ImageReader {
-    pub fn read_from_path(path: impl AsRef<Path>, image_kind: Option<ImageKind>) -> Result<Image> {
+    pub const fn new() -> Self {
+        Self {
+            apply_orientation: false,
+            apply_color_profile: false,
+        }
+    }
+
+    /// Whether to transform images according to their EXIF Orientation tag, so they display
+    /// upright. Off by default, in which case the stored pixels are returned as is.
+    pub const fn apply_orientation(mut self, apply_orientation: bool) -> Self {
+        self.apply_orientation = apply_orientation;
+        self
+    }
+
+    /// Whether to convert images with an embedded ICC profile to sRGB, so they display with the
+    /// intended colors. Off by default, in which case the stored pixels are returned as is.
+    pub const fn apply_color_profile(mut self, apply_color_profile: bool) -> Self {
+        self.apply_color_profile = apply_color_profile;
+        self
+    }
+
+    /// Reads the image at `path`. Without an `image_kind` hint, the format is detected from the
+    /// file's contents, falling back to its extension.
+    pub fn read_from_path(
+        &self,
+        path: impl AsRef<Path>,
+        image_kind: Option<ImageKind>,
+    ) -> Result<Image> {
+        let path = path.as_ref();
         let data = std::fs::read(path)?;
 
-        let image_kind = image_kind.expect("how do you infer which image decoder to run?");
+        let image_kind = image_kind
+            .or_else(|| ImageKind::from_magic_bytes(&data))
+            .or_else(|| ImageKind::from_path(path))
+            .ok_or_else(|| anyhow!("Unrecognized image format: {}", path.display()))?;
+
+        self.read_from_bytes(&data, Some(image_kind))
+    }
+
+    pub fn read_from_reader(
+        &self,
+        mut reader: impl Read,
+        image_kind: Option<ImageKind>,
+    ) -> Result<Image> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+
+        self.read_from_bytes(&data, image_kind)
+    }
 
-        let image: Box<dyn ImageExt> = match image_kind {
-            ImageKind::Png => Box::new(PngDecoder::new(&data).decode()?),
-            ImageKind::Jpeg => Box::new(JpegDecoder::new(&data).decode()?),
+    /// Decodes an image held in memory. Without an `image_kind` hint, the format is detected
+    /// from the data's contents.
+    pub fn read_from_bytes(&self, data: &[u8], image_kind: Option<ImageKind>) -> Result<Image> {
+        let image_kind = image_kind
+            .or_else(|| ImageKind::from_magic_bytes(data))
+            .ok_or_else(|| anyhow!("Unrecognized image format."))?;
+
+        let mut image: Box<dyn ImageExt> = match image_kind {
+            ImageKind::Png => Box::new(PngDecoder::new(data).decode()?),
+            ImageKind::Jpeg => Box::new(JpegDecoder::new(data).decode()?),
+            ImageKind::Bmp => Box::new(BmpDecoder::new(data).decode()?),
+            ImageKind::Gif => Box::new(GifDecoder::new(data).decode()?),
+            ImageKind::Pnm => Box::new(PnmDecoder::new(data).decode()?),
+            ImageKind::Qoi => Box::new(QoiDecoder::new(data).decode()?),
+            ImageKind::Tiff => Box::new(TiffDecoder::new(data).decode()?),
+            ImageKind::Webp => Box::new(WebpDecoder::new(data).decode()?),
+            ImageKind::Hdr => Box::new(HdrDecoder::new(data).decode()?),
+            ImageKind::Exr => Box::new(ExrDecoder::new(data).decode()?),
+            ImageKind::Ico => Box::new(IcoDecoder::new(data).decode()?),
+            ImageKind::Tga => Box::new(TgaDecoder::new(data).decode()?),
         };
 
-        Ok(image)
+        if self.apply_color_profile {
+            image = SrgbImage::wrap(image);
+        }
+
+        if !self.apply_orientation {
+            return Ok(image);
+        }
+
+        match image.exif().map(|exif| exif.orientation()) {
+            Some(orientation) if orientation != Orientation::Normal => {
+                Ok(Box::new(OrientedImage::new(image, orientation)))
+            }
+            _ => Ok(image),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageDecoder};
+
+    #[test]
+    fn test_apply_orientation() -> Result<()> {
+        let path = "./tests/exif_orientation.jpg";
+
+        let mut decoder = image::ImageReader::open(path)?.into_decoder()?;
+        let orientation = decoder.orientation()?;
+        let mut reference = DynamicImage::from_decoder(decoder)?;
+        reference.apply_orientation(orientation);
+
+        let stored = ImageReader::new().read_from_path(path, Some(ImageKind::Jpeg))?;
+        let oriented = ImageReader::new()
+            .apply_orientation(true)
+            .read_from_path(path, Some(ImageKind::Jpeg))?;
+
+        // The fixture is rotated by 90 degrees.
+        assert_eq!(stored.dimensions(), (48, 32));
+        assert_eq!(
+            oriented.dimensions(),
+            (reference.width(), reference.height())
+        );
+        assert_eq!(oriented.dimensions(), (32, 48));
+
+        let reference_rgbs = reference.to_rgb8().to_vec();
+        let oriented_rgbs = oriented.rgb8();
+
+        let mean_absolute_error = reference_rgbs
+            .iter()
+            .zip(oriented_rgbs.iter())
+            .map(|(&a, &b)| a.abs_diff(b) as f64)
+            .sum::<f64>()
+            / reference_rgbs.len() as f64;
+
+        assert!(mean_absolute_error < 1.5, "MAE: {mean_absolute_error}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_detect_format() -> Result<()> {
+        for (path, image_kind) in [
+            ("./tests/obama.png", ImageKind::Png),
+            ("./test_suite/basn0g08.png", ImageKind::Png),
+            ("./tests/taxi_zone_map_manhattan.jpg", ImageKind::Jpeg),
+            ("./tests/arithmetic_lossless.jpg", ImageKind::Jpeg),
+            ("./tests/bmp/rgba32_v5.bmp", ImageKind::Bmp),
+            ("./tests/gif/animated.gif", ImageKind::Gif),
+            ("./tests/pnm/p6.ppm", ImageKind::Pnm),
+            ("./tests/pnm/rgb_alpha.pam", ImageKind::Pnm),
+            ("./tests/qoi/basn6a08.qoi", ImageKind::Qoi),
+            ("./tests/tiff/gray8_strips.tif", ImageKind::Tiff),
+            ("./tests/tiff/rgb8_lzw_predictor.tif", ImageKind::Tiff),
+            ("./tests/webp/lossless_rgba.webp", ImageKind::Webp),
+            ("./tests/webp/lossy_rgb.webp", ImageKind::Webp),
+            ("./tests/hdr/ramp_rle.hdr", ImageKind::Hdr),
+            ("./tests/exr/rgb_half_zip.exr", ImageKind::Exr),
+            ("./tests/ico/multiple.ico", ImageKind::Ico),
+            ("./tests/ico/pointer.cur", ImageKind::Ico),
+            ("./tests/tga/rgb16_right_to_left_v2.tga", ImageKind::Tga),
+        ] {
+            let data = std::fs::read(path)?;
+            assert_eq!(
+                ImageKind::from_magic_bytes(&data),
+                Some(image_kind),
+                "{path}"
+            );
+            assert_eq!(ImageKind::from_path(path), Some(image_kind), "{path}");
+        }
+
+        assert_eq!(ImageKind::from_path("photo.JPEG"), Some(ImageKind::Jpeg));
+        assert_eq!(ImageKind::from_path("notes.txt"), None);
+        assert_eq!(ImageKind::from_path("no_extension"), None);
+        assert_eq!(ImageKind::from_magic_bytes(b"\x89PN"), None);
+        assert_eq!(ImageKind::from_magic_bytes(&[]), None);
+
+        // TGA 1.0 files have no signature.
+        let data = std::fs::read("./tests/tga/rgb24_bottom_up.tga")?;
+        assert_eq!(ImageKind::from_magic_bytes(&data), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_without_format_hint() -> Result<()> {
+        let path = "./tests/taxi_zone_map_manhattan.jpg";
+        let hinted = ImageReader::new().read_from_path(path, Some(ImageKind::Jpeg))?;
+
+        let detected = ImageReader::new().read_from_path(path, None)?;
+        assert_eq!(detected.rgb8(), hinted.rgb8());
+
+        let data = std::fs::read(path)?;
+        let from_bytes = ImageReader::new().read_from_bytes(&data, None)?;
+        assert_eq!(from_bytes.rgb8(), hinted.rgb8());
+
+        let from_reader = ImageReader::new().read_from_reader(data.as_slice(), None)?;
+        assert_eq!(from_reader.rgb8(), hinted.rgb8());
+
+        assert!(ImageReader::new()
+            .read_from_bytes(b"not an image", None)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_apply_color_profile() -> Result<()> {
+        let path = "./tests/display_p3.png";
+
+        let stored = ImageReader::new().read_from_path(path, Some(ImageKind::Png))?;
+        let managed = ImageReader::new()
+            .apply_color_profile(true)
+            .read_from_path(path, Some(ImageKind::Png))?;
+
+        assert!(stored.icc_profile().is_some());
+        assert!(managed.icc_profile().is_none());
+
+        // Display P3 (200, 100, 50) is (215, 93, 31) in sRGB, gray is unchanged.
+        assert_eq!(&stored.rgb8()[15..21], &[200, 100, 50, 0, 0, 0]);
+        assert_eq!(
+            &managed.rgb8()[12..21],
+            &[128, 128, 128, 215, 93, 31, 0, 0, 0]
+        );
+        assert_eq!(&managed.rgba8()[20..24], &[215, 93, 31, 255]);
+        assert_eq!(managed.bitmap()[5] & 0x00FF_FFFF, 0x00D7_5D1F);
+
+        Ok(())
     }
 }
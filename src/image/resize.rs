@@ -0,0 +1,157 @@
+use crate::image::{pixel::Pixel, ImageBuffer};
+
+/// For each of `dst_len` output pixels, the first source pixel it averages and the weight of
+/// each from there. The weights follow a triangle one source pixel wide on either side, widened
+/// to one output pixel when shrinking so that every source pixel contributes.
+fn triangle_weights(src_len: u32, dst_len: u32) -> Vec<(usize, Vec<f32>)> {
+    let scale = src_len as f32 / dst_len as f32;
+    let radius = scale.max(1.0);
+
+    (0..dst_len)
+        .map(|i| {
+            let center = (i as f32 + 0.5) * scale;
+            let start = (center - radius).floor().max(0.0) as usize;
+            let end = ((center + radius).ceil() as usize).min(src_len as usize);
+
+            let mut weights = (start..end)
+                .map(|j| (1.0 - ((j as f32 + 0.5 - center) / radius).abs()).max(0.0))
+                .collect::<Vec<_>>();
+
+            // The nearest source pixel is at most half a pixel from the center, so the sum is
+            // never zero.
+            let sum = weights.iter().sum::<f32>();
+            weights.iter_mut().for_each(|weight| *weight /= sum);
+
+            (start, weights)
+        })
+        .collect()
+}
+
+fn weighted_sum<'a>(weights: &[f32], pixels: impl Iterator<Item = &'a [f32; 4]>) -> [f32; 4] {
+    let mut sum = [0.0; 4];
+
+    for (&weight, pixel) in weights.iter().zip(pixels) {
+        for (total, &channel) in sum.iter_mut().zip(pixel) {
+            *total += weight * channel;
+        }
+    }
+
+    sum
+}
+
+impl<P: Pixel> ImageBuffer<P> {
+    /// Scales the image to `width` by `height` with a triangle (bilinear) filter, horizontally
+    /// then vertically. Colors are weighted by their alpha, so transparent pixels don't bleed
+    /// into their neighbors.
+    pub fn resize(&self, width: u32, height: u32) -> Self {
+        if (width, height) == self.dimensions() {
+            return self.clone();
+        }
+
+        if self.width() == 0 || self.height() == 0 {
+            return Self::new(width, height);
+        }
+
+        let premultiplied = self
+            .pixels()
+            .iter()
+            .map(|pixel| {
+                let [r, g, b, a] = pixel.to_rgba();
+                [r * a, g * a, b * a, a]
+            })
+            .collect::<Vec<_>>();
+
+        let columns = triangle_weights(self.width(), width);
+        let mut resized_rows = Vec::with_capacity(width as usize * self.height() as usize);
+
+        for row in premultiplied.chunks_exact(self.width() as usize) {
+            for (start, weights) in &columns {
+                resized_rows.push(weighted_sum(weights, row[*start..].iter()));
+            }
+        }
+
+        let rows = triangle_weights(self.height(), height);
+
+        Self::from_fn(width, height, |x, y| {
+            let (start, weights) = &rows[y as usize];
+            let column = resized_rows[start * width as usize + x as usize..]
+                .iter()
+                .step_by(width as usize);
+
+            match weighted_sum(weights, column) {
+                [_, _, _, a] if a <= 0.0 => P::from_rgba([0.0; 4]),
+                [r, g, b, a] => P::from_rgba([r / a, g / a, b / a, a]),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::{DynamicImageBuffer, Rgb8, Rgba, Rgba8},
+        png::PngDecoder,
+    };
+    use anyhow::Result;
+    use image::imageops::FilterType;
+
+    #[test]
+    fn test_resize_against_image_crate() -> Result<()> {
+        let path = "./tests/obama.png";
+        let data = std::fs::read(path)?;
+        let png = PngDecoder::new(&data).decode()?;
+        let source = DynamicImageBuffer::try_from(png)?.to::<Rgb8>();
+
+        let reference = image::open(path)?.to_rgb8();
+
+        for (width, height) in [(64, 48), (17, 130), (600, 500)] {
+            let resized = source.resize(width, height);
+            let expected = image::imageops::resize(&reference, width, height, FilterType::Triangle);
+
+            assert_eq!(resized.dimensions(), expected.dimensions());
+
+            let mean_absolute_error = resized
+                .as_raw()
+                .iter()
+                .zip(expected.as_raw())
+                .map(|(&a, &b)| a.abs_diff(b) as f64)
+                .sum::<f64>()
+                / expected.len() as f64;
+
+            assert!(
+                mean_absolute_error < 1.0,
+                "{width}x{height} MAE: {mean_absolute_error}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_resize_alpha() {
+        let unchanged = ImageBuffer::<Rgba8>::from_fn(3, 2, |x, y| Rgba([x as u8, y as u8, 0, 9]));
+        assert_eq!(unchanged.resize(3, 2), unchanged);
+
+        let constant = ImageBuffer::<Rgba8>::from_pixel(5, 7, Rgba([10, 200, 30, 255]));
+        assert_eq!(
+            constant.resize(2, 11),
+            ImageBuffer::from_pixel(2, 11, constant[(0, 0)])
+        );
+
+        // The transparent pixel's black doesn't darken the red.
+        let half = ImageBuffer::<Rgba8>::from_fn(2, 1, |x, _| {
+            if x == 0 {
+                Rgba([255, 0, 0, 255])
+            } else {
+                Rgba([0, 0, 0, 0])
+            }
+        });
+        assert_eq!(half.resize(1, 1).pixels(), [Rgba([255, 0, 0, 128])]);
+
+        assert_eq!(
+            ImageBuffer::<Rgba8>::new(0, 0).resize(2, 1).dimensions(),
+            (2, 1)
+        );
+    }
+}
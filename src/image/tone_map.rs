@@ -0,0 +1,148 @@
+#![allow(clippy::suboptimal_flops)]
+
+/// How a linear high dynamic range value is compressed into the displayable range [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ToneMapOperator {
+    /// `1 - e^-x`, like the response of film to exposure.
+    Exposure,
+    /// `x / (1 + x)`, which keeps dark values nearly linear.
+    Reinhard,
+    /// Krzysztof Narkowicz's fit of the ACES filmic curve, with a toe and a shoulder.
+    #[default]
+    AcesFilmic,
+}
+
+impl ToneMapOperator {
+    pub fn apply(self, value: f32) -> f32 {
+        let value = value.max(0.0);
+
+        let mapped = match self {
+            Self::Exposure => 1.0 - (-value).exp(),
+            Self::Reinhard => value / (1.0 + value),
+            Self::AcesFilmic => {
+                (value * (2.51 * value + 0.03)) / (value * (2.43 * value + 0.59) + 0.14)
+            }
+        };
+
+        mapped.clamp(0.0, 1.0)
+    }
+
+    /// The operator after this one, wrapping around.
+    pub const fn next(self) -> Self {
+        match self {
+            Self::Exposure => Self::Reinhard,
+            Self::Reinhard => Self::AcesFilmic,
+            Self::AcesFilmic => Self::Exposure,
+        }
+    }
+}
+
+/// Converts linear high dynamic range colors to displayable 8-bit sRGB.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ToneMapper {
+    operator: ToneMapOperator,
+    exposure: f32,
+}
+
+impl ToneMapper {
+    pub const fn new(operator: ToneMapOperator) -> Self {
+        Self {
+            operator,
+            exposure: 0.0,
+        }
+    }
+
+    /// Scales colors by `2^stops` before mapping them. 0 by default, which leaves them as is.
+    pub const fn exposure(mut self, stops: f32) -> Self {
+        self.exposure = stops;
+        self
+    }
+
+    /// Maps a linear color, then encodes it with the sRGB transfer function.
+    pub fn map(&self, rgb: [f32; 3]) -> [u8; 3] {
+        let scale = self.exposure.exp2();
+
+        rgb.map(|channel| {
+            let linear = self.operator.apply(channel * scale);
+
+            let encoded = if linear <= 0.003_130_8 {
+                12.92 * linear
+            } else {
+                1.055 * linear.powf(1.0 / 2.4) - 0.055
+            };
+
+            (encoded * 255.0).round() as u8
+        })
+    }
+
+    /// Maps pixels of `num_channels` linear samples, 3 for RGB or 4 for RGBA, to 8-bit RGBA.
+    /// Alpha is clamped to [0, 1] without tone mapping, and images without it are opaque.
+    pub fn map_to_rgba8(&self, samples: &[f32], num_channels: usize) -> Vec<u8> {
+        samples
+            .chunks_exact(num_channels)
+            .flat_map(|pixel| {
+                let [r, g, b] = self.map([pixel[0], pixel[1], pixel[2]]);
+                let alpha = pixel
+                    .get(3)
+                    .map_or(255, |&alpha| (alpha.clamp(0.0, 1.0) * 255.0).round() as u8);
+
+                [r, g, b, alpha]
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operators() {
+        for operator in [
+            ToneMapOperator::Exposure,
+            ToneMapOperator::Reinhard,
+            ToneMapOperator::AcesFilmic,
+        ] {
+            assert_eq!(operator.apply(0.0), 0.0, "{operator:?}");
+            assert_eq!(operator.apply(-1.0), 0.0, "{operator:?}");
+            assert!(operator.apply(1000.0) > 0.99, "{operator:?}");
+
+            let mapped = (0..100).map(|i| operator.apply(i as f32 / 10.0));
+            assert!(
+                mapped.clone().zip(mapped.skip(1)).all(|(a, b)| a <= b),
+                "{operator:?} is monotonic"
+            );
+
+            assert_eq!(operator.next().next().next(), operator);
+        }
+
+        assert_eq!(ToneMapOperator::Reinhard.apply(1.0), 0.5);
+    }
+
+    #[test]
+    fn test_tone_mapper() {
+        let reinhard = ToneMapper::new(ToneMapOperator::Reinhard);
+
+        // 0.5 linear is 188 in sRGB.
+        assert_eq!(reinhard.map([0.0, 1.0, 1e9]), [0, 188, 255]);
+
+        // Each stop doubles the exposure.
+        assert_eq!(
+            reinhard.exposure(1.0).map([0.5, 1.0, 2.0]),
+            reinhard.map([1.0, 2.0, 4.0])
+        );
+        assert_eq!(
+            reinhard.exposure(-2.0).map([4.0, 4.0, 4.0]),
+            reinhard.map([1.0, 1.0, 1.0])
+        );
+
+        assert_eq!(
+            reinhard.map_to_rgba8(&[1.0, 1.0, 1.0, 0.5, 0.0, 0.0, 0.0, 2.0], 4),
+            [188, 188, 188, 128, 0, 0, 0, 255]
+        );
+        assert_eq!(
+            reinhard.map_to_rgba8(&[1.0, 0.0, 1.0], 3),
+            [188, 0, 188, 255]
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use crate::{
+    bmp::BmpEncoder,
+    gif::GifEncoder,
+    ico::IcoEncoder,
+    image::grammar::{ImageEncoder, ImageExt, ImageKind},
+    jpeg::{ChromaSubsampling, JpegEncoder},
+    png::PngEncoder,
+    pnm::PnmEncoder,
+    qoi::QoiEncoder,
+    tga::TgaEncoder,
+};
+use anyhow::{anyhow, bail, Result};
+use std::{io::Write, path::Path};
+
+/// How `ImageWriter` encodes an image. Options that do not apply to the chosen format are
+/// ignored.
+#[derive(Debug, Clone, Copy)]
+pub struct WriteOptions {
+    image_kind: Option<ImageKind>,
+    quality: u8,
+    chroma_subsampling: ChromaSubsampling,
+    optimize_huffman_tables: bool,
+    dither: bool,
+}
+
+impl Default for WriteOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl WriteOptions {
+    pub const fn new() -> Self {
+        Self {
+            image_kind: None,
+            quality: 75,
+            chroma_subsampling: ChromaSubsampling::Chroma420,
+            optimize_huffman_tables: false,
+            dither: false,
+        }
+    }
+
+    /// The format to write. Without one, `write_to_path` picks the format from the path's
+    /// extension.
+    pub const fn image_kind(mut self, image_kind: ImageKind) -> Self {
+        self.image_kind = Some(image_kind);
+        self
+    }
+
+    /// The JPEG quality, from 1 to 100.
+    pub const fn quality(mut self, quality: u8) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub const fn chroma_subsampling(mut self, chroma_subsampling: ChromaSubsampling) -> Self {
+        self.chroma_subsampling = chroma_subsampling;
+        self
+    }
+
+    pub const fn optimize_huffman_tables(mut self, optimize_huffman_tables: bool) -> Self {
+        self.optimize_huffman_tables = optimize_huffman_tables;
+        self
+    }
+
+    /// Whether to dither images with more colors than a GIF palette holds.
+    pub const fn dither(mut self, dither: bool) -> Self {
+        self.dither = dither;
+        self
+    }
+
+    fn encoder<'a>(
+        &self,
+        image_kind: ImageKind,
+        writer: impl Write + 'a,
+    ) -> Result<Box<dyn ImageEncoder + 'a>> {
+        let encoder: Box<dyn ImageEncoder + 'a> = match image_kind {
+            ImageKind::Png => Box::new(PngEncoder::new(writer)),
+            ImageKind::Jpeg => Box::new(
+                JpegEncoder::new(writer)
+                    .quality(self.quality)
+                    .chroma_subsampling(self.chroma_subsampling)
+                    .optimize_huffman_tables(self.optimize_huffman_tables),
+            ),
+            ImageKind::Bmp => Box::new(BmpEncoder::new(writer)),
+            ImageKind::Gif => Box::new(GifEncoder::new(writer).dither(self.dither)),
+            ImageKind::Pnm => Box::new(PnmEncoder::new(writer)),
+            ImageKind::Qoi => Box::new(QoiEncoder::new(writer)),
+            ImageKind::Ico => Box::new(IcoEncoder::new(writer)),
+            ImageKind::Tga => Box::new(TgaEncoder::new(writer)),
+            ImageKind::Tiff | ImageKind::Webp | ImageKind::Hdr | ImageKind::Exr => {
+                bail!("Writing {:?} images is unsupported.", image_kind)
+            }
+        };
+
+        Ok(encoder)
+    }
+}
+
+/// Writes images in any supported format, the counterpart to `ImageReader`.
+#[derive(Debug)]
+pub struct ImageWriter;
+
+impl ImageWriter {
+    /// Writes `image` to `path`, in the format of `options` or else the one its extension
+    /// names.
+    pub fn write_to_path(
+        image: &dyn ImageExt,
+        path: impl AsRef<Path>,
+        options: WriteOptions,
+    ) -> Result<()> {
+        let path = path.as_ref();
+
+        let image_kind = options
+            .image_kind
+            .or_else(|| ImageKind::from_path(path))
+            .ok_or_else(|| anyhow!("Cannot infer an image format for {}", path.display()))?;
+
+        // Encode first, so that a failure leaves any existing file untouched.
+        let mut encoded = Vec::new();
+        options
+            .encoder(image_kind, &mut encoded)?
+            .write_image(image)?;
+
+        std::fs::write(path, encoded)?;
+
+        Ok(())
+    }
+
+    /// Writes `image` to `writer`, in the format of `options`.
+    pub fn write_to_writer(
+        image: &dyn ImageExt,
+        writer: impl Write,
+        options: WriteOptions,
+    ) -> Result<()> {
+        let image_kind = options
+            .image_kind
+            .ok_or_else(|| anyhow!("Writing to a writer needs an explicit image format."))?;
+
+        options.encoder(image_kind, writer)?.write_image(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{
+        buffer::ImageBuffer,
+        grammar::ColorType,
+        pixel::{LumaA, Rgba},
+        reader::ImageReader,
+    };
+
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("norm_writer_{}_{name}", std::process::id()))
+    }
+
+    #[test]
+    fn test_write_by_extension() -> Result<()> {
+        let source = ImageReader::new().read_from_path("./tests/obama.png", None)?;
+
+        let png_path = temp_path("obama.png");
+        ImageWriter::write_to_path(source.as_ref(), &png_path, WriteOptions::new())?;
+
+        let png = ImageReader::new().read_from_path(&png_path, None)?;
+        assert_eq!(png.color_type(), source.color_type());
+        assert_eq!(png.rgb8(), source.rgb8());
+
+        let jpeg_path = temp_path("obama.JPG");
+        ImageWriter::write_to_path(source.as_ref(), &jpeg_path, WriteOptions::new().quality(95))?;
+
+        let data = std::fs::read(&jpeg_path)?;
+        assert_eq!(ImageKind::from_magic_bytes(&data), Some(ImageKind::Jpeg));
+        assert_eq!(
+            ImageReader::new()
+                .read_from_bytes(&data, None)?
+                .dimensions(),
+            source.dimensions()
+        );
+
+        std::fs::remove_file(png_path)?;
+        std::fs::remove_file(jpeg_path)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_explicit_format() -> Result<()> {
+        let image = ImageBuffer::from_fn(5, 4, |x, y| Rgba([x as u8 * 50, y as u8, 9, 128]));
+
+        let path = temp_path("image.bin");
+        assert!(ImageWriter::write_to_path(&image, &path, WriteOptions::new()).is_err());
+
+        ImageWriter::write_to_path(
+            &image,
+            &path,
+            WriteOptions::new().image_kind(ImageKind::Png),
+        )?;
+        let written = ImageReader::new().read_from_path(&path, None)?;
+        std::fs::remove_file(path)?;
+
+        assert_eq!(written.color_type(), ColorType::RGBA);
+        assert_eq!(written.rgba8(), image.rgba8());
+
+        let mut encoded = Vec::new();
+        assert!(ImageWriter::write_to_writer(&image, &mut encoded, WriteOptions::new()).is_err());
+
+        // TIFF is only read.
+        assert!(ImageWriter::write_to_writer(
+            &image,
+            &mut encoded,
+            WriteOptions::new().image_kind(ImageKind::Tiff)
+        )
+        .is_err());
+        assert!(encoded.is_empty());
+
+        ImageWriter::write_to_writer(
+            &image,
+            &mut encoded,
+            WriteOptions::new().image_kind(ImageKind::Jpeg),
+        )?;
+        assert_eq!(ImageKind::from_magic_bytes(&encoded), Some(ImageKind::Jpeg));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_failure_keeps_existing_file() -> Result<()> {
+        let image = ImageBuffer::from_fn(5, 4, |x, y| Rgba([x as u8 * 50, y as u8, 9, 128]));
+
+        let path = temp_path("existing.tiff");
+        std::fs::write(&path, b"existing")?;
+
+        assert!(ImageWriter::write_to_path(&image, &path, WriteOptions::new()).is_err());
+        let contents = std::fs::read(&path)?;
+        std::fs::remove_file(path)?;
+
+        assert_eq!(contents, b"existing");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_write_png_keeps_color_type() -> Result<()> {
+        let image = ImageBuffer::from_fn(3, 2, |x, y| LumaA([x as u8 * 100, y as u8 * 200]));
+
+        let mut encoded = Vec::new();
+        ImageWriter::write_to_writer(
+            &image,
+            &mut encoded,
+            WriteOptions::new().image_kind(ImageKind::Png),
+        )?;
+
+        let written = ImageReader::new().read_from_bytes(&encoded, None)?;
+        assert_eq!(written.color_type(), ColorType::GrayscaleAlpha);
+        assert_eq!(written.rgba8(), image.rgba8());
+
+        Ok(())
+    }
+}
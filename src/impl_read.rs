@@ -10,6 +10,19 @@ macro_rules! impl_read_for_datatype {
     };
 }
 
+/// Like `impl_read_for_datatype`, for formats that store integers little-endian.
+#[macro_export]
+macro_rules! impl_read_le_for_datatype {
+    ($name:ident, $type:ty) => {
+        fn $name(&mut self) -> Result<$type> {
+            let width = std::mem::size_of::<$type>();
+            let slice = self.read_slice(width)?;
+
+            Ok(<$type>::from_le_bytes(slice.try_into()?))
+        }
+    };
+}
+
 #[macro_export]
 macro_rules! impl_read_slice {
     () => {
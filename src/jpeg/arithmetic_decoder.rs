@@ -0,0 +1,653 @@
+use crate::jpeg::{
+    frame::Frame,
+    grammar::{ConditioningTable, HuffmanTableClass, Scan},
+    idct::ZIGZAG,
+    qm_decoder::{Context, QmDecoder},
+};
+use anyhow::{ensure, Result};
+use std::ops::RangeInclusive;
+
+/// The conditioning currently installed in each of the four DC and AC destinations.
+#[derive(Debug)]
+pub struct ConditioningTables {
+    /// The (L, U) bounds of the difference categories, defaulting to (0, 1).
+    dc: [(u8, u8); 4],
+    /// Kx, which splits the magnitude statistics of low and high frequencies, defaulting to 5.
+    ac: [u8; 4],
+}
+
+impl Default for ConditioningTables {
+    fn default() -> Self {
+        Self {
+            dc: [(0, 1); 4],
+            ac: [5; 4],
+        }
+    }
+}
+
+impl ConditioningTables {
+    /// Installs `table`, replacing any conditioning previously defined at the same destination.
+    pub(crate) fn install(&mut self, table: &ConditioningTable) -> Result<()> {
+        let destination = table.table_identifier() as usize;
+        ensure!(
+            destination < 4,
+            "Invalid conditioning table destination: {destination}"
+        );
+
+        match table.table_class() {
+            HuffmanTableClass::DC => {
+                let (lower, upper) = (table.value & 0b1111, table.value >> 4);
+                ensure!(
+                    lower <= upper,
+                    "Invalid DC conditioning: {:#X}",
+                    table.value
+                );
+
+                self.dc[destination] = (lower, upper);
+            }
+            HuffmanTableClass::AC => {
+                ensure!(
+                    (1..=63).contains(&table.value),
+                    "Invalid AC conditioning: {}",
+                    table.value
+                );
+
+                self.ac[destination] = table.value;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Bins of the DC statistics (see Table F.4). The first decisions of a difference are conditioned
+/// on the category of the previous difference, which selects one of five groups of four bins.
+const DC_MAGNITUDE_CATEGORIES: usize = 20;
+const DC_BINS: usize = 64;
+
+/// Bins of the AC statistics (see Table F.5). Each coefficient index has three bins of its own,
+/// followed by magnitude categories for low and high frequencies.
+const AC_LOW_MAGNITUDE_CATEGORIES: usize = 189;
+const AC_HIGH_MAGNITUDE_CATEGORIES: usize = 217;
+const AC_BINS: usize = 256;
+
+/// Bins of the lossless statistics (see Table H.3). The first decisions are conditioned on the
+/// categories of the differences to the left and above, the magnitude categories on whether the
+/// difference above is large.
+const LOSSLESS_MAGNITUDE_CATEGORIES: usize = 100;
+const LOSSLESS_LARGE_MAGNITUDE_CATEGORIES: usize = 129;
+const LOSSLESS_BINS: usize = 158;
+
+/// The offset between a magnitude category bin and the bin of the magnitude's bit pattern.
+const MAGNITUDE_BITS: usize = 14;
+
+/// The adaptive probability estimates of a scan, which start over at each restart interval.
+#[derive(Debug)]
+struct Statistics {
+    dc: [[Context; DC_BINS]; 4],
+    ac: [[Context; AC_BINS]; 4],
+    lossless: [[Context; LOSSLESS_BINS]; 4],
+    fixed: Context,
+}
+
+impl Default for Statistics {
+    fn default() -> Self {
+        Self {
+            dc: [[Context::default(); DC_BINS]; 4],
+            ac: [[Context::default(); AC_BINS]; 4],
+            lossless: [[Context::default(); LOSSLESS_BINS]; 4],
+            fixed: Context::FIXED,
+        }
+    }
+}
+
+/// Classifies a difference as zero (0), small positive (1), small negative (2), large positive
+/// (3) or large negative (4) relative to the conditioning bounds (see F.1.4.4.1.2).
+const fn difference_category(difference: i32, (lower, upper): (u8, u8)) -> usize {
+    if difference == 0 {
+        return 0;
+    }
+
+    // The magnitude category of the difference, as a power of two.
+    let magnitude = match difference.unsigned_abs() - 1 {
+        0 => 0,
+        n => 1 << n.ilog2(),
+    };
+
+    let negative = (difference < 0) as usize;
+
+    if magnitude < (1 << lower) >> 1 {
+        0
+    } else if magnitude > (1 << upper) >> 1 {
+        3 + negative
+    } else {
+        1 + negative
+    }
+}
+
+/// Decodes the sign, magnitude category and magnitude bits of a nonzero value (see F.1.4.4.1.1),
+/// starting with the first magnitude decision at `first_bin`. Magnitude categories past the
+/// first are coded in bins starting at `categories`.
+fn decode_nonzero(
+    decoder: &mut QmDecoder,
+    bins: &mut [Context],
+    negative: bool,
+    first_bin: usize,
+    categories: usize,
+) -> Result<i32> {
+    let mut bin = first_bin;
+    let mut magnitude = 0_i32;
+
+    if decoder.decode(&mut bins[bin]) {
+        magnitude = 1;
+        bin = categories;
+
+        while decoder.decode(&mut bins[bin]) {
+            magnitude <<= 1;
+            ensure!(magnitude < 1 << 15, "Invalid magnitude category.");
+
+            bin += 1;
+        }
+    }
+
+    Ok(decode_magnitude_bits(
+        decoder, bins, negative, magnitude, bin,
+    ))
+}
+
+/// Decodes the bits below the leading bit of `magnitude`, whose category was decoded at `bin`,
+/// and applies the sign.
+fn decode_magnitude_bits(
+    decoder: &mut QmDecoder,
+    bins: &mut [Context],
+    negative: bool,
+    magnitude: i32,
+    bin: usize,
+) -> i32 {
+    let mut value = magnitude;
+    let mut bit = magnitude >> 1;
+
+    while bit > 0 {
+        if decoder.decode(&mut bins[bin + MAGNITUDE_BITS]) {
+            value |= bit;
+        }
+
+        bit >>= 1;
+    }
+
+    if negative {
+        -(value + 1)
+    } else {
+        value + 1
+    }
+}
+
+impl Statistics {
+    /// Decodes a DC difference. `context` holds the category of the component's previous
+    /// difference and is updated with the category of this one.
+    fn decode_dc_difference(
+        &mut self,
+        decoder: &mut QmDecoder,
+        table: usize,
+        conditioning: (u8, u8),
+        context: &mut usize,
+    ) -> Result<i32> {
+        let bins = &mut self.dc[table];
+        let base = *context * 4;
+
+        if !decoder.decode(&mut bins[base]) {
+            *context = 0;
+            return Ok(0);
+        }
+
+        let negative = decoder.decode(&mut bins[base + 1]);
+        let first_bin = base + 2 + negative as usize;
+
+        let difference =
+            decode_nonzero(decoder, bins, negative, first_bin, DC_MAGNITUDE_CATEGORIES)?;
+
+        *context = difference_category(difference, conditioning);
+
+        Ok(difference)
+    }
+
+    /// Decodes the AC coefficients of `band` in a sequential scan or the first scan of a
+    /// progressive band, scaled by `point_transform` (see F.1.4.4.2).
+    fn decode_ac_coefficients(
+        &mut self,
+        decoder: &mut QmDecoder,
+        table: usize,
+        kx: u8,
+        band: RangeInclusive<usize>,
+        point_transform: u8,
+        coefficients: &mut [i16; 64],
+    ) -> Result<()> {
+        let bins = &mut self.ac[table];
+        let end = *band.end();
+        let mut k = *band.start();
+
+        while k <= end {
+            let mut bin = 3 * (k - 1);
+
+            // End of block.
+            if decoder.decode(&mut bins[bin]) {
+                break;
+            }
+
+            while !decoder.decode(&mut bins[bin + 1]) {
+                bin += 3;
+                k += 1;
+                ensure!(k <= end, "AC coefficient index out of bounds.");
+            }
+
+            let negative = decoder.decode(&mut self.fixed);
+            bin += 2;
+
+            // The first two magnitude decisions share a bin, the rest depend on the frequency.
+            let mut magnitude = 0;
+
+            if decoder.decode(&mut bins[bin]) {
+                magnitude = 1;
+
+                if decoder.decode(&mut bins[bin]) {
+                    magnitude = 2;
+                    bin = if k <= kx as usize {
+                        AC_LOW_MAGNITUDE_CATEGORIES
+                    } else {
+                        AC_HIGH_MAGNITUDE_CATEGORIES
+                    };
+
+                    while decoder.decode(&mut bins[bin]) {
+                        magnitude <<= 1;
+                        ensure!(magnitude < 1 << 15, "Invalid magnitude category.");
+
+                        bin += 1;
+                    }
+                }
+            }
+
+            let value = decode_magnitude_bits(decoder, bins, negative, magnitude, bin);
+            coefficients[ZIGZAG[k]] = (value << point_transform) as i16;
+
+            k += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Decodes a sequential (extended) scan coded with arithmetic coding into the frame's
+/// coefficients.
+pub fn decode_arithmetic_sequential_scan(
+    frame: &mut Frame,
+    scan: &Scan,
+    conditioning: &ConditioningTables,
+) -> Result<()> {
+    let start_of_scan = &scan.start_of_scan;
+    let component_indices = frame.scan_component_indices(start_of_scan)?;
+    let selectors = table_selectors(scan)?;
+
+    let mut decoder = QmDecoder::new(scan.image_data);
+    let mut statistics = Statistics::default();
+    let mut dc_predictors = vec![0; component_indices.len()];
+    let mut dc_contexts = vec![0; component_indices.len()];
+
+    frame.for_each_block(
+        &component_indices,
+        scan.restart_interval,
+        |block, component| {
+            if block.restart {
+                decoder.restart()?;
+                statistics = Statistics::default();
+                dc_predictors.fill(0);
+                dc_contexts.fill(0);
+            }
+
+            let (dc_table, ac_table) = selectors[block.position];
+            let coefficients = &mut component.coefficients[block.index];
+
+            dc_predictors[block.position] += statistics.decode_dc_difference(
+                &mut decoder,
+                dc_table,
+                conditioning.dc[dc_table],
+                &mut dc_contexts[block.position],
+            )?;
+            coefficients[0] = dc_predictors[block.position] as i16;
+
+            statistics.decode_ac_coefficients(
+                &mut decoder,
+                ac_table,
+                conditioning.ac[ac_table],
+                1..=63,
+                0,
+                coefficients,
+            )
+        },
+    )
+}
+
+/// Decodes a progressive scan coded with arithmetic coding (see G.1.3). Like its Huffman
+/// counterpart, it codes either the DC coefficients or a band of AC coefficients, in a first
+/// pass or as a refinement by one bit.
+pub fn decode_arithmetic_progressive_scan(
+    frame: &mut Frame,
+    scan: &Scan,
+    conditioning: &ConditioningTables,
+) -> Result<()> {
+    let start_of_scan = &scan.start_of_scan;
+    let component_indices = frame.scan_component_indices(start_of_scan)?;
+    let selectors = table_selectors(scan)?;
+
+    let spectral_start = *start_of_scan.spectral_select.start() as usize;
+    let spectral_end = *start_of_scan.spectral_select.end() as usize;
+    let high = start_of_scan.successive_approximation_high();
+    let low = start_of_scan.successive_approximation_low();
+
+    ensure!(
+        spectral_start <= spectral_end && spectral_end < 64,
+        "Invalid spectral selection: {:?}",
+        start_of_scan.spectral_select
+    );
+
+    ensure!(
+        (spectral_start == 0) == (spectral_end == 0),
+        "A progressive scan codes either DC or AC coefficients, not both."
+    );
+
+    ensure!(
+        spectral_start == 0 || component_indices.len() == 1,
+        "AC scans must be non-interleaved."
+    );
+
+    ensure!(
+        low <= 13 && (high == 0 || high == low + 1),
+        "Invalid successive approximation: {:#X}",
+        start_of_scan.approximation
+    );
+
+    let mut decoder = QmDecoder::new(scan.image_data);
+    let mut statistics = Statistics::default();
+    let mut dc_predictors = vec![0; component_indices.len()];
+    let mut dc_contexts = vec![0; component_indices.len()];
+
+    let positive = 1_i16 << low;
+    let negative = -1_i16 << low;
+
+    frame.for_each_block(
+        &component_indices,
+        scan.restart_interval,
+        |block, component| {
+            if block.restart {
+                decoder.restart()?;
+                statistics = Statistics::default();
+                dc_predictors.fill(0);
+                dc_contexts.fill(0);
+            }
+
+            let (dc_table, ac_table) = selectors[block.position];
+            let coefficients = &mut component.coefficients[block.index];
+
+            match (spectral_start, high) {
+                (0, 0) => {
+                    dc_predictors[block.position] += statistics.decode_dc_difference(
+                        &mut decoder,
+                        dc_table,
+                        conditioning.dc[dc_table],
+                        &mut dc_contexts[block.position],
+                    )?;
+                    coefficients[0] = (dc_predictors[block.position] << low) as i16;
+                }
+                (0, _) => {
+                    if decoder.decode(&mut statistics.fixed) {
+                        coefficients[0] |= positive;
+                    }
+                }
+                (_, 0) => statistics.decode_ac_coefficients(
+                    &mut decoder,
+                    ac_table,
+                    conditioning.ac[ac_table],
+                    spectral_start..=spectral_end,
+                    low,
+                    coefficients,
+                )?,
+                _ => {
+                    let bins = &mut statistics.ac[ac_table];
+
+                    // Past the last coefficient that was nonzero after previous scans, the end
+                    // of the band is coded like in a first scan.
+                    let end_of_previous_band = (1..=spectral_end)
+                        .rev()
+                        .find(|&k| coefficients[ZIGZAG[k]] != 0)
+                        .unwrap_or(0);
+
+                    let mut k = spectral_start;
+
+                    while k <= spectral_end {
+                        let mut bin = 3 * (k - 1);
+
+                        if k > end_of_previous_band && decoder.decode(&mut bins[bin]) {
+                            break;
+                        }
+
+                        loop {
+                            let coefficient = &mut coefficients[ZIGZAG[k]];
+
+                            if *coefficient != 0 {
+                                // A correction bit for a coefficient that is already nonzero.
+                                if decoder.decode(&mut bins[bin + 2]) {
+                                    *coefficient +=
+                                        if *coefficient < 0 { negative } else { positive };
+                                }
+
+                                break;
+                            }
+
+                            if decoder.decode(&mut bins[bin + 1]) {
+                                // A newly nonzero coefficient.
+                                *coefficient = if decoder.decode(&mut statistics.fixed) {
+                                    negative
+                                } else {
+                                    positive
+                                };
+
+                                break;
+                            }
+
+                            bin += 3;
+                            k += 1;
+                            ensure!(k <= spectral_end, "AC coefficient index out of bounds.");
+                        }
+
+                        k += 1;
+                    }
+                }
+            }
+
+            Ok(())
+        },
+    )
+}
+
+/// Decodes a lossless scan coded with arithmetic coding into the frame's samples (see H.1.2.3).
+///
+/// Samples are predicted exactly like in Huffman coded lossless scans. The statistical model of
+/// the differences follows that of DC differences, except that its first decisions are
+/// conditioned on the differences coded to the left and above rather than on the previous one.
+pub fn decode_arithmetic_lossless_scan(
+    frame: &mut Frame,
+    scan: &Scan,
+    conditioning: &ConditioningTables,
+) -> Result<()> {
+    let start_of_scan = &scan.start_of_scan;
+    let component_indices = frame.scan_component_indices(start_of_scan)?;
+    let selectors = table_selectors(scan)?;
+
+    let predictor = *start_of_scan.spectral_select.start();
+    let point_transform = start_of_scan.successive_approximation_low();
+
+    ensure!(
+        (1..=7).contains(&predictor),
+        "Invalid lossless predictor: {predictor}"
+    );
+
+    ensure!(
+        point_transform < frame.precision,
+        "Invalid point transform: {point_transform}"
+    );
+
+    let default_prediction = 1_i32 << (frame.precision - point_transform - 1);
+
+    let mut interval_start_lines = vec![0; component_indices.len()];
+    let mut starts_interval = vec![true; component_indices.len()];
+
+    // The differences coded for each component so far, laid out like its samples.
+    let mut differences = component_indices
+        .iter()
+        .map(|&index| vec![0_i32; frame.components[index].samples.len()])
+        .collect::<Vec<_>>();
+
+    let mut decoder = QmDecoder::new(scan.image_data);
+    let mut statistics = Statistics::default();
+
+    frame.for_each_block(
+        &component_indices,
+        scan.restart_interval,
+        |block, component| {
+            if block.restart {
+                decoder.restart()?;
+                statistics = Statistics::default();
+                starts_interval.fill(true);
+            }
+
+            let line_stride = component.blocks_per_line;
+            let (x, y) = (block.index % line_stride, block.index / line_stride);
+            let samples = &mut component.samples;
+            let differences = &mut differences[block.position];
+
+            let sample = |index: usize| samples[index] as i32;
+
+            let first_line = if std::mem::take(&mut starts_interval[block.position]) {
+                interval_start_lines[block.position] = y;
+                true
+            } else {
+                y == interval_start_lines[block.position]
+            };
+
+            let prediction = if first_line && x == 0 {
+                default_prediction
+            } else if first_line {
+                sample(block.index - 1)
+            } else if x == 0 {
+                sample(block.index - line_stride)
+            } else {
+                let a = sample(block.index - 1);
+                let b = sample(block.index - line_stride);
+                let c = sample(block.index - line_stride - 1);
+
+                match predictor {
+                    1 => a,
+                    2 => b,
+                    3 => c,
+                    4 => a + b - c,
+                    5 => a + ((b - c) >> 1),
+                    6 => b + ((a - c) >> 1),
+                    _ => (a + b) >> 1,
+                }
+            };
+
+            // Differences outside the current restart interval or the image count as zero.
+            let left = if x == 0 {
+                0
+            } else {
+                differences[block.index - 1]
+            };
+            let above = if first_line {
+                0
+            } else {
+                differences[block.index - line_stride]
+            };
+
+            let table = selectors[block.position].0;
+            let bounds = conditioning.dc[table];
+            let bins = &mut statistics.lossless[table];
+
+            let left_category = difference_category(left, bounds);
+            let above_category = difference_category(above, bounds);
+            let base = (5 * left_category + above_category) * 4;
+
+            let difference = if decoder.decode(&mut bins[base]) {
+                let negative = decoder.decode(&mut bins[base + 1]);
+
+                let categories = if above_category >= 3 {
+                    LOSSLESS_LARGE_MAGNITUDE_CATEGORIES
+                } else {
+                    LOSSLESS_MAGNITUDE_CATEGORIES
+                };
+
+                decode_nonzero(
+                    &mut decoder,
+                    bins,
+                    negative,
+                    base + 2 + negative as usize,
+                    categories,
+                )?
+            } else {
+                0
+            };
+
+            differences[block.index] = difference;
+
+            // Reconstruction is modulo 2^16.
+            samples[block.index] = (prediction + difference) as u16;
+
+            Ok(())
+        },
+    )?;
+
+    for index in component_indices {
+        frame.components[index]
+            .samples
+            .iter_mut()
+            .for_each(|sample| *sample <<= point_transform);
+    }
+
+    Ok(())
+}
+
+/// The (DC, AC) conditioning table destinations of each scan component.
+fn table_selectors(scan: &Scan) -> Result<Vec<(usize, usize)>> {
+    scan.start_of_scan
+        .components
+        .iter()
+        .map(|&(_, selectors)| {
+            let (dc, ac) = ((selectors >> 4) as usize, (selectors & 0b1111) as usize);
+            ensure!(dc < 4 && ac < 4, "Invalid table selectors: {selectors:#X}");
+
+            Ok((dc, ac))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_difference_category() {
+        // With the default bounds, only zero is in the zero category, and magnitudes up to 2
+        // are small.
+        let bounds = (0, 1);
+        assert_eq!(difference_category(0, bounds), 0);
+        assert_eq!(difference_category(1, bounds), 1);
+        assert_eq!(difference_category(-2, bounds), 2);
+        assert_eq!(difference_category(3, bounds), 3);
+        assert_eq!(difference_category(-9, bounds), 4);
+
+        // Magnitudes up to 2 are zero, and up to 8 small.
+        let bounds = (2, 3);
+        assert_eq!(difference_category(-2, bounds), 0);
+        assert_eq!(difference_category(-3, bounds), 2);
+        assert_eq!(difference_category(8, bounds), 1);
+        assert_eq!(difference_category(9, bounds), 3);
+    }
+}
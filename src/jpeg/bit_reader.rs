@@ -0,0 +1,150 @@
+use anyhow::{anyhow, Result};
+
+/// Reads bits MSB-first from an entropy-coded segment.
+///
+/// A 0xFF data byte is always followed by a stuffed 0x00 byte, which is discarded. Any other byte
+/// after 0xFF is a marker. Once a marker is reached, the reader stops consuming input and
+/// pads the buffer with zeros instead.
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    buffer: u64,
+    num_bits: u8,
+}
+
+impl<'a> BitReader<'a> {
+    pub(crate) const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            buffer: 0,
+            num_bits: 0,
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.num_bits <= 56 {
+            let byte = match self.data.get(self.cursor) {
+                Some(&0xFF) => match self.data.get(self.cursor + 1) {
+                    Some(0x00) => {
+                        self.cursor += 2;
+                        0xFF
+                    }
+                    // A marker (or EOF) was reached.
+                    _ => 0,
+                },
+                Some(&byte) => {
+                    self.cursor += 1;
+                    byte
+                }
+                None => 0,
+            };
+
+            self.buffer |= (byte as u64) << (56 - self.num_bits);
+            self.num_bits += 8;
+        }
+    }
+
+    /// Returns the next `n` bits without consuming them, `n` must be at most 16.
+    pub(crate) fn peek_bits(&mut self, n: u8) -> u16 {
+        debug_assert!(n <= 16);
+
+        if n == 0 {
+            return 0;
+        }
+
+        if self.num_bits < n {
+            self.fill();
+        }
+
+        (self.buffer >> (64 - n)) as u16
+    }
+
+    /// Discards the bits left in the current restart interval and skips past the restart marker
+    /// ending it. If the marker isn't where it is expected, e.g. because the interval is corrupt,
+    /// the reader resynchronizes at the next restart marker.
+    pub(crate) fn restart(&mut self) -> Result<()> {
+        self.buffer = 0;
+        self.num_bits = 0;
+
+        let offset = self.data[self.cursor..]
+            .windows(2)
+            .position(|marker| marker[0] == 0xFF && matches!(marker[1], 0xD0..=0xD7))
+            .ok_or_else(|| anyhow!("Expected a restart marker."))?;
+
+        self.cursor += offset + 2;
+
+        Ok(())
+    }
+
+    pub(crate) fn consume_bits(&mut self, n: u8) {
+        debug_assert!(n <= self.num_bits);
+
+        self.buffer <<= n;
+        self.num_bits -= n;
+    }
+
+    pub(crate) fn read_bits(&mut self, n: u8) -> u16 {
+        let bits = self.peek_bits(n);
+        self.consume_bits(n);
+
+        bits
+    }
+
+    /// Reads an `s`-bit magnitude category value and sign-extends it (the RECEIVE and EXTEND
+    /// procedures in F.2.2.1).
+    pub(crate) fn receive_extend(&mut self, s: u8) -> i32 {
+        if s == 0 {
+            return 0;
+        }
+
+        let value = self.read_bits(s) as i32;
+
+        if value < 1 << (s - 1) {
+            value - (1 << s) + 1
+        } else {
+            value
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_stuffing() {
+        let mut reader = BitReader::new(&[0xFF, 0x00, 0b1010_0000, 0xFF, 0xD9]);
+
+        assert_eq!(reader.read_bits(8), 0xFF);
+        assert_eq!(reader.read_bits(3), 0b101);
+        assert_eq!(reader.read_bits(5), 0);
+
+        // Reading past the marker yields zeros.
+        assert_eq!(reader.read_bits(16), 0);
+    }
+
+    #[test]
+    fn test_restart() -> Result<()> {
+        let mut reader = BitReader::new(&[0b1011_1111, 0xFF, 0xD0, 0b0110_0000]);
+
+        assert_eq!(reader.read_bits(2), 0b10);
+
+        // The padding bits of the interval are discarded.
+        reader.restart()?;
+        assert_eq!(reader.read_bits(3), 0b011);
+
+        assert!(reader.restart().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_receive_extend() {
+        let mut reader = BitReader::new(&[0b0101_1000]);
+
+        assert_eq!(reader.receive_extend(3), -5);
+        assert_eq!(reader.receive_extend(3), 6);
+    }
+}
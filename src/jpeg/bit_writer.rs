@@ -0,0 +1,59 @@
+/// Writes entropy-coded data, most significant bit first, stuffing a zero byte after every 0xFF
+/// byte so it cannot be mistaken for a marker (see F.1.2.3).
+#[derive(Debug, Default)]
+pub struct BitWriter {
+    data: Vec<u8>,
+    buffer: u32,
+    num_bits: u8,
+}
+
+impl BitWriter {
+    pub(crate) fn write_bits(&mut self, bits: u16, length: u8) {
+        debug_assert!(length <= 16);
+
+        self.buffer = (self.buffer << length) | (bits as u32 & ((1 << length) - 1));
+        self.num_bits += length;
+
+        while self.num_bits >= 8 {
+            self.num_bits -= 8;
+
+            let byte = (self.buffer >> self.num_bits) as u8;
+            self.data.push(byte);
+
+            if byte == 0xFF {
+                self.data.push(0x00);
+            }
+        }
+    }
+
+    /// Pads the last byte with 1 bits and returns the written data.
+    pub(crate) fn finish(mut self) -> Vec<u8> {
+        if self.num_bits > 0 {
+            self.write_bits(0xFF, 8 - self.num_bits);
+        }
+
+        self.data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::bit_reader::BitReader;
+
+    #[test]
+    fn test_round_trip_with_byte_stuffing() {
+        let mut writer = BitWriter::default();
+        writer.write_bits(0b101, 3);
+        writer.write_bits(0xFFFF, 16);
+        writer.write_bits(0b0, 1);
+
+        let data = writer.finish();
+        assert_eq!(data, [0b1011_1111, 0xFF, 0x00, 0b1110_1111]);
+
+        let mut reader = BitReader::new(&data);
+        assert_eq!(reader.read_bits(3), 0b101);
+        assert_eq!(reader.read_bits(16), 0xFFFF);
+        assert_eq!(reader.read_bits(1), 0);
+    }
+}
@@ -0,0 +1,186 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::jpeg::{
+    frame::{Frame, FrameComponent},
+    grammar::{AdobeHeader, ColorTransform},
+};
+use anyhow::{bail, Result};
+
+/// The color space of a frame's components.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorSpace {
+    Grayscale,
+    YCbCr,
+    RGB,
+    CMYK,
+    /// CMYK as written by Adobe applications, where 0 means full ink.
+    InvertedCMYK,
+    /// YCbCr for the first three components of inverted CMYK, followed by inverted K.
+    YCCK,
+}
+
+impl ColorSpace {
+    /// Infers the color space like libjpeg does. An Adobe APP14 segment's transform takes
+    /// precedence, three component files are otherwise YCbCr unless their component identifiers
+    /// spell out RGB, and four component files are CMYK.
+    pub fn infer(frame: &Frame, jfif: bool, adobe_header: Option<&AdobeHeader>) -> Result<Self> {
+        let transform = adobe_header.map(|header| header.color_transform);
+
+        let color_space = match (frame.components.len(), transform) {
+            (1, _) => Self::Grayscale,
+            (3, Some(ColorTransform::None)) => Self::RGB,
+            (3, Some(_)) => Self::YCbCr,
+            (3, None) => {
+                let identifiers = frame.components.iter().map(|c| c.identifier);
+
+                if !jfif && identifiers.eq(*b"RGB") {
+                    Self::RGB
+                } else {
+                    Self::YCbCr
+                }
+            }
+            (4, Some(ColorTransform::YCCK)) => Self::YCCK,
+            (4, Some(_)) => Self::InvertedCMYK,
+            (4, None) => Self::CMYK,
+            (n, _) => bail!("Unsupported number of components: {n}"),
+        };
+
+        Ok(color_space)
+    }
+}
+
+/// Upsamples a component plane to the full frame resolution.
+///
+/// Samples are treated as sitting at the center of the area they cover, and output samples are
+/// linearly interpolated between their nearest neighbors. For 2x subsampling this weighs the
+/// nearer sample by 3/4 and the farther one by 1/4.
+pub fn upsample(frame: &Frame, component: &FrameComponent, plane: &[u16]) -> Vec<u16> {
+    let line_stride = component.blocks_per_line * frame.block_size;
+
+    if component.horizontal_sampling_factor == frame.max_horizontal_sampling
+        && component.vertical_sampling_factor == frame.max_vertical_sampling
+    {
+        return plane
+            .chunks_exact(line_stride)
+            .take(frame.height)
+            .flat_map(|line| &line[..frame.width])
+            .copied()
+            .collect();
+    }
+
+    let (sample_width, sample_height) = component.sample_dimensions(frame);
+
+    let interpolation_weights = |length: usize, factor: usize, max_factor: usize, limit: usize| {
+        (0..length)
+            .map(|i| {
+                let position = (i as f32 + 0.5) * factor as f32 / max_factor as f32 - 0.5;
+                let position = position.max(0.0);
+
+                let near = (position as usize).min(limit - 1);
+                let far = (near + 1).min(limit - 1);
+
+                (near, far, position - near as f32)
+            })
+            .collect::<Vec<_>>()
+    };
+
+    let columns = interpolation_weights(
+        frame.width,
+        component.horizontal_sampling_factor,
+        frame.max_horizontal_sampling,
+        sample_width,
+    );
+
+    let rows = interpolation_weights(
+        frame.height,
+        component.vertical_sampling_factor,
+        frame.max_vertical_sampling,
+        sample_height,
+    );
+
+    let mut samples = Vec::with_capacity(frame.width * frame.height);
+
+    for &(top, bottom, vertical_weight) in &rows {
+        let top = &plane[top * line_stride..];
+        let bottom = &plane[bottom * line_stride..];
+
+        for &(left, right, horizontal_weight) in &columns {
+            let lerp = |a: u16, b: u16, t: f32| a as f32 + (b as f32 - a as f32) * t;
+
+            let upper = lerp(top[left], top[right], horizontal_weight);
+            let lower = lerp(bottom[left], bottom[right], horizontal_weight);
+
+            samples.push((upper + (lower - upper) * vertical_weight).round() as u16);
+        }
+    }
+
+    samples
+}
+
+/// Converts a JFIF YCbCr sample to RGB, where `max` is the largest sample value at the frame's
+/// precision.
+pub fn ycbcr_to_rgb(y: u16, cb: u16, cr: u16, max: u16) -> [u16; 3] {
+    let center = (max / 2 + 1) as f32;
+
+    let y = y as f32;
+    let cb = cb as f32 - center;
+    let cr = cr as f32 - center;
+
+    let r = y + 1.402 * cr;
+    let g = y - 0.344_136 * cb - 0.714_136 * cr;
+    let b = y + 1.772 * cb;
+
+    [r, g, b].map(|c| c.round().clamp(0.0, max as f32) as u16)
+}
+
+/// Converts a CMYK sample to RGB. `inverted` samples are stored the way Adobe applications write
+/// them, with `max` meaning no ink.
+pub fn cmyk_to_rgb(c: u16, m: u16, y: u16, k: u16, inverted: bool, max: u16) -> [u16; 3] {
+    let [c, m, y, k] = if inverted {
+        [c, m, y, k]
+    } else {
+        [max - c, max - m, max - y, max - k]
+    };
+
+    // Each channel is the amount of light left by its ink, scaled by the light left by black.
+    let mix = |ink: u16| ((ink as u32 * k as u32 + max as u32 / 2) / max as u32) as u16;
+
+    [mix(c), mix(m), mix(y)]
+}
+
+/// Converts a YCCK sample to RGB. The YCbCr components encode the inverted CMY components as if
+/// they were RGB.
+pub fn ycck_to_rgb(y: u16, cb: u16, cr: u16, k: u16, max: u16) -> [u16; 3] {
+    let [r, g, b] = ycbcr_to_rgb(y, cb, cr, max);
+
+    cmyk_to_rgb(max - r, max - g, max - b, k, true, max)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ycbcr_to_rgb() {
+        assert_eq!(ycbcr_to_rgb(0, 128, 128, 255), [0, 0, 0]);
+        assert_eq!(ycbcr_to_rgb(255, 128, 128, 255), [255, 255, 255]);
+        assert_eq!(ycbcr_to_rgb(76, 85, 255, 255), [254, 0, 0]);
+
+        // 12-bit samples are centered on 2048.
+        assert_eq!(ycbcr_to_rgb(4095, 2048, 2048, 4095), [4095, 4095, 4095]);
+        assert_eq!(ycbcr_to_rgb(1224, 1357, 4095, 4095), [4094, 0, 0]);
+    }
+
+    #[test]
+    fn test_cmyk_to_rgb() {
+        // No ink is white, full black is black.
+        assert_eq!(cmyk_to_rgb(0, 0, 0, 0, false, 255), [255, 255, 255]);
+        assert_eq!(cmyk_to_rgb(0, 0, 0, 255, false, 255), [0, 0, 0]);
+        assert_eq!(cmyk_to_rgb(255, 0, 0, 0, false, 255), [0, 255, 255]);
+
+        assert_eq!(cmyk_to_rgb(255, 255, 255, 255, true, 255), [255, 255, 255]);
+        assert_eq!(cmyk_to_rgb(0, 255, 255, 255, true, 255), [0, 255, 255]);
+        assert_eq!(cmyk_to_rgb(255, 255, 255, 128, true, 255), [128, 128, 128]);
+    }
+}
@@ -1,12 +1,27 @@
 use crate::{
+    exif::ExifDecoder,
+    icc::IccDecoder,
+    image::grammar::ColorType,
     impl_read_for_datatype, impl_read_slice,
-    jpeg::grammar::{
-        ApplicationHeader, Component, EncodingProcess, HuffmanTable, Jpeg, Marker, Precision,
-        QuantizationTable, StartOfFrame, StartOfScan, JFIF,
+    jpeg::{
+        arithmetic_decoder::{
+            decode_arithmetic_lossless_scan, decode_arithmetic_progressive_scan,
+            decode_arithmetic_sequential_scan, ConditioningTables,
+        },
+        color_convert::{cmyk_to_rgb, upsample, ycbcr_to_rgb, ycck_to_rgb, ColorSpace},
+        entropy_decoder::{
+            decode_lossless_scan, decode_progressive_scan, decode_sequential_scan, HuffmanTables,
+        },
+        frame::{install_quantization_tables, Frame},
+        grammar::{
+            AdobeHeader, ApplicationHeader, Component, ConditioningTable, EncodingProcess,
+            HuffmanTable, Jpeg, Marker, Precision, QuantizationTable, Scan, StartOfFrame,
+            StartOfScan, JFIF,
+        },
     },
 };
 
-use anyhow::{anyhow, ensure, Result};
+use anyhow::{anyhow, bail, ensure, Result};
 use std::ops::RangeInclusive;
 
 #[derive(Debug)]
@@ -21,68 +36,236 @@ impl<'a> JpegDecoder<'a> {
     }
 
     pub fn decode(&mut self) -> Result<Jpeg> {
-        let _jfif = self.parse_jfif()?;
+        self.decode_scans(None)
+    }
+
+    /// Decodes the image like `decode`, additionally calling `on_scan` after every scan but the
+    /// last with the image reconstructed from the scans decoded so far. Each scan of a
+    /// progressive JPEG refines the whole image, so this allows displaying it before decoding
+    /// has finished.
+    pub fn decode_progressively(
+        &mut self,
+        mut on_scan: impl FnMut(Jpeg) -> Result<()>,
+    ) -> Result<Jpeg> {
+        self.decode_scans(Some(&mut on_scan))
+    }
+
+    fn decode_scans(
+        &mut self,
+        mut on_scan: Option<&mut dyn FnMut(Jpeg) -> Result<()>>,
+    ) -> Result<Jpeg> {
+        let JFIF {
+            application_header,
+            adobe_header,
+            comments,
+            exif,
+            metadata_segments,
+            quantization_tables,
+            start_of_frame,
+            scans,
+        } = self.parse_jfif()?;
+
+        let comments = comments
+            .iter()
+            .map(|comment| String::from_utf8_lossy(comment).into_owned())
+            .collect::<Vec<_>>();
+
+        // Malformed metadata should not prevent displaying the image.
+        let exif = exif.and_then(|exif| ExifDecoder::new(exif).decode().ok());
+        let icc_profile = assemble_icc_profile(&metadata_segments)
+            .and_then(|profile| IccDecoder::new(&profile).decode().ok());
+
+        let sample_precision = start_of_frame.sample_precision;
+        let installed_quantization_tables = install_quantization_tables(&quantization_tables)?;
+
+        let mut frame = Frame::new(&start_of_frame)?;
+        let mut scan_decoder = ScanDecoder::new(&start_of_frame)?;
+
+        let color_space =
+            ColorSpace::infer(&frame, application_header.is_some(), adobe_header.as_ref())?;
+
+        let render = |frame: &Frame| -> Result<Jpeg> {
+            let (color_type, samples) =
+                render_frame(frame, &installed_quantization_tables, color_space)?;
+
+            let pixel_buffer = if sample_precision == 8 {
+                samples.into_iter().map(|sample| sample as u8).collect()
+            } else {
+                samples.into_iter().flat_map(u16::to_be_bytes).collect()
+            };
+
+            Ok(Jpeg {
+                width: frame.width as u32,
+                height: frame.height as u32,
+                color_type,
+                sample_precision,
+                comments: comments.clone(),
+                exif: exif.clone(),
+                icc_profile: icc_profile.clone(),
+                pixel_buffer,
+            })
+        };
+
+        for (i, scan) in scans.iter().enumerate() {
+            scan_decoder.decode(&mut frame, scan)?;
+
+            if let Some(on_scan) = on_scan.as_mut() {
+                if i + 1 < scans.len() {
+                    on_scan(render(&frame)?)?;
+                }
+            }
+        }
+
+        render(&frame)
+    }
+
+    /// Parses the file and decodes the quantized coefficients (or lossless samples) of every
+    /// scan, without reconstructing the image.
+    pub(crate) fn decode_frame(&mut self) -> Result<(JFIF<'a>, Frame)> {
+        let jfif = self.parse_jfif()?;
+
+        let mut frame = Frame::new(&jfif.start_of_frame)?;
+        let mut scan_decoder = ScanDecoder::new(&jfif.start_of_frame)?;
 
-        dbg!(_jfif);
+        for scan in &jfif.scans {
+            scan_decoder.decode(&mut frame, scan)?;
+        }
 
-        todo!();
+        Ok((jfif, frame))
     }
 
-    fn parse_jfif(&mut self) -> Result<JFIF<'a>> {
-        ensure!(self.read_marker()? == 0xFFD8);
+    pub(crate) fn parse_jfif(&mut self) -> Result<JFIF<'a>> {
+        ensure!(
+            self.read_marker()? == 0xFFD8,
+            "Expected start of image marker."
+        );
 
         let mut application_header = None;
+        let mut adobe_header = None;
+        let mut comments = Vec::new();
+        let mut exif = None;
+        let mut metadata_segments = Vec::new();
         let mut quantization_tables = Vec::with_capacity(4);
         let mut huffman_tables = Vec::new();
+        let mut conditioning_tables = Vec::new();
+        let mut restart_interval = 0;
         let mut start_of_frame = None;
-        let mut start_of_scan = None;
-        let mut image_data = None;
+        let mut scans = Vec::new();
 
         loop {
-            match self.read_marker()? {
-                0xFFE0 => {
+            let marker = self.read_marker()?;
+
+            if matches!(marker, 0xFFE0..=0xFFEF | 0xFFFE) {
+                metadata_segments.push((marker, self.peek_segment()?));
+            }
+
+            match marker {
+                0xFFE0 if self.peek_segment_identifier(b"JFIF\0") => {
                     application_header = Some(self.parse_application_header()?);
                 }
+                0xFFEE if self.peek_segment_identifier(b"Adobe") => {
+                    adobe_header = Some(self.parse_adobe_header()?);
+                }
+                0xFFE1 if exif.is_none() => {
+                    exif = self.read_segment()?.strip_prefix(b"Exif\0\0");
+                }
                 0xFFDB => {
-                    quantization_tables.push(self.parse_quantization_table()?);
+                    quantization_tables.extend(self.parse_quantization_tables()?);
                 }
                 0xFFC4 => {
-                    huffman_tables.push(self.parse_huffman_table()?);
+                    huffman_tables.extend(self.parse_huffman_tables()?);
+                }
+                0xFFCC => {
+                    conditioning_tables.extend(self.parse_conditioning_tables()?);
+                }
+                0xFFDD => {
+                    restart_interval = self.parse_restart_interval()?;
+                }
+                0xFFFE => {
+                    comments.push(self.read_segment()?);
                 }
                 0xFFDA => {
-                    ensure!(start_of_scan.is_none() && image_data.is_none());
-                    start_of_scan = Some(self.parse_start_of_scan()?);
-                    image_data = Some(self.parse_image_data()?);
-
-                    break;
+                    ensure!(
+                        start_of_frame.is_some(),
+                        "Expected start of frame before start of scan."
+                    );
+
+                    scans.push(Scan {
+                        huffman_tables: std::mem::take(&mut huffman_tables),
+                        conditioning_tables: std::mem::take(&mut conditioning_tables),
+                        restart_interval,
+                        start_of_scan: self.parse_start_of_scan()?,
+                        image_data: self.parse_image_data()?,
+                    });
                 }
-                start_of_frame_marker
-                    if start_of_frame_marker >> 8 == 0xFF
-                        && (start_of_frame_marker as u8 & 0xF0) == 0xC0 =>
-                {
-                    ensure!(start_of_frame.is_none());
+                0xFFD9 => break,
+                // Restart markers only belong in entropy-coded data, TEM has no segment.
+                0xFFD0..=0xFFD7 | 0xFF01 => {}
+                start_of_frame_marker @ (0xFFC0..=0xFFC3
+                | 0xFFC5..=0xFFC7
+                | 0xFFC9..=0xFFCB
+                | 0xFFCD..=0xFFCF) => {
+                    ensure!(start_of_frame.is_none(), "Encountered multiple frames.");
                     start_of_frame = Some(self.parse_start_of_frame(start_of_frame_marker as u8)?);
                 }
-                foreign => unimplemented!("{:X}", foreign),
+                // Application segments other than JFIF, along with DNL, DHP, EXP and the
+                // reserved JPGn markers, are skipped.
+                0xFFC8 | 0xFFDC | 0xFFDE | 0xFFDF | 0xFFE0..=0xFFEF | 0xFFF0..=0xFFFD => {
+                    self.read_segment()?;
+                }
+                foreign => bail!("Encountered unknown marker: {:X}", foreign),
             };
         }
 
-        ensure!(self.read_marker()? == 0xFFD9);
+        ensure!(!scans.is_empty(), "expected start of scan");
 
         Ok(JFIF {
-            application_header: application_header
-                .ok_or_else(|| anyhow!("expected application header"))?,
+            application_header,
+            adobe_header,
+            comments,
+            exif,
+            metadata_segments,
             quantization_tables,
-            huffman_tables: {
-                ensure!(huffman_tables.len() == 4);
-                huffman_tables
-            },
             start_of_frame: start_of_frame.ok_or_else(|| anyhow!("expected start of frame"))?,
-            start_of_scan: start_of_scan.ok_or_else(|| anyhow!("expected start of scan"))?,
-            image_data: image_data.ok_or_else(|| anyhow!("expected image data"))?,
+            scans,
         })
     }
 
+    /// Reads a marker code, skipping any fill bytes that precede it.
+    fn read_marker(&mut self) -> Result<Marker> {
+        ensure!(self.read_u8()? == 0xFF, "Expected a marker.");
+
+        let mut code = self.read_u8()?;
+        while code == 0xFF {
+            code = self.read_u8()?;
+        }
+
+        Ok(0xFF00 | code as Marker)
+    }
+
+    /// Reads the parameters of a marker segment, excluding its length.
+    fn read_segment(&mut self) -> Result<&'a [u8]> {
+        let length = self.read_u16()? as usize;
+        ensure!(length >= 2, "Invalid segment length: {length}");
+
+        self.read_slice(length - 2)
+    }
+
+    /// The parameters of the marker segment at the cursor, without advancing past it.
+    fn peek_segment(&self) -> Result<&'a [u8]> {
+        let length = u16::from_be_bytes(self.peek_slice(2)?.try_into()?) as usize;
+        ensure!(length >= 2, "Invalid segment length: {length}");
+
+        Ok(&self.peek_slice(length)?[2..])
+    }
+
+    /// Whether the segment at the cursor starts with the null-terminated `identifier`, as is the
+    /// convention for application segments.
+    fn peek_segment_identifier(&self, identifier: &[u8]) -> bool {
+        self.peek_slice(2 + identifier.len())
+            .is_ok_and(|segment| &segment[2..] == identifier)
+    }
+
     fn parse_application_header(&mut self) -> Result<ApplicationHeader> {
         let offset = self.cursor;
         let length = self.read_u16()? as usize;
@@ -96,34 +279,65 @@ impl<'a> JpegDecoder<'a> {
             thumbnail: (self.read_u8()?, self.read_u8()?),
         };
 
-        ensure!(self.cursor == offset + length);
+        // Skip the uncompressed thumbnail, if any.
+        ensure!(self.cursor <= offset + length);
+        self.cursor = offset + length;
 
         Ok(app_header)
     }
 
-    fn parse_quantization_table(&mut self) -> Result<QuantizationTable> {
+    fn parse_adobe_header(&mut self) -> Result<AdobeHeader> {
+        let offset = self.cursor;
+        let length = self.read_u16()? as usize;
+
+        ensure!(self.read_slice(5)? == b"Adobe");
+
+        let adobe_header = AdobeHeader {
+            version: self.read_u16()?,
+            flags: (self.read_u16()?, self.read_u16()?),
+            color_transform: self.read_u8()?.try_into()?,
+        };
+
+        ensure!(self.cursor <= offset + length);
+        self.cursor = offset + length;
+
+        Ok(adobe_header)
+    }
+
+    fn parse_quantization_tables(&mut self) -> Result<Vec<QuantizationTable>> {
         let offset = self.cursor;
         let length = self.read_u16()? as usize;
 
-        let flag = self.read_u8()?;
+        let mut quantization_tables = Vec::new();
+
+        while self.cursor < offset + length {
+            let flag = self.read_u8()?;
+
+            let table_elements = match Precision::from((flag >> 4) == 1) {
+                Precision::Eight => {
+                    self.read_fixed_array::<64, _>(|this| this.read_u8().map(|b| b as u16))?
+                }
+                Precision::Sixteen => self.read_fixed_array::<64, _>(Self::read_u16)?,
+            };
+
+            quantization_tables.push(QuantizationTable {
+                flag,
+                table_elements,
+            });
+        }
+
+        ensure!(self.cursor == offset + length);
 
-        let precision = Precision::from((flag >> 4) == 1);
+        Ok(quantization_tables)
+    }
 
+    fn parse_restart_interval(&mut self) -> Result<u16> {
         ensure!(
-            self.cursor + (precision as usize * QuantizationTable::NUM_ELEMENTS) == offset + length
+            self.read_u16()? == 4,
+            "Invalid restart interval segment length."
         );
 
-        let table_elements = match precision {
-            Precision::Eight => {
-                self.read_fixed_array::<64, _>(|this| this.read_u8().map(|b| b as u16))?
-            }
-            Precision::Sixteen => self.read_fixed_array::<64, _>(Self::read_u16)?,
-        };
-
-        Ok(QuantizationTable {
-            flag,
-            table_elements,
-        })
+        self.read_u16()
     }
 
     fn parse_start_of_frame(&mut self, start_of_frame: u8) -> Result<StartOfFrame> {
@@ -156,24 +370,46 @@ impl<'a> JpegDecoder<'a> {
         })
     }
 
-    fn parse_huffman_table(&mut self) -> Result<HuffmanTable> {
+    fn parse_huffman_tables(&mut self) -> Result<Vec<HuffmanTable>> {
         let offset = self.cursor;
         let length = self.read_u16()? as usize;
 
-        let flag = self.read_u8()?;
-        let code_lengths = self.read_fixed_array::<16, _>(Self::read_u8)?;
-        let num_values = code_lengths.iter().sum::<u8>();
-        let values = self.read_vec(num_values as usize, Self::read_u8)?;
+        let mut huffman_tables = Vec::new();
 
-        let ht = HuffmanTable {
-            flag,
-            code_lengths,
-            values,
-        };
+        while self.cursor < offset + length {
+            let flag = self.read_u8()?;
+            let code_lengths = self.read_fixed_array::<16, _>(Self::read_u8)?;
+            let num_values = code_lengths.iter().map(|&n| n as usize).sum::<usize>();
+            let values = self.read_vec(num_values, Self::read_u8)?;
+
+            huffman_tables.push(HuffmanTable {
+                flag,
+                code_lengths,
+                values,
+            });
+        }
+
+        ensure!(self.cursor == offset + length);
+
+        Ok(huffman_tables)
+    }
+
+    fn parse_conditioning_tables(&mut self) -> Result<Vec<ConditioningTable>> {
+        let offset = self.cursor;
+        let length = self.read_u16()? as usize;
+
+        let mut conditioning_tables = Vec::new();
+
+        while self.cursor < offset + length {
+            conditioning_tables.push(ConditioningTable {
+                flag: self.read_u8()?,
+                value: self.read_u8()?,
+            });
+        }
 
         ensure!(self.cursor == offset + length);
 
-        Ok(ht)
+        Ok(conditioning_tables)
     }
 
     fn parse_start_of_scan(&mut self) -> Result<StartOfScan> {
@@ -196,37 +432,517 @@ impl<'a> JpegDecoder<'a> {
         Ok(start_of_scan)
     }
 
+    /// Reads entropy-coded data up to the next marker. Stuffed zero bytes and restart markers
+    /// are part of the entropy-coded data.
     fn parse_image_data(&mut self) -> Result<&'a [u8]> {
-        let len = self.count_until(|this| Ok(this.peek_slice(2)? != [0xFF, 0xD9]))?;
+        let len = self.data[self.cursor..]
+            .windows(2)
+            .position(|marker| marker[0] == 0xFF && !matches!(marker[1], 0x00 | 0xD0..=0xD7))
+            .ok_or_else(|| anyhow!("Expected a marker after entropy-coded data."))?;
 
         self.read_slice(len)
     }
 
     impl_read_for_datatype!(read_u8, u8);
     impl_read_for_datatype!(read_u16, u16);
-    impl_read_for_datatype!(read_marker, Marker);
     impl_read_slice!();
+}
+
+/// Reassembles an ICC profile from its APP2 segments. Profiles too large for a single segment
+/// are split into numbered chunks, which need not appear in order.
+fn assemble_icc_profile(metadata_segments: &[(Marker, &[u8])]) -> Option<Vec<u8>> {
+    const IDENTIFIER: &[u8] = b"ICC_PROFILE\0";
+
+    // Pairs of (sequence number, chunk), where sequence numbers start at 1.
+    let mut chunks = Vec::new();
+    let mut num_chunks = None;
+
+    for (marker, parameters) in metadata_segments {
+        let Some(chunk) = parameters.strip_prefix(IDENTIFIER) else {
+            continue;
+        };
+
+        if *marker != 0xFFE2 || chunk.len() < 2 {
+            continue;
+        }
+
+        if *num_chunks.get_or_insert(chunk[1]) != chunk[1] {
+            return None;
+        }
+
+        chunks.push((chunk[0], &chunk[2..]));
+    }
+
+    chunks.sort_by_key(|&(sequence_number, _)| sequence_number);
+
+    let num_chunks = num_chunks?;
+    let complete = chunks.len() == num_chunks as usize
+        && chunks
+            .iter()
+            .zip(1..)
+            .all(|(&(sequence_number, _), expected)| sequence_number == expected);
+
+    complete.then(|| {
+        chunks
+            .into_iter()
+            .flat_map(|(_, chunk)| chunk.to_vec())
+            .collect()
+    })
+}
+
+/// Decodes the scans of a frame one after another, keeping track of the entropy coding tables
+/// installed so far.
+struct ScanDecoder {
+    encoding_process: EncodingProcess,
+    huffman_tables: HuffmanTables,
+    conditioning_tables: ConditioningTables,
+}
+
+impl ScanDecoder {
+    fn new(start_of_frame: &StartOfFrame) -> Result<Self> {
+        // Baseline frames are 8-bit, other DCT frames 8 or 12-bit, and lossless frames anywhere
+        // from 2 to 16-bit.
+        let sample_precision = start_of_frame.sample_precision;
+        let supported_precision = match start_of_frame.encoding_process {
+            EncodingProcess::BaselineDCT => sample_precision == 8,
+            EncodingProcess::HuffmanLossless | EncodingProcess::ArithmeticLossless => {
+                (2..=16).contains(&sample_precision)
+            }
+            _ => matches!(sample_precision, 8 | 12),
+        };
+
+        ensure!(
+            supported_precision,
+            "Unsupported sample precision: {sample_precision}"
+        );
+
+        Ok(Self {
+            encoding_process: start_of_frame.encoding_process,
+            huffman_tables: HuffmanTables::default(),
+            conditioning_tables: ConditioningTables::default(),
+        })
+    }
 
-    /// counts the number of bytes until the reader reaches term function
-    fn count_until(&self, term_fn: impl Fn(&Self) -> Result<bool>) -> Result<usize> {
-        let mut len = 0;
+    fn decode(&mut self, frame: &mut Frame, scan: &Scan) -> Result<()> {
+        for table in &scan.huffman_tables {
+            self.huffman_tables.install(table)?;
+        }
 
-        while !term_fn(self)? {
-            len += 1;
+        for table in &scan.conditioning_tables {
+            self.conditioning_tables.install(table)?;
         }
 
-        Ok(len)
+        let (huffman_tables, conditioning_tables) =
+            (&self.huffman_tables, &self.conditioning_tables);
+
+        match self.encoding_process {
+            EncodingProcess::BaselineDCT | EncodingProcess::HuffmanExtendedSequentialDCT => {
+                decode_sequential_scan(frame, scan, huffman_tables)
+            }
+            EncodingProcess::HuffmanProgressiveDCT => {
+                decode_progressive_scan(frame, scan, huffman_tables)
+            }
+            EncodingProcess::HuffmanLossless => decode_lossless_scan(frame, scan, huffman_tables),
+            EncodingProcess::ArithmeticExtendedSequentialDCT => {
+                decode_arithmetic_sequential_scan(frame, scan, conditioning_tables)
+            }
+            EncodingProcess::ArithmeticProgressiveDCT => {
+                decode_arithmetic_progressive_scan(frame, scan, conditioning_tables)
+            }
+            EncodingProcess::ArithmeticLossless => {
+                decode_arithmetic_lossless_scan(frame, scan, conditioning_tables)
+            }
+        }
     }
 }
 
+/// Reconstructs the pixels from the coefficients decoded so far, at the frame's precision.
+fn render_frame(
+    frame: &Frame,
+    quantization_tables: &[Option<&QuantizationTable>; 4],
+    color_space: ColorSpace,
+) -> Result<(ColorType, Vec<u16>)> {
+    let planes = frame.reconstruct(quantization_tables)?;
+    let max = frame.max_sample();
+
+    let planes = frame
+        .components
+        .iter()
+        .zip(&planes)
+        .map(|(component, plane)| upsample(frame, component, plane))
+        .collect::<Vec<_>>();
+
+    let rendered = match (color_space, planes.as_slice()) {
+        (ColorSpace::Grayscale, [luma]) => (ColorType::Grayscale, luma.clone()),
+        (ColorSpace::YCbCr, [y, cb, cr]) => {
+            let pixel_buffer = y
+                .iter()
+                .zip(cb)
+                .zip(cr)
+                .flat_map(|((&y, &cb), &cr)| ycbcr_to_rgb(y, cb, cr, max))
+                .collect();
+
+            (ColorType::RGB, pixel_buffer)
+        }
+        (ColorSpace::RGB, [r, g, b]) => {
+            let pixel_buffer = r
+                .iter()
+                .zip(g)
+                .zip(b)
+                .flat_map(|((&r, &g), &b)| [r, g, b])
+                .collect();
+
+            (ColorType::RGB, pixel_buffer)
+        }
+        (ColorSpace::CMYK | ColorSpace::InvertedCMYK | ColorSpace::YCCK, [c0, c1, c2, k]) => {
+            let pixel_buffer = c0
+                .iter()
+                .zip(c1)
+                .zip(c2)
+                .zip(k)
+                .flat_map(|(((&c0, &c1), &c2), &k)| match color_space {
+                    ColorSpace::YCCK => ycck_to_rgb(c0, c1, c2, k, max),
+                    _ => {
+                        let inverted = color_space == ColorSpace::InvertedCMYK;
+                        cmyk_to_rgb(c0, c1, c2, k, inverted, max)
+                    }
+                })
+                .collect();
+
+            (ColorType::RGB, pixel_buffer)
+        }
+        _ => bail!(
+            "{:?} does not have {} components.",
+            color_space,
+            planes.len()
+        ),
+    };
+
+    Ok(rendered)
+}
+
 #[cfg(test)]
 mod tests {
-    // use super::*;
+    use super::*;
+    use crate::{
+        exif::grammar::{ByteOrder, Orientation},
+        image::grammar::ImageExt,
+        jpeg::grammar::ColorTransform,
+        png::PngDecoder,
+    };
+    use image::ImageReader;
+
+    /// Decodes `path` and compares it against the `image` crate's decoding. Decoders are free to
+    /// choose their own IDCT and upsampling, so samples are only expected to be close. The
+    /// reference's fixed-point color conversion also rounds down, which alone accounts for a
+    /// mean error of up to ~0.9 on full resolution chroma.
+    fn compare_jpeg(path: &str) -> Result<()> {
+        let reference_rgbs = ImageReader::open(path)?.decode()?.to_rgb8().to_vec();
+
+        let content = std::fs::read(path)?;
+        let generated_rgbs = JpegDecoder::new(&content).decode()?.rgb8().to_vec();
+
+        assert_eq!(reference_rgbs.len(), generated_rgbs.len());
+
+        let mean_absolute_error = reference_rgbs
+            .iter()
+            .zip(&generated_rgbs)
+            .map(|(&a, &b)| a.abs_diff(b) as f64)
+            .sum::<f64>()
+            / reference_rgbs.len() as f64;
+
+        assert!(
+            mean_absolute_error < 1.5,
+            "Mean absolute error too large: {mean_absolute_error}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_taxi_zone_map_manhattan() -> Result<()> {
+        compare_jpeg("./tests/taxi_zone_map_manhattan.jpg")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_restart_intervals() -> Result<()> {
+        // An Exif file without a JFIF segment, with a comment, a fill byte before a marker, and
+        // all tables of each kind defined in a single segment. Restart intervals span 7 MCUs,
+        // which do not line up with the image's MCU rows.
+        compare_jpeg("./tests/tower_restart.jpg")?;
+
+        let content = std::fs::read("./tests/tower_restart.jpg")?;
+        let jpeg = JpegDecoder::new(&content).decode()?;
+        assert_eq!(jpeg.comments(), ["restart interval fixture"]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_exif() -> Result<()> {
+        // A little-endian Exif APP1 segment following the JFIF APP0 segment.
+        let content = std::fs::read("./tests/exif_orientation.jpg")?;
+        let jpeg = JpegDecoder::new(&content).decode()?;
+
+        let exif = jpeg
+            .exif()
+            .ok_or_else(|| anyhow!("Expected Exif segment."))?;
+
+        assert_eq!(exif.byte_order(), ByteOrder::LittleEndian);
+        assert_eq!(exif.orientation(), Orientation::Rotate90);
+        assert_eq!(exif.make(), Some("norm"));
+        assert_eq!(exif.model(), Some("Fixture"));
+        assert_eq!(exif.date_time_original(), Some("2024:05:01 12:34:56"));
+
+        let (latitude, longitude) = exif
+            .gps_coordinates()
+            .ok_or_else(|| anyhow!("Expected GPS coordinates."))?;
+
+        assert!((latitude - 40.712_867).abs() < 1e-6);
+        assert!((longitude + 74.006).abs() < 1e-6);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_truncated_exif() {
+        // An APP1 segment too short to hold the Exif identifier it starts with.
+        let content = b"\xFF\xD8\xFF\xE1\x00\x04Exif\x00\x00\xFF\xD9";
+        assert!(JpegDecoder::new(content).decode().is_err());
+    }
+
+    #[test]
+    fn test_decode_oversized() -> Result<()> {
+        // Baseline and lossless frames patched to 65535x65535. The last start of frame marker
+        // is the image's, after any Exif thumbnail's.
+        for (path, marker) in [
+            ("./tests/tower_restart.jpg", 0xC0),
+            ("./tests/lossless_rgb12.jpg", 0xC3),
+        ] {
+            let mut content = std::fs::read(path)?;
+            let start_of_frame = content
+                .windows(2)
+                .rposition(|m| m == [0xFF, marker])
+                .expect("start of frame");
+
+            content[start_of_frame + 5..start_of_frame + 9].fill(0xFF);
+            assert!(JpegDecoder::new(&content).decode().is_err(), "{path}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_progressive() -> Result<()> {
+        compare_jpeg("./tests/tower_progressive.jpg")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_progressively_reports_each_scan() -> Result<()> {
+        let content = std::fs::read("./tests/tower_progressive.jpg")?;
+
+        let mut intermediate_images = Vec::new();
+        let jpeg = JpegDecoder::new(&content).decode_progressively(|image| {
+            intermediate_images.push(image);
+            Ok(())
+        })?;
+
+        // The fixture is coded in 10 scans.
+        assert_eq!(intermediate_images.len(), 9);
+
+        for image in &intermediate_images {
+            assert_eq!(image.dimensions(), jpeg.dimensions());
+        }
+
+        // The final scan refines the last bit of the luma AC coefficients, so the image before
+        // it is close to, but not exactly, the final image.
+        let last = intermediate_images.last().unwrap();
+        assert_ne!(last.pixel_buffer, jpeg.pixel_buffer);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_adobe_color_transforms() -> Result<()> {
+        // Interleaved scans with an Adobe APP14 segment and no JFIF segment. The CMYK fixture is
+        // stored inverted, as Adobe applications write it, and the RGB fixture uses transform 0
+        // to opt out of YCbCr.
+        compare_jpeg("./tests/adobe_cmyk.jpg")?;
+        compare_jpeg("./tests/adobe_ycck.jpg")?;
+        compare_jpeg("./tests/adobe_rgb.jpg")?;
+
+        for (path, color_transform) in [
+            ("./tests/adobe_cmyk.jpg", ColorTransform::None),
+            ("./tests/adobe_ycck.jpg", ColorTransform::YCCK),
+        ] {
+            let content = std::fs::read(path)?;
+            let jpeg = JpegDecoder::new(&content).decode()?;
+            assert_eq!(jpeg.color_type(), ColorType::RGB);
+            assert_eq!(jpeg.dimensions(), (40, 24));
+
+            let jfif = JpegDecoder::new(&content).parse_jfif()?;
+            let adobe_header = jfif
+                .adobe_header
+                .ok_or_else(|| anyhow!("Expected Adobe segment."))?;
+            assert_eq!(adobe_header.color_transform, color_transform);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_extended_12_bit() -> Result<()> {
+        // A 12-bit extended sequential YCbCr image of smooth gradients, with 16-bit quantization
+        // tables. The reference decoder does not support 12-bit images.
+        let content = std::fs::read("./tests/extended_12bit.jpg")?;
+        let jpeg = JpegDecoder::new(&content).decode()?;
+
+        let (width, height) = (32, 16);
+        assert_eq!(jpeg.dimensions(), (width, height));
+        assert_eq!(jpeg.sample_precision(), 12);
+        assert_eq!(jpeg.color_type(), ColorType::RGB);
+
+        let expected = (0..height)
+            .flat_map(|y| {
+                (0..width).flat_map(move |x| {
+                    [
+                        x * 4095 / (width - 1),
+                        y * 4095 / (height - 1),
+                        (x + y) * 4095 / (width + height - 2),
+                    ]
+                })
+            })
+            .collect::<Vec<_>>();
+
+        let samples = jpeg.samples();
+        assert_eq!(samples.len(), expected.len());
+
+        let mean_absolute_error = samples
+            .iter()
+            .zip(&expected)
+            .map(|(&a, &b)| (a as u32).abs_diff(b) as f64)
+            .sum::<f64>()
+            / samples.len() as f64;
+
+        // About a quarter of an 8-bit step.
+        assert!(
+            mean_absolute_error < 4.0,
+            "Mean absolute error too large: {mean_absolute_error}"
+        );
+
+        // 8-bit views scale samples down rather than truncating them.
+        let rgb8 = jpeg.rgb8();
+        assert_eq!(rgb8.len(), samples.len());
+        assert!(rgb8
+            .iter()
+            .zip(&samples)
+            .all(|(&b, &s)| (b as f32 - s as f32 * 255.0 / 4095.0).abs() <= 0.5));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_lossless() -> Result<()> {
+        // 12-bit RGB with the average predictor and a restart interval every line.
+        let content = std::fs::read("./tests/lossless_rgb12.jpg")?;
+        let jpeg = JpegDecoder::new(&content).decode()?;
+
+        assert_eq!(jpeg.dimensions(), (24, 12));
+        assert_eq!(jpeg.sample_precision(), 12);
+        assert_eq!(jpeg.color_type(), ColorType::RGB);
+
+        let expected = (0..12_u32)
+            .flat_map(|y| {
+                (0..24_u32).flat_map(move |x| {
+                    (0..3).map(move |c| {
+                        ((x * x * 37 + y * y * 53 + x * y * 11 + c * 1000) % 4096) as u16
+                    })
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(jpeg.samples(), expected);
+
+        // 16-bit grayscale with the planar predictor and a point transform of 1.
+        let content = std::fs::read("./tests/lossless_gray16.jpg")?;
+        let jpeg = JpegDecoder::new(&content).decode()?;
+
+        assert_eq!(jpeg.dimensions(), (20, 10));
+        assert_eq!(jpeg.sample_precision(), 16);
+        assert_eq!(jpeg.color_type(), ColorType::Grayscale);
+
+        let expected = (0..10_u32)
+            .flat_map(|y| {
+                (0..20_u32).map(move |x| {
+                    (((x * x * 2311 + y * y * 997 + x * y * 131) % 65536) & !1) as u16
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(jpeg.samples(), expected);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_arithmetic() -> Result<()> {
+        // The same quantized coefficients coded with Huffman tables, arithmetic sequential and
+        // arithmetic progressive coding. The arithmetic files define conditioning tables and
+        // restart intervals. The reference decoder does not support arithmetic coding.
+        compare_jpeg("./tests/arithmetic_reference.jpg")?;
+
+        let content = std::fs::read("./tests/arithmetic_reference.jpg")?;
+        let reference = JpegDecoder::new(&content).decode()?;
+        assert_eq!(reference.dimensions(), (40, 24));
+
+        for path in [
+            "./tests/arithmetic_sequential.jpg",
+            "./tests/arithmetic_progressive.jpg",
+        ] {
+            let content = std::fs::read(path)?;
+            let jpeg = JpegDecoder::new(&content).decode()?;
+
+            assert_eq!(jpeg, reference, "{path}");
+        }
+
+        // The 12-bit lossless fixture, coded arithmetically.
+        let content = std::fs::read("./tests/arithmetic_lossless.jpg")?;
+        let jpeg = JpegDecoder::new(&content).decode()?;
+
+        let content = std::fs::read("./tests/lossless_rgb12.jpg")?;
+        let reference = JpegDecoder::new(&content).decode()?;
+
+        assert_eq!(jpeg.samples(), reference.samples());
+
+        Ok(())
+    }
 
     #[test]
-    fn test_decode_taxi_zone_map_manhattan() {
-        let _data = std::fs::read("./tests/taxi_zone_map_manhattan.jpg").unwrap();
+    fn test_decode_icc_profile() -> Result<()> {
+        // The arithmetic reference fixture with a profile split across three APP2 markers, stored
+        // out of sequence.
+        let content = std::fs::read("./tests/display_p3.jpg")?;
+        let jpeg = JpegDecoder::new(&content).decode()?;
+
+        let content = std::fs::read("./tests/display_p3.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+
+        assert_eq!(jpeg.icc_profile(), png.icc_profile());
+        assert_eq!(
+            jpeg.icc_profile().unwrap().description(),
+            Some("Display P3")
+        );
+
+        let content = std::fs::read("./tests/arithmetic_reference.jpg")?;
+        let reference = JpegDecoder::new(&content).decode()?;
+
+        assert_eq!(reference.icc_profile(), None);
+        assert_eq!(jpeg.rgb8(), reference.rgb8());
 
-        // let _ = JpegDecoder::new(&data).decode().unwrap();
+        Ok(())
     }
 }
@@ -0,0 +1,713 @@
+#![allow(clippy::suboptimal_flops)]
+
+use crate::{
+    image::grammar::{ColorType, ImageEncoder, ImageExt},
+    jpeg::{
+        bit_writer::BitWriter,
+        fdct::fdct_8x8,
+        grammar::{HuffmanTable, HuffmanTableClass, Precision, QuantizationTable},
+        huffman::{optimal_huffman_table, HuffmanCodes},
+        idct::ZIGZAG,
+        tables::{scaled_quantization_table, standard_huffman_table},
+    },
+};
+use anyhow::{ensure, Result};
+use std::io::Write;
+
+/// The resolution of the chroma components relative to luma.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChromaSubsampling {
+    /// Full resolution chroma.
+    Chroma444,
+    /// Half the horizontal resolution.
+    Chroma422,
+    /// Half the horizontal and vertical resolution.
+    Chroma420,
+}
+
+impl ChromaSubsampling {
+    /// The horizontal and vertical sampling factors of the luma component. Chroma components are
+    /// always sampled at 1x1.
+    const fn luma_sampling_factors(&self) -> (usize, usize) {
+        match self {
+            Self::Chroma444 => (1, 1),
+            Self::Chroma422 => (2, 1),
+            Self::Chroma420 => (2, 2),
+        }
+    }
+}
+
+/// A component of the image being encoded.
+pub struct EncoderComponent {
+    pub(crate) identifier: u8,
+    pub(crate) horizontal_sampling_factor: usize,
+    pub(crate) vertical_sampling_factor: usize,
+    pub(crate) quantization_table_selector: u8,
+    /// 0 for luma, 1 for chroma. Selects the Huffman tables.
+    pub(crate) table_destination: u8,
+    pub(crate) blocks_per_line: usize,
+    /// Quantized coefficients in zig-zag order, in raster order of blocks. Blocks cover whole
+    /// MCUs.
+    pub(crate) blocks: Vec<[i16; 64]>,
+}
+
+/// A Huffman coded value followed by `extra_length` additional bits.
+struct Symbol {
+    class: HuffmanTableClass,
+    table_destination: u8,
+    value: u8,
+    extra_bits: u16,
+    extra_length: u8,
+}
+
+/// Encodes images as baseline JFIF files.
+pub struct JpegEncoder<W: Write> {
+    writer: W,
+    quality: u8,
+    chroma_subsampling: ChromaSubsampling,
+    optimize_huffman_tables: bool,
+}
+
+impl<W: Write> JpegEncoder<W> {
+    /// Creates an encoder with quality 75, 4:2:0 chroma subsampling and the Huffman tables of
+    /// K.3.
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            quality: 75,
+            chroma_subsampling: ChromaSubsampling::Chroma420,
+            optimize_huffman_tables: false,
+        }
+    }
+
+    /// Sets the quality, from 1 to 100, that the quantization tables of K.1 are scaled by.
+    pub const fn quality(mut self, quality: u8) -> Self {
+        self.quality = quality;
+        self
+    }
+
+    pub const fn chroma_subsampling(mut self, chroma_subsampling: ChromaSubsampling) -> Self {
+        self.chroma_subsampling = chroma_subsampling;
+        self
+    }
+
+    /// Whether to build Huffman tables from the image's own statistics instead of using the
+    /// tables of K.3. This costs a second pass over the coefficients and usually saves a few
+    /// percent of the file size.
+    pub const fn optimize_huffman_tables(mut self, optimize_huffman_tables: bool) -> Self {
+        self.optimize_huffman_tables = optimize_huffman_tables;
+        self
+    }
+
+    /// Encodes `image`. Grayscale images are written with a single component and any alpha
+    /// channel is discarded.
+    pub fn encode(&mut self, image: &dyn ImageExt) -> Result<()> {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+
+        ensure!(
+            width > 0 && height > 0 && width <= u16::MAX as usize && height <= u16::MAX as usize,
+            "JPEG dimensions must be between 1 and 65535, got {width}x{height}."
+        );
+
+        let grayscale = matches!(
+            image.color_type(),
+            ColorType::Grayscale | ColorType::GrayscaleAlpha
+        );
+
+        let quantization_tables = if grayscale {
+            vec![scaled_quantization_table(0, self.quality)]
+        } else {
+            vec![
+                scaled_quantization_table(0, self.quality),
+                scaled_quantization_table(1, self.quality),
+            ]
+        };
+
+        let components = self.transform_components(image, grayscale, &quantization_tables);
+
+        self.write_marker(0xD8)?;
+        self.write_application_header()?;
+
+        if let Some(profile) = image.icc_profile() {
+            self.write_icc_profile(profile.data())?;
+        }
+
+        self.write_frame(&quantization_tables, &components, 8, width, height)?;
+        self.write_marker(0xD9)?;
+
+        Ok(())
+    }
+
+    /// Writes the tables, frame header and a single interleaved scan of already quantized
+    /// `components`. 8-bit frames whose quantization tables all have 8-bit precision are written
+    /// as baseline frames, others as extended sequential frames.
+    pub(crate) fn write_frame(
+        &mut self,
+        quantization_tables: &[QuantizationTable],
+        components: &[EncoderComponent],
+        precision: u8,
+        width: usize,
+        height: usize,
+    ) -> Result<()> {
+        let num_destinations = components
+            .iter()
+            .map(|component| component.table_destination as usize + 1)
+            .max()
+            .unwrap_or(1);
+
+        let symbols = encode_symbols(components, width, height);
+
+        let huffman_tables = if self.optimize_huffman_tables {
+            optimal_huffman_tables(&symbols, num_destinations)
+        } else {
+            (0..num_destinations as u8)
+                .flat_map(|destination| {
+                    [HuffmanTableClass::DC, HuffmanTableClass::AC]
+                        .map(|class| standard_huffman_table(class, destination))
+                })
+                .collect()
+        };
+
+        let entropy_coded_data = write_symbols(&symbols, &huffman_tables)?;
+
+        let baseline = precision == 8
+            && quantization_tables
+                .iter()
+                .all(|table| matches!(table.precision(), Precision::Eight));
+
+        self.write_quantization_tables(quantization_tables)?;
+        self.write_start_of_frame(
+            if baseline { 0xC0 } else { 0xC1 },
+            precision,
+            components,
+            width,
+            height,
+        )?;
+        self.write_huffman_tables(&huffman_tables)?;
+        self.write_start_of_scan(components)?;
+        self.writer.write_all(&entropy_coded_data)?;
+
+        Ok(())
+    }
+
+    /// Converts the image to YCbCr, subsamples chroma and transforms and quantizes every block.
+    fn transform_components(
+        &self,
+        image: &dyn ImageExt,
+        grayscale: bool,
+        quantization_tables: &[QuantizationTable],
+    ) -> Vec<EncoderComponent> {
+        let (width, height) = (image.width() as usize, image.height() as usize);
+        let rgb = image.rgb8();
+
+        let (max_horizontal_sampling, max_vertical_sampling) = if grayscale {
+            (1, 1)
+        } else {
+            self.chroma_subsampling.luma_sampling_factors()
+        };
+
+        let mcus_per_line = width.div_ceil(8 * max_horizontal_sampling);
+        let mcus_per_column = height.div_ceil(8 * max_vertical_sampling);
+
+        // Planes are padded to whole MCUs by repeating the last column and line.
+        let padded_width = mcus_per_line * 8 * max_horizontal_sampling;
+        let padded_height = mcus_per_column * 8 * max_vertical_sampling;
+
+        let num_components = if grayscale { 1 } else { 3 };
+        let mut planes = vec![vec![0.0_f32; padded_width * padded_height]; num_components];
+
+        for y in 0..padded_height {
+            for x in 0..padded_width {
+                let offset = (y.min(height - 1) * width + x.min(width - 1)) * 3;
+                let [r, g, b] = [rgb[offset], rgb[offset + 1], rgb[offset + 2]].map(|c| c as f32);
+
+                let i = y * padded_width + x;
+
+                if grayscale {
+                    planes[0][i] = r;
+                } else {
+                    planes[0][i] = 0.299 * r + 0.587 * g + 0.114 * b;
+                    planes[1][i] = -0.168_736 * r - 0.331_264 * g + 0.5 * b + 128.0;
+                    planes[2][i] = 0.5 * r - 0.418_688 * g - 0.081_312 * b + 128.0;
+                }
+            }
+        }
+
+        planes
+            .iter()
+            .enumerate()
+            .map(|(i, plane)| {
+                let (horizontal_sampling_factor, vertical_sampling_factor) = if i == 0 {
+                    (max_horizontal_sampling, max_vertical_sampling)
+                } else {
+                    (1, 1)
+                };
+
+                let table_destination = (i > 0) as u8;
+
+                // Average each area of the full resolution plane covered by a sample.
+                let (step_x, step_y) = (
+                    max_horizontal_sampling / horizontal_sampling_factor,
+                    max_vertical_sampling / vertical_sampling_factor,
+                );
+
+                let plane_width = padded_width / step_x;
+                let plane_height = padded_height / step_y;
+
+                let sample = |x: usize, y: usize| {
+                    let mut sum = 0.0;
+
+                    for dy in 0..step_y {
+                        for dx in 0..step_x {
+                            sum += plane[(y * step_y + dy) * padded_width + x * step_x + dx];
+                        }
+                    }
+
+                    sum / (step_x * step_y) as f32
+                };
+
+                let blocks_per_line = plane_width / 8;
+                let blocks_per_column = plane_height / 8;
+
+                let table = &quantization_tables[table_destination as usize].table_elements;
+
+                let blocks = (0..blocks_per_column)
+                    .flat_map(|block_y| (0..blocks_per_line).map(move |block_x| (block_x, block_y)))
+                    .map(|(block_x, block_y)| {
+                        let samples = std::array::from_fn(|i| {
+                            sample(block_x * 8 + i % 8, block_y * 8 + i / 8) - 128.0
+                        });
+
+                        let coefficients = fdct_8x8(&samples);
+
+                        let mut block = [0_i16; 64];
+                        for (k, &natural) in ZIGZAG.iter().enumerate() {
+                            let quantized = (coefficients[natural] / table[k] as f32).round();
+                            block[k] = quantized.clamp(-1023.0, 1023.0) as i16;
+                        }
+
+                        block
+                    })
+                    .collect();
+
+                EncoderComponent {
+                    identifier: i as u8 + 1,
+                    horizontal_sampling_factor,
+                    vertical_sampling_factor,
+                    quantization_table_selector: table_destination,
+                    table_destination,
+                    blocks_per_line,
+                    blocks,
+                }
+            })
+            .collect()
+    }
+
+    pub(crate) fn write_marker(&mut self, marker: u8) -> Result<()> {
+        self.writer.write_all(&[0xFF, marker])?;
+
+        Ok(())
+    }
+
+    pub(crate) fn write_segment(&mut self, marker: u8, parameters: &[u8]) -> Result<()> {
+        self.write_marker(marker)?;
+        self.writer
+            .write_all(&(parameters.len() as u16 + 2).to_be_bytes())?;
+        self.writer.write_all(parameters)?;
+
+        Ok(())
+    }
+
+    fn write_application_header(&mut self) -> Result<()> {
+        let mut parameters = b"JFIF\0".to_vec();
+        // Version 1.02, no units, a 1:1 pixel aspect ratio and no thumbnail.
+        parameters.extend_from_slice(&[1, 2, 0, 0, 1, 0, 1, 0, 0]);
+
+        self.write_segment(0xE0, &parameters)
+    }
+
+    /// Embeds an ICC profile in APP2 segments, split into as many numbered chunks as needed.
+    fn write_icc_profile(&mut self, profile: &[u8]) -> Result<()> {
+        const IDENTIFIER: &[u8] = b"ICC_PROFILE\0";
+        const MAX_CHUNK_LENGTH: usize = u16::MAX as usize - 2 - IDENTIFIER.len() - 2;
+
+        let chunks = profile.chunks(MAX_CHUNK_LENGTH).collect::<Vec<_>>();
+        ensure!(
+            chunks.len() <= u8::MAX as usize,
+            "ICC profile is too large to embed."
+        );
+
+        for (i, chunk) in chunks.iter().enumerate() {
+            let mut parameters = IDENTIFIER.to_vec();
+            parameters.extend_from_slice(&[i as u8 + 1, chunks.len() as u8]);
+            parameters.extend_from_slice(chunk);
+
+            self.write_segment(0xE2, &parameters)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_quantization_tables(&mut self, tables: &[QuantizationTable]) -> Result<()> {
+        let mut parameters = Vec::new();
+
+        for table in tables {
+            parameters.push(table.flag);
+
+            for &q in &table.table_elements {
+                match table.precision() {
+                    Precision::Eight => parameters.push(q as u8),
+                    Precision::Sixteen => parameters.extend_from_slice(&q.to_be_bytes()),
+                }
+            }
+        }
+
+        self.write_segment(0xDB, &parameters)
+    }
+
+    fn write_start_of_frame(
+        &mut self,
+        marker: u8,
+        precision: u8,
+        components: &[EncoderComponent],
+        width: usize,
+        height: usize,
+    ) -> Result<()> {
+        let mut parameters = vec![precision];
+        parameters.extend_from_slice(&(height as u16).to_be_bytes());
+        parameters.extend_from_slice(&(width as u16).to_be_bytes());
+        parameters.push(components.len() as u8);
+
+        for component in components {
+            parameters.extend_from_slice(&[
+                component.identifier,
+                (component.horizontal_sampling_factor << 4 | component.vertical_sampling_factor)
+                    as u8,
+                component.quantization_table_selector,
+            ]);
+        }
+
+        self.write_segment(marker, &parameters)
+    }
+
+    fn write_huffman_tables(&mut self, tables: &[HuffmanTable]) -> Result<()> {
+        let mut parameters = Vec::new();
+
+        for table in tables {
+            parameters.push(table.flag);
+            parameters.extend_from_slice(&table.code_lengths);
+            parameters.extend_from_slice(&table.values);
+        }
+
+        self.write_segment(0xC4, &parameters)
+    }
+
+    fn write_start_of_scan(&mut self, components: &[EncoderComponent]) -> Result<()> {
+        let mut parameters = vec![components.len() as u8];
+
+        for component in components {
+            let destination = component.table_destination;
+            parameters.extend_from_slice(&[component.identifier, destination << 4 | destination]);
+        }
+
+        // Sequential scans cover the full spectrum without successive approximation.
+        parameters.extend_from_slice(&[0, 63, 0]);
+
+        self.write_segment(0xDA, &parameters)
+    }
+}
+
+impl<W: Write> ImageEncoder for JpegEncoder<W> {
+    fn write_image(&mut self, image: &dyn ImageExt) -> Result<()> {
+        self.encode(image)
+    }
+}
+
+/// Returns the magnitude category of `value` and its additional bits (see F.1.2.1).
+const fn categorize(value: i16) -> (u8, u16) {
+    if value == 0 {
+        return (0, 0);
+    }
+
+    let category = 16 - value.unsigned_abs().leading_zeros() as u8;
+
+    // Negative values are coded as the one's complement of their magnitude.
+    let extra_bits = if value < 0 {
+        (value - 1) as u16
+    } else {
+        value as u16
+    };
+
+    (category, extra_bits & ((1 << category) - 1))
+}
+
+/// Huffman codes the blocks of a single interleaved scan into symbols, in MCU order.
+fn encode_symbols(components: &[EncoderComponent], width: usize, height: usize) -> Vec<Symbol> {
+    let max_horizontal_sampling = components
+        .iter()
+        .map(|component| component.horizontal_sampling_factor)
+        .max()
+        .unwrap_or(1);
+    let max_vertical_sampling = components
+        .iter()
+        .map(|component| component.vertical_sampling_factor)
+        .max()
+        .unwrap_or(1);
+
+    let mcus_per_line = width.div_ceil(8 * max_horizontal_sampling);
+    let mcus_per_column = height.div_ceil(8 * max_vertical_sampling);
+
+    let mut symbols = Vec::new();
+    let mut dc_predictors = vec![0_i16; components.len()];
+
+    for mcu_y in 0..mcus_per_column {
+        for mcu_x in 0..mcus_per_line {
+            for (component, dc_predictor) in components.iter().zip(&mut dc_predictors) {
+                let table_destination = component.table_destination;
+
+                for v in 0..component.vertical_sampling_factor {
+                    for h in 0..component.horizontal_sampling_factor {
+                        let block_x = mcu_x * component.horizontal_sampling_factor + h;
+                        let block_y = mcu_y * component.vertical_sampling_factor + v;
+                        let block =
+                            &component.blocks[block_y * component.blocks_per_line + block_x];
+
+                        let mut push = |class, value, (extra_length, extra_bits)| {
+                            symbols.push(Symbol {
+                                class,
+                                table_destination,
+                                value,
+                                extra_bits,
+                                extra_length,
+                            });
+                        };
+
+                        let difference = block[0] - *dc_predictor;
+                        *dc_predictor = block[0];
+
+                        let (category, extra_bits) = categorize(difference);
+                        push(HuffmanTableClass::DC, category, (category, extra_bits));
+
+                        let mut run = 0;
+                        for &coefficient in &block[1..] {
+                            if coefficient == 0 {
+                                run += 1;
+                                continue;
+                            }
+
+                            while run > 15 {
+                                // ZRL, a run of 16 zeros.
+                                push(HuffmanTableClass::AC, 0xF0, (0, 0));
+                                run -= 16;
+                            }
+
+                            let (category, extra_bits) = categorize(coefficient);
+                            push(
+                                HuffmanTableClass::AC,
+                                run << 4 | category,
+                                (category, extra_bits),
+                            );
+
+                            run = 0;
+                        }
+
+                        if run > 0 {
+                            // EOB, the remaining coefficients are zero.
+                            push(HuffmanTableClass::AC, 0x00, (0, 0));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    symbols
+}
+
+/// Builds DC and AC tables for every table destination from the frequencies of `symbols`.
+fn optimal_huffman_tables(symbols: &[Symbol], num_destinations: usize) -> Vec<HuffmanTable> {
+    let mut frequencies = vec![[0_u32; 256]; num_destinations * 2];
+
+    for symbol in symbols {
+        let index = symbol.table_destination as usize * 2
+            + (symbol.class == HuffmanTableClass::AC) as usize;
+        frequencies[index][symbol.value as usize] += 1;
+    }
+
+    frequencies
+        .iter()
+        .enumerate()
+        .map(|(index, frequencies)| {
+            let class = (index % 2) as u8;
+            let destination = (index / 2) as u8;
+
+            optimal_huffman_table(class << 4 | destination, frequencies)
+        })
+        .collect()
+}
+
+fn write_symbols(symbols: &[Symbol], tables: &[HuffmanTable]) -> Result<Vec<u8>> {
+    let mut codes = [[None, None], [None, None]];
+
+    for table in tables {
+        let class = (table.table_class() == HuffmanTableClass::AC) as usize;
+        codes[table.table_identifier() as usize][class] = Some(HuffmanCodes::new(table)?);
+    }
+
+    let mut writer = BitWriter::default();
+
+    for symbol in symbols {
+        let class = (symbol.class == HuffmanTableClass::AC) as usize;
+        let codes = codes[symbol.table_destination as usize][class]
+            .as_ref()
+            .ok_or_else(|| anyhow::anyhow!("Missing Huffman table for symbol."))?;
+
+        let (code, length) = codes.code(symbol.value)?;
+        writer.write_bits(code, length);
+        writer.write_bits(symbol.extra_bits, symbol.extra_length);
+    }
+
+    Ok(writer.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{jpeg::JpegDecoder, png::PngDecoder};
+    use image::ImageReader;
+    use std::io::Cursor;
+
+    fn mean_absolute_error(a: &[u8], b: &[u8]) -> f64 {
+        assert_eq!(a.len(), b.len());
+
+        a.iter()
+            .zip(b)
+            .map(|(&a, &b)| a.abs_diff(b) as f64)
+            .sum::<f64>()
+            / a.len() as f64
+    }
+
+    #[test]
+    fn test_categorize() {
+        assert_eq!(categorize(0), (0, 0));
+        assert_eq!(categorize(1), (1, 0b1));
+        assert_eq!(categorize(-1), (1, 0b0));
+        assert_eq!(categorize(5), (3, 0b101));
+        assert_eq!(categorize(-5), (3, 0b010));
+        assert_eq!(categorize(-1023), (10, 0));
+    }
+
+    #[test]
+    fn test_encode_round_trip() -> Result<()> {
+        let data = std::fs::read("./tests/obama.png")?;
+        let png = PngDecoder::new(&data).decode()?;
+        let source = png.rgb8();
+
+        for chroma_subsampling in [
+            ChromaSubsampling::Chroma444,
+            ChromaSubsampling::Chroma422,
+            ChromaSubsampling::Chroma420,
+        ] {
+            let mut encoded = Vec::new();
+            JpegEncoder::new(&mut encoded)
+                .quality(90)
+                .chroma_subsampling(chroma_subsampling)
+                .encode(&png)?;
+
+            let jpeg = JpegDecoder::new(&encoded).decode()?;
+            assert_eq!(jpeg.dimensions(), png.dimensions());
+
+            let error = mean_absolute_error(&source, &jpeg.rgb8());
+            assert!(error < 3.0, "{chroma_subsampling:?}: MAE {error}");
+
+            // The reference decoder agrees on the encoded file.
+            let reference = ImageReader::new(Cursor::new(&encoded))
+                .with_guessed_format()?
+                .decode()?
+                .to_rgb8();
+
+            let error = mean_absolute_error(&reference, &jpeg.rgb8());
+            assert!(error < 1.5, "{chroma_subsampling:?}: MAE {error}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_quality_trades_size_for_error() -> Result<()> {
+        let data = std::fs::read("./tests/obama.png")?;
+        let png = PngDecoder::new(&data).decode()?;
+
+        let mut low = Vec::new();
+        JpegEncoder::new(&mut low).quality(20).encode(&png)?;
+
+        let mut high = Vec::new();
+        JpegEncoder::new(&mut high).quality(95).encode(&png)?;
+
+        assert!(low.len() < high.len());
+
+        let low_error = mean_absolute_error(&png.rgb8(), &JpegDecoder::new(&low).decode()?.rgb8());
+        let high_error =
+            mean_absolute_error(&png.rgb8(), &JpegDecoder::new(&high).decode()?.rgb8());
+
+        assert!(high_error < low_error);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_optimized_huffman_tables() -> Result<()> {
+        let data = std::fs::read("./tests/obama.png")?;
+        let png = PngDecoder::new(&data).decode()?;
+
+        let mut standard = Vec::new();
+        JpegEncoder::new(&mut standard).encode(&png)?;
+
+        let mut optimized = Vec::new();
+        JpegEncoder::new(&mut optimized)
+            .optimize_huffman_tables(true)
+            .encode(&png)?;
+
+        assert!(optimized.len() < standard.len());
+
+        // Only the entropy coding differs.
+        assert_eq!(
+            JpegDecoder::new(&standard).decode()?.rgb8(),
+            JpegDecoder::new(&optimized).decode()?.rgb8()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_grayscale() -> Result<()> {
+        let data = std::fs::read("./test_suite/basn0g08.png")?;
+        let png = PngDecoder::new(&data).decode()?;
+
+        let mut encoded = Vec::new();
+        JpegEncoder::new(&mut encoded).quality(100).encode(&png)?;
+
+        let jpeg = JpegDecoder::new(&encoded).decode()?;
+        assert_eq!(jpeg.color_type(), ColorType::Grayscale);
+
+        let error = mean_absolute_error(&png.rgb8(), &jpeg.rgb8());
+        assert!(error < 1.0, "MAE {error}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_preserves_icc_profile() -> Result<()> {
+        let data = std::fs::read("./tests/display_p3.png")?;
+        let png = PngDecoder::new(&data).decode()?;
+
+        let mut encoded = Vec::new();
+        JpegEncoder::new(&mut encoded).encode(&png)?;
+
+        let jpeg = JpegDecoder::new(&encoded).decode()?;
+        assert!(jpeg.icc_profile().is_some());
+        assert_eq!(jpeg.icc_profile(), png.icc_profile());
+
+        Ok(())
+    }
+}
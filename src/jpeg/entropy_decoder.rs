@@ -0,0 +1,438 @@
+use crate::jpeg::{
+    bit_reader::BitReader,
+    frame::Frame,
+    grammar::{HuffmanTable, HuffmanTableClass, Scan},
+    huffman::HuffmanLookup,
+    idct::ZIGZAG,
+};
+use anyhow::{anyhow, bail, ensure, Result};
+
+/// The Huffman tables currently installed in each of the four DC and AC destinations.
+#[derive(Debug, Default)]
+pub struct HuffmanTables {
+    dc: [Option<HuffmanLookup>; 4],
+    ac: [Option<HuffmanLookup>; 4],
+}
+
+impl HuffmanTables {
+    /// Installs `table`, replacing any table previously defined at the same destination.
+    pub(crate) fn install(&mut self, table: &HuffmanTable) -> Result<()> {
+        let destination = table.table_identifier() as usize;
+        ensure!(
+            destination < 4,
+            "Invalid Huffman table destination: {destination}"
+        );
+
+        let lookup = HuffmanLookup::new(table)?;
+
+        match table.table_class() {
+            HuffmanTableClass::DC => self.dc[destination] = Some(lookup),
+            HuffmanTableClass::AC => self.ac[destination] = Some(lookup),
+        }
+
+        Ok(())
+    }
+
+    fn dc(&self, selector: u8) -> Result<&HuffmanLookup> {
+        self.dc
+            .get(selector as usize)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| anyhow!("Missing DC Huffman table {selector}"))
+    }
+
+    fn ac(&self, selector: u8) -> Result<&HuffmanLookup> {
+        self.ac
+            .get(selector as usize)
+            .and_then(Option::as_ref)
+            .ok_or_else(|| anyhow!("Missing AC Huffman table {selector}"))
+    }
+}
+
+/// Decodes a sequential (baseline or extended) scan into the frame's coefficients.
+pub fn decode_sequential_scan(
+    frame: &mut Frame,
+    scan: &Scan,
+    tables: &HuffmanTables,
+) -> Result<()> {
+    let start_of_scan = &scan.start_of_scan;
+    let component_indices = frame.scan_component_indices(start_of_scan)?;
+
+    let scan_tables = start_of_scan
+        .components
+        .iter()
+        .map(|&(_, selectors)| Ok((tables.dc(selectors >> 4)?, tables.ac(selectors & 0b1111)?)))
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut reader = BitReader::new(scan.image_data);
+    let mut dc_predictors = vec![0; component_indices.len()];
+
+    frame.for_each_block(
+        &component_indices,
+        scan.restart_interval,
+        |block, component| {
+            if block.restart {
+                reader.restart()?;
+                dc_predictors.fill(0);
+            }
+
+            let (dc_table, ac_table) = scan_tables[block.position];
+            let coefficients = &mut component.coefficients[block.index];
+
+            let magnitude = dc_table.decode(&mut reader)?;
+            ensure!(
+                magnitude <= 16,
+                "Invalid DC magnitude category: {magnitude}"
+            );
+
+            dc_predictors[block.position] += reader.receive_extend(magnitude);
+            coefficients[0] = dc_predictors[block.position] as i16;
+
+            let mut k = 1;
+            while k < 64 {
+                let rs = ac_table.decode(&mut reader)?;
+                let (run, size) = (rs >> 4, rs & 0b1111);
+
+                if size == 0 {
+                    if run == 15 {
+                        // ZRL, a run of 16 zeros.
+                        k += 16;
+                        continue;
+                    }
+
+                    // EOB, the remaining coefficients are zero.
+                    break;
+                }
+
+                k += run as usize;
+                ensure!(k < 64, "AC coefficient index out of bounds.");
+
+                coefficients[ZIGZAG[k]] = reader.receive_extend(size) as i16;
+                k += 1;
+            }
+
+            Ok(())
+        },
+    )
+}
+
+/// Decodes a progressive scan, which codes either the DC coefficients or a band of AC
+/// coefficients, in a first pass or as a refinement by one bit (see G.1.2).
+pub fn decode_progressive_scan(
+    frame: &mut Frame,
+    scan: &Scan,
+    tables: &HuffmanTables,
+) -> Result<()> {
+    let start_of_scan = &scan.start_of_scan;
+    let component_indices = frame.scan_component_indices(start_of_scan)?;
+
+    let spectral_start = *start_of_scan.spectral_select.start() as usize;
+    let spectral_end = *start_of_scan.spectral_select.end() as usize;
+    let high = start_of_scan.successive_approximation_high();
+    let low = start_of_scan.successive_approximation_low();
+
+    ensure!(
+        spectral_start <= spectral_end && spectral_end < 64,
+        "Invalid spectral selection: {:?}",
+        start_of_scan.spectral_select
+    );
+
+    ensure!(
+        (spectral_start == 0) == (spectral_end == 0),
+        "A progressive scan codes either DC or AC coefficients, not both."
+    );
+
+    ensure!(
+        spectral_start == 0 || component_indices.len() == 1,
+        "AC scans must be non-interleaved."
+    );
+
+    ensure!(
+        low <= 13 && (high == 0 || high == low + 1),
+        "Invalid successive approximation: {:#X}",
+        start_of_scan.approximation
+    );
+
+    let mut reader = BitReader::new(scan.image_data);
+
+    if spectral_start == 0 {
+        if high > 0 {
+            return frame.for_each_block(
+                &component_indices,
+                scan.restart_interval,
+                |block, component| {
+                    if block.restart {
+                        reader.restart()?;
+                    }
+
+                    if reader.read_bits(1) == 1 {
+                        component.coefficients[block.index][0] |= 1 << low;
+                    }
+
+                    Ok(())
+                },
+            );
+        }
+
+        let scan_tables = start_of_scan
+            .components
+            .iter()
+            .map(|&(_, selectors)| tables.dc(selectors >> 4))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut dc_predictors = vec![0; component_indices.len()];
+
+        return frame.for_each_block(
+            &component_indices,
+            scan.restart_interval,
+            |block, component| {
+                if block.restart {
+                    reader.restart()?;
+                    dc_predictors.fill(0);
+                }
+
+                let magnitude = scan_tables[block.position].decode(&mut reader)?;
+                ensure!(
+                    magnitude <= 16,
+                    "Invalid DC magnitude category: {magnitude}"
+                );
+
+                dc_predictors[block.position] += reader.receive_extend(magnitude);
+                component.coefficients[block.index][0] =
+                    (dc_predictors[block.position] << low) as i16;
+
+                Ok(())
+            },
+        );
+    }
+
+    let ac_table = tables.ac(start_of_scan.components[0].1 & 0b1111)?;
+
+    // The number of blocks left in the current run of end-of-bands.
+    let mut eob_run = 0_u32;
+
+    if high == 0 {
+        return frame.for_each_block(
+            &component_indices,
+            scan.restart_interval,
+            |block, component| {
+                if block.restart {
+                    reader.restart()?;
+                    eob_run = 0;
+                }
+
+                if eob_run > 0 {
+                    eob_run -= 1;
+                    return Ok(());
+                }
+
+                let coefficients = &mut component.coefficients[block.index];
+
+                let mut k = spectral_start;
+                while k <= spectral_end {
+                    let rs = ac_table.decode(&mut reader)?;
+                    let (run, size) = (rs >> 4, rs & 0b1111);
+
+                    if size == 0 {
+                        if run < 15 {
+                            // EOBn, this block and the following 2^n - 1 + bits blocks end here.
+                            eob_run = (1 << run) - 1 + reader.read_bits(run) as u32;
+                            break;
+                        }
+
+                        k += 16;
+                        continue;
+                    }
+
+                    k += run as usize;
+                    ensure!(k <= spectral_end, "AC coefficient index out of bounds.");
+
+                    coefficients[ZIGZAG[k]] = (reader.receive_extend(size) << low) as i16;
+                    k += 1;
+                }
+
+                Ok(())
+            },
+        );
+    }
+
+    let positive = 1_i16 << low;
+    let negative = -1_i16 << low;
+
+    // Adds a correction bit to a coefficient that was already nonzero after previous scans.
+    let refine = |reader: &mut BitReader, coefficient: &mut i16| {
+        if reader.read_bits(1) == 1 && *coefficient & positive == 0 {
+            *coefficient += if *coefficient >= 0 {
+                positive
+            } else {
+                negative
+            };
+        }
+    };
+
+    frame.for_each_block(
+        &component_indices,
+        scan.restart_interval,
+        |block, component| {
+            if block.restart {
+                reader.restart()?;
+                eob_run = 0;
+            }
+
+            let coefficients = &mut component.coefficients[block.index];
+            let mut k = spectral_start;
+
+            if eob_run == 0 {
+                while k <= spectral_end {
+                    let rs = ac_table.decode(&mut reader)?;
+                    let (mut run, size) = (rs >> 4, rs & 0b1111);
+
+                    let value = match size {
+                        0 if run < 15 => {
+                            eob_run = (1 << run) + reader.read_bits(run) as u32;
+                            break;
+                        }
+                        // ZRL, skip 16 coefficients that are zero so far.
+                        0 => 0,
+                        1 if reader.read_bits(1) == 1 => positive,
+                        1 => negative,
+                        _ => bail!("Refinement scans only code coefficients of magnitude 1."),
+                    };
+
+                    // Skip `run` coefficients that are zero so far, refining the nonzero ones
+                    // along the way, then place the new coefficient.
+                    while k <= spectral_end {
+                        let coefficient = &mut coefficients[ZIGZAG[k]];
+                        k += 1;
+
+                        if *coefficient != 0 {
+                            refine(&mut reader, coefficient);
+                        } else if run == 0 {
+                            *coefficient = value;
+                            break;
+                        } else {
+                            run -= 1;
+                        }
+                    }
+                }
+            }
+
+            if eob_run > 0 {
+                // The band of this block has ended, but nonzero coefficients still get their
+                // correction bits.
+                for &natural in &ZIGZAG[k..=spectral_end] {
+                    if coefficients[natural] != 0 {
+                        refine(&mut reader, &mut coefficients[natural]);
+                    }
+                }
+
+                eob_run -= 1;
+            }
+
+            Ok(())
+        },
+    )
+}
+
+/// Decodes a lossless scan into the frame's samples. Each sample is predicted from its
+/// neighbors, and only the difference to the prediction is coded (see H.1.2).
+pub fn decode_lossless_scan(frame: &mut Frame, scan: &Scan, tables: &HuffmanTables) -> Result<()> {
+    let start_of_scan = &scan.start_of_scan;
+    let component_indices = frame.scan_component_indices(start_of_scan)?;
+
+    // The scan header's spectral selection start holds the predictor, and the successive
+    // approximation low bits hold the point transform.
+    let predictor = *start_of_scan.spectral_select.start();
+    let point_transform = start_of_scan.successive_approximation_low();
+
+    ensure!(
+        (1..=7).contains(&predictor),
+        "Invalid lossless predictor: {predictor}"
+    );
+
+    ensure!(
+        point_transform < frame.precision,
+        "Invalid point transform: {point_transform}"
+    );
+
+    let scan_tables = start_of_scan
+        .components
+        .iter()
+        .map(|&(_, selectors)| tables.dc(selectors >> 4))
+        .collect::<Result<Vec<_>>>()?;
+
+    let default_prediction = 1_i32 << (frame.precision - point_transform - 1);
+
+    // The first line of each restart interval is predicted from the left only, and its first
+    // sample from the default prediction.
+    let mut interval_start_lines = vec![0; component_indices.len()];
+    let mut starts_interval = vec![true; component_indices.len()];
+
+    let mut reader = BitReader::new(scan.image_data);
+
+    frame.for_each_block(
+        &component_indices,
+        scan.restart_interval,
+        |block, component| {
+            if block.restart {
+                reader.restart()?;
+                starts_interval.fill(true);
+            }
+
+            let line_stride = component.blocks_per_line;
+            let (x, y) = (block.index % line_stride, block.index / line_stride);
+            let samples = &mut component.samples;
+
+            let sample = |index: usize| samples[index] as i32;
+
+            let prediction = if std::mem::take(&mut starts_interval[block.position]) {
+                interval_start_lines[block.position] = y;
+                default_prediction
+            } else if y == interval_start_lines[block.position] {
+                sample(block.index - 1)
+            } else if x == 0 {
+                sample(block.index - line_stride)
+            } else {
+                let a = sample(block.index - 1);
+                let b = sample(block.index - line_stride);
+                let c = sample(block.index - line_stride - 1);
+
+                match predictor {
+                    1 => a,
+                    2 => b,
+                    3 => c,
+                    4 => a + b - c,
+                    5 => a + ((b - c) >> 1),
+                    6 => b + ((a - c) >> 1),
+                    _ => (a + b) >> 1,
+                }
+            };
+
+            let magnitude = scan_tables[block.position].decode(&mut reader)?;
+            ensure!(
+                magnitude <= 16,
+                "Invalid difference magnitude category: {magnitude}"
+            );
+
+            // Category 16 has no additional bits, its only difference is 32768.
+            let difference = if magnitude == 16 {
+                32768
+            } else {
+                reader.receive_extend(magnitude)
+            };
+
+            // Reconstruction is modulo 2^16.
+            samples[block.index] = (prediction + difference) as u16;
+
+            Ok(())
+        },
+    )?;
+
+    for index in component_indices {
+        frame.components[index]
+            .samples
+            .iter_mut()
+            .for_each(|sample| *sample <<= point_transform);
+    }
+
+    Ok(())
+}
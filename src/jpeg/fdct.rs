@@ -0,0 +1,47 @@
+use crate::jpeg::idct::cosines;
+
+/// Computes the forward DCT of a level shifted block in natural order (see A.3.3).
+pub fn fdct_8x8(samples: &[f32; 64]) -> [f32; 64] {
+    let cosines = cosines();
+
+    // Rows first: transform each row of samples into coefficients along u.
+    let mut intermediate = [0.0_f32; 64];
+
+    for y in 0..8 {
+        let row = &samples[y * 8..y * 8 + 8];
+
+        for u in 0..8 {
+            intermediate[y * 8 + u] = (0..8).map(|x| cosines[u][x] * row[x]).sum();
+        }
+    }
+
+    let mut coefficients = [0.0_f32; 64];
+
+    for v in 0..8 {
+        for u in 0..8 {
+            coefficients[v * 8 + u] = (0..8)
+                .map(|y| cosines[v][y] * intermediate[y * 8 + u])
+                .sum();
+        }
+    }
+
+    coefficients
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::idct::idct_8x8;
+
+    #[test]
+    fn test_inverts_idct() {
+        let samples: [f32; 64] = std::array::from_fn(|i| ((i * 37) % 255) as f32 - 128.0);
+
+        let coefficients = fdct_8x8(&samples).map(|c| c.round() as i32);
+
+        idct_8x8(&coefficients)
+            .iter()
+            .zip(&samples)
+            .for_each(|(a, b)| assert!((a - b).abs() < 2.0));
+    }
+}
@@ -0,0 +1,344 @@
+use crate::jpeg::{
+    grammar::{EncodingProcess, QuantizationTable, StartOfFrame, StartOfScan},
+    idct::{idct_8x8, ZIGZAG},
+};
+use anyhow::{anyhow, ensure, Result};
+
+/// Blocks can take next to no entropy-coded data, so the scans don't bound the frame's size.
+const MAX_PIXELS: u64 = 400_000_000;
+
+/// A frame component along with the quantized DCT coefficients, or for lossless frames the
+/// samples, decoded for it so far.
+#[derive(Debug)]
+pub struct FrameComponent {
+    pub(crate) identifier: u8,
+    pub(crate) horizontal_sampling_factor: usize,
+    pub(crate) vertical_sampling_factor: usize,
+    pub(crate) quantization_table_selector: u8,
+
+    /// The number of blocks per line and column that cover the component's samples.
+    pub(crate) width_in_blocks: usize,
+    pub(crate) height_in_blocks: usize,
+
+    /// The number of blocks per line and column once padded out to whole MCUs.
+    pub(crate) blocks_per_line: usize,
+    pub(crate) blocks_per_column: usize,
+
+    /// Quantized coefficients of each block in natural (row-major) order. Empty for lossless
+    /// frames.
+    pub(crate) coefficients: Vec<[i16; 64]>,
+
+    /// Samples of lossless frames, `blocks_per_line` to a line. Empty for DCT frames.
+    pub(crate) samples: Vec<u16>,
+}
+
+impl FrameComponent {
+    /// The width and height of the component's samples, before padding.
+    pub(crate) const fn sample_dimensions(&self, frame: &Frame) -> (usize, usize) {
+        (
+            (frame.width * self.horizontal_sampling_factor).div_ceil(frame.max_horizontal_sampling),
+            (frame.height * self.vertical_sampling_factor).div_ceil(frame.max_vertical_sampling),
+        )
+    }
+}
+
+/// A block visited by `Frame::for_each_block`.
+#[derive(Debug, Clone, Copy)]
+pub struct ScanBlock {
+    /// The position of the block's component within the scan header.
+    pub(crate) position: usize,
+    /// The index of the block within the component's coefficients, or of the sample within the
+    /// component's samples for lossless frames.
+    pub(crate) index: usize,
+    /// Whether the block is the first of a restart interval, other than the first interval.
+    pub(crate) restart: bool,
+}
+
+#[derive(Debug)]
+pub struct Frame {
+    /// The number of bits per sample.
+    pub(crate) precision: u8,
+    /// The width and height of a block in samples. Lossless frames code samples individually,
+    /// so their blocks are a single sample.
+    pub(crate) block_size: usize,
+    pub(crate) width: usize,
+    pub(crate) height: usize,
+    pub(crate) max_horizontal_sampling: usize,
+    pub(crate) max_vertical_sampling: usize,
+    pub(crate) mcus_per_line: usize,
+    pub(crate) mcus_per_column: usize,
+    pub(crate) components: Vec<FrameComponent>,
+}
+
+impl Frame {
+    pub(crate) fn new(start_of_frame: &StartOfFrame) -> Result<Self> {
+        let width = start_of_frame.samples_per_line as usize;
+        let height = start_of_frame.lines as usize;
+
+        ensure!(
+            width > 0 && height > 0,
+            "Frames with a deferred number of lines (DNL) are not supported."
+        );
+
+        // The dimensions come straight from the header, so check them before allocating.
+        ensure!(
+            width as u64 * height as u64 <= MAX_PIXELS,
+            "JPEG is too large: {width}x{height}"
+        );
+
+        ensure!(
+            !start_of_frame.components.is_empty(),
+            "Frame has no components."
+        );
+
+        for component in &start_of_frame.components {
+            ensure!(
+                (1..=4).contains(&component.horizontal_sampling_factor())
+                    && (1..=4).contains(&component.vertical_sampling_factor()),
+                "Invalid sampling factor: {:#X}",
+                component.sampling_factor
+            );
+        }
+
+        let max_horizontal_sampling = start_of_frame
+            .components
+            .iter()
+            .map(|c| c.horizontal_sampling_factor() as usize)
+            .max()
+            .unwrap_or(1);
+
+        let max_vertical_sampling = start_of_frame
+            .components
+            .iter()
+            .map(|c| c.vertical_sampling_factor() as usize)
+            .max()
+            .unwrap_or(1);
+
+        let lossless = matches!(
+            start_of_frame.encoding_process,
+            EncodingProcess::HuffmanLossless | EncodingProcess::ArithmeticLossless
+        );
+
+        let block_size = if lossless { 1 } else { 8 };
+
+        let mcus_per_line = width.div_ceil(block_size * max_horizontal_sampling);
+        let mcus_per_column = height.div_ceil(block_size * max_vertical_sampling);
+
+        let components = start_of_frame
+            .components
+            .iter()
+            .map(|component| {
+                let h = component.horizontal_sampling_factor() as usize;
+                let v = component.vertical_sampling_factor() as usize;
+
+                let blocks_per_line = mcus_per_line * h;
+                let blocks_per_column = mcus_per_column * v;
+                let num_blocks = blocks_per_line * blocks_per_column;
+
+                FrameComponent {
+                    identifier: component.identifier,
+                    horizontal_sampling_factor: h,
+                    vertical_sampling_factor: v,
+                    quantization_table_selector: component.quantization_table_destination_selector,
+                    width_in_blocks: (width * h)
+                        .div_ceil(max_horizontal_sampling)
+                        .div_ceil(block_size),
+                    height_in_blocks: (height * v)
+                        .div_ceil(max_vertical_sampling)
+                        .div_ceil(block_size),
+                    blocks_per_line,
+                    blocks_per_column,
+                    coefficients: if lossless {
+                        Vec::new()
+                    } else {
+                        vec![[0; 64]; num_blocks]
+                    },
+                    samples: if lossless {
+                        vec![0; num_blocks]
+                    } else {
+                        Vec::new()
+                    },
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            precision: start_of_frame.sample_precision,
+            block_size,
+            width,
+            height,
+            max_horizontal_sampling,
+            max_vertical_sampling,
+            mcus_per_line,
+            mcus_per_column,
+            components,
+        })
+    }
+
+    /// Resolves each scan component selector to an index into `components`.
+    pub(crate) fn scan_component_indices(&self, start_of_scan: &StartOfScan) -> Result<Vec<usize>> {
+        start_of_scan
+            .components
+            .iter()
+            .map(|&(selector, _)| {
+                self.components
+                    .iter()
+                    .position(|c| c.identifier == selector)
+                    .ok_or_else(|| anyhow!("Scan references unknown component {selector}"))
+            })
+            .collect()
+    }
+
+    /// Visits every block of a scan in coding order, calling `decode_block` with each block and
+    /// the component it belongs to.
+    ///
+    /// An interleaved scan (more than one component) codes whole MCUs. A non-interleaved scan
+    /// codes only the blocks that cover the component's samples, line by line, and each block is
+    /// an MCU of its own (see A.2).
+    pub(crate) fn for_each_block(
+        &mut self,
+        component_indices: &[usize],
+        restart_interval: u16,
+        mut decode_block: impl FnMut(ScanBlock, &mut FrameComponent) -> Result<()>,
+    ) -> Result<()> {
+        let restart_interval = restart_interval as usize;
+        let starts_interval =
+            |mcu: usize| restart_interval > 0 && mcu > 0 && mcu.is_multiple_of(restart_interval);
+
+        if let [index] = component_indices {
+            let component = &mut self.components[*index];
+
+            for block_y in 0..component.height_in_blocks {
+                for block_x in 0..component.width_in_blocks {
+                    let mcu = block_y * component.width_in_blocks + block_x;
+
+                    let block = ScanBlock {
+                        position: 0,
+                        index: block_y * component.blocks_per_line + block_x,
+                        restart: starts_interval(mcu),
+                    };
+
+                    decode_block(block, component)?;
+                }
+            }
+
+            return Ok(());
+        }
+
+        for mcu_y in 0..self.mcus_per_column {
+            for mcu_x in 0..self.mcus_per_line {
+                let mut restart = starts_interval(mcu_y * self.mcus_per_line + mcu_x);
+
+                for (position, &index) in component_indices.iter().enumerate() {
+                    let component = &mut self.components[index];
+                    let h = component.horizontal_sampling_factor;
+                    let v = component.vertical_sampling_factor;
+
+                    for y in 0..v {
+                        for x in 0..h {
+                            let block_y = mcu_y * v + y;
+                            let block_x = mcu_x * h + x;
+
+                            let block = ScanBlock {
+                                position,
+                                index: block_y * component.blocks_per_line + block_x,
+                                restart: std::mem::take(&mut restart),
+                            };
+
+                            decode_block(block, component)?;
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The largest sample value at the frame's precision.
+    pub(crate) const fn max_sample(&self) -> u16 {
+        ((1_u32 << self.precision) - 1) as u16
+    }
+
+    /// Dequantizes and inverse transforms every block, returning one plane of samples per
+    /// component. Each plane spans `blocks_per_line` by `blocks_per_column` blocks.
+    pub(crate) fn reconstruct(
+        &self,
+        quantization_tables: &[Option<&QuantizationTable>; 4],
+    ) -> Result<Vec<Vec<u16>>> {
+        let level_shift = (1 << (self.precision - 1)) as f32;
+        let max_sample = self.max_sample() as f32;
+
+        self.components
+            .iter()
+            .map(|component| {
+                if self.block_size == 1 {
+                    return Ok(component.samples.clone());
+                }
+
+                let table = quantization_tables
+                    .get(component.quantization_table_selector as usize)
+                    .copied()
+                    .flatten()
+                    .ok_or_else(|| {
+                        anyhow!(
+                            "Missing quantization table {}",
+                            component.quantization_table_selector
+                        )
+                    })?;
+
+                let mut quantization = [0_i32; 64];
+                for (k, &q) in table.table_elements.iter().enumerate() {
+                    quantization[ZIGZAG[k]] = q as i32;
+                }
+
+                let line_stride = component.blocks_per_line * 8;
+                let mut plane = vec![0_u16; line_stride * component.blocks_per_column * 8];
+
+                for (i, coefficients) in component.coefficients.iter().enumerate() {
+                    let mut block = [0_i32; 64];
+                    for k in 0..64 {
+                        block[k] = coefficients[k] as i32 * quantization[k];
+                    }
+
+                    let samples = idct_8x8(&block);
+
+                    let block_x = i % component.blocks_per_line;
+                    let block_y = i / component.blocks_per_line;
+
+                    for y in 0..8 {
+                        let offset = (block_y * 8 + y) * line_stride + block_x * 8;
+
+                        for x in 0..8 {
+                            plane[offset + x] = (samples[y * 8 + x] + level_shift)
+                                .round()
+                                .clamp(0.0, max_sample)
+                                as u16;
+                        }
+                    }
+                }
+
+                Ok(plane)
+            })
+            .collect()
+    }
+}
+
+/// Installs quantization tables by destination. Tables defined later replace earlier ones.
+pub fn install_quantization_tables(
+    quantization_tables: &[QuantizationTable],
+) -> Result<[Option<&QuantizationTable>; 4]> {
+    let mut installed = [None; 4];
+
+    for table in quantization_tables {
+        let destination = table.table_identifier() as usize;
+        ensure!(
+            destination < 4,
+            "Invalid quantization table destination: {destination}"
+        );
+
+        installed[destination] = Some(table);
+    }
+
+    Ok(installed)
+}
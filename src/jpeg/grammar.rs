@@ -1,4 +1,8 @@
-use crate::image::grammar::{ColorType, ImageExt};
+use crate::{
+    exif::grammar::Exif,
+    icc::grammar::IccProfile,
+    image::grammar::{ColorType, ImageExt},
+};
 use anyhow::bail;
 use std::{borrow::Cow, ops::RangeInclusive};
 
@@ -12,6 +16,39 @@ pub struct ApplicationHeader {
     pub thumbnail: (u8, u8),
 }
 
+/// The transform applied to the components before encoding, as recorded by an Adobe APP14
+/// segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorTransform {
+    /// RGB for three components, CMYK for four.
+    None = 0,
+    YCbCr = 1,
+    /// YCbCr for the first three components of inverted CMYK, leaving K untouched.
+    YCCK = 2,
+}
+
+impl TryFrom<u8> for ColorTransform {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> anyhow::Result<Self, Self::Error> {
+        let transform = match value {
+            0 => Self::None,
+            1 => Self::YCbCr,
+            2 => Self::YCCK,
+            foreign => bail!("Unrecognized Adobe color transform: {}", foreign),
+        };
+
+        Ok(transform)
+    }
+}
+
+#[derive(Debug)]
+pub struct AdobeHeader {
+    pub version: u16,
+    pub flags: (u16, u16),
+    pub color_transform: ColorTransform,
+}
+
 #[derive(Debug, Clone, Copy)]
 pub enum Precision {
     Eight = 1,
@@ -47,6 +84,7 @@ impl QuantizationTable {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum HuffmanTableClass {
     AC,
     DC,
@@ -73,7 +111,31 @@ impl HuffmanTable {
     }
 }
 
+/// A conditioning table from a DAC segment, which parameterizes the statistical model of
+/// arithmetic coding for a destination.
 #[derive(Debug)]
+pub struct ConditioningTable {
+    pub flag: u8,
+    /// For DC (and lossless) tables, the upper bound U in the high nibble and the lower bound L
+    /// in the low nibble of the difference categories. For AC tables, Kx.
+    pub value: u8,
+}
+
+impl ConditioningTable {
+    pub const fn table_class(&self) -> HuffmanTableClass {
+        if (self.flag >> 4) == 1 {
+            return HuffmanTableClass::AC;
+        }
+
+        HuffmanTableClass::DC
+    }
+
+    pub const fn table_identifier(&self) -> u8 {
+        self.flag & 0b1111
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum EncodingProcess {
     BaselineDCT = 0,
     HuffmanExtendedSequentialDCT = 1,
@@ -119,52 +181,201 @@ pub struct Component {
     pub quantization_table_destination_selector: u8,
 }
 
+impl Component {
+    pub const fn horizontal_sampling_factor(&self) -> u8 {
+        self.sampling_factor >> 4
+    }
+
+    pub const fn vertical_sampling_factor(&self) -> u8 {
+        self.sampling_factor & 0b1111
+    }
+}
+
 #[derive(Debug)]
 pub struct StartOfScan {
+    /// Pairs of (component selector, DC table selector << 4 | AC table selector).
     pub components: Vec<(u8, u8)>,
     pub spectral_select: RangeInclusive<u8>,
     pub approximation: u8,
 }
 
+impl StartOfScan {
+    /// The point transform used in the preceding scan of the same band (Ah), 0 for a first scan.
+    pub const fn successive_approximation_high(&self) -> u8 {
+        self.approximation >> 4
+    }
+
+    /// The point transform applied to coefficients in this scan (Al).
+    pub const fn successive_approximation_low(&self) -> u8 {
+        self.approximation & 0b1111
+    }
+}
+
 #[derive(Debug)]
-pub struct JFIF<'a> {
-    pub application_header: ApplicationHeader,
-    pub quantization_tables: Vec<QuantizationTable>,
+pub struct Scan<'a> {
+    /// Huffman tables defined since the previous scan. They replace any earlier table with the
+    /// same class and destination.
     pub huffman_tables: Vec<HuffmanTable>,
-    pub start_of_frame: StartOfFrame,
+    /// Arithmetic coding conditioning tables defined since the previous scan, likewise.
+    pub conditioning_tables: Vec<ConditioningTable>,
+    /// The number of MCUs per restart interval, 0 if restart intervals are disabled.
+    pub restart_interval: u16,
     pub start_of_scan: StartOfScan,
     pub image_data: &'a [u8],
 }
 
 #[derive(Debug)]
-pub struct Jpeg {}
+pub struct JFIF<'a> {
+    /// Absent for files without a JFIF APP0 segment, such as Exif or Adobe files.
+    pub application_header: Option<ApplicationHeader>,
+    pub adobe_header: Option<AdobeHeader>,
+    pub comments: Vec<&'a [u8]>,
+    /// The TIFF structure of the first Exif APP1 segment.
+    pub exif: Option<&'a [u8]>,
+    /// The parameters of every APPn and COM segment in file order, so they can be carried over
+    /// when rewriting the file.
+    pub metadata_segments: Vec<(Marker, &'a [u8])>,
+    pub quantization_tables: Vec<QuantizationTable>,
+    pub start_of_frame: StartOfFrame,
+    pub scans: Vec<Scan<'a>>,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Jpeg {
+    pub(crate) width: u32,
+    pub(crate) height: u32,
+    pub(crate) color_type: ColorType,
+    pub(crate) sample_precision: u8,
+    pub(crate) comments: Vec<String>,
+    pub(crate) exif: Option<Exif>,
+    pub(crate) icc_profile: Option<IccProfile>,
+    /// One byte per sample for 8-bit images, two big-endian bytes per sample otherwise.
+    pub(crate) pixel_buffer: Vec<u8>,
+}
+
+impl Jpeg {
+    /// The contents of the image's COM segments.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// The number of bits per sample: 8 for most images, 8 or 12 for extended and progressive
+    /// images, and anywhere from 2 to 16 for lossless images.
+    pub const fn sample_precision(&self) -> u8 {
+        self.sample_precision
+    }
+
+    /// The decoded samples at their full precision, laid out like `color_type`.
+    pub fn samples(&self) -> Vec<u16> {
+        if self.sample_precision == 8 {
+            return self.pixel_buffer.iter().map(|&b| b as u16).collect();
+        }
+
+        self.pixel_buffer
+            .chunks_exact(2)
+            .map(|b| u16::from_be_bytes([b[0], b[1]]))
+            .collect()
+    }
+
+    /// The decoded samples, scaled to 8 bits if necessary.
+    fn samples8(&self) -> Cow<'_, [u8]> {
+        if self.sample_precision == 8 {
+            return Cow::from(&self.pixel_buffer);
+        }
 
+        let max = ((1_u32 << self.sample_precision) - 1) as f32;
+
+        let b = self
+            .samples()
+            .into_iter()
+            .map(|sample| (sample as f32 * 255.0 / max).round() as u8)
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+}
 impl ImageExt for Jpeg {
     fn width(&self) -> u32 {
-        todo!()
+        self.width
     }
 
     fn height(&self) -> u32 {
-        todo!()
-    }
-
-    fn gamma(&self) -> u32 {
-        todo!()
+        self.height
     }
 
     fn color_type(&self) -> ColorType {
-        todo!()
+        self.color_type
     }
 
     fn rgb8(&self) -> Cow<'_, [u8]> {
-        todo!()
+        match self.color_type {
+            ColorType::RGB => self.samples8(),
+            ColorType::Grayscale => {
+                let b = self
+                    .samples8()
+                    .iter()
+                    .flat_map(|&y| [y, y, y])
+                    .collect::<Vec<_>>();
+
+                Cow::from(b)
+            }
+            foreign => unreachable!("Jpeg does not decode into {:?}", foreign),
+        }
     }
 
     fn rgba8(&self) -> Cow<'_, [u8]> {
-        todo!()
+        match self.color_type {
+            ColorType::RGB => {
+                let b = self
+                    .samples8()
+                    .chunks_exact(3)
+                    .flat_map(|b| [b[0], b[1], b[2], 0xFF])
+                    .collect::<Vec<_>>();
+
+                Cow::from(b)
+            }
+            ColorType::Grayscale => {
+                let b = self
+                    .samples8()
+                    .iter()
+                    .flat_map(|&y| [y, y, y, 0xFF])
+                    .collect::<Vec<_>>();
+
+                Cow::from(b)
+            }
+            foreign => unreachable!("Jpeg does not decode into {:?}", foreign),
+        }
     }
 
     fn bitmap(&self) -> Cow<'_, [u32]> {
-        todo!()
+        match self.color_type {
+            ColorType::RGB => {
+                let b = self
+                    .samples8()
+                    .chunks_exact(3)
+                    .map(|b| u32::from_be_bytes([0xFF, b[0], b[1], b[2]]))
+                    .collect::<Vec<_>>();
+
+                Cow::from(b)
+            }
+            ColorType::Grayscale => {
+                let b = self
+                    .samples8()
+                    .iter()
+                    .map(|&b| u32::from_be_bytes([0xFF, b, b, b]))
+                    .collect::<Vec<_>>();
+
+                Cow::from(b)
+            }
+            foreign => unreachable!("Jpeg does not decode into {:?}", foreign),
+        }
+    }
+
+    fn exif(&self) -> Option<&Exif> {
+        self.exif.as_ref()
+    }
+
+    fn icc_profile(&self) -> Option<&IccProfile> {
+        self.icc_profile.as_ref()
     }
 }
@@ -0,0 +1,296 @@
+use crate::jpeg::{bit_reader::BitReader, grammar::HuffmanTable};
+use anyhow::{anyhow, bail, ensure, Result};
+
+/// Number of bits resolved by a single lookup in `HuffmanLookup::fast`.
+const FAST_BITS: u8 = 9;
+
+/// A decoding table derived from a `HuffmanTable` (see F.2.2.3 and Annex C).
+///
+/// Codes no longer than `FAST_BITS` are resolved with a single table lookup; longer codes fall
+/// back to walking `max_code` one code length at a time.
+#[derive(Debug, Clone)]
+pub struct HuffmanLookup {
+    /// Indexed by the next `FAST_BITS` bits, yields (code length, value). A length of 0 means the
+    /// code is longer than `FAST_BITS`.
+    fast: Box<[(u8, u8); 1 << FAST_BITS]>,
+    /// The largest code of each length, or -1 if there are no codes of that length.
+    max_code: [i32; 17],
+    /// The difference between a code's index into `values` and the code itself, per length.
+    value_offset: [i32; 17],
+    values: Vec<u8>,
+}
+
+impl HuffmanLookup {
+    pub(crate) fn new(table: &HuffmanTable) -> Result<Self> {
+        let mut fast = Box::new([(0, 0); 1 << FAST_BITS]);
+        let mut max_code = [-1; 17];
+        let mut value_offset = [0; 17];
+
+        let mut code = 0_i32;
+        let mut k = 0_usize;
+
+        for length in 1..=16 {
+            let count = table.code_lengths[length - 1] as usize;
+
+            value_offset[length] = k as i32 - code;
+
+            for _ in 0..count {
+                ensure!(
+                    code < 1 << length,
+                    "Huffman table has more codes than fit in {length} bits."
+                );
+
+                if length <= FAST_BITS as usize {
+                    let shift = FAST_BITS as usize - length;
+                    let start = (code as usize) << shift;
+
+                    for entry in &mut fast[start..start + (1 << shift)] {
+                        *entry = (length as u8, table.values[k]);
+                    }
+                }
+
+                code += 1;
+                k += 1;
+            }
+
+            if count > 0 {
+                max_code[length] = code - 1;
+            }
+
+            code <<= 1;
+        }
+
+        Ok(Self {
+            fast,
+            max_code,
+            value_offset,
+            values: table.values.clone(),
+        })
+    }
+
+    pub(crate) fn decode(&self, reader: &mut BitReader) -> Result<u8> {
+        let (length, value) = self.fast[reader.peek_bits(FAST_BITS) as usize];
+
+        if length > 0 {
+            reader.consume_bits(length);
+            return Ok(value);
+        }
+
+        let bits = reader.peek_bits(16) as i32;
+
+        for length in FAST_BITS as usize + 1..=16 {
+            let code = bits >> (16 - length);
+
+            if code <= self.max_code[length] {
+                reader.consume_bits(length as u8);
+
+                let index = (code + self.value_offset[length]) as usize;
+                return Ok(self.values[index]);
+            }
+        }
+
+        bail!("Encountered an invalid Huffman code.")
+    }
+}
+
+/// The code of every value of a `HuffmanTable`, for encoding (see C.2).
+#[derive(Debug, Clone)]
+pub struct HuffmanCodes {
+    /// Indexed by value, yields (code, code length). A length of 0 means the value has no code.
+    codes: [(u16, u8); 256],
+}
+
+impl HuffmanCodes {
+    pub(crate) fn new(table: &HuffmanTable) -> Result<Self> {
+        let mut codes = [(0, 0); 256];
+
+        let mut code = 0_u32;
+        let mut values = table.values.iter();
+
+        for length in 1..=16 {
+            for _ in 0..table.code_lengths[length - 1] {
+                let value = values
+                    .next()
+                    .ok_or_else(|| anyhow!("Huffman table has fewer values than codes."))?;
+
+                ensure!(
+                    code < 1 << length,
+                    "Huffman table has more codes than fit in {length} bits."
+                );
+
+                codes[*value as usize] = (code as u16, length as u8);
+                code += 1;
+            }
+
+            code <<= 1;
+        }
+
+        Ok(Self { codes })
+    }
+
+    pub(crate) fn code(&self, value: u8) -> Result<(u16, u8)> {
+        let (code, length) = self.codes[value as usize];
+        ensure!(length > 0, "Huffman table has no code for {value:#04X}");
+
+        Ok((code, length))
+    }
+}
+
+/// Builds a Huffman table for `frequencies`, the number of occurrences of each value, with the
+/// procedure of K.2. Codes are limited to 16 bits and no code consists of only 1 bits.
+pub fn optimal_huffman_table(flag: u8, frequencies: &[u32; 256]) -> HuffmanTable {
+    // A reserved value with the lowest frequency claims the all-ones code and is removed at the
+    // end.
+    let mut frequencies = frequencies.map(|f| f as u64).to_vec();
+    frequencies.push(1);
+
+    let mut code_sizes = [0_usize; 257];
+    let mut others = [None::<usize>; 257];
+
+    loop {
+        // The value with the least frequency, preferring the larger value on ties, then the one
+        // with the next least.
+        let least = |excluded: Option<usize>| {
+            (0..257)
+                .filter(|&i| frequencies[i] > 0 && Some(i) != excluded)
+                .min_by_key(|&i| (frequencies[i], std::cmp::Reverse(i)))
+        };
+
+        let Some(mut v1) = least(None) else {
+            break;
+        };
+
+        let Some(mut v2) = least(Some(v1)) else {
+            break;
+        };
+
+        frequencies[v1] += frequencies[v2];
+        frequencies[v2] = 0;
+
+        code_sizes[v1] += 1;
+        while let Some(next) = others[v1] {
+            v1 = next;
+            code_sizes[v1] += 1;
+        }
+
+        others[v1] = Some(v2);
+
+        code_sizes[v2] += 1;
+        while let Some(next) = others[v2] {
+            v2 = next;
+            code_sizes[v2] += 1;
+        }
+    }
+
+    let mut bits = [0_u32; 33];
+    for &size in code_sizes.iter().filter(|&&size| size > 0) {
+        bits[size.min(32)] += 1;
+    }
+
+    // Move pairs of codes longer than 16 bits up the tree (see Figure K.3).
+    for i in (17..=32).rev() {
+        while bits[i] > 0 {
+            let mut j = i - 2;
+            while bits[j] == 0 {
+                j -= 1;
+            }
+
+            bits[i] -= 2;
+            bits[i - 1] += 1;
+            bits[j + 1] += 2;
+            bits[j] -= 1;
+        }
+    }
+
+    // Remove the reserved code, which is among the longest.
+    if let Some(longest) = (1..=16).rev().find(|&i| bits[i] > 0) {
+        bits[longest] -= 1;
+    }
+
+    let values = (1..=32)
+        .flat_map(|size| (0..256).filter(move |&v| code_sizes[v] == size))
+        .map(|v| v as u8)
+        .collect();
+
+    HuffmanTable {
+        flag,
+        code_lengths: std::array::from_fn(|i| bits[i + 1] as u8),
+        values,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jpeg::bit_writer::BitWriter;
+
+    #[test]
+    fn test_decode_codes() -> Result<()> {
+        // One code of length 2, two of length 3, and one of length 12:
+        //   00 -> 0x0A, 010 -> 0x0B, 011 -> 0x0C, 1000_0000_0000 -> 0x0D
+        let mut code_lengths = [0; 16];
+        code_lengths[1] = 1;
+        code_lengths[2] = 2;
+        code_lengths[11] = 1;
+
+        let lookup = HuffmanLookup::new(&HuffmanTable {
+            flag: 0,
+            code_lengths,
+            values: vec![0x0A, 0x0B, 0x0C, 0x0D],
+        })?;
+
+        let data = [0b0001_0011, 0b1000_0000, 0b0000_0000];
+        let mut reader = BitReader::new(&data);
+
+        assert_eq!(lookup.decode(&mut reader)?, 0x0A);
+        assert_eq!(lookup.decode(&mut reader)?, 0x0B);
+        assert_eq!(lookup.decode(&mut reader)?, 0x0C);
+        assert_eq!(lookup.decode(&mut reader)?, 0x0D);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_optimal_table_round_trips() -> Result<()> {
+        // Skewed frequencies, with enough values to force codes past 16 bits before limiting.
+        let mut frequencies = [0; 256];
+        for (i, frequency) in frequencies.iter_mut().enumerate().take(40) {
+            *frequency = 1 << (i / 2).min(30);
+        }
+
+        let table = optimal_huffman_table(0x10, &frequencies);
+        assert!(
+            table
+                .code_lengths
+                .iter()
+                .map(|&n| n as usize)
+                .sum::<usize>()
+                == 40
+        );
+
+        let codes = HuffmanCodes::new(&table)?;
+        let lookup = HuffmanLookup::new(&table)?;
+
+        // Write the code of every value, then decode them back.
+        let mut writer = BitWriter::default();
+
+        for value in 0..40 {
+            let (code, length) = codes.code(value)?;
+            assert!(
+                code as u32 != (1 << length) - 1,
+                "All-ones codes are reserved."
+            );
+
+            writer.write_bits(code, length);
+        }
+
+        let data = writer.finish();
+
+        let mut reader = BitReader::new(&data);
+        for value in 0..40 {
+            assert_eq!(lookup.decode(&mut reader)?, value);
+        }
+
+        Ok(())
+    }
+}
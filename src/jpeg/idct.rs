@@ -0,0 +1,86 @@
+#![allow(clippy::suboptimal_flops)]
+
+use std::{f32::consts::PI, sync::OnceLock};
+
+/// Maps the zig-zag index of a coefficient to its natural (row-major) index in an 8x8 block.
+pub const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10, 17, 24, 32, 25, 18, 11, 4, 5, 12, 19, 26, 33, 40, 48, 41, 34, 27, 20,
+    13, 6, 7, 14, 21, 28, 35, 42, 49, 56, 57, 50, 43, 36, 29, 22, 15, 23, 30, 37, 44, 51, 58, 59,
+    52, 45, 38, 31, 39, 46, 53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+/// `cosines()[u][x]` is C(u) / 2 * cos((2x + 1)uπ / 16), the basis function of A.3.3.
+pub fn cosines() -> &'static [[f32; 8]; 8] {
+    static COSINES: OnceLock<[[f32; 8]; 8]> = OnceLock::new();
+
+    COSINES.get_or_init(|| {
+        let mut table = [[0.0; 8]; 8];
+
+        for (u, row) in table.iter_mut().enumerate() {
+            let c = if u == 0 { 1.0 / 2.0_f32.sqrt() } else { 1.0 };
+
+            for (x, value) in row.iter_mut().enumerate() {
+                *value = c / 2.0 * (((2 * x + 1) * u) as f32 * PI / 16.0).cos();
+            }
+        }
+
+        table
+    })
+}
+
+/// Computes the inverse DCT of a dequantized block in natural order. The result is not level
+/// shifted.
+pub fn idct_8x8(block: &[i32; 64]) -> [f32; 64] {
+    let cosines = cosines();
+
+    // Columns first: transform each column of coefficients into samples along y.
+    let mut intermediate = [0.0_f32; 64];
+
+    for u in 0..8 {
+        if (0..8).all(|v| block[v * 8 + u] == 0) {
+            continue;
+        }
+
+        for y in 0..8 {
+            intermediate[y * 8 + u] = (0..8)
+                .map(|v| cosines[v][y] * block[v * 8 + u] as f32)
+                .sum();
+        }
+    }
+
+    let mut samples = [0.0_f32; 64];
+
+    for y in 0..8 {
+        let row = &intermediate[y * 8..y * 8 + 8];
+
+        for x in 0..8 {
+            samples[y * 8 + x] = (0..8).map(|u| cosines[u][x] * row[u]).sum();
+        }
+    }
+
+    samples
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zigzag_is_a_permutation() {
+        let mut seen = [false; 64];
+        ZIGZAG.iter().for_each(|&i| seen[i] = true);
+
+        assert!(seen.iter().all(|&b| b));
+    }
+
+    #[test]
+    fn test_dc_only_block() {
+        let mut block = [0; 64];
+        block[0] = 80;
+
+        // A DC coefficient of 8n decodes to a flat block of n.
+        idct_8x8(&block)
+            .iter()
+            .for_each(|&s| assert!((s - 10.0).abs() < 1e-4));
+    }
+}
@@ -1,4 +1,19 @@
+mod arithmetic_decoder;
+mod bit_reader;
+mod bit_writer;
+mod color_convert;
 mod decoder;
+mod encoder;
+mod entropy_decoder;
+mod fdct;
+mod frame;
+mod huffman;
+mod idct;
+mod qm_decoder;
+mod tables;
+mod transform;
 
 pub mod grammar;
 pub use decoder::*;
+pub use encoder::*;
+pub use transform::*;
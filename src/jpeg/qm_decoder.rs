@@ -0,0 +1,307 @@
+use anyhow::{anyhow, Result};
+
+/// The probability estimation state machine of Table D.2: (Qe, next index after an LPS, next
+/// index after an MPS, whether an LPS switches the sense of the MPS).
+///
+/// The extra last entry is a non-adapting estimate of 1/2, used for decisions that are coded with
+/// a fixed probability.
+const PROBABILITY_ESTIMATES: [(u32, u8, u8, bool); 114] = [
+    (0x5A1D, 1, 1, true),
+    (0x2586, 14, 2, false),
+    (0x1114, 16, 3, false),
+    (0x080B, 18, 4, false),
+    (0x03D8, 20, 5, false),
+    (0x01DA, 23, 6, false),
+    (0x00E5, 25, 7, false),
+    (0x006F, 28, 8, false),
+    (0x0036, 30, 9, false),
+    (0x001A, 33, 10, false),
+    (0x000D, 35, 11, false),
+    (0x0006, 9, 12, false),
+    (0x0003, 10, 13, false),
+    (0x0001, 12, 13, false),
+    (0x5A7F, 15, 15, true),
+    (0x3F25, 36, 16, false),
+    (0x2CF2, 38, 17, false),
+    (0x207C, 39, 18, false),
+    (0x17B9, 40, 19, false),
+    (0x1182, 42, 20, false),
+    (0x0CEF, 43, 21, false),
+    (0x09A1, 45, 22, false),
+    (0x072F, 46, 23, false),
+    (0x055C, 48, 24, false),
+    (0x0406, 49, 25, false),
+    (0x0303, 51, 26, false),
+    (0x0240, 52, 27, false),
+    (0x01B1, 54, 28, false),
+    (0x0144, 56, 29, false),
+    (0x00F5, 57, 30, false),
+    (0x00B7, 59, 31, false),
+    (0x008A, 60, 32, false),
+    (0x0068, 62, 33, false),
+    (0x004E, 63, 34, false),
+    (0x003B, 32, 35, false),
+    (0x002C, 33, 9, false),
+    (0x5AE1, 37, 37, true),
+    (0x484C, 64, 38, false),
+    (0x3A0D, 65, 39, false),
+    (0x2EF1, 67, 40, false),
+    (0x261F, 68, 41, false),
+    (0x1F33, 69, 42, false),
+    (0x19A8, 70, 43, false),
+    (0x1518, 72, 44, false),
+    (0x1177, 73, 45, false),
+    (0x0E74, 74, 46, false),
+    (0x0BFB, 75, 47, false),
+    (0x09F8, 77, 48, false),
+    (0x0861, 78, 49, false),
+    (0x0706, 79, 50, false),
+    (0x05CD, 48, 51, false),
+    (0x04DE, 50, 52, false),
+    (0x040F, 50, 53, false),
+    (0x0363, 51, 54, false),
+    (0x02D4, 52, 55, false),
+    (0x025C, 53, 56, false),
+    (0x01F8, 54, 57, false),
+    (0x01A4, 55, 58, false),
+    (0x0160, 56, 59, false),
+    (0x0125, 57, 60, false),
+    (0x00F6, 58, 61, false),
+    (0x00CB, 59, 62, false),
+    (0x00AB, 61, 63, false),
+    (0x008F, 61, 32, false),
+    (0x5B12, 65, 65, true),
+    (0x4D04, 80, 66, false),
+    (0x412C, 81, 67, false),
+    (0x37D8, 82, 68, false),
+    (0x2FE8, 83, 69, false),
+    (0x293C, 84, 70, false),
+    (0x2379, 86, 71, false),
+    (0x1EDF, 87, 72, false),
+    (0x1AA9, 87, 73, false),
+    (0x174E, 72, 74, false),
+    (0x1424, 72, 75, false),
+    (0x119C, 74, 76, false),
+    (0x0F6B, 74, 77, false),
+    (0x0D51, 75, 78, false),
+    (0x0BB6, 77, 79, false),
+    (0x0A40, 77, 48, false),
+    (0x5832, 80, 81, true),
+    (0x4D1C, 88, 82, false),
+    (0x438E, 89, 83, false),
+    (0x3BDD, 90, 84, false),
+    (0x34EE, 91, 85, false),
+    (0x2EAE, 92, 86, false),
+    (0x299A, 93, 87, false),
+    (0x2516, 86, 71, false),
+    (0x5570, 88, 89, true),
+    (0x4CA9, 95, 90, false),
+    (0x44D9, 96, 91, false),
+    (0x3E22, 97, 92, false),
+    (0x3824, 99, 93, false),
+    (0x32B4, 99, 94, false),
+    (0x2E17, 93, 86, false),
+    (0x56A8, 95, 96, true),
+    (0x4F46, 101, 97, false),
+    (0x47E5, 102, 98, false),
+    (0x41CF, 103, 99, false),
+    (0x3C3D, 104, 100, false),
+    (0x375E, 99, 93, false),
+    (0x5231, 105, 102, false),
+    (0x4C0F, 106, 103, false),
+    (0x4639, 107, 104, false),
+    (0x415E, 103, 99, false),
+    (0x5627, 105, 106, true),
+    (0x50E7, 108, 107, false),
+    (0x4B85, 109, 103, false),
+    (0x5597, 110, 109, false),
+    (0x504F, 111, 107, false),
+    (0x5A10, 110, 111, true),
+    (0x5522, 112, 109, false),
+    (0x59EB, 112, 111, true),
+    (0x5A1D, 113, 113, false),
+];
+
+/// The adaptive probability estimate of a single binary decision: an index into Table D.2 along
+/// with the sense of the more probable symbol.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Context {
+    index: u8,
+    mps: bool,
+}
+
+impl Context {
+    /// A context that always estimates both symbols as equally probable.
+    pub(crate) const FIXED: Self = Self {
+        index: 113,
+        mps: false,
+    };
+}
+
+/// Decodes binary decisions coded with the QM-coder, the adaptive binary arithmetic coder of
+/// Annex D.
+///
+/// Like `BitReader`, the decoder discards stuffed zero bytes and feeds zeros once it reaches a
+/// marker.
+#[derive(Debug)]
+pub struct QmDecoder<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    /// The code register, aligned so its top 16 bits line up with `interval`.
+    code: u32,
+    /// The size of the current interval (A).
+    interval: u32,
+    /// The number of bits left in the low byte of `code`. Negative while the two initial bytes
+    /// are being read.
+    num_bits: i32,
+}
+
+impl<'a> QmDecoder<'a> {
+    pub(crate) const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            code: 0,
+            interval: 0,
+            num_bits: -16,
+        }
+    }
+
+    /// Reads the next byte of entropy-coded data, or 0 once a marker is reached.
+    fn read_byte(&mut self) -> u32 {
+        match self.data.get(self.cursor) {
+            Some(&0xFF) => match self.data.get(self.cursor + 1) {
+                Some(0x00) => {
+                    self.cursor += 2;
+                    0xFF
+                }
+                _ => 0,
+            },
+            Some(&byte) => {
+                self.cursor += 1;
+                byte as u32
+            }
+            None => 0,
+        }
+    }
+
+    /// Decodes a decision with the probability estimate of `context`, then updates the estimate
+    /// (see D.2).
+    pub(crate) fn decode(&mut self, context: &mut Context) -> bool {
+        // Renormalize, reading bytes as needed (D.2.6). The interval is 0 on the first call, which
+        // reads the two initial bytes.
+        while self.interval < 0x8000 {
+            self.num_bits -= 1;
+
+            if self.num_bits < 0 {
+                self.code = (self.code << 8) | self.read_byte();
+                self.num_bits += 8;
+
+                if self.num_bits < 0 {
+                    self.num_bits += 1;
+
+                    if self.num_bits == 0 {
+                        // Both initial bytes have been read, the loop exits with an interval of
+                        // 0x10000.
+                        self.interval = 0x8000;
+                    }
+                }
+            }
+
+            self.interval <<= 1;
+        }
+
+        let (qe, next_lps, next_mps, switch) = PROBABILITY_ESTIMATES[context.index as usize];
+
+        self.interval -= qe;
+        let threshold = self.interval << self.num_bits;
+
+        if self.code >= threshold {
+            // The code falls in the upper subinterval, assigned to the LPS unless the interval
+            // is now smaller than Qe and they are exchanged.
+            self.code -= threshold;
+
+            let lps = self.interval >= qe;
+            self.interval = qe;
+
+            if lps {
+                Self::update_after_lps(context, next_lps, switch)
+            } else {
+                Self::update_after_mps(context, next_mps)
+            }
+        } else if self.interval < 0x8000 {
+            if self.interval < qe {
+                Self::update_after_lps(context, next_lps, switch)
+            } else {
+                Self::update_after_mps(context, next_mps)
+            }
+        } else {
+            context.mps
+        }
+    }
+
+    const fn update_after_mps(context: &mut Context, next_mps: u8) -> bool {
+        context.index = next_mps;
+
+        context.mps
+    }
+
+    const fn update_after_lps(context: &mut Context, next_lps: u8, switch: bool) -> bool {
+        let lps = !context.mps;
+
+        context.index = next_lps;
+        context.mps ^= switch;
+
+        lps
+    }
+
+    /// Skips past the restart marker ending the current interval and resets the decoder. The
+    /// statistics are reset by the caller.
+    pub(crate) fn restart(&mut self) -> Result<()> {
+        let offset = self.data[self.cursor..]
+            .windows(2)
+            .position(|marker| marker[0] == 0xFF && matches!(marker[1], 0xD0..=0xD7))
+            .ok_or_else(|| anyhow!("Expected a restart marker."))?;
+
+        self.cursor += offset + 2;
+        self.code = 0;
+        self.interval = 0;
+        self.num_bits = -16;
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_reference_sequence() {
+        // The test sequence of K.4.1, coded with a single context.
+        let coded = [
+            0x65, 0x5B, 0x51, 0x44, 0xF7, 0x96, 0x9D, 0x51, 0x78, 0x55, 0xBF, 0xFF, 0x00, 0xFC,
+            0x51, 0x84, 0xC7, 0xCE, 0xF9, 0x39, 0x00, 0x28, 0x7D, 0x46, 0x70, 0x8E, 0xCB, 0xC0,
+            0xF6, 0xFF, 0xD9, 0x00,
+        ];
+
+        let expected = [
+            0x00, 0x02, 0x00, 0x51, 0x00, 0x00, 0x00, 0xC0, 0x03, 0x52, 0x87, 0x2A, 0xAA, 0xAA,
+            0xAA, 0xAA, 0x82, 0xC0, 0x20, 0x00, 0xFC, 0xD7, 0x9E, 0xF6, 0x74, 0xEA, 0xAB, 0xF7,
+            0x69, 0x7E, 0xE7, 0x4C,
+        ];
+
+        let mut decoder = QmDecoder::new(&coded);
+        let mut context = Context::default();
+
+        let decoded = (0..expected.len())
+            .map(|_| {
+                (0..8).fold(0_u8, |byte, _| {
+                    byte << 1 | decoder.decode(&mut context) as u8
+                })
+            })
+            .collect::<Vec<_>>();
+
+        assert_eq!(decoded, expected);
+    }
+}
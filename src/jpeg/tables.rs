@@ -0,0 +1,143 @@
+use crate::jpeg::{
+    grammar::{HuffmanTable, HuffmanTableClass, QuantizationTable},
+    idct::ZIGZAG,
+};
+
+/// The luminance quantization table of K.1, in natural order.
+const LUMINANCE_QUANTIZATION: [u16; 64] = [
+    16, 11, 10, 16, 24, 40, 51, 61, //
+    12, 12, 14, 19, 26, 58, 60, 55, //
+    14, 13, 16, 24, 40, 57, 69, 56, //
+    14, 17, 22, 29, 51, 87, 80, 62, //
+    18, 22, 37, 56, 68, 109, 103, 77, //
+    24, 35, 55, 64, 81, 104, 113, 92, //
+    49, 64, 78, 87, 103, 121, 120, 101, //
+    72, 92, 95, 98, 112, 100, 103, 99,
+];
+
+/// The chrominance quantization table of K.1, in natural order.
+const CHROMINANCE_QUANTIZATION: [u16; 64] = [
+    17, 18, 24, 47, 99, 99, 99, 99, //
+    18, 21, 26, 66, 99, 99, 99, 99, //
+    24, 26, 56, 99, 99, 99, 99, 99, //
+    47, 66, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99, //
+    99, 99, 99, 99, 99, 99, 99, 99,
+];
+
+/// Scales the K.1 table for `destination` (0 for luminance, 1 for chrominance) to `quality`, on
+/// the 1 to 100 scale popularized by the IJG.
+///
+/// Quality 50 uses the tables as given, lower qualities scale them up and higher qualities scale
+/// them down, down to all ones at quality 100.
+pub fn scaled_quantization_table(destination: u8, quality: u8) -> QuantizationTable {
+    let base = if destination == 0 {
+        &LUMINANCE_QUANTIZATION
+    } else {
+        &CHROMINANCE_QUANTIZATION
+    };
+
+    let quality = quality.clamp(1, 100) as u32;
+    let scale = if quality < 50 {
+        5000 / quality
+    } else {
+        200 - 2 * quality
+    };
+
+    // Tables are stored in zig-zag order.
+    let table_elements =
+        ZIGZAG.map(|natural| ((base[natural] as u32 * scale + 50) / 100).clamp(1, 255) as u16);
+
+    QuantizationTable {
+        flag: destination,
+        table_elements,
+    }
+}
+
+const LUMINANCE_DC_CODE_LENGTHS: [u8; 16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const CHROMINANCE_DC_CODE_LENGTHS: [u8; 16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_VALUES: [u8; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+const LUMINANCE_AC_CODE_LENGTHS: [u8; 16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7D];
+const LUMINANCE_AC_VALUES: [u8; 162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12, 0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xA1, 0x08, 0x23, 0x42, 0xB1, 0xC1, 0x15, 0x52, 0xD1, 0xF0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0A, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2A, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5, 0xA6, 0xA7,
+    0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3, 0xC4, 0xC5,
+    0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA, 0xE1, 0xE2,
+    0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF1, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+const CHROMINANCE_AC_CODE_LENGTHS: [u8; 16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const CHROMINANCE_AC_VALUES: [u8; 162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21, 0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91, 0xA1, 0xB1, 0xC1, 0x09, 0x23, 0x33, 0x52, 0xF0,
+    0x15, 0x62, 0x72, 0xD1, 0x0A, 0x16, 0x24, 0x34, 0xE1, 0x25, 0xF1, 0x17, 0x18, 0x19, 0x1A, 0x26,
+    0x27, 0x28, 0x29, 0x2A, 0x35, 0x36, 0x37, 0x38, 0x39, 0x3A, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4A, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59, 0x5A, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6A, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79, 0x7A, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8A, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98, 0x99, 0x9A, 0xA2, 0xA3, 0xA4, 0xA5,
+    0xA6, 0xA7, 0xA8, 0xA9, 0xAA, 0xB2, 0xB3, 0xB4, 0xB5, 0xB6, 0xB7, 0xB8, 0xB9, 0xBA, 0xC2, 0xC3,
+    0xC4, 0xC5, 0xC6, 0xC7, 0xC8, 0xC9, 0xCA, 0xD2, 0xD3, 0xD4, 0xD5, 0xD6, 0xD7, 0xD8, 0xD9, 0xDA,
+    0xE2, 0xE3, 0xE4, 0xE5, 0xE6, 0xE7, 0xE8, 0xE9, 0xEA, 0xF2, 0xF3, 0xF4, 0xF5, 0xF6, 0xF7, 0xF8,
+    0xF9, 0xFA,
+];
+
+/// The example Huffman table of K.3 for `class` at `destination` (0 for luminance, 1 for
+/// chrominance). These perform well on most 8-bit images.
+pub fn standard_huffman_table(class: HuffmanTableClass, destination: u8) -> HuffmanTable {
+    let (code_lengths, values): (_, &[u8]) = match (class, destination) {
+        (HuffmanTableClass::DC, 0) => (LUMINANCE_DC_CODE_LENGTHS, &DC_VALUES),
+        (HuffmanTableClass::DC, _) => (CHROMINANCE_DC_CODE_LENGTHS, &DC_VALUES),
+        (HuffmanTableClass::AC, 0) => (LUMINANCE_AC_CODE_LENGTHS, &LUMINANCE_AC_VALUES),
+        (HuffmanTableClass::AC, _) => (CHROMINANCE_AC_CODE_LENGTHS, &CHROMINANCE_AC_VALUES),
+    };
+
+    HuffmanTable {
+        flag: ((class == HuffmanTableClass::AC) as u8) << 4 | destination,
+        code_lengths,
+        values: values.to_vec(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scaled_quantization_table() {
+        let table = scaled_quantization_table(0, 50);
+        assert_eq!(table.table_elements[..3], [16, 11, 12]);
+
+        let table = scaled_quantization_table(1, 100);
+        assert!(table.table_elements.iter().all(|&q| q == 1));
+
+        let table = scaled_quantization_table(0, 1);
+        assert!(table.table_elements.iter().all(|&q| q == 255));
+    }
+
+    #[test]
+    fn test_standard_huffman_tables_are_complete() {
+        for class in [HuffmanTableClass::DC, HuffmanTableClass::AC] {
+            for destination in 0..2 {
+                let table = standard_huffman_table(class, destination);
+
+                let num_codes = table
+                    .code_lengths
+                    .iter()
+                    .map(|&n| n as usize)
+                    .sum::<usize>();
+                assert_eq!(num_codes, table.values.len());
+                assert_eq!(table.table_class(), class);
+                assert_eq!(table.table_identifier(), destination);
+            }
+        }
+    }
+}
@@ -0,0 +1,660 @@
+use crate::{
+    exif::{
+        grammar::{
+            ByteOrder, Orientation, Value, EXIF_IFD_POINTER, ORIENTATION, PIXEL_X_DIMENSION,
+            PIXEL_Y_DIMENSION,
+        },
+        ifd::IfdReader,
+    },
+    jpeg::{
+        decoder::JpegDecoder,
+        encoder::{EncoderComponent, JpegEncoder},
+        frame::{install_quantization_tables, Frame},
+        grammar::QuantizationTable,
+        idct::ZIGZAG,
+    },
+};
+use anyhow::{ensure, Result};
+use std::io::Write;
+
+/// Whether `orientation` reads the stored image's lines right to left.
+const fn mirrors_lines(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::FlipHorizontal
+            | Orientation::Rotate180
+            | Orientation::Transverse
+            | Orientation::Rotate270
+    )
+}
+
+/// Whether `orientation` reads the stored image's columns bottom to top.
+const fn mirrors_columns(orientation: Orientation) -> bool {
+    matches!(
+        orientation,
+        Orientation::Rotate180
+            | Orientation::FlipVertical
+            | Orientation::Rotate90
+            | Orientation::Transverse
+    )
+}
+
+/// Rewrites an Exif APP1 segment for an image that has been transformed to `orientation` and
+/// is now `width` by `height` pixels. A transform that undoes the stored orientation resets it
+/// to normal; any other leaves it alone, as jpegtran does. The pixel dimensions are updated to
+/// match. Returns `None` for segments that can't be parsed, which are carried over as is.
+fn update_exif(
+    segment: &[u8],
+    orientation: Orientation,
+    width: usize,
+    height: usize,
+) -> Option<Vec<u8>> {
+    const IDENTIFIER: &[u8] = b"Exif\0\0";
+
+    let tiff = segment.strip_prefix(IDENTIFIER)?;
+    let mut reader = IfdReader::new(tiff).ok()?;
+    let byte_order = reader.byte_order();
+
+    let primary_offset = reader.next_ifd_offset();
+    let primary = reader.read_ifd(primary_offset).ok()?;
+    let primary_entries = reader.entry_offsets(primary_offset).ok()?;
+
+    let (exif, exif_entries) = match primary.get(EXIF_IFD_POINTER).and_then(Value::as_u32) {
+        Some(offset) => (
+            reader.read_ifd(offset as usize).ok()?,
+            reader.entry_offsets(offset as usize).ok()?,
+        ),
+        None => Default::default(),
+    };
+
+    let mut rewritten = segment.to_vec();
+    let tiff = &mut rewritten[IDENTIFIER.len()..];
+
+    // A single SHORT, stored in place of the value offset.
+    if let (Some(Value::Short(stored)), Some(&entry)) =
+        (primary.get(ORIENTATION), primary_entries.get(&ORIENTATION))
+    {
+        if stored.first() == Some(&(orientation as u16)) {
+            tiff[entry + 8..entry + 10].copy_from_slice(&match byte_order {
+                ByteOrder::LittleEndian => 1u16.to_le_bytes(),
+                ByteOrder::BigEndian => 1u16.to_be_bytes(),
+            });
+        }
+    }
+
+    // Either dimension may be a SHORT or a LONG. Changed ones are rewritten as a single LONG,
+    // which also fits in place of the value offset.
+    for (tag, dimension) in [(PIXEL_X_DIMENSION, width), (PIXEL_Y_DIMENSION, height)] {
+        let dimension = u32::try_from(dimension).ok()?;
+
+        if let (Some(stored), Some(&entry)) = (exif.get(tag), exif_entries.get(&tag)) {
+            if stored.as_u32() != Some(dimension) {
+                let (long, count, value) = match byte_order {
+                    ByteOrder::LittleEndian => (
+                        4u16.to_le_bytes(),
+                        1u32.to_le_bytes(),
+                        dimension.to_le_bytes(),
+                    ),
+                    ByteOrder::BigEndian => (
+                        4u16.to_be_bytes(),
+                        1u32.to_be_bytes(),
+                        dimension.to_be_bytes(),
+                    ),
+                };
+
+                tiff[entry + 2..entry + 4].copy_from_slice(&long);
+                tiff[entry + 4..entry + 8].copy_from_slice(&count);
+                tiff[entry + 8..entry + 12].copy_from_slice(&value);
+            }
+        }
+    }
+
+    Some(rewritten)
+}
+
+/// Rotates, flips and crops JPEGs without loss by rearranging their quantized DCT coefficients
+/// instead of decoding and re-encoding the pixels, in the manner of jpegtran.
+///
+/// Every transform maps whole blocks to whole blocks, so the partial MCUs along an edge that
+/// would end up on the leading edge of the output are dropped. Transformed files are written
+/// as a single sequential scan with optimized Huffman tables, carrying over the source's APPn
+/// and COM segments. Exif orientation is reset when the transform undoes it, and the Exif pixel
+/// dimensions follow the output.
+pub struct JpegTransformer<W: Write> {
+    encoder: JpegEncoder<W>,
+    orientation: Orientation,
+    crop: Option<(u32, u32, u32, u32)>,
+}
+
+impl<W: Write> JpegTransformer<W> {
+    /// Creates a transformer that leaves the image as is.
+    pub const fn new(writer: W) -> Self {
+        Self {
+            encoder: JpegEncoder::new(writer).optimize_huffman_tables(true),
+            orientation: Orientation::Normal,
+            crop: None,
+        }
+    }
+
+    /// Sets the transform, expressed as the orientation the stored image is displayed in. For
+    /// example, `Orientation::Rotate90` rotates the image 90 degrees clockwise.
+    pub const fn orientation(mut self, orientation: Orientation) -> Self {
+        self.orientation = orientation;
+        self
+    }
+
+    /// Crops the transformed image to `width` by `height` pixels starting at (`x`, `y`). The
+    /// offsets must fall on MCU boundaries of the transformed image.
+    pub const fn crop(mut self, x: u32, y: u32, width: u32, height: u32) -> Self {
+        self.crop = Some((x, y, width, height));
+        self
+    }
+
+    /// Transforms the JPEG file `data`. Lossless JPEGs have no coefficients to rearrange and are
+    /// not supported.
+    pub fn transform(&mut self, data: &[u8]) -> Result<()> {
+        let (jfif, frame) = JpegDecoder::new(data).decode_frame()?;
+
+        ensure!(
+            frame.block_size == 8,
+            "Lossless JPEGs cannot be transformed in the DCT domain."
+        );
+
+        let orientation = self.orientation;
+        let transposes = orientation.swaps_dimensions();
+
+        let mcu_width = 8 * frame.max_horizontal_sampling;
+        let mcu_height = 8 * frame.max_vertical_sampling;
+
+        // Drop the partial MCUs that would be mirrored onto the leading edge.
+        let mut trimmed_width = frame.width;
+        if mirrors_lines(orientation) {
+            trimmed_width -= trimmed_width % mcu_width;
+        }
+
+        let mut trimmed_height = frame.height;
+        if mirrors_columns(orientation) {
+            trimmed_height -= trimmed_height % mcu_height;
+        }
+
+        ensure!(
+            trimmed_width > 0 && trimmed_height > 0,
+            "A {}x{} image is smaller than an MCU and cannot be transformed.",
+            frame.width,
+            frame.height
+        );
+
+        let (transformed_width, transformed_height, output_mcu_width, output_mcu_height) =
+            if transposes {
+                (trimmed_height, trimmed_width, mcu_height, mcu_width)
+            } else {
+                (trimmed_width, trimmed_height, mcu_width, mcu_height)
+            };
+
+        let (x, y, width, height) = self.crop.map_or(
+            (0, 0, transformed_width, transformed_height),
+            |(x, y, width, height)| (x as usize, y as usize, width as usize, height as usize),
+        );
+
+        ensure!(
+            x.is_multiple_of(output_mcu_width) && y.is_multiple_of(output_mcu_height),
+            "Crop offsets must be multiples of the {output_mcu_width}x{output_mcu_height} MCU."
+        );
+
+        ensure!(
+            width > 0
+                && height > 0
+                && x + width <= transformed_width
+                && y + height <= transformed_height,
+            "Crop region {width}x{height}+{x}+{y} does not fit the \
+             {transformed_width}x{transformed_height} image."
+        );
+
+        let quantization_tables = install_quantization_tables(&jfif.quantization_tables)?;
+
+        let components = transform_components(
+            &frame,
+            orientation,
+            (trimmed_width, trimmed_height),
+            (x / output_mcu_width, y / output_mcu_height),
+            (width, height),
+        );
+
+        // Tables are rearranged like the coefficients they quantize. Only the tables in effect
+        // for the final scan are needed.
+        let quantization_tables = quantization_tables
+            .iter()
+            .flatten()
+            .map(|table| QuantizationTable {
+                flag: table.flag,
+                table_elements: if transposes {
+                    ZIGZAG.map(|natural| {
+                        let transposed = (natural % 8) * 8 + natural / 8;
+                        let k = ZIGZAG.iter().position(|&n| n == transposed).unwrap();
+
+                        table.table_elements[k]
+                    })
+                } else {
+                    table.table_elements
+                },
+            })
+            .collect::<Vec<_>>();
+
+        for component in &components {
+            ensure!(
+                quantization_tables
+                    .iter()
+                    .any(|table| table.table_identifier() == component.quantization_table_selector),
+                "Missing quantization table {}",
+                component.quantization_table_selector
+            );
+        }
+
+        self.encoder.write_marker(0xD8)?;
+
+        for &(marker, parameters) in &jfif.metadata_segments {
+            let updated = match marker {
+                0xFFE1 => update_exif(parameters, orientation, width, height),
+                _ => None,
+            };
+
+            self.encoder
+                .write_segment(marker as u8, updated.as_deref().unwrap_or(parameters))?;
+        }
+
+        self.encoder.write_frame(
+            &quantization_tables,
+            &components,
+            frame.precision,
+            width,
+            height,
+        )?;
+        self.encoder.write_marker(0xD9)?;
+
+        Ok(())
+    }
+}
+
+/// Rearranges the blocks of every component of `frame` into the cropped output, which starts
+/// `mcu_x` by `mcu_y` MCUs into the transformed image and spans `width` by `height` pixels.
+fn transform_components(
+    frame: &Frame,
+    orientation: Orientation,
+    (trimmed_width, trimmed_height): (usize, usize),
+    (mcu_x, mcu_y): (usize, usize),
+    (width, height): (usize, usize),
+) -> Vec<EncoderComponent> {
+    let transposes = orientation.swaps_dimensions();
+
+    let (max_horizontal_sampling, max_vertical_sampling) = if transposes {
+        (frame.max_vertical_sampling, frame.max_horizontal_sampling)
+    } else {
+        (frame.max_horizontal_sampling, frame.max_vertical_sampling)
+    };
+
+    let mcus_per_line = width.div_ceil(8 * max_horizontal_sampling);
+    let mcus_per_column = height.div_ceil(8 * max_vertical_sampling);
+
+    frame
+        .components
+        .iter()
+        .enumerate()
+        .map(|(i, component)| {
+            // The blocks covering the component's samples once the image has been trimmed. Along
+            // a mirrored axis the trimmed image spans whole MCUs, so these are exact.
+            let source_width = (trimmed_width * component.horizontal_sampling_factor)
+                .div_ceil(frame.max_horizontal_sampling)
+                .div_ceil(8);
+            let source_height = (trimmed_height * component.vertical_sampling_factor)
+                .div_ceil(frame.max_vertical_sampling)
+                .div_ceil(8);
+
+            let (h, v) = if transposes {
+                (
+                    component.vertical_sampling_factor,
+                    component.horizontal_sampling_factor,
+                )
+            } else {
+                (
+                    component.horizontal_sampling_factor,
+                    component.vertical_sampling_factor,
+                )
+            };
+
+            let blocks_per_line = mcus_per_line * h;
+            let blocks_per_column = mcus_per_column * v;
+
+            let blocks = (0..blocks_per_column)
+                .flat_map(|block_y| (0..blocks_per_line).map(move |block_x| (block_x, block_y)))
+                .map(|(block_x, block_y)| {
+                    let (source_x, source_y) = orientation.source_position(
+                        (mcu_x * h + block_x) as u32,
+                        (mcu_y * v + block_y) as u32,
+                        source_width as u32,
+                        source_height as u32,
+                    );
+
+                    let (source_x, source_y) = (source_x as usize, source_y as usize);
+
+                    // Blocks past the source's padding only pad the output's last MCUs, and are
+                    // never displayed.
+                    if source_x >= component.blocks_per_line
+                        || source_y >= component.blocks_per_column
+                    {
+                        return [0; 64];
+                    }
+
+                    let coefficients =
+                        &component.coefficients[source_y * component.blocks_per_line + source_x];
+
+                    transform_block(coefficients, orientation)
+                })
+                .collect();
+
+            EncoderComponent {
+                identifier: component.identifier,
+                horizontal_sampling_factor: h,
+                vertical_sampling_factor: v,
+                quantization_table_selector: component.quantization_table_selector,
+                table_destination: (i > 0) as u8,
+                blocks_per_line,
+                blocks,
+            }
+        })
+        .collect()
+}
+
+/// Transforms the coefficients of a block, given in natural order, returning them in zig-zag
+/// order.
+///
+/// Transposing the block transposes its coefficients, and mirroring it along an axis negates
+/// the coefficients of odd frequencies along that axis.
+fn transform_block(coefficients: &[i16; 64], orientation: Orientation) -> [i16; 64] {
+    let transposes = orientation.swaps_dimensions();
+
+    ZIGZAG.map(|natural| {
+        let (u, v) = (natural % 8, natural / 8);
+        let (source_u, source_v) = if transposes { (v, u) } else { (u, v) };
+
+        let mut coefficient = coefficients[source_v * 8 + source_u];
+
+        if mirrors_lines(orientation) && source_u % 2 == 1 {
+            coefficient = -coefficient;
+        }
+
+        if mirrors_columns(orientation) && source_v % 2 == 1 {
+            coefficient = -coefficient;
+        }
+
+        coefficient
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::{grammar::ImageExt, ImageReader},
+        jpeg::{ChromaSubsampling, JpegDecoder},
+        png::PngDecoder,
+    };
+
+    const ORIENTATIONS: [Orientation; 8] = [
+        Orientation::Normal,
+        Orientation::FlipHorizontal,
+        Orientation::Rotate180,
+        Orientation::FlipVertical,
+        Orientation::Transpose,
+        Orientation::Rotate90,
+        Orientation::Transverse,
+        Orientation::Rotate270,
+    ];
+
+    fn transform(
+        data: &[u8],
+        orientation: Orientation,
+        crop: Option<(u32, u32, u32, u32)>,
+    ) -> Result<Vec<u8>> {
+        let mut transformed = Vec::new();
+        let mut transformer = JpegTransformer::new(&mut transformed).orientation(orientation);
+
+        if let Some((x, y, width, height)) = crop {
+            transformer = transformer.crop(x, y, width, height);
+        }
+
+        transformer.transform(data)?;
+
+        Ok(transformed)
+    }
+
+    /// Checks that `transformed` shows the `source` image, trimmed to `trimmed_width` by
+    /// `trimmed_height`, in `orientation` and cropped to start at (`x`, `y`). Upsampled chroma
+    /// is interpolated in a different direction, so samples are only expected to be close.
+    fn assert_transformed(
+        source: &[u8],
+        transformed: &[u8],
+        orientation: Orientation,
+        (trimmed_width, trimmed_height): (u32, u32),
+        (x, y): (u32, u32),
+    ) -> Result<()> {
+        let source = JpegDecoder::new(source).decode()?;
+        let transformed = JpegDecoder::new(transformed).decode()?;
+
+        let (width, height) = transformed.dimensions();
+        let (source_rgb, transformed_rgb) = (source.rgb8(), transformed.rgb8());
+
+        let mut error = 0;
+
+        for ty in 0..height {
+            for tx in 0..width {
+                let (sx, sy) =
+                    orientation.source_position(tx + x, ty + y, trimmed_width, trimmed_height);
+
+                let s = ((sy * source.width() + sx) * 3) as usize;
+                let t = ((ty * width + tx) * 3) as usize;
+
+                for c in 0..3 {
+                    error += source_rgb[s + c].abs_diff(transformed_rgb[t + c]) as u32;
+                }
+            }
+        }
+
+        let mean_absolute_error = error as f64 / transformed_rgb.len() as f64;
+        assert!(
+            mean_absolute_error < 1.0,
+            "{orientation:?}: mean absolute error too large: {mean_absolute_error}"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_whole_mcus() -> Result<()> {
+        // A 32x32 image spans whole MCUs, so no transform trims it. 4:2:2 sampling makes the
+        // MCUs rectangular, and transposing swaps the sampling factors.
+        let data = std::fs::read("./test_suite/basn2c08.png")?;
+        let png = PngDecoder::new(&data).decode()?;
+
+        for chroma_subsampling in [ChromaSubsampling::Chroma420, ChromaSubsampling::Chroma422] {
+            let mut source = Vec::new();
+            JpegEncoder::new(&mut source)
+                .quality(90)
+                .chroma_subsampling(chroma_subsampling)
+                .encode(&png)?;
+
+            for orientation in ORIENTATIONS {
+                let transformed = transform(&source, orientation, None)?;
+
+                let jpeg = JpegDecoder::new(&transformed).decode()?;
+                assert_eq!(jpeg.dimensions(), (32, 32));
+
+                assert_transformed(&source, &transformed, orientation, (32, 32), (0, 0))?;
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_trims_partial_mcus() -> Result<()> {
+        // 40x24 with 16x16 MCUs, so mirroring lines drops 8 columns and mirroring columns drops
+        // 8 lines. The source is arithmetic coded and carries its own tables.
+        let source = std::fs::read("./tests/arithmetic_reference.jpg")?;
+
+        for orientation in ORIENTATIONS {
+            let trimmed_width = if mirrors_lines(orientation) { 32 } else { 40 };
+            let trimmed_height = if mirrors_columns(orientation) { 16 } else { 24 };
+
+            let transformed = transform(&source, orientation, None)?;
+            let jpeg = JpegDecoder::new(&transformed).decode()?;
+
+            let expected_dimensions = if orientation.swaps_dimensions() {
+                (trimmed_height, trimmed_width)
+            } else {
+                (trimmed_width, trimmed_height)
+            };
+
+            assert_eq!(jpeg.dimensions(), expected_dimensions, "{orientation:?}");
+
+            assert_transformed(
+                &source,
+                &transformed,
+                orientation,
+                (trimmed_width, trimmed_height),
+                (0, 0),
+            )?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_is_lossless() -> Result<()> {
+        let source = std::fs::read("./tests/tower_progressive.jpg")?;
+        let original = JpegDecoder::new(&source).decode()?;
+
+        // Four quarter turns restore the original coefficients, and so the original pixels. The
+        // fixture spans whole MCUs.
+        let mut transformed = source;
+        for _ in 0..4 {
+            transformed = transform(&transformed, Orientation::Rotate90, None)?;
+        }
+
+        let restored = JpegDecoder::new(&transformed).decode()?;
+
+        assert_eq!(restored.dimensions(), original.dimensions());
+        assert_eq!(restored.rgb8(), original.rgb8());
+
+        // Metadata segments are carried over.
+        assert_eq!(restored.comments(), original.comments());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_crop() -> Result<()> {
+        let source = std::fs::read("./tests/arithmetic_reference.jpg")?;
+
+        // Rotated, the image is 16x40 with 16x16 MCUs.
+        let transformed = transform(&source, Orientation::Rotate90, Some((0, 16, 13, 20)))?;
+        let jpeg = JpegDecoder::new(&transformed).decode()?;
+        assert_eq!(jpeg.dimensions(), (13, 20));
+
+        assert_transformed(
+            &source,
+            &transformed,
+            Orientation::Rotate90,
+            (40, 16),
+            (0, 16),
+        )?;
+
+        let transformed = transform(&source, Orientation::Normal, Some((16, 16, 24, 8)))?;
+        let jpeg = JpegDecoder::new(&transformed).decode()?;
+        assert_eq!(jpeg.dimensions(), (24, 8));
+
+        assert_transformed(
+            &source,
+            &transformed,
+            Orientation::Normal,
+            (40, 24),
+            (16, 16),
+        )?;
+
+        // Offsets off an MCU boundary, and regions past the image, are rejected.
+        assert!(transform(&source, Orientation::Normal, Some((8, 0, 8, 8))).is_err());
+        assert!(transform(&source, Orientation::Normal, Some((32, 0, 16, 8))).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_updates_exif() -> Result<()> {
+        // The fixture is stored 48x32 and displayed rotated by 90 degrees.
+        let path = "./tests/exif_orientation.jpg";
+        let source = std::fs::read(path)?;
+
+        let exif_of = |transformed: &[u8]| -> Result<(Orientation, Option<u32>, Option<u32>)> {
+            let jpeg = JpegDecoder::new(transformed).decode()?;
+            let exif = jpeg.exif().expect("Exif segment");
+            assert_eq!(exif.make(), Some("norm"));
+
+            Ok((
+                exif.orientation(),
+                exif.get(PIXEL_X_DIMENSION).and_then(Value::as_u32),
+                exif.get(PIXEL_Y_DIMENSION).and_then(Value::as_u32),
+            ))
+        };
+
+        // Rotating by the stored orientation undoes it.
+        let transformed = transform(&source, Orientation::Rotate90, None)?;
+        assert_eq!(
+            exif_of(&transformed)?,
+            (Orientation::Normal, Some(32), Some(48))
+        );
+
+        // Reading either file with its orientation applied shows the same image.
+        let reader = ImageReader::new().apply_orientation(true);
+        let displayed = reader.read_from_path(path, None)?;
+        let baked = reader.read_from_bytes(&transformed, None)?;
+
+        assert_eq!(baked.dimensions(), (32, 48));
+        assert_eq!(baked.dimensions(), displayed.dimensions());
+
+        let mean_absolute_error = baked
+            .rgb8()
+            .iter()
+            .zip(displayed.rgb8().iter())
+            .map(|(&a, &b)| a.abs_diff(b) as f64)
+            .sum::<f64>()
+            / baked.rgb8().len() as f64;
+        assert!(mean_absolute_error < 1.0, "MAE: {mean_absolute_error}");
+
+        // Any other transform leaves the orientation alone.
+        let flipped = transform(&source, Orientation::FlipVertical, None)?;
+        assert_eq!(
+            exif_of(&flipped)?,
+            (Orientation::Rotate90, Some(48), Some(32))
+        );
+
+        let rotated = transform(&source, Orientation::Rotate180, None)?;
+        assert_eq!(
+            exif_of(&rotated)?,
+            (Orientation::Rotate90, Some(48), Some(32))
+        );
+
+        // Cropping shrinks the dimensions.
+        let cropped = transform(&source, Orientation::Rotate90, Some((0, 16, 32, 16)))?;
+        assert_eq!(
+            exif_of(&cropped)?,
+            (Orientation::Normal, Some(32), Some(16))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_transform_lossless_jpeg() {
+        let source = std::fs::read("./tests/lossless_rgb12.jpg").unwrap();
+        assert!(transform(&source, Orientation::Rotate90, None).is_err());
+    }
+}
@@ -3,11 +3,23 @@
 #[cfg(target_arch = "wasm32")]
 use wasm_bindgen::prelude::*;
 
+pub mod bmp;
+pub mod exif;
+pub mod exr;
 pub mod font;
+pub mod gif;
+pub mod hdr;
+pub mod icc;
+pub mod ico;
 pub mod image;
 pub mod jpeg;
 pub mod png;
+pub mod pnm;
+pub mod qoi;
 pub mod renderer;
+pub mod tga;
+pub mod tiff;
+pub mod webp;
 
 pub mod event_log;
 pub(crate) mod impl_read;
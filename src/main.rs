@@ -1,8 +1,5 @@
 use anyhow::{anyhow, Result};
-use normeditor::{
-    image::{grammar::ImageKind, ImageReader},
-    renderer,
-};
+use normeditor::{image::ImageReader, renderer};
 use pollster::block_on;
 
 fn main() -> Result<()> {
@@ -11,15 +8,10 @@ fn main() -> Result<()> {
         .next()
         .ok_or_else(|| anyhow!("Failed to read image path"))?;
 
-    let image_kind = args
-        .next()
-        .map(|t| match t.as_bytes() {
-            b"jpg" | b"jpeg" | b"j" => ImageKind::Jpeg,
-            _ => ImageKind::Png,
-        })
-        .unwrap_or(ImageKind::Png);
-
-    let image = ImageReader::read_from_path(&image_path, Some(image_kind))?;
+    let image = ImageReader::new()
+        .apply_orientation(true)
+        .apply_color_profile(true)
+        .read_from_path(&image_path, None)?;
 
     let _ = block_on(renderer::run(image));
 
@@ -1,4 +1,8 @@
-use crate::png::{grammar::ImageHeader, scanline_writer::ScanlineWriter};
+use crate::{
+    icc::grammar::IccProfile,
+    image::grammar::Gamma,
+    png::{grammar::ImageHeader, scanline_writer::ScanlineWriter},
+};
 use anyhow::Result;
 use flate2::{write::ZlibEncoder, Compression};
 use std::io::Write;
@@ -66,6 +70,39 @@ impl PngChunk for IHDRChunk<'_> {
     }
 }
 
+#[derive(Debug)]
+pub struct GAMAChunk {
+    pub gamma: Gamma,
+}
+
+impl PngChunk for GAMAChunk {
+    const NAME: [u8; 4] = *b"gAMA";
+
+    fn data(&self) -> Result<Vec<u8>> {
+        Ok(self.gamma.scaled().to_be_bytes().to_vec())
+    }
+}
+
+#[derive(Debug)]
+pub struct ICCPChunk<'a> {
+    pub profile: &'a IccProfile,
+}
+
+impl PngChunk for ICCPChunk<'_> {
+    const NAME: [u8; 4] = *b"iCCP";
+
+    fn data(&self) -> Result<Vec<u8>> {
+        // The profile name, a null separator and compression method 0.
+        let mut buffer = b"ICC profile\0\0".to_vec();
+
+        let mut encoder = ZlibEncoder::new(buffer, Compression::default());
+        encoder.write_all(self.profile.data())?;
+        buffer = encoder.finish()?;
+
+        Ok(buffer)
+    }
+}
+
 // #[derive(Debug)]
 // pub struct PLTEChunk; // todo!, how does the palette chunk serialize?
 
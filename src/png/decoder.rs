@@ -1,7 +1,9 @@
 #[cfg(feature = "time")]
-use crate::util::event_log::{log_event, Event};
+use crate::event_log::{log_event, Event};
 use crate::{
-    image::grammar::ColorType,
+    exif::ExifDecoder,
+    icc::IccDecoder,
+    image::grammar::{ColorType, Gamma},
     impl_read_for_datatype, impl_read_slice,
     png::{
         crc32::compute_crc,
@@ -59,13 +61,36 @@ impl<'a> PngDecoder<'a> {
         // the concatenation of the contents of all image data chunks.
         let mut compressed_stream = Vec::new();
 
-        let mut gamma = 0;
+        let mut gamma = None;
+        let mut exif = None;
+        let mut icc_profile = None;
+        let mut palette = Vec::new();
 
         while let Some(chunk) = chunks.peek() {
-            // todo, how would you collect palettes if ColorType::Palette?
             // todo, how do you collect ancillary chunks?
+            if let Chunk::Palette(entries) = chunk {
+                palette = entries.clone().map(|e| [e[0], e[1], e[2]]).collect();
+            }
+
             if let &Chunk::Gamma(g) = chunk {
-                gamma = g;
+                // A gamma of 0 is invalid and ignored.
+                gamma = Some(Gamma(g)).filter(|gamma| gamma.0 != 0);
+            }
+
+            if let &Chunk::Exif(data) = chunk {
+                // Malformed metadata should not prevent displaying the image.
+                exif = ExifDecoder::new(data).decode().ok();
+            }
+
+            if let &Chunk::IccProfile(_, compressed_profile) = chunk {
+                let mut profile = Vec::new();
+
+                if ZlibDecoder::new(compressed_profile)
+                    .read_to_end(&mut profile)
+                    .is_ok()
+                {
+                    icc_profile = IccDecoder::new(&profile).decode().ok();
+                }
             }
 
             if let &Chunk::ImageData(sub_data) = chunk {
@@ -108,6 +133,9 @@ impl<'a> PngDecoder<'a> {
         Ok(Png {
             image_header,
             gamma,
+            exif,
+            icc_profile,
+            palette,
             pixel_buffer,
         })
     }
@@ -184,6 +212,23 @@ impl<'a> PngDecoder<'a> {
                 b"IDAT" => Chunk::ImageData(self.read_slice(length)?),
                 b"IEND" => break,
                 b"gAMA" => Chunk::Gamma(self.read_u32()?),
+                b"eXIf" => Chunk::Exif(self.read_slice(length)?),
+                b"iCCP" => {
+                    let chunk = self.read_slice(length)?;
+
+                    // A profile name of 1 to 79 bytes, a null separator and the compression
+                    // method, which is always 0 (zlib).
+                    match chunk.iter().position(|&b| b == 0) {
+                        Some(separator) if chunk.get(separator + 1) == Some(&0) => {
+                            Chunk::IccProfile(&chunk[..separator], &chunk[separator + 2..])
+                        }
+                        // Malformed metadata should not prevent displaying the image.
+                        _ => {
+                            self.skip_crc()?;
+                            continue;
+                        }
+                    }
+                }
                 // b"sRGB" => todo!("Parse srgb chunks"),
                 b"tEXt" => {
                     let cursor_start = self.cursor;
@@ -243,7 +288,11 @@ impl<'a> PngDecoder<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::{image::grammar::ImageExt, test_file_parser::parse_test_file};
+    use crate::{
+        exif::grammar::{ByteOrder, Orientation, Value, COPYRIGHT},
+        image::grammar::ImageExt,
+        test_file_parser::parse_test_file,
+    };
     use anyhow::anyhow;
     use image::ImageReader;
     use pretty_assertions::assert_eq;
@@ -313,6 +362,42 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_decode_malformed_icc_profile() -> Result<()> {
+        // display_p3.png with an iCCP compression method of 1.
+        let content = std::fs::read("./tests/corrupt_iccp.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+
+        let content = std::fs::read("./tests/display_p3.png")?;
+        let reference = PngDecoder::new(&content).decode()?;
+
+        assert!(png.icc_profile().is_none());
+        assert!(reference.icc_profile().is_some());
+        assert_eq!(png.rgb8(), reference.rgb8());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_exif() -> Result<()> {
+        let content = std::fs::read("./test_suite/exif2c08.png")?;
+        let png = PngDecoder::new(&content).decode()?;
+
+        let exif = png.exif().ok_or_else(|| anyhow!("Expected eXIf chunk."))?;
+
+        assert_eq!(exif.byte_order(), ByteOrder::BigEndian);
+        assert_eq!(exif.orientation(), Orientation::Normal);
+        assert_eq!(
+            exif.get(COPYRIGHT),
+            Some(&Value::Ascii("2017 Willem van Schaik".into()))
+        );
+
+        // ExifVersion, from the Exif IFD.
+        assert_eq!(exif.get(0x9000), Some(&Value::Undefined(b"0220".to_vec())));
+
+        Ok(())
+    }
+
     #[test]
     fn test_filter_0() -> Result<()> {
         // generate_blob("./test_suite/f00n2c08")?;
@@ -1,4 +1,8 @@
-use crate::image::grammar::{ColorType, ImageExt};
+use crate::{
+    exif::grammar::Exif,
+    icc::grammar::IccProfile,
+    image::grammar::{ColorType, Gamma, ImageExt},
+};
 use anyhow::{bail, Result};
 #[cfg(test)]
 use std::io::Write;
@@ -13,6 +17,9 @@ pub enum Chunk<'a> {
     ImageData(&'a [u8]),
     TextData(BTreeMap<Cow<'a, [u8]>, Cow<'a, [u8]>>),
     Gamma(u32),
+    Exif(&'a [u8]),
+    /// The profile name and the compressed profile of an iCCP chunk.
+    IccProfile(&'a [u8], &'a [u8]),
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -62,10 +69,14 @@ impl TryFrom<u8> for Filter {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq)]
 pub struct Png {
     pub(crate) image_header: ImageHeader,
-    pub(crate) gamma: u32,
+    pub(crate) gamma: Option<Gamma>,
+    pub(crate) exif: Option<Exif>,
+    pub(crate) icc_profile: Option<IccProfile>,
+    /// The PLTE entries palette images index into, empty for other color types.
+    pub(crate) palette: Vec<[u8; 3]>,
     pub(crate) pixel_buffer: Vec<u8>,
 }
 
@@ -78,7 +89,7 @@ impl ImageExt for Png {
         self.image_header.height
     }
 
-    fn gamma(&self) -> u32 {
+    fn gamma(&self) -> Option<Gamma> {
         self.gamma
     }
 
@@ -88,10 +99,10 @@ impl ImageExt for Png {
 
     fn rgb8(&self) -> Cow<'_, [u8]> {
         match self.color_type() {
-            ColorType::RGB => Cow::from(&self.pixel_buffer),
+            ColorType::RGB => self.samples8(),
             ColorType::RGBA => {
                 let b = self
-                    .pixel_buffer
+                    .samples8()
                     .chunks_exact(4)
                     .flat_map(|b| [b[0], b[1], b[2]])
                     .collect::<Vec<_>>();
@@ -100,7 +111,7 @@ impl ImageExt for Png {
             }
             ColorType::GrayscaleAlpha => {
                 let b = self
-                    .pixel_buffer
+                    .samples8()
                     .chunks_exact(2)
                     .flat_map(|b| [b[0], b[0], b[0]])
                     .collect::<Vec<u8>>();
@@ -109,48 +120,55 @@ impl ImageExt for Png {
             }
             ColorType::Grayscale => {
                 let b = self
-                    .pixel_buffer
+                    .samples8()
                     .iter()
                     .flat_map(|&y| [y, y, y])
                     .collect::<Vec<u8>>();
 
                 Cow::from(b)
             }
-            foreign => unimplemented!("{:?}", foreign),
+            ColorType::Palette => Cow::from(self.palette_colors().flatten().collect::<Vec<_>>()),
         }
     }
 
     fn rgba8(&self) -> Cow<'_, [u8]> {
         match self.color_type() {
-            ColorType::RGBA => Cow::from(&self.pixel_buffer),
+            ColorType::RGBA => self.samples8(),
             ColorType::RGB => {
                 let b = self
-                    .pixel_buffer
+                    .samples8()
                     .chunks_exact(3)
-                    .flat_map(|b| [b[0], b[1], b[2], 0])
+                    .flat_map(|b| [b[0], b[1], b[2], 0xFF])
                     .collect::<Vec<_>>();
 
                 Cow::from(b)
             }
             ColorType::Grayscale => {
                 let b = self
-                    .pixel_buffer
+                    .samples8()
                     .iter()
-                    .flat_map(|&y| [y, y, y, 0])
+                    .flat_map(|&y| [y, y, y, 0xFF])
                     .collect::<Vec<_>>();
 
                 Cow::from(b)
             }
             ColorType::GrayscaleAlpha => {
                 let b = self
-                    .pixel_buffer
+                    .samples8()
                     .chunks_exact(2)
                     .flat_map(|b| [b[0], b[0], b[0], b[1]])
                     .collect::<Vec<_>>();
 
                 Cow::from(b)
             }
-            foreign => unimplemented!("{:?}", foreign),
+            ColorType::Palette => {
+                let b = self
+                    .palette_colors()
+                    .flat_map(|[r, g, b]| [r, g, b, 0xFF])
+                    .collect::<Vec<_>>();
+
+                Cow::from(b)
+            }
         }
     }
 
@@ -158,16 +176,16 @@ impl ImageExt for Png {
         match self.color_type() {
             ColorType::RGB => {
                 let b = self
-                    .pixel_buffer
+                    .samples8()
                     .chunks_exact(3)
-                    .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]))
+                    .map(|b| u32::from_be_bytes([0xFF, b[0], b[1], b[2]]))
                     .collect::<Vec<u32>>();
 
                 Cow::from(b)
             }
             ColorType::RGBA => {
                 let b = self
-                    .pixel_buffer
+                    .samples8()
                     .chunks_exact(4)
                     .map(|b| u32::from_be_bytes([b[3], b[0], b[1], b[2]]))
                     .collect::<Vec<u32>>();
@@ -176,28 +194,65 @@ impl ImageExt for Png {
             }
             ColorType::Grayscale => {
                 let l = self
-                    .pixel_buffer
+                    .samples8()
                     .iter()
-                    .map(|&b| u32::from_be_bytes([0, b, b, b]))
+                    .map(|&b| u32::from_be_bytes([0xFF, b, b, b]))
                     .collect::<Vec<u32>>();
 
                 Cow::from(l)
             }
             ColorType::GrayscaleAlpha => {
                 let l = self
-                    .pixel_buffer
+                    .samples8()
                     .chunks_exact(2)
                     .map(|b| u32::from_be_bytes([b[1], b[0], b[0], b[0]]))
                     .collect::<Vec<u32>>();
 
                 Cow::from(l)
             }
-            _ => todo!("What do other color type pixels look like?"),
+            ColorType::Palette => {
+                let b = self
+                    .palette_colors()
+                    .map(|[r, g, b]| u32::from_be_bytes([0xFF, r, g, b]))
+                    .collect::<Vec<u32>>();
+
+                Cow::from(b)
+            }
         }
     }
+
+    fn exif(&self) -> Option<&Exif> {
+        self.exif.as_ref()
+    }
+
+    fn icc_profile(&self) -> Option<&IccProfile> {
+        self.icc_profile.as_ref()
+    }
 }
 
 impl Png {
+    /// The decoded samples, scaled to 8 bits if necessary.
+    fn samples8(&self) -> Cow<'_, [u8]> {
+        if self.image_header.bit_depth != 16 {
+            return Cow::from(&self.pixel_buffer);
+        }
+
+        let b = self
+            .pixel_buffer
+            .chunks_exact(2)
+            .map(|b| ((u16::from_be_bytes([b[0], b[1]]) as u32 + 128) / 257) as u8)
+            .collect::<Vec<_>>();
+
+        Cow::from(b)
+    }
+
+    /// The color of each pixel of an 8-bit palette image. Indices past the palette are black.
+    fn palette_colors(&self) -> impl Iterator<Item = [u8; 3]> + '_ {
+        self.pixel_buffer
+            .iter()
+            .map(|&index| self.palette.get(index as usize).copied().unwrap_or([0; 3]))
+    }
+
     #[cfg(test)]
     #[allow(dead_code)]
     pub(crate) fn write_to_binary_blob(&self, path: &str) -> Result<()> {
@@ -211,7 +266,8 @@ impl Png {
         file.write_all(&self.image_header.filter_method.to_be_bytes())?;
         file.write_all(&(self.image_header.interlace_method as u8).to_be_bytes())?;
 
-        file.write(&self.gamma.to_be_bytes())?;
+        // 0 is not a valid gamma, so it stands in for a missing gAMA chunk.
+        file.write_all(&self.gamma.map_or(0, |gamma| gamma.0).to_be_bytes())?;
         file.write_all(&self.pixel_buffer)?;
 
         Ok(())
@@ -257,7 +313,10 @@ impl Png {
                 filter_method: filter_method[0],
                 interlace_method: interlace_method[0] != 0,
             },
-            gamma: u32::from_be_bytes(gamma),
+            gamma: Some(Gamma(u32::from_be_bytes(gamma))).filter(|gamma| gamma.0 != 0),
+            exif: None,
+            icc_profile: None,
+            palette: Vec::new(),
             pixel_buffer,
         })
     }
@@ -5,7 +5,7 @@ use std::io::Write;
 const fn paeth_predict(orig_a: u8, orig_b: u8, orig_c: u8) -> u8 {
     let (a, b, c) = (orig_a as i16, orig_b as i16, orig_c as i16);
 
-    let p = a + b + c;
+    let p = a + b - c;
     let pa = (p - a).abs();
     let pb = (p - b).abs();
     let pc = (p - c).abs();
@@ -0,0 +1,439 @@
+use crate::{
+    image::{DynamicImageBuffer, ImageBuffer},
+    impl_read_slice,
+    pnm::grammar::{Pnm, PnmFormat, PnmHeader, TupleType},
+};
+use anyhow::{anyhow, bail, ensure, Result};
+
+#[derive(Debug)]
+pub struct PnmDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> PnmDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Pnm> {
+        let format = PnmFormat::try_from(self.read_slice(2)?)?;
+
+        let header = if format == PnmFormat::P7 {
+            self.parse_pam_header()?
+        } else {
+            self.parse_header(format)?
+        };
+
+        let samples = self.read_samples(&header)?;
+        let image = build_image(&header, samples)?;
+
+        Ok(Pnm { header, image })
+    }
+
+    fn parse_header(&mut self, format: PnmFormat) -> Result<PnmHeader> {
+        let width = self.read_number()?;
+        let height = self.read_number()?;
+        let maxval = if format.is_bitmap() {
+            1
+        } else {
+            self.read_number()?
+        };
+
+        // A single whitespace character separates the header from a binary raster.
+        if !format.is_ascii() {
+            ensure!(
+                self.read_slice(1)?[0].is_ascii_whitespace(),
+                "Expected whitespace after the Netpbm header."
+            );
+        }
+
+        let (depth, tuple_type) = match format {
+            PnmFormat::P1 | PnmFormat::P4 => (1, TupleType::BlackAndWhite),
+            PnmFormat::P2 | PnmFormat::P5 => (1, TupleType::Grayscale),
+            _ => (3, TupleType::Rgb),
+        };
+
+        validate(PnmHeader {
+            format,
+            width,
+            height,
+            depth,
+            maxval,
+            tuple_type,
+        })
+    }
+
+    /// Reads `KEY value` lines up to `ENDHDR`. Repeated `TUPLTYPE` lines are joined by spaces.
+    fn parse_pam_header(&mut self) -> Result<PnmHeader> {
+        let (mut width, mut height, mut depth, mut maxval) = (None, None, None, None);
+        let mut tuple_type = None::<String>;
+
+        loop {
+            match self.read_token()? {
+                b"ENDHDR" => break,
+                b"WIDTH" => width = Some(self.read_number()?),
+                b"HEIGHT" => height = Some(self.read_number()?),
+                b"DEPTH" => depth = Some(self.read_number()?),
+                b"MAXVAL" => maxval = Some(self.read_number()?),
+                b"TUPLTYPE" => {
+                    let line = self.read_line()?.trim();
+
+                    tuple_type = Some(tuple_type.map_or_else(
+                        || line.to_string(),
+                        |tuple_type| format!("{tuple_type} {line}"),
+                    ));
+                }
+                key => bail!(
+                    "Unrecognized PAM header field: {}",
+                    String::from_utf8_lossy(key)
+                ),
+            }
+        }
+
+        self.read_line()?;
+
+        let missing = |field: &str| anyhow!("PAM header is missing {}.", field);
+
+        let depth = depth.ok_or_else(|| missing("DEPTH"))?;
+        let tuple_type = tuple_type.map_or_else(
+            || match depth {
+                1 => TupleType::Grayscale,
+                2 => TupleType::GrayscaleAlpha,
+                3 => TupleType::Rgb,
+                4 => TupleType::RgbAlpha,
+                _ => TupleType::Custom(String::new()),
+            },
+            |tuple_type| TupleType::from(tuple_type.as_str()),
+        );
+
+        validate(PnmHeader {
+            format: PnmFormat::P7,
+            width: width.ok_or_else(|| missing("WIDTH"))?,
+            height: height.ok_or_else(|| missing("HEIGHT"))?,
+            depth,
+            maxval: maxval.ok_or_else(|| missing("MAXVAL"))?,
+            tuple_type,
+        })
+    }
+
+    fn read_samples(&mut self, header: &PnmHeader) -> Result<Vec<u16>> {
+        let &PnmHeader {
+            format,
+            width,
+            height,
+            depth,
+            maxval,
+            ..
+        } = header;
+
+        let num_samples = width as usize * height as usize * depth as usize;
+
+        // Every ASCII sample takes at least a byte.
+        ensure!(
+            !format.is_ascii() || num_samples <= self.data.len() - self.cursor,
+            "Netpbm data is truncated."
+        );
+
+        let samples = match format {
+            PnmFormat::P1 => (0..num_samples)
+                .map(|_| {
+                    // Bits need no separating whitespace.
+                    self.skip_whitespace_and_comments();
+                    match self.read_slice(1)? {
+                        b"0" => Ok(0),
+                        b"1" => Ok(1),
+                        foreign => bail!("Invalid PBM sample: {:?}", foreign),
+                    }
+                })
+                .collect::<Result<Vec<_>>>()?,
+            PnmFormat::P2 | PnmFormat::P3 => (0..num_samples)
+                .map(|_| {
+                    self.read_number()
+                        .map(|sample| sample.min(u16::MAX as u32) as u16)
+                })
+                .collect::<Result<Vec<_>>>()?,
+            PnmFormat::P4 => {
+                let stride = (width as usize).div_ceil(8);
+                let raster = self.read_slice(stride * height as usize)?;
+
+                raster
+                    .chunks_exact(stride)
+                    .flat_map(|row| {
+                        (0..width as usize).map(|x| ((row[x / 8] >> (7 - x % 8)) & 1) as u16)
+                    })
+                    .collect()
+            }
+            PnmFormat::P5 | PnmFormat::P6 | PnmFormat::P7 if maxval > 255 => self
+                .read_slice(num_samples * 2)?
+                .chunks_exact(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]]))
+                .collect(),
+            PnmFormat::P5 | PnmFormat::P6 | PnmFormat::P7 => self
+                .read_slice(num_samples)?
+                .iter()
+                .map(|&b| b as u16)
+                .collect(),
+        };
+
+        ensure!(
+            samples.iter().all(|&sample| sample as u32 <= maxval),
+            "Netpbm sample exceeds the maxval of {}.",
+            maxval
+        );
+
+        Ok(samples)
+    }
+
+    fn skip_whitespace_and_comments(&mut self) {
+        while let Some(&byte) = self.data.get(self.cursor) {
+            match byte {
+                b'#' => {
+                    while self.data.get(self.cursor).is_some_and(|&b| b != b'\n') {
+                        self.cursor += 1;
+                    }
+                }
+                byte if byte.is_ascii_whitespace() => self.cursor += 1,
+                _ => break,
+            }
+        }
+    }
+
+    fn read_token(&mut self) -> Result<&'a [u8]> {
+        self.skip_whitespace_and_comments();
+
+        let start = self.cursor;
+        while self
+            .data
+            .get(self.cursor)
+            .is_some_and(|b| !b.is_ascii_whitespace() && *b != b'#')
+        {
+            self.cursor += 1;
+        }
+
+        ensure!(self.cursor > start, "Netpbm data is truncated.");
+
+        Ok(&self.data[start..self.cursor])
+    }
+
+    fn read_number(&mut self) -> Result<u32> {
+        let token = self.read_token()?;
+
+        std::str::from_utf8(token)?
+            .parse()
+            .map_err(|_| anyhow!("Invalid Netpbm number: {}", String::from_utf8_lossy(token)))
+    }
+
+    /// Reads up to and including the next line feed, returning the line without it.
+    fn read_line(&mut self) -> Result<&'a str> {
+        let start = self.cursor;
+        while self.data.get(self.cursor).is_some_and(|&b| b != b'\n') {
+            self.cursor += 1;
+        }
+
+        let line = std::str::from_utf8(&self.data[start..self.cursor])?;
+        self.cursor = (self.cursor + 1).min(self.data.len());
+
+        Ok(line)
+    }
+
+    impl_read_slice!();
+}
+
+fn validate(header: PnmHeader) -> Result<PnmHeader> {
+    let PnmHeader {
+        width,
+        height,
+        depth,
+        maxval,
+        ref tuple_type,
+        ..
+    } = header;
+
+    ensure!(width > 0 && height > 0, "Invalid Netpbm dimensions.");
+    ensure!(
+        (1..=u16::MAX as u32).contains(&maxval),
+        "Invalid Netpbm maxval: {}",
+        maxval
+    );
+    ensure!((1..=4).contains(&depth), "Unsupported PAM depth: {}", depth);
+
+    if let Some(tuple_depth) = tuple_type.depth() {
+        ensure!(
+            tuple_depth == depth,
+            "PAM tuple type {} does not have a depth of {}.",
+            tuple_type.name(),
+            depth
+        );
+    }
+
+    Ok(header)
+}
+
+/// Scales samples to 8 bits, or 16 bits if the maxval is above 255. PBM samples are inverted,
+/// since 1 is black.
+fn build_image(header: &PnmHeader, samples: Vec<u16>) -> Result<DynamicImageBuffer> {
+    let &PnmHeader {
+        format,
+        width,
+        height,
+        depth,
+        maxval,
+        ..
+    } = header;
+
+    let samples = if format.is_bitmap() {
+        samples.into_iter().map(|sample| 1 - sample).collect()
+    } else {
+        samples
+    };
+
+    let scale =
+        |target: u32| move |sample: u16| ((sample as u32 * target + maxval / 2) / maxval) as u16;
+
+    let image = if maxval > 255 {
+        let samples = samples.into_iter().map(scale(65535)).collect::<Vec<_>>();
+
+        match depth {
+            1 => ImageBuffer::from_raw(width, height, samples).map(DynamicImageBuffer::Luma16),
+            2 => ImageBuffer::from_raw(width, height, samples).map(DynamicImageBuffer::LumaA16),
+            3 => ImageBuffer::from_raw(width, height, samples).map(DynamicImageBuffer::Rgb16),
+            _ => ImageBuffer::from_raw(width, height, samples).map(DynamicImageBuffer::Rgba16),
+        }
+    } else {
+        let samples = samples
+            .into_iter()
+            .map(|sample| scale(255)(sample) as u8)
+            .collect::<Vec<_>>();
+
+        match depth {
+            1 => ImageBuffer::from_raw(width, height, samples).map(DynamicImageBuffer::Luma8),
+            2 => ImageBuffer::from_raw(width, height, samples).map(DynamicImageBuffer::LumaA8),
+            3 => ImageBuffer::from_raw(width, height, samples).map(DynamicImageBuffer::Rgb8),
+            _ => ImageBuffer::from_raw(width, height, samples).map(DynamicImageBuffer::Rgba8),
+        }
+    }?;
+
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::{grammar::ImageExt, Rgba16};
+    use image::ImageReader;
+
+    /// Decodes `path` and compares it against the `image` crate's decoding, at 16 bits so that
+    /// 16-bit samples are compared in full.
+    fn compare_pnm(path: &str) -> Result<Pnm> {
+        let reference = ImageReader::open(path)?.decode()?.to_rgba16();
+
+        let content = std::fs::read(path)?;
+        let pnm = PnmDecoder::new(&content).decode()?;
+
+        assert_eq!(pnm.dimensions(), reference.dimensions(), "{path}");
+        assert_eq!(
+            pnm.image().to::<Rgba16>().as_raw(),
+            reference.as_raw(),
+            "{path}"
+        );
+
+        Ok(pnm)
+    }
+
+    #[test]
+    fn test_decode_ascii() -> Result<()> {
+        let p1 = compare_pnm("./tests/pnm/p1.pbm")?;
+        assert_eq!(p1.header().tuple_type, TupleType::BlackAndWhite);
+
+        let p2 = compare_pnm("./tests/pnm/p2.pgm")?;
+        assert_eq!(p2.header().maxval, 100);
+
+        let p3 = compare_pnm("./tests/pnm/p3.ppm")?;
+        assert_eq!(p3.image().bit_depth(), 16);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_binary() -> Result<()> {
+        let p4 = compare_pnm("./tests/pnm/p4.pbm")?;
+        let p1 = compare_pnm("./tests/pnm/p1.pbm")?;
+        assert_eq!(p4.rgb8(), p1.rgb8());
+
+        compare_pnm("./tests/pnm/p5.pgm")?;
+        compare_pnm("./tests/pnm/p6.ppm")?;
+
+        let p5 = compare_pnm("./tests/pnm/p5_16.pgm")?;
+        assert_eq!(p5.image().bit_depth(), 16);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_pam() -> Result<()> {
+        // The `image` crate rejects PAM tuple types with alpha, so these are checked against the
+        // samples the fixtures were written from.
+        let decode = |path| -> Result<Pnm> { PnmDecoder::new(&std::fs::read(path)?).decode() };
+
+        let rgba = decode("./tests/pnm/rgb_alpha.pam")?;
+        assert_eq!(rgba.header().tuple_type, TupleType::RgbAlpha);
+
+        let p6 = compare_pnm("./tests/pnm/p6.ppm")?;
+        let alpha = (0..7).flat_map(|y| (0..13).map(move |x| ((x * 20 + y) % 256) as u8));
+        let expected = p6
+            .rgb8()
+            .chunks_exact(3)
+            .zip(alpha)
+            .flat_map(|(rgb, a)| [rgb[0], rgb[1], rgb[2], a])
+            .collect::<Vec<_>>();
+        assert_eq!(rgba.rgba8().as_ref(), expected.as_slice());
+
+        let gray_alpha = decode("./tests/pnm/gray_alpha_16.pam")?;
+        assert_eq!(gray_alpha.header().tuple_type, TupleType::GrayscaleAlpha);
+
+        let DynamicImageBuffer::LumaA16(gray_alpha) = gray_alpha.image() else {
+            bail!("Expected 16-bit gray with alpha.");
+        };
+        for (x, y) in (0..7).flat_map(|y| (0..13).map(move |x| (x, y))) {
+            let pixel = gray_alpha.get_pixel(x, y).unwrap();
+            let gray = (x * 17 + y * 29) * 65535 / (12 * 17 + 6 * 29);
+            let alpha = (x * 5000 + y * 300) % 65536;
+            assert_eq!(pixel.0, [gray as u16, alpha as u16], "({x}, {y})");
+        }
+
+        let black_and_white = decode("./tests/pnm/blackandwhite.pam")?;
+        let p1 = compare_pnm("./tests/pnm/p1.pbm")?;
+        // Unlike PBM, 1 is white in a BLACKANDWHITE PAM, so the same bits decode inverted.
+        let inverted = p1.rgb8().iter().map(|v| 255 - v).collect::<Vec<_>>();
+        assert_eq!(black_and_white.rgb8().as_ref(), inverted.as_slice());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_comment_before_raster() -> Result<()> {
+        let pnm = PnmDecoder::new(b"P1 3 1 # the raster follows\n0 1 0\n").decode()?;
+        assert_eq!(
+            pnm.rgb8().as_ref(),
+            &[255, 255, 255, 0, 0, 0, 255, 255, 255]
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        for data in [
+            &b"P8\n1 1\n255\n\0"[..],
+            b"P5\n1 1\n255\n",
+            b"P5\n0 1\n255\n\0",
+            b"P2\n1 1\n9\n10\n",
+            b"P7\nWIDTH 1\nHEIGHT 1\nDEPTH 3\nMAXVAL 255\nTUPLTYPE GRAYSCALE\nENDHDR\n\0\0\0",
+            b"P7\nWIDTH 1\nHEIGHT 1\nMAXVAL 255\nENDHDR\n\0",
+            b"P3\n65535 65535\n255\n0",
+        ] {
+            assert!(PnmDecoder::new(data).decode().is_err(), "{data:?}");
+        }
+    }
+}
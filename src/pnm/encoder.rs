@@ -0,0 +1,267 @@
+use crate::{
+    image::{
+        grammar::{ColorType, ImageEncoder, ImageExt},
+        DynamicImageBuffer, Luma16, Luma8, LumaA16, LumaA8, Rgb16, Rgb8, Rgba16, Rgba8,
+    },
+    pnm::grammar::{PnmFormat, TupleType},
+};
+use anyhow::Result;
+use std::io::Write;
+
+/// ASCII rasters wrap before lines grow longer than this, as the format asks.
+const MAX_LINE_LEN: usize = 70;
+
+pub struct PnmEncoder<W: Write> {
+    writer: W,
+    format: Option<PnmFormat>,
+}
+
+impl<W: Write> PnmEncoder<W> {
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            format: None,
+        }
+    }
+
+    /// The format to write, converting the image to its channels. Without one, grayscale images
+    /// are written as P5, RGB as P6 and images with alpha as P7.
+    pub const fn format(mut self, format: PnmFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Writes `image` with a maxval of 255, or 65535 if it has more than 8 bits per channel. PBM
+    /// formats write pixels darker than middle gray as black.
+    pub fn encode(&mut self, image: &DynamicImageBuffer) -> Result<()> {
+        let format = self.format.unwrap_or_else(|| match image.color_type() {
+            ColorType::Grayscale => PnmFormat::P5,
+            ColorType::RGB | ColorType::Palette => PnmFormat::P6,
+            ColorType::GrayscaleAlpha | ColorType::RGBA => PnmFormat::P7,
+        });
+
+        let wide = image.bit_depth() > 8 && !format.is_bitmap();
+        let maxval = if wide { u16::MAX } else { u8::MAX as u16 };
+
+        let tuple_type = match format {
+            PnmFormat::P1 | PnmFormat::P4 => TupleType::BlackAndWhite,
+            PnmFormat::P2 | PnmFormat::P5 => TupleType::Grayscale,
+            PnmFormat::P3 | PnmFormat::P6 => TupleType::Rgb,
+            PnmFormat::P7 => match image.color_type() {
+                ColorType::Grayscale => TupleType::Grayscale,
+                ColorType::GrayscaleAlpha => TupleType::GrayscaleAlpha,
+                ColorType::RGB | ColorType::Palette => TupleType::Rgb,
+                ColorType::RGBA => TupleType::RgbAlpha,
+            },
+        };
+
+        let samples = samples(image, &tuple_type, wide);
+        let samples = if format.is_bitmap() {
+            samples
+                .into_iter()
+                .map(|sample| (sample < 128) as u16)
+                .collect()
+        } else {
+            samples
+        };
+
+        let (width, height) = image.dimensions();
+        let mut buffer = Vec::new();
+
+        buffer.extend_from_slice(format.magic_number());
+
+        buffer.push(b'\n');
+
+        match format {
+            PnmFormat::P7 => {
+                writeln!(buffer, "WIDTH {width}")?;
+                writeln!(buffer, "HEIGHT {height}")?;
+                writeln!(buffer, "DEPTH {}", tuple_type.depth().unwrap_or(1))?;
+                writeln!(buffer, "MAXVAL {maxval}")?;
+                writeln!(buffer, "TUPLTYPE {}", tuple_type.name())?;
+                writeln!(buffer, "ENDHDR")?;
+            }
+            format if format.is_bitmap() => writeln!(buffer, "{width} {height}")?,
+            _ => {
+                writeln!(buffer, "{width} {height}")?;
+                writeln!(buffer, "{maxval}")?;
+            }
+        }
+
+        match format {
+            PnmFormat::P1 => {
+                for row in samples.chunks(width as usize) {
+                    for line in row.chunks(MAX_LINE_LEN) {
+                        buffer.extend(line.iter().map(|&sample| b'0' + sample as u8));
+                        buffer.push(b'\n');
+                    }
+                }
+            }
+            PnmFormat::P2 | PnmFormat::P3 => {
+                let row_len = width as usize * tuple_type.depth().unwrap_or(1) as usize;
+
+                for row in samples.chunks(row_len) {
+                    let mut line = String::new();
+
+                    for sample in row {
+                        let sample = sample.to_string();
+
+                        if !line.is_empty() && line.len() + 1 + sample.len() > MAX_LINE_LEN {
+                            buffer.extend_from_slice(line.as_bytes());
+                            buffer.push(b'\n');
+                            line.clear();
+                        }
+
+                        if !line.is_empty() {
+                            line.push(' ');
+                        }
+                        line.push_str(&sample);
+                    }
+
+                    buffer.extend_from_slice(line.as_bytes());
+                    buffer.push(b'\n');
+                }
+            }
+            PnmFormat::P4 => {
+                for row in samples.chunks(width as usize) {
+                    buffer.extend(row.chunks(8).map(|bits| {
+                        bits.iter()
+                            .enumerate()
+                            .fold(0u8, |byte, (i, &bit)| byte | (bit as u8) << (7 - i))
+                    }));
+                }
+            }
+            _ if wide => {
+                buffer.extend(samples.iter().flat_map(|sample| sample.to_be_bytes()));
+            }
+            _ => {
+                buffer.extend(samples.iter().map(|&sample| sample as u8));
+            }
+        }
+
+        self.writer.write_all(&buffer)?;
+
+        Ok(())
+    }
+}
+
+/// The samples of the channels `tuple_type` describes, with 16 bits if `wide` and 8 otherwise.
+fn samples(image: &DynamicImageBuffer, tuple_type: &TupleType, wide: bool) -> Vec<u16> {
+    let widen = |samples: Vec<u8>| samples.into_iter().map(u16::from).collect();
+
+    match (tuple_type.depth(), wide) {
+        (Some(1), false) => widen(image.to::<Luma8>().into_raw()),
+        (Some(1), true) => image.to::<Luma16>().into_raw(),
+        (Some(2), false) => widen(image.to::<LumaA8>().into_raw()),
+        (Some(2), true) => image.to::<LumaA16>().into_raw(),
+        (Some(3), false) => widen(image.to::<Rgb8>().into_raw()),
+        (Some(3), true) => image.to::<Rgb16>().into_raw(),
+        (_, false) => widen(image.to::<Rgba8>().into_raw()),
+        (_, true) => image.to::<Rgba16>().into_raw(),
+    }
+}
+
+impl<W: Write> ImageEncoder for PnmEncoder<W> {
+    fn write_image(&mut self, image: &dyn ImageExt) -> Result<()> {
+        self.encode(&DynamicImageBuffer::try_from(image)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::{ImageBuffer, Luma, LumaA, Rgb, Rgba},
+        pnm::PnmDecoder,
+    };
+
+    fn encode(image: &DynamicImageBuffer, format: PnmFormat) -> Result<Vec<u8>> {
+        let mut encoded = Vec::new();
+        PnmEncoder::new(&mut encoded).format(format).encode(image)?;
+
+        Ok(encoded)
+    }
+
+    #[test]
+    fn test_encode_round_trip() -> Result<()> {
+        let rgb = DynamicImageBuffer::Rgb16(ImageBuffer::from_fn(37, 5, |x, y| {
+            Rgb([x as u16 * 1700, y as u16 * 9000, 65535 - x as u16 * 3])
+        }));
+        let rgba = DynamicImageBuffer::Rgba8(ImageBuffer::from_fn(37, 5, |x, y| {
+            Rgba([x as u8 * 7, y as u8 * 50, 3, x as u8 * 6])
+        }));
+        let gray_alpha = DynamicImageBuffer::LumaA8(ImageBuffer::from_fn(37, 5, |x, y| {
+            LumaA([x as u8 * 7, y as u8 * 50])
+        }));
+
+        for (image, format) in [
+            (&rgb, PnmFormat::P3),
+            (&rgb, PnmFormat::P6),
+            (&rgba, PnmFormat::P7),
+            (&gray_alpha, PnmFormat::P7),
+            (&rgb, PnmFormat::P7),
+        ] {
+            let encoded = encode(image, format)?;
+            assert!(encoded.starts_with(format.magic_number()));
+
+            let decoded = PnmDecoder::new(&encoded).decode()?;
+            assert_eq!(decoded.image(), image, "{format:?}");
+
+            // The `image` crate only reads PAM files without alpha.
+            if matches!(
+                image,
+                DynamicImageBuffer::Rgba8(_) | DynamicImageBuffer::LumaA8(_)
+            ) {
+                continue;
+            }
+
+            let reference = image::load_from_memory(&encoded)?.to_rgba16();
+            assert_eq!(
+                reference.as_raw(),
+                image.to::<Rgba16>().as_raw(),
+                "{format:?}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_grayscale() -> Result<()> {
+        let rgb = DynamicImageBuffer::Rgb8(ImageBuffer::from_fn(37, 5, |x, y| {
+            Rgb([x as u8 * 7, y as u8 * 50, 90])
+        }));
+        let expected = rgb.to::<Luma8>();
+
+        for format in [PnmFormat::P2, PnmFormat::P5] {
+            let decoded = PnmDecoder::new(&encode(&rgb, format)?).decode()?;
+            assert_eq!(
+                decoded.image(),
+                &DynamicImageBuffer::Luma8(expected.clone())
+            );
+        }
+
+        // PBM keeps whether each pixel is lighter than middle gray.
+        let threshold = ImageBuffer::from_fn(37, 5, |x, y| {
+            Luma([if expected[(x, y)].0[0] < 128 { 0 } else { 255 }])
+        });
+
+        for format in [PnmFormat::P1, PnmFormat::P4] {
+            let encoded = encode(&rgb, format)?;
+            let decoded = PnmDecoder::new(&encoded).decode()?;
+            assert_eq!(
+                decoded.image(),
+                &DynamicImageBuffer::Luma8(threshold.clone())
+            );
+
+            let reference = image::load_from_memory(&encoded)?.to_luma8();
+            assert_eq!(reference.as_raw(), threshold.as_raw());
+        }
+
+        // ASCII lines are at most 70 characters long.
+        let encoded = String::from_utf8(encode(&rgb, PnmFormat::P3)?)?;
+        assert!(encoded.lines().all(|line| line.len() <= 70));
+
+        Ok(())
+    }
+}
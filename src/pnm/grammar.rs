@@ -0,0 +1,176 @@
+use crate::image::{
+    grammar::{ColorType, ImageExt},
+    DynamicImageBuffer,
+};
+use anyhow::bail;
+use std::borrow::Cow;
+
+/// The Netpbm formats, named after their magic numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PnmFormat {
+    /// ASCII PBM: black and white.
+    P1,
+    /// ASCII PGM: grayscale.
+    P2,
+    /// ASCII PPM: RGB.
+    P3,
+    /// Binary PBM, 8 pixels per byte.
+    P4,
+    /// Binary PGM.
+    P5,
+    /// Binary PPM.
+    P6,
+    /// PAM: any number of channels, described by the tuple type.
+    P7,
+}
+
+impl PnmFormat {
+    pub const fn magic_number(&self) -> &'static [u8; 2] {
+        match self {
+            Self::P1 => b"P1",
+            Self::P2 => b"P2",
+            Self::P3 => b"P3",
+            Self::P4 => b"P4",
+            Self::P5 => b"P5",
+            Self::P6 => b"P6",
+            Self::P7 => b"P7",
+        }
+    }
+
+    pub const fn is_ascii(&self) -> bool {
+        matches!(self, Self::P1 | Self::P2 | Self::P3)
+    }
+
+    /// PBM, where a sample of 1 is black and there is no maxval.
+    pub const fn is_bitmap(&self) -> bool {
+        matches!(self, Self::P1 | Self::P4)
+    }
+}
+
+impl TryFrom<&[u8]> for PnmFormat {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &[u8]) -> anyhow::Result<Self, Self::Error> {
+        let format = match value {
+            b"P1" => Self::P1,
+            b"P2" => Self::P2,
+            b"P3" => Self::P3,
+            b"P4" => Self::P4,
+            b"P5" => Self::P5,
+            b"P6" => Self::P6,
+            b"P7" => Self::P7,
+            foreign => bail!("Unrecognized Netpbm magic number: {:?}", foreign),
+        };
+
+        Ok(format)
+    }
+}
+
+/// What the channels of a PAM image mean.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TupleType {
+    /// One channel, where 0 is black and 1 is white.
+    BlackAndWhite,
+    Grayscale,
+    Rgb,
+    BlackAndWhiteAlpha,
+    GrayscaleAlpha,
+    RgbAlpha,
+    /// Read by the number of channels, like the tuple type it most resembles.
+    Custom(String),
+}
+
+impl TupleType {
+    pub fn name(&self) -> &str {
+        match self {
+            Self::BlackAndWhite => "BLACKANDWHITE",
+            Self::Grayscale => "GRAYSCALE",
+            Self::Rgb => "RGB",
+            Self::BlackAndWhiteAlpha => "BLACKANDWHITE_ALPHA",
+            Self::GrayscaleAlpha => "GRAYSCALE_ALPHA",
+            Self::RgbAlpha => "RGB_ALPHA",
+            Self::Custom(name) => name,
+        }
+    }
+
+    /// The number of channels, unknown for custom tuple types.
+    pub const fn depth(&self) -> Option<u32> {
+        match self {
+            Self::BlackAndWhite | Self::Grayscale => Some(1),
+            Self::BlackAndWhiteAlpha | Self::GrayscaleAlpha => Some(2),
+            Self::Rgb => Some(3),
+            Self::RgbAlpha => Some(4),
+            Self::Custom(_) => None,
+        }
+    }
+}
+
+impl From<&str> for TupleType {
+    fn from(value: &str) -> Self {
+        match value {
+            "BLACKANDWHITE" => Self::BlackAndWhite,
+            "GRAYSCALE" => Self::Grayscale,
+            "RGB" => Self::Rgb,
+            "BLACKANDWHITE_ALPHA" => Self::BlackAndWhiteAlpha,
+            "GRAYSCALE_ALPHA" => Self::GrayscaleAlpha,
+            "RGB_ALPHA" => Self::RgbAlpha,
+            custom => Self::Custom(custom.to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PnmHeader {
+    pub format: PnmFormat,
+    pub width: u32,
+    pub height: u32,
+    /// The number of channels.
+    pub depth: u32,
+    /// The sample value of full intensity, 1 for PBM.
+    pub maxval: u32,
+    /// The PAM tuple type, or the one equivalent to the other formats.
+    pub tuple_type: TupleType,
+}
+
+/// A Netpbm image, holding samples with 8 bits, or 16 bits if the maxval is above 255.
+#[derive(Debug)]
+pub struct Pnm {
+    pub(crate) header: PnmHeader,
+    pub(crate) image: DynamicImageBuffer,
+}
+
+impl Pnm {
+    pub const fn header(&self) -> &PnmHeader {
+        &self.header
+    }
+
+    pub const fn image(&self) -> &DynamicImageBuffer {
+        &self.image
+    }
+}
+
+impl ImageExt for Pnm {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.image.color_type()
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        self.image.rgb8()
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        self.image.rgba8()
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        self.image.bitmap()
+    }
+}
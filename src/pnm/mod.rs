@@ -0,0 +1,7 @@
+mod decoder;
+mod encoder;
+
+pub mod grammar;
+
+pub use decoder::*;
+pub use encoder::*;
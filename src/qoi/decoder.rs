@@ -0,0 +1,198 @@
+use crate::{
+    image::{DynamicImageBuffer, ImageBuffer},
+    impl_read_for_datatype, impl_read_slice,
+    qoi::grammar::{
+        index_position, Channels, ColorSpace, Qoi, QoiHeader, END_MARKER, OP_DIFF, OP_INDEX,
+        OP_LUMA, OP_RGB, OP_RGBA, QOI_MAGIC, TAG_MASK,
+    },
+};
+use anyhow::{ensure, Result};
+
+/// The most pixels an image may have, as the reference implementation allows.
+const MAX_PIXELS: u64 = 400_000_000;
+
+#[derive(Debug)]
+pub struct QoiDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> QoiDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Qoi> {
+        let header = self.parse_header()?;
+        let QoiHeader {
+            width,
+            height,
+            channels,
+            ..
+        } = header;
+
+        let num_channels = channels as usize;
+        let num_pixels = width as usize * height as usize;
+        let mut samples = Vec::with_capacity(num_pixels * num_channels);
+
+        let mut seen = [[0u8; 4]; 64];
+        let mut pixel = [0, 0, 0, 255];
+        let mut run = 0;
+
+        for _ in 0..num_pixels {
+            if run > 0 {
+                run -= 1;
+            } else {
+                let op = self.read_u8()?;
+
+                match op {
+                    OP_RGB => pixel[..3].copy_from_slice(self.read_slice(3)?),
+                    OP_RGBA => pixel.copy_from_slice(self.read_slice(4)?),
+                    _ => match op & TAG_MASK {
+                        OP_INDEX => pixel = seen[op as usize],
+                        OP_DIFF => {
+                            // Each difference is biased by 2.
+                            let diff = |shift: u8| ((op >> shift) & 0b11).wrapping_sub(2);
+                            pixel[0] = pixel[0].wrapping_add(diff(4));
+                            pixel[1] = pixel[1].wrapping_add(diff(2));
+                            pixel[2] = pixel[2].wrapping_add(diff(0));
+                        }
+                        OP_LUMA => {
+                            // The red and blue differences are relative to the green one.
+                            let green = (op & 0b11_1111).wrapping_sub(32);
+                            let byte = self.read_u8()?;
+                            pixel[0] = pixel[0]
+                                .wrapping_add(green.wrapping_sub(8).wrapping_add(byte >> 4));
+                            pixel[1] = pixel[1].wrapping_add(green);
+                            pixel[2] = pixel[2]
+                                .wrapping_add(green.wrapping_sub(8).wrapping_add(byte & 0xF));
+                        }
+                        // OP_RUN, whose length is biased by 1. This pixel is its first.
+                        _ => run = op & 0b11_1111,
+                    },
+                }
+
+                seen[index_position(pixel)] = pixel;
+            }
+
+            samples.extend_from_slice(&pixel[..num_channels]);
+        }
+
+        ensure!(
+            self.read_slice(END_MARKER.len())? == END_MARKER,
+            "Expected the QOI end marker."
+        );
+
+        let image = match channels {
+            Channels::Rgb => {
+                DynamicImageBuffer::Rgb8(ImageBuffer::from_raw(width, height, samples)?)
+            }
+            Channels::Rgba => {
+                DynamicImageBuffer::Rgba8(ImageBuffer::from_raw(width, height, samples)?)
+            }
+        };
+
+        Ok(Qoi { header, image })
+    }
+
+    fn parse_header(&mut self) -> Result<QoiHeader> {
+        ensure!(
+            self.read_slice(QOI_MAGIC.len())? == QOI_MAGIC,
+            "Expected a QOI signature."
+        );
+
+        let width = self.read_u32()?;
+        let height = self.read_u32()?;
+        ensure!(
+            width > 0 && height > 0 && width as u64 * height as u64 <= MAX_PIXELS,
+            "Invalid QOI dimensions: {}x{}",
+            width,
+            height
+        );
+
+        Ok(QoiHeader {
+            width,
+            height,
+            channels: Channels::try_from(self.read_u8()?)?,
+            color_space: ColorSpace::try_from(self.read_u8()?)?,
+        })
+    }
+
+    impl_read_for_datatype!(read_u8, u8);
+    impl_read_for_datatype!(read_u32, u32);
+
+    impl_read_slice!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{image::grammar::ImageExt, png::PngDecoder, qoi::grammar::HEADER_SIZE};
+
+    #[test]
+    fn test_decode_against_png() -> Result<()> {
+        for (qoi_path, png_path, channels) in [
+            (
+                "./tests/qoi/basn2c08.qoi",
+                "./test_suite/basn2c08.png",
+                Channels::Rgb,
+            ),
+            (
+                "./tests/qoi/basn6a08.qoi",
+                "./test_suite/basn6a08.png",
+                Channels::Rgba,
+            ),
+        ] {
+            let qoi = QoiDecoder::new(&std::fs::read(qoi_path)?).decode()?;
+            let png = PngDecoder::new(&std::fs::read(png_path)?).decode()?;
+
+            assert_eq!(qoi.header().channels, channels, "{qoi_path}");
+            assert_eq!(qoi.header().color_space, ColorSpace::Srgb, "{qoi_path}");
+            assert_eq!(qoi.dimensions(), png.dimensions(), "{qoi_path}");
+            assert_eq!(qoi.rgba8(), png.rgba8(), "{qoi_path}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_against_image_crate() -> Result<()> {
+        let reference = image::open("./tests/obama.png")?;
+        let mut encoded = Vec::new();
+        reference.write_to(
+            &mut std::io::Cursor::new(&mut encoded),
+            image::ImageFormat::Qoi,
+        )?;
+
+        let qoi = QoiDecoder::new(&encoded).decode()?;
+        assert_eq!(qoi.rgba8().as_ref(), reference.to_rgba8().as_raw());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_invalid() -> Result<()> {
+        let data = std::fs::read("./tests/qoi/basn2c08.qoi")?;
+
+        let mut bad_channels = data.clone();
+        bad_channels[12] = 2;
+
+        let mut bad_color_space = data.clone();
+        bad_color_space[13] = 2;
+
+        let mut no_end_marker = data.clone();
+        no_end_marker.truncate(data.len() - 1);
+
+        for data in [
+            &data[..HEADER_SIZE],
+            &bad_channels,
+            &bad_color_space,
+            &no_end_marker,
+            b"qoif\0\0\0\0\0\0\0\x01\x03\0",
+        ] {
+            assert!(QoiDecoder::new(data).decode().is_err());
+        }
+
+        Ok(())
+    }
+}
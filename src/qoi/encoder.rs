@@ -0,0 +1,235 @@
+use crate::{
+    image::grammar::{ColorType, ImageEncoder, ImageExt},
+    qoi::grammar::{
+        index_position, Channels, ColorSpace, END_MARKER, HEADER_SIZE, MAX_RUN, OP_DIFF, OP_INDEX,
+        OP_LUMA, OP_RGB, OP_RGBA, OP_RUN, QOI_MAGIC,
+    },
+};
+use anyhow::{ensure, Result};
+use std::io::Write;
+
+pub struct QoiEncoder<W: Write> {
+    writer: W,
+    color_space: Option<ColorSpace>,
+}
+
+impl<W: Write> QoiEncoder<W> {
+    pub const fn new(writer: W) -> Self {
+        Self {
+            writer,
+            color_space: None,
+        }
+    }
+
+    /// The color space flag to write. Without one, images with a gamma of 1 are marked linear
+    /// and all others sRGB.
+    pub const fn color_space(mut self, color_space: ColorSpace) -> Self {
+        self.color_space = Some(color_space);
+        self
+    }
+
+    /// Writes `image` with 4 channels if it has alpha, and 3 otherwise.
+    pub fn encode(&mut self, image: &dyn ImageExt) -> Result<()> {
+        let (width, height) = image.dimensions();
+        ensure!(
+            width > 0 && height > 0,
+            "Invalid QOI dimensions: {}x{}",
+            width,
+            height
+        );
+
+        let channels = match image.color_type() {
+            ColorType::GrayscaleAlpha | ColorType::RGBA => Channels::Rgba,
+            ColorType::Grayscale | ColorType::RGB | ColorType::Palette => Channels::Rgb,
+        };
+
+        let color_space = self.color_space.unwrap_or_else(|| {
+            if image.gamma() == ColorSpace::Linear.gamma() {
+                ColorSpace::Linear
+            } else {
+                ColorSpace::Srgb
+            }
+        });
+
+        let rgba = image.straight_rgba8();
+
+        // The worst case is an RGBA op for every pixel.
+        let mut buffer = Vec::with_capacity(HEADER_SIZE + rgba.len() / 4 * 5 + END_MARKER.len());
+
+        buffer.extend_from_slice(&QOI_MAGIC);
+        buffer.extend_from_slice(&width.to_be_bytes());
+        buffer.extend_from_slice(&height.to_be_bytes());
+        buffer.push(channels as u8);
+        buffer.push(color_space as u8);
+
+        let mut seen = [[0u8; 4]; 64];
+        let mut previous = [0, 0, 0, 255];
+        let mut run = 0;
+
+        for pixel in rgba.chunks_exact(4) {
+            let pixel = [pixel[0], pixel[1], pixel[2], pixel[3]];
+
+            if pixel == previous {
+                run += 1;
+
+                if run == MAX_RUN {
+                    buffer.push(OP_RUN | (run - 1));
+                    run = 0;
+                }
+
+                continue;
+            }
+
+            if run > 0 {
+                buffer.push(OP_RUN | (run - 1));
+                run = 0;
+            }
+
+            let position = index_position(pixel);
+
+            if seen[position] == pixel {
+                buffer.push(OP_INDEX | position as u8);
+            } else {
+                seen[position] = pixel;
+
+                if pixel[3] == previous[3] {
+                    let diff = |c: usize| pixel[c].wrapping_sub(previous[c]) as i8;
+                    let (red, green, blue) = (diff(0), diff(1), diff(2));
+                    let (green_red, green_blue) =
+                        (red.wrapping_sub(green), blue.wrapping_sub(green));
+
+                    if [red, green, blue].iter().all(|d| (-2..=1).contains(d)) {
+                        buffer.push(
+                            OP_DIFF
+                                | ((red + 2) as u8) << 4
+                                | ((green + 2) as u8) << 2
+                                | (blue + 2) as u8,
+                        );
+                    } else if (-32..=31).contains(&green)
+                        && (-8..=7).contains(&green_red)
+                        && (-8..=7).contains(&green_blue)
+                    {
+                        buffer.push(OP_LUMA | (green + 32) as u8);
+                        buffer.push(((green_red + 8) as u8) << 4 | (green_blue + 8) as u8);
+                    } else {
+                        buffer.push(OP_RGB);
+                        buffer.extend_from_slice(&pixel[..3]);
+                    }
+                } else {
+                    buffer.push(OP_RGBA);
+                    buffer.extend_from_slice(&pixel);
+                }
+            }
+
+            previous = pixel;
+        }
+
+        if run > 0 {
+            buffer.push(OP_RUN | (run - 1));
+        }
+
+        buffer.extend_from_slice(&END_MARKER);
+
+        self.writer.write_all(&buffer)?;
+
+        Ok(())
+    }
+}
+
+impl<W: Write> ImageEncoder for QoiEncoder<W> {
+    fn write_image(&mut self, image: &dyn ImageExt) -> Result<()> {
+        self.encode(image)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::{DynamicImageBuffer, ImageBuffer, Rgba},
+        png::PngDecoder,
+        qoi::QoiDecoder,
+    };
+
+    #[test]
+    fn test_encode_matches_reference() -> Result<()> {
+        // The format leaves no choices, so the output matches the reference encoder byte for
+        // byte.
+        for (png_path, qoi_path) in [
+            ("./test_suite/basn2c08.png", "./tests/qoi/basn2c08.qoi"),
+            ("./test_suite/basn6a08.png", "./tests/qoi/basn6a08.qoi"),
+        ] {
+            let png = PngDecoder::new(&std::fs::read(png_path)?).decode()?;
+
+            // The suite declares a gamma of 1, which the reference encoder ignores.
+            assert_eq!(png.gamma(), ColorSpace::Linear.gamma());
+
+            let mut encoded = Vec::new();
+            QoiEncoder::new(&mut encoded)
+                .color_space(ColorSpace::Srgb)
+                .encode(&png)?;
+
+            assert_eq!(encoded, std::fs::read(qoi_path)?, "{png_path}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_round_trip() -> Result<()> {
+        let png = PngDecoder::new(&std::fs::read("./tests/obama.png")?).decode()?;
+
+        let mut encoded = Vec::new();
+        QoiEncoder::new(&mut encoded).encode(&png)?;
+
+        let qoi = QoiDecoder::new(&encoded).decode()?;
+        assert_eq!(qoi.rgba8(), png.rgba8());
+
+        let reference = image::load_from_memory(&encoded)?.to_rgba8();
+        assert_eq!(reference.as_raw(), qoi.rgba8().as_ref());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_runs_and_alpha() -> Result<()> {
+        // Long runs, runs at the end, alpha changes and every op between them.
+        let image = DynamicImageBuffer::Rgba8(ImageBuffer::from_fn(100, 3, |x, y| match y {
+            0 => Rgba([0, 0, 0, 255]),
+            1 => Rgba([x as u8 * 2, 100 - x as u8, x as u8 % 7, 255 - x as u8 % 3]),
+            _ => Rgba([9, 9, 9, 0]),
+        }));
+
+        let mut encoded = Vec::new();
+        QoiEncoder::new(&mut encoded).encode(&image)?;
+
+        let qoi = QoiDecoder::new(&encoded).decode()?;
+        assert_eq!(qoi.image(), &image);
+
+        let reference = image::load_from_memory(&encoded)?.to_rgba8();
+        assert_eq!(reference.as_raw(), image.rgba8().as_ref());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_color_space() -> Result<()> {
+        let image = DynamicImageBuffer::Rgba8(ImageBuffer::from_pixel(2, 2, Rgba([1, 2, 3, 4])));
+
+        let mut encoded = Vec::new();
+        QoiEncoder::new(&mut encoded)
+            .color_space(ColorSpace::Linear)
+            .encode(&image)?;
+
+        let qoi = QoiDecoder::new(&encoded).decode()?;
+        assert_eq!(qoi.header().color_space, ColorSpace::Linear);
+        assert_eq!(qoi.gamma(), ColorSpace::Linear.gamma());
+
+        // Without an explicit color space, the decoded gamma of 1 keeps the image linear.
+        let mut reencoded = Vec::new();
+        QoiEncoder::new(&mut reencoded).encode(&qoi)?;
+        assert_eq!(reencoded, encoded);
+
+        Ok(())
+    }
+}
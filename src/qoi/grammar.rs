@@ -0,0 +1,138 @@
+use crate::image::{
+    grammar::{ColorType, Gamma, ImageExt},
+    DynamicImageBuffer,
+};
+use anyhow::bail;
+use std::borrow::Cow;
+
+pub const QOI_MAGIC: [u8; 4] = *b"qoif";
+
+/// The header size in bytes: magic, width, height, channels and color space.
+pub const HEADER_SIZE: usize = 14;
+
+/// Seven zero bytes and a one mark the end of the stream.
+pub const END_MARKER: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 1];
+
+pub const OP_RGB: u8 = 0b1111_1110;
+pub const OP_RGBA: u8 = 0b1111_1111;
+
+/// The 2-bit tags of the remaining ops, in the top bits of their first byte.
+pub const OP_INDEX: u8 = 0b0000_0000;
+pub const OP_DIFF: u8 = 0b0100_0000;
+pub const OP_LUMA: u8 = 0b1000_0000;
+pub const OP_RUN: u8 = 0b1100_0000;
+pub const TAG_MASK: u8 = 0b1100_0000;
+
+/// Runs are stored biased by -1, and the two lengths that would collide with `OP_RGB` and
+/// `OP_RGBA` are illegal.
+pub const MAX_RUN: u8 = 62;
+
+/// The position of `pixel` in the array of previously seen pixels.
+pub const fn index_position([r, g, b, a]: [u8; 4]) -> usize {
+    (r as usize * 3 + g as usize * 5 + b as usize * 7 + a as usize * 11) % 64
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Channels {
+    Rgb = 3,
+    Rgba = 4,
+}
+
+impl TryFrom<u8> for Channels {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            3 => Self::Rgb,
+            4 => Self::Rgba,
+            _ => bail!("Invalid QOI channel count: {value}"),
+        })
+    }
+}
+
+/// How the color channels were encoded. The header flag is informative: pixels are decoded the
+/// same either way.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ColorSpace {
+    /// sRGB color channels with linear alpha.
+    #[default]
+    Srgb = 0,
+    /// All channels linear.
+    Linear = 1,
+}
+
+impl ColorSpace {
+    /// Linear channels have a gamma of 1. sRGB is left to the display.
+    pub const fn gamma(&self) -> Option<Gamma> {
+        match self {
+            Self::Srgb => None,
+            Self::Linear => Some(Gamma::new(100_000)),
+        }
+    }
+}
+
+impl TryFrom<u8> for ColorSpace {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::Srgb,
+            1 => Self::Linear,
+            _ => bail!("Invalid QOI color space: {value}"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QoiHeader {
+    pub width: u32,
+    pub height: u32,
+    pub channels: Channels,
+    pub color_space: ColorSpace,
+}
+
+#[derive(Debug)]
+pub struct Qoi {
+    pub(crate) header: QoiHeader,
+    pub(crate) image: DynamicImageBuffer,
+}
+
+impl Qoi {
+    pub const fn header(&self) -> &QoiHeader {
+        &self.header
+    }
+
+    pub const fn image(&self) -> &DynamicImageBuffer {
+        &self.image
+    }
+}
+
+impl ImageExt for Qoi {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn gamma(&self) -> Option<Gamma> {
+        self.header.color_space.gamma()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.image.color_type()
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        self.image.rgb8()
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        self.image.rgba8()
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        self.image.bitmap()
+    }
+}
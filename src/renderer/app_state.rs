@@ -1,5 +1,5 @@
 use crate::{
-    image::grammar::Image,
+    image::{grammar::Image, ToneMapOperator, ToneMapper},
     renderer::{
         draw_uniform::DrawUniform,
         effect_pipeline::EffectPipeline,
@@ -19,6 +19,10 @@ use crate::renderer::camera::Camera;
 use winit::event::MouseScrollDelta;
 
 use anyhow::Result;
+use std::{
+    borrow::Cow,
+    fmt::{self, Debug, Formatter},
+};
 use winit::{
     dpi::PhysicalSize,
     event::{ElementState, Event, KeyEvent, Modifiers, MouseButton, WindowEvent},
@@ -27,12 +31,92 @@ use winit::{
     window::{CursorIcon, Window, WindowBuilder},
 };
 
+/// The image on display, and which of its frames is shown if it is animated.
+pub struct ImageFrames<'a> {
+    image: &'a Image,
+    index: usize,
+}
+
+impl<'a> ImageFrames<'a> {
+    pub const fn new(image: &'a Image) -> Self {
+        Self { image, index: 0 }
+    }
+
+    /// Moves `step` frames forward or backward, wrapping around, and returns the new frame.
+    /// Still images have a single frame, so nothing changes.
+    pub fn step(&mut self, step: isize) -> Option<Cow<'a, [u8]>> {
+        let frame_count = self.image.frame_count();
+        if frame_count < 2 {
+            return None;
+        }
+
+        self.index = (self.index as isize + step).rem_euclid(frame_count as isize) as usize;
+        self.image.frame_rgba8(self.index)
+    }
+}
+
+impl Debug for ImageFrames<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageFrames")
+            .field("frame_count", &self.image.frame_count())
+            .field("index", &self.index)
+            .finish()
+    }
+}
+
+/// How a high dynamic range image on display is tone mapped.
+pub struct ImageToneMapping<'a> {
+    image: &'a Image,
+    operator: ToneMapOperator,
+    exposure: f32,
+}
+
+impl<'a> ImageToneMapping<'a> {
+    pub fn new(image: &'a Image) -> Self {
+        Self {
+            image,
+            operator: ToneMapOperator::default(),
+            exposure: 0.0,
+        }
+    }
+
+    /// Changes the exposure by `stops` and returns the image mapped again. Images without a
+    /// high dynamic range are not tone mapped, so nothing changes.
+    pub fn adjust_exposure(&mut self, stops: f32) -> Option<Cow<'a, [u8]>> {
+        self.exposure += stops;
+        self.tone_map()
+    }
+
+    /// Switches to the next tone mapping operator and returns the image mapped with it.
+    pub fn cycle_operator(&mut self) -> Option<Cow<'a, [u8]>> {
+        self.operator = self.operator.next();
+        self.tone_map()
+    }
+
+    fn tone_map(&self) -> Option<Cow<'a, [u8]>> {
+        self.image
+            .tone_mapped_rgba8(ToneMapper::new(self.operator).exposure(self.exposure))
+    }
+}
+
+impl Debug for ImageToneMapping<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ImageToneMapping")
+            .field("operator", &self.operator)
+            .field("exposure", &self.exposure)
+            .finish()
+    }
+}
+
 /// AppState is the state that is created by user input.
 #[derive(Debug)]
 pub struct AppState<'a> {
     pub gpu_allocator: GpuResourceAllocator<'a>,
 
     pub window: &'a Window,
+    pub image_frames: ImageFrames<'a>,
+    pub image_tone_mapping: ImageToneMapping<'a>,
+    pub image_texture: TextureResource,
     pub(crate) size: PhysicalSize<u32>,
 
     pub feature_uniform: FeatureUniform,
@@ -62,7 +146,13 @@ impl<'a> AppState<'a> {
         let image_texture_resource =
             gpu_allocator.create_texture_resource("image_texture", image)?;
 
-        let feature_uniform = { FeatureUniform::new(size.width, size.height, image.gamma()) };
+        let feature_uniform = {
+            FeatureUniform::new(
+                size.width,
+                size.height,
+                image.gamma().map_or(0, |gamma| gamma.scaled()),
+            )
+        };
         let feature_uniform_resource =
             gpu_allocator.create_uniform_resource("feature_uniform", feature_uniform)?;
 
@@ -156,6 +246,9 @@ impl<'a> AppState<'a> {
         Ok(Self {
             gpu_allocator,
             window,
+            image_frames: ImageFrames::new(image),
+            image_tone_mapping: ImageToneMapping::new(image),
+            image_texture: image_texture_resource,
             size,
             feature_uniform,
             draw_uniform,
@@ -189,6 +282,25 @@ impl<'a> AppState<'a> {
         }
     }
 
+    /// Displays the frame `step` frames away from the current one.
+    fn step_frame(&mut self, step: isize) {
+        if let Some(rgba) = self.image_frames.step(step) {
+            self.image_texture
+                .resource
+                .write_rgba8(&self.gpu_allocator.queue, &rgba);
+        }
+    }
+
+    /// Displays the image tone mapped again after `retone` changes how, if it has a high
+    /// dynamic range.
+    fn retone(&mut self, retone: impl FnOnce(&mut ImageToneMapping<'a>) -> Option<Cow<'a, [u8]>>) {
+        if let Some(rgba) = retone(&mut self.image_tone_mapping) {
+            self.image_texture
+                .resource
+                .write_rgba8(&self.gpu_allocator.queue, &rgba);
+        }
+    }
+
     pub(crate) fn input(&mut self, event: &WindowEvent) -> bool {
         let feature_uniform = &mut self.feature_uniform;
         let draw_uniform = &mut self.draw_uniform;
@@ -458,6 +570,21 @@ impl<'a> AppState<'a> {
                     (KeyCode::KeyY, ElementState::Pressed) => {
                         feature_uniform.apply_transform(TransformAction::FlipY);
                     }
+                    (KeyCode::Period, ElementState::Pressed) => {
+                        self.step_frame(1);
+                    }
+                    (KeyCode::Comma, ElementState::Pressed) => {
+                        self.step_frame(-1);
+                    }
+                    (KeyCode::BracketRight, ElementState::Pressed) => {
+                        self.retone(|tone_mapping| tone_mapping.adjust_exposure(0.5));
+                    }
+                    (KeyCode::BracketLeft, ElementState::Pressed) => {
+                        self.retone(|tone_mapping| tone_mapping.adjust_exposure(-0.5));
+                    }
+                    (KeyCode::KeyT, ElementState::Pressed) => {
+                        self.retone(ImageToneMapping::cycle_operator);
+                    }
                     (KeyCode::Delete, ElementState::Pressed)
                     | (KeyCode::Backspace, ElementState::Pressed) => {
                         // Delete the selected circle
@@ -661,7 +788,7 @@ impl<'a> AppState<'a> {
     }
 }
 
-#[allow(clippy::future_not_send)]
+#[allow(clippy::future_not_send, clippy::collapsible_match)]
 #[cfg_attr(target_arch = "wasm32", wasm_bindgen(start))]
 pub async fn run(image: Image) -> anyhow::Result<()> {
     cfg_if::cfg_if! {
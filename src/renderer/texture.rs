@@ -25,7 +25,7 @@ impl Texture {
         img: &Image,
         label: Option<&str>,
     ) -> Result<Self> {
-        let rgba = img.rgba8();
+        let rgba = img.straight_rgba8();
         let dimensions = img.dimensions();
 
         let size = Extent3d {
@@ -45,21 +45,7 @@ impl Texture {
             view_formats: &[],
         });
 
-        queue.write_texture(
-            ImageCopyTexture {
-                aspect: TextureAspect::All,
-                texture: &texture,
-                mip_level: 0,
-                origin: Origin3d::ZERO,
-            },
-            &rgba,
-            ImageDataLayout {
-                offset: 0,
-                bytes_per_row: Some(4 * dimensions.0),
-                rows_per_image: Some(dimensions.1),
-            },
-            size,
-        );
+        write_rgba8(queue, &texture, &rgba);
 
         let view = texture.create_view(&TextureViewDescriptor::default());
         let sampler = device.create_sampler(&SamplerDescriptor {
@@ -78,4 +64,29 @@ impl Texture {
             sampler,
         })
     }
+
+    /// Replaces the texture's pixels, e.g. with another frame of an animated image.
+    pub fn write_rgba8(&self, queue: &Queue, rgba: &[u8]) {
+        write_rgba8(queue, &self.texture, rgba);
+    }
+}
+
+fn write_rgba8(queue: &Queue, texture: &wgpu::Texture, rgba: &[u8]) {
+    let size = texture.size();
+
+    queue.write_texture(
+        ImageCopyTexture {
+            aspect: TextureAspect::All,
+            texture,
+            mip_level: 0,
+            origin: Origin3d::ZERO,
+        },
+        rgba,
+        ImageDataLayout {
+            offset: 0,
+            bytes_per_row: Some(4 * size.width),
+            rows_per_image: Some(size.height),
+        },
+        size,
+    );
 }
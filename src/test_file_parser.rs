@@ -208,6 +208,7 @@ mod tests {
     use std::{ffi::OsStr, fs};
 
     #[test]
+    #[allow(clippy::equatable_if_let)]
     fn parse_every_file() -> Result<()> {
         for entry in fs::read_dir("./test_suite")? {
             let path = entry?.path();
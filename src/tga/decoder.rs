@@ -0,0 +1,519 @@
+use crate::{
+    image::{DynamicImageBuffer, ImageBuffer, Luma, LumaA, Pixel, Rgba, Rgba8},
+    impl_read_le_for_datatype, impl_read_slice,
+    tga::grammar::{
+        AttributesType, ColorMapSpec, ImageType, Tga, TgaExtension, TgaHeader, ALPHA_BITS_MASK,
+        EXTENSION_SIZE, FOOTER_SIGNATURE, FOOTER_SIZE, HEADER_SIZE, MAX_PACKET_LEN, RIGHT_TO_LEFT,
+        RUN_PACKET, TOP_TO_BOTTOM,
+    },
+};
+use anyhow::{ensure, Result};
+use std::borrow::Cow;
+
+#[derive(Debug)]
+pub struct TgaDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> TgaDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    pub fn decode(&mut self) -> Result<Tga> {
+        let header = self.parse_header()?;
+        let extension = self.parse_extension()?;
+
+        // Color maps are skipped over even when the pixels don't use them.
+        let palette = match header.color_map {
+            Some(spec) => self.parse_color_map(spec)?,
+            None => vec![],
+        };
+
+        let TgaHeader {
+            image_type,
+            pixel_depth,
+            alpha_bits,
+            ..
+        } = header;
+
+        let valid_depths: &[u8] = match image_type {
+            ImageType::ColorMapped => &[8, 16],
+            ImageType::Truecolor => &[15, 16, 24, 32],
+            ImageType::Grayscale => &[8, 16],
+        };
+
+        ensure!(
+            valid_depths.contains(&pixel_depth),
+            "Unsupported TGA pixel depth {} for {:?} images.",
+            pixel_depth,
+            image_type
+        );
+
+        let bytes_per_pixel = pixel_depth.div_ceil(8) as usize;
+        let stored = self.read_pixels(&header, bytes_per_pixel)?;
+        let pixels = stored.chunks_exact(bytes_per_pixel);
+
+        // Writers often leave the alpha bit count of 32-bit colors at 0, so their fourth byte is
+        // taken as alpha unless the extension area says otherwise.
+        let alpha_declared = extension.as_ref().is_none_or(|extension| {
+            matches!(
+                extension.attributes_type,
+                AttributesType::Alpha | AttributesType::PremultipliedAlpha
+            )
+        });
+
+        let image = match image_type {
+            ImageType::Grayscale if pixel_depth == 8 => {
+                DynamicImageBuffer::Luma8(orient(&header, pixels.map(|p| Luma([p[0]])).collect()))
+            }
+            ImageType::Grayscale => {
+                let image = orient(&header, pixels.map(|p| LumaA([p[0], p[1]])).collect());
+
+                if alpha_declared {
+                    DynamicImageBuffer::LumaA8(image)
+                } else {
+                    DynamicImageBuffer::Luma8(image.convert())
+                }
+            }
+            ImageType::Truecolor | ImageType::ColorMapped => {
+                let color_depth = match header.color_map {
+                    Some(spec) if image_type == ImageType::ColorMapped => spec.entry_size,
+                    _ => pixel_depth,
+                };
+
+                let colors = if image_type == ImageType::ColorMapped {
+                    let first_entry_index = header.color_map.map_or(0, |s| s.first_entry_index);
+
+                    pixels
+                        .map(|p| {
+                            let index = match *p {
+                                [index] => index as usize,
+                                [low, high] => u16::from_le_bytes([low, high]) as usize,
+                                _ => unreachable!("Validated with the pixel depth."),
+                            };
+
+                            // Indices outside of the map are black, like most decoders render
+                            // them.
+                            index
+                                .checked_sub(first_entry_index as usize)
+                                .and_then(|index| palette.get(index))
+                                .copied()
+                                .unwrap_or(Rgba([0, 0, 0, 255]))
+                        })
+                        .collect()
+                } else {
+                    pixels.map(|p| read_color(p, pixel_depth)).collect()
+                };
+
+                let image = orient(&header, colors);
+                let has_alpha = alpha_declared
+                    && match color_depth {
+                        32 => true,
+                        16 => alpha_bits > 0,
+                        _ => false,
+                    };
+
+                if has_alpha {
+                    DynamicImageBuffer::Rgba8(image)
+                } else {
+                    DynamicImageBuffer::Rgb8(image.convert())
+                }
+            }
+        };
+
+        Ok(Tga {
+            header,
+            extension,
+            image,
+        })
+    }
+
+    fn parse_header(&mut self) -> Result<TgaHeader> {
+        ensure!(self.data.len() >= HEADER_SIZE, "TGA header is truncated.");
+
+        let id_length = self.read_u8()? as usize;
+        let color_map_type = self.read_u8()?;
+        let (image_type, run_length_encoded) = ImageType::from_code(self.read_u8()?)?;
+
+        let first_entry_index = self.read_u16()?;
+        let color_map_len = self.read_u16()?;
+        let entry_size = self.read_u8()?;
+
+        let x_origin = self.read_u16()?;
+        let y_origin = self.read_u16()?;
+        let width = self.read_u16()?;
+        let height = self.read_u16()?;
+        let pixel_depth = self.read_u8()?;
+        let descriptor = self.read_u8()?;
+
+        ensure!(
+            color_map_type <= 1,
+            "Invalid TGA color map type: {}",
+            color_map_type
+        );
+        ensure!(
+            width > 0 && height > 0,
+            "Invalid TGA dimensions: {}x{}",
+            width,
+            height
+        );
+
+        let color_map = (color_map_type == 1).then_some(ColorMapSpec {
+            first_entry_index,
+            len: color_map_len,
+            entry_size,
+        });
+
+        ensure!(
+            image_type != ImageType::ColorMapped || color_map.is_some(),
+            "Color-mapped TGA has no color map."
+        );
+
+        let image_id = self.read_slice(id_length)?.to_vec();
+
+        Ok(TgaHeader {
+            image_type,
+            run_length_encoded,
+            color_map,
+            x_origin,
+            y_origin,
+            width,
+            height,
+            pixel_depth,
+            alpha_bits: descriptor & ALPHA_BITS_MASK,
+            right_to_left: descriptor & RIGHT_TO_LEFT != 0,
+            top_to_bottom: descriptor & TOP_TO_BOTTOM != 0,
+            image_id,
+        })
+    }
+
+    /// Reads the extension area that the footer of TGA 2.0 files points to, leaving the cursor
+    /// where it was.
+    fn parse_extension(&mut self) -> Result<Option<TgaExtension>> {
+        let Some(footer) = self
+            .data
+            .len()
+            .checked_sub(FOOTER_SIZE)
+            .map(|start| &self.data[start..])
+            .filter(|footer| footer.ends_with(&FOOTER_SIGNATURE))
+        else {
+            return Ok(None);
+        };
+
+        let offset = u32::from_le_bytes(footer[..4].try_into()?) as usize;
+        if offset == 0 {
+            return Ok(None);
+        }
+
+        let cursor = std::mem::replace(&mut self.cursor, offset);
+
+        let size = self.read_u16()?;
+        ensure!(
+            size >= EXTENSION_SIZE,
+            "Invalid TGA extension area size: {}",
+            size
+        );
+
+        let author_name = self.read_text(41)?;
+
+        // Four lines of 80 characters, each terminated.
+        let lines = self.read_vec(4, |decoder| decoder.read_text(81))?;
+        let author_comments = lines.join("\n").trim_end_matches('\n').to_owned();
+
+        let timestamp = self.read_fixed_array(Self::read_u16)?;
+        let job_name = self.read_text(41)?;
+        let _job_time: [u16; 3] = self.read_fixed_array(Self::read_u16)?;
+        let software_id = self.read_text(41)?;
+        let _software_version = self.read_slice(3)?;
+        let key_color = self.read_u32()?;
+        let pixel_aspect_ratio = (self.read_u16()?, self.read_u16()?);
+        let gamma = (self.read_u16()?, self.read_u16()?);
+        let _color_correction_offset = self.read_u32()?;
+        let _postage_stamp_offset = self.read_u32()?;
+        let _scan_line_offset = self.read_u32()?;
+        let attributes_type = AttributesType::try_from(self.read_u8()?)?;
+
+        self.cursor = cursor;
+
+        Ok(Some(TgaExtension {
+            author_name,
+            author_comments,
+            timestamp,
+            job_name,
+            software_id,
+            key_color,
+            pixel_aspect_ratio,
+            gamma,
+            attributes_type,
+        }))
+    }
+
+    fn parse_color_map(&mut self, spec: ColorMapSpec) -> Result<Vec<Rgba8>> {
+        ensure!(
+            [15, 16, 24, 32].contains(&spec.entry_size),
+            "Unsupported TGA color map entry size: {}",
+            spec.entry_size
+        );
+
+        let entry_len = spec.entry_size.div_ceil(8) as usize;
+
+        self.read_vec(spec.len as usize, |decoder| {
+            Ok(read_color(decoder.read_slice(entry_len)?, spec.entry_size))
+        })
+    }
+
+    /// Reads the stored pixels, expanding run-length packets.
+    fn read_pixels(&mut self, header: &TgaHeader, bytes_per_pixel: usize) -> Result<Cow<'a, [u8]>> {
+        let num_pixels = header.width as usize * header.height as usize;
+        let len = num_pixels * bytes_per_pixel;
+
+        if !header.run_length_encoded {
+            return Ok(Cow::Borrowed(self.read_slice(len)?));
+        }
+
+        // Each packet byte holds at most 128 pixels, which bounds the allocation.
+        ensure!(
+            num_pixels <= (self.data.len() - self.cursor) * MAX_PACKET_LEN,
+            "TGA pixel data is truncated."
+        );
+
+        let mut output = Vec::with_capacity(len);
+
+        // Packets may cross scanlines, as TGA 1.0 allowed.
+        while output.len() < len {
+            let packet = self.read_u8()?;
+            let count = (packet & !RUN_PACKET) as usize + 1;
+
+            if packet & RUN_PACKET != 0 {
+                let pixel = self.read_slice(bytes_per_pixel)?;
+                for _ in 0..count {
+                    output.extend_from_slice(pixel);
+                }
+            } else {
+                output.extend_from_slice(self.read_slice(count * bytes_per_pixel)?);
+            }
+        }
+
+        // The last packet may run past the image.
+        output.truncate(len);
+
+        Ok(Cow::Owned(output))
+    }
+
+    /// Reads a NUL-padded text field of `len` bytes.
+    fn read_text(&mut self, len: usize) -> Result<String> {
+        let bytes = self.read_slice(len)?;
+        let end = bytes.iter().position(|&b| b == 0).unwrap_or(len);
+
+        Ok(String::from_utf8_lossy(&bytes[..end]).into_owned())
+    }
+
+    impl_read_le_for_datatype!(read_u8, u8);
+    impl_read_le_for_datatype!(read_u16, u16);
+    impl_read_le_for_datatype!(read_u32, u32);
+
+    impl_read_slice!();
+}
+
+/// Reads a color stored as blue, green, red and alpha. 15 and 16-bit colors pack 5 bits per
+/// channel, with an alpha bit on top.
+fn read_color(bytes: &[u8], depth: u8) -> Rgba8 {
+    match (depth, bytes) {
+        (15 | 16, &[low, high]) => {
+            let value = u16::from_le_bytes([low, high]);
+            let channel = |shift: u16| {
+                let value = ((value >> shift) & 0x1F) as u8;
+                (value << 3) | (value >> 2)
+            };
+            let alpha = if value & 0x8000 != 0 { 255 } else { 0 };
+
+            Rgba([channel(10), channel(5), channel(0), alpha])
+        }
+        (24, &[b, g, r]) => Rgba([r, g, b, 255]),
+        (32, &[b, g, r, a]) => Rgba([r, g, b, a]),
+        _ => unreachable!("Validated with the pixel depth."),
+    }
+}
+
+/// Places pixels stored in the order the header's origin bits describe, bottom to top and left
+/// to right unless flipped.
+fn orient<P: Pixel>(header: &TgaHeader, pixels: Vec<P>) -> ImageBuffer<P> {
+    let (width, height) = (header.width as u32, header.height as u32);
+
+    ImageBuffer::from_fn(width, height, |x, y| {
+        let stored_x = if header.right_to_left {
+            width - 1 - x
+        } else {
+            x
+        };
+        let stored_y = if header.top_to_bottom {
+            y
+        } else {
+            height - 1 - y
+        };
+
+        pixels[(stored_y * width + stored_x) as usize]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::image::grammar::{AlphaMode, ColorType, Gamma, ImageExt};
+
+    fn compare_tga(path: &str, color_type: ColorType) -> Result<Tga> {
+        let tga = TgaDecoder::new(&std::fs::read(path)?).decode()?;
+        let reference = image::open(path)?.to_rgba8();
+
+        assert_eq!(tga.dimensions(), reference.dimensions(), "{path}");
+        assert_eq!(tga.color_type(), color_type, "{path}");
+        assert_eq!(
+            tga.rgba8().as_ref(),
+            reference.as_raw().as_slice(),
+            "{path}"
+        );
+
+        Ok(tga)
+    }
+
+    #[test]
+    fn test_decode_color_mapped() -> Result<()> {
+        // The map starts at index 2, which the image crate treats as a byte offset instead.
+        let tga = TgaDecoder::new(&std::fs::read("./tests/tga/cmap8_bottom_up.tga")?).decode()?;
+        assert_eq!(tga.header().color_map.map(|s| s.first_entry_index), Some(2));
+        assert_eq!(tga.color_type(), ColorType::RGB);
+
+        let palette = [
+            [255, 0, 0],
+            [0, 255, 0],
+            [0, 0, 255],
+            [10, 20, 30],
+            [200, 100, 50],
+        ];
+        for (i, pixel) in tga.rgb8().chunks_exact(3).enumerate() {
+            let (x, y) = (i % 7, i / 7);
+            assert_eq!(pixel, palette[(x + 2 * y) % 5], "({x}, {y})");
+        }
+
+        let tga = compare_tga("./tests/tga/cmap32_rle.tga", ColorType::RGBA)?;
+        assert_eq!(tga.header().image_id, b"palette");
+        assert!(tga.header().run_length_encoded);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_truecolor() -> Result<()> {
+        compare_tga("./tests/tga/rgb24_bottom_up.tga", ColorType::RGB)?;
+        // Packets cross scanlines.
+        compare_tga("./tests/tga/rgba32_rle.tga", ColorType::RGBA)?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_grayscale() -> Result<()> {
+        compare_tga("./tests/tga/gray8.tga", ColorType::Grayscale)?;
+        compare_tga(
+            "./tests/tga/gray_alpha16_rle.tga",
+            ColorType::GrayscaleAlpha,
+        )?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_16_bit_right_to_left() -> Result<()> {
+        let reference =
+            TgaDecoder::new(&std::fs::read("./tests/tga/rgb24_bottom_up.tga")?).decode()?;
+        let tga =
+            TgaDecoder::new(&std::fs::read("./tests/tga/rgb16_right_to_left_v2.tga")?).decode()?;
+
+        assert!(tga.header().right_to_left && tga.header().top_to_bottom);
+        assert_eq!(tga.color_type(), ColorType::RGBA);
+
+        // 5 bits per channel keep the top bits of the reference, repeated below them.
+        for (i, (pixel, expected)) in tga
+            .rgba8()
+            .chunks_exact(4)
+            .zip(reference.rgba8().chunks_exact(4))
+            .enumerate()
+        {
+            let truncate = |c: u8| (c & 0xF8) | (c >> 5);
+            let alpha = if i == 0 { 0 } else { 255 };
+
+            assert_eq!(
+                pixel,
+                [
+                    truncate(expected[0]),
+                    truncate(expected[1]),
+                    truncate(expected[2]),
+                    alpha
+                ],
+                "pixel {i}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_extension_area() -> Result<()> {
+        let tga =
+            TgaDecoder::new(&std::fs::read("./tests/tga/rgb16_right_to_left_v2.tga")?).decode()?;
+        let extension = tga.extension().unwrap();
+
+        assert_eq!(extension.author_name, "Ada");
+        assert_eq!(extension.author_comments, "");
+        assert_eq!(extension.timestamp, [10, 18, 2026, 14, 30, 0]);
+        assert_eq!(extension.software_id, "norm test");
+        assert_eq!(extension.pixel_aspect_ratio, (1, 1));
+        assert_eq!(
+            extension.attributes_type,
+            AttributesType::PremultipliedAlpha
+        );
+        assert_eq!(tga.alpha_mode(), AlphaMode::Premultiplied);
+        assert_eq!(tga.gamma(), Some(Gamma::new(45455)));
+
+        // The fourth byte of these 32-bit pixels is declared undefined.
+        let tga = TgaDecoder::new(&std::fs::read("./tests/tga/rgb32_no_alpha_v2.tga")?).decode()?;
+        assert_eq!(
+            tga.extension().map(|e| e.attributes_type),
+            Some(AttributesType::NoAlpha)
+        );
+        assert_eq!(tga.color_type(), ColorType::RGB);
+        assert_eq!(tga.alpha_mode(), AlphaMode::Straight);
+        assert_eq!(tga.gamma(), None);
+
+        let reference =
+            TgaDecoder::new(&std::fs::read("./tests/tga/rgb24_bottom_up.tga")?).decode()?;
+        assert_eq!(tga.rgb8(), reference.rgb8());
+        assert!(reference.extension().is_none());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_invalid() -> Result<()> {
+        let data = std::fs::read("./tests/tga/rgba32_rle.tga")?;
+        assert!(TgaDecoder::new(&data[..data.len() - 1]).decode().is_err());
+        assert!(TgaDecoder::new(&data[..10]).decode().is_err());
+
+        let data = std::fs::read("./tests/tga/rgb24_bottom_up.tga")?;
+        assert!(TgaDecoder::new(&data[..data.len() - 1]).decode().is_err());
+
+        // No image data, a color-mapped image without a map, and 12-bit pixels.
+        for patches in [&[(2, 0)][..], &[(2, 1)], &[(16, 12)]] {
+            let mut invalid = data.clone();
+            for &(offset, value) in patches {
+                invalid[offset] = value;
+            }
+
+            assert!(TgaDecoder::new(&invalid).decode().is_err(), "{patches:?}");
+        }
+
+        Ok(())
+    }
+}
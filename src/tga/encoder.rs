@@ -0,0 +1,208 @@
+use crate::{
+    image::grammar::{ColorType, ImageEncoder, ImageExt},
+    tga::grammar::{
+        AttributesType, ImageType, EXTENSION_SIZE, FOOTER_SIGNATURE, HEADER_SIZE, MAX_PACKET_LEN,
+        RUN_PACKET, TOP_TO_BOTTOM,
+    },
+};
+use anyhow::{ensure, Result};
+use std::io::Write;
+
+pub struct TgaEncoder<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> TgaEncoder<W> {
+    pub const fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Writes `image` top to bottom as run-length encoded 24-bit BGR, or 32-bit BGRA when it
+    /// has alpha, followed by a TGA 2.0 extension area declaring which.
+    pub fn encode(&mut self, image: &dyn ImageExt) -> Result<()> {
+        let (width, height) = image.dimensions();
+        ensure!(
+            (1..=u16::MAX as u32).contains(&width) && (1..=u16::MAX as u32).contains(&height),
+            "Invalid TGA dimensions: {}x{}",
+            width,
+            height
+        );
+
+        let has_alpha = matches!(
+            image.color_type(),
+            ColorType::RGBA | ColorType::GrayscaleAlpha
+        );
+
+        let (pixels, bytes_per_pixel, alpha_bits, attributes_type) = if has_alpha {
+            let bgra = image
+                .straight_rgba8()
+                .chunks_exact(4)
+                .flat_map(|p| [p[2], p[1], p[0], p[3]])
+                .collect::<Vec<_>>();
+
+            (bgra, 4, 8, AttributesType::Alpha)
+        } else {
+            let bgr = image
+                .rgb8()
+                .chunks_exact(3)
+                .flat_map(|p| [p[2], p[1], p[0]])
+                .collect::<Vec<_>>();
+
+            (bgr, 3, 0, AttributesType::NoAlpha)
+        };
+
+        let mut buffer = Vec::with_capacity(HEADER_SIZE + pixels.len());
+
+        // No image ID or color map.
+        buffer.extend_from_slice(&[0, 0, ImageType::Truecolor.code(true)]);
+        buffer.extend_from_slice(&[0; 5]);
+
+        buffer.extend_from_slice(&0u16.to_le_bytes());
+        buffer.extend_from_slice(&0u16.to_le_bytes());
+        buffer.extend_from_slice(&(width as u16).to_le_bytes());
+        buffer.extend_from_slice(&(height as u16).to_le_bytes());
+        buffer.push(bytes_per_pixel as u8 * 8);
+        buffer.push(TOP_TO_BOTTOM | alpha_bits);
+
+        for row in pixels.chunks_exact(width as usize * bytes_per_pixel) {
+            encode_packets(row, bytes_per_pixel, &mut buffer);
+        }
+
+        let extension_offset = buffer.len() as u32;
+
+        // Only the attributes type, the last field, is set.
+        buffer.extend_from_slice(&EXTENSION_SIZE.to_le_bytes());
+        buffer.extend(std::iter::repeat_n(0, EXTENSION_SIZE as usize - 3));
+        buffer.push(attributes_type as u8);
+
+        // No developer area.
+        buffer.extend_from_slice(&extension_offset.to_le_bytes());
+        buffer.extend_from_slice(&0u32.to_le_bytes());
+        buffer.extend_from_slice(&FOOTER_SIGNATURE);
+
+        self.writer.write_all(&buffer)?;
+
+        Ok(())
+    }
+}
+
+impl<W: Write> ImageEncoder for TgaEncoder<W> {
+    fn write_image(&mut self, image: &dyn ImageExt) -> Result<()> {
+        self.encode(image)
+    }
+}
+
+/// Appends a scanline as run-length packets for repeated pixels and raw packets for the pixels
+/// between them. Packets stay within the scanline, as TGA 2.0 requires.
+fn encode_packets(row: &[u8], bytes_per_pixel: usize, output: &mut Vec<u8>) {
+    let pixels = row.chunks_exact(bytes_per_pixel).collect::<Vec<_>>();
+    let mut start = 0;
+
+    while start < pixels.len() {
+        let run = pixels[start..]
+            .iter()
+            .take(MAX_PACKET_LEN)
+            .take_while(|&&pixel| pixel == pixels[start])
+            .count();
+
+        if run > 1 {
+            output.push(RUN_PACKET | (run - 1) as u8);
+            output.extend_from_slice(pixels[start]);
+            start += run;
+            continue;
+        }
+
+        // Raw pixels up to the next pair of equal ones, which starts a run.
+        let mut end = start + 1;
+        while end < pixels.len()
+            && end - start < MAX_PACKET_LEN
+            && pixels.get(end + 1) != Some(&pixels[end])
+        {
+            end += 1;
+        }
+
+        output.push((end - start - 1) as u8);
+        output.extend_from_slice(&row[start * bytes_per_pixel..end * bytes_per_pixel]);
+        start = end;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        image::{ImageBuffer, LumaA, Rgb, Rgb8},
+        png::PngDecoder,
+        tga::{grammar::FOOTER_SIZE, TgaDecoder},
+    };
+
+    #[test]
+    fn test_encode_packets() {
+        let row = [1, 1, 1, 2, 3, 4, 4];
+        let mut output = Vec::new();
+        encode_packets(&row, 1, &mut output);
+
+        assert_eq!(output, [0x82, 1, 1, 2, 3, 0x81, 4]);
+
+        // Runs longer than a packet are split.
+        let mut output = Vec::new();
+        encode_packets(&[7; 130], 1, &mut output);
+        assert_eq!(output, [0xFF, 7, 0x81, 7]);
+
+        let mut output = Vec::new();
+        let raw = (0..130).collect::<Vec<u8>>();
+        encode_packets(&raw, 1, &mut output);
+        assert_eq!(output[0], 127);
+        assert_eq!(output[129], 1);
+        assert_eq!(output.len(), 2 + 130);
+    }
+
+    #[test]
+    fn test_encode_round_trip() -> Result<()> {
+        let data = std::fs::read("./tests/obama.png")?;
+        let png = PngDecoder::new(&data).decode()?;
+
+        let mut encoded = Vec::new();
+        TgaEncoder::new(&mut encoded).encode(&png)?;
+
+        let tga = TgaDecoder::new(&encoded).decode()?;
+        assert_eq!(tga.color_type(), ColorType::RGB);
+        assert_eq!(tga.rgb8(), png.rgb8());
+        assert!(tga.header().run_length_encoded);
+
+        let reference = image::load_from_memory_with_format(&encoded, image::ImageFormat::Tga)?;
+        assert_eq!(reference.to_rgb8().as_raw().as_slice(), png.rgb8().as_ref());
+
+        // Grayscale with alpha is written as BGRA.
+        let image = ImageBuffer::from_fn(40, 3, |x, y| LumaA([(x / 8) as u8 * 50, y as u8 * 100]));
+
+        let mut encoded = Vec::new();
+        TgaEncoder::new(&mut encoded).encode(&image)?;
+
+        let tga = TgaDecoder::new(&encoded).decode()?;
+        assert_eq!(tga.color_type(), ColorType::RGBA);
+        assert_eq!(
+            tga.extension().map(|e| e.attributes_type),
+            Some(AttributesType::Alpha)
+        );
+        assert_eq!(tga.rgba8(), image.rgba8());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_encode_compresses_runs() -> Result<()> {
+        let image = ImageBuffer::<Rgb8>::from_pixel(100, 50, Rgb([10, 20, 30]));
+
+        let mut encoded = Vec::new();
+        TgaEncoder::new(&mut encoded).encode(&image)?;
+
+        // A run packet per scanline, then the extension area and footer.
+        assert_eq!(
+            encoded.len(),
+            HEADER_SIZE + 50 * 4 + EXTENSION_SIZE as usize + FOOTER_SIZE
+        );
+
+        Ok(())
+    }
+}
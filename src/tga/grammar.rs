@@ -0,0 +1,214 @@
+use crate::image::{
+    grammar::{AlphaMode, ColorType, Gamma, ImageExt},
+    DynamicImageBuffer,
+};
+use anyhow::bail;
+use std::borrow::Cow;
+
+pub const HEADER_SIZE: usize = 18;
+
+/// The size of the TGA 2.0 footer: the extension and developer area offsets, then the
+/// signature.
+pub const FOOTER_SIZE: usize = 26;
+
+/// Ends TGA 2.0 files, the only mark the format has.
+pub const FOOTER_SIGNATURE: [u8; 18] = *b"TRUEVISION-XFILE.\0";
+
+/// The size of the TGA 2.0 extension area.
+pub const EXTENSION_SIZE: u16 = 495;
+
+/// Image descriptor bits: the number of alpha bits per pixel, then the origin.
+pub const ALPHA_BITS_MASK: u8 = 0b0000_1111;
+pub const RIGHT_TO_LEFT: u8 = 0b0001_0000;
+pub const TOP_TO_BOTTOM: u8 = 0b0010_0000;
+
+/// Run-length packets repeat one pixel, raw packets list pixels. Either holds the count minus
+/// one in the low 7 bits.
+pub const RUN_PACKET: u8 = 0b1000_0000;
+pub const MAX_PACKET_LEN: usize = 128;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageType {
+    ColorMapped,
+    Truecolor,
+    Grayscale,
+}
+
+impl ImageType {
+    /// The image type and whether it is run-length encoded, from the header's image type byte.
+    /// Files without image data are not supported.
+    pub fn from_code(code: u8) -> anyhow::Result<(Self, bool)> {
+        Ok(match code {
+            1 => (Self::ColorMapped, false),
+            2 => (Self::Truecolor, false),
+            3 => (Self::Grayscale, false),
+            9 => (Self::ColorMapped, true),
+            10 => (Self::Truecolor, true),
+            11 => (Self::Grayscale, true),
+            _ => bail!("Unsupported TGA image type: {code}"),
+        })
+    }
+
+    pub const fn code(&self, run_length_encoded: bool) -> u8 {
+        let code = match self {
+            Self::ColorMapped => 1,
+            Self::Truecolor => 2,
+            Self::Grayscale => 3,
+        };
+
+        if run_length_encoded {
+            code + 8
+        } else {
+            code
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ColorMapSpec {
+    /// The index of the first entry, which pixels count from.
+    pub first_entry_index: u16,
+    pub len: u16,
+    pub entry_size: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TgaHeader {
+    pub image_type: ImageType,
+    pub run_length_encoded: bool,
+    /// Present whenever the file has a color map, which images other than color-mapped ones
+    /// may carry too.
+    pub color_map: Option<ColorMapSpec>,
+    /// Where to place the image on screen, which decoding ignores.
+    pub x_origin: u16,
+    pub y_origin: u16,
+    pub width: u16,
+    pub height: u16,
+    pub pixel_depth: u8,
+    pub alpha_bits: u8,
+    pub right_to_left: bool,
+    pub top_to_bottom: bool,
+    /// Free-form identification of the image.
+    pub image_id: Vec<u8>,
+}
+
+/// What the alpha channel holds, as declared by the extension area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AttributesType {
+    NoAlpha = 0,
+    /// Undefined data that may be ignored.
+    Undefined = 1,
+    /// Undefined data that should be retained.
+    UndefinedRetained = 2,
+    Alpha = 3,
+    PremultipliedAlpha = 4,
+}
+
+impl TryFrom<u8> for AttributesType {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::NoAlpha,
+            1 => Self::Undefined,
+            2 => Self::UndefinedRetained,
+            3 => Self::Alpha,
+            4 => Self::PremultipliedAlpha,
+            _ => bail!("Invalid TGA attributes type: {value}"),
+        })
+    }
+}
+
+/// The TGA 2.0 extension area, describing how the image was made and how to display it.
+/// Text fields are stored NUL-padded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TgaExtension {
+    pub author_name: String,
+    pub author_comments: String,
+    /// Month, day, year, hour, minute and second, all zero when unset.
+    pub timestamp: [u16; 6],
+    pub job_name: String,
+    pub software_id: String,
+    /// The color of the background around the image, as `0xAARRGGBB`.
+    pub key_color: u32,
+    /// The width of a pixel relative to its height, as a fraction. A zero denominator means
+    /// unset.
+    pub pixel_aspect_ratio: (u16, u16),
+    /// The gamma the image is meant to be displayed with, as a fraction. A zero denominator
+    /// means unset.
+    pub gamma: (u16, u16),
+    pub attributes_type: AttributesType,
+}
+
+impl TgaExtension {
+    /// The display gamma as the gamma the image was encoded with, its reciprocal.
+    pub fn encoding_gamma(&self) -> Option<Gamma> {
+        match self.gamma {
+            (0, _) | (_, 0) => None,
+            (numerator, denominator) => Some(Gamma::new(
+                (100_000.0 * denominator as f32 / numerator as f32).round() as u32,
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Tga {
+    pub(crate) header: TgaHeader,
+    pub(crate) extension: Option<TgaExtension>,
+    pub(crate) image: DynamicImageBuffer,
+}
+
+impl Tga {
+    pub const fn header(&self) -> &TgaHeader {
+        &self.header
+    }
+
+    /// The extension area of TGA 2.0 files that have one.
+    pub const fn extension(&self) -> Option<&TgaExtension> {
+        self.extension.as_ref()
+    }
+
+    pub const fn image(&self) -> &DynamicImageBuffer {
+        &self.image
+    }
+}
+
+impl ImageExt for Tga {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn gamma(&self) -> Option<Gamma> {
+        self.extension
+            .as_ref()
+            .and_then(TgaExtension::encoding_gamma)
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.image.color_type()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        match self.extension.as_ref().map(|e| e.attributes_type) {
+            Some(AttributesType::PremultipliedAlpha) => AlphaMode::Premultiplied,
+            _ => AlphaMode::Straight,
+        }
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        self.image.rgb8()
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        self.image.rgba8()
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        self.image.bitmap()
+    }
+}
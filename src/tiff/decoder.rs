@@ -0,0 +1,678 @@
+use crate::{
+    exif::{
+        grammar::{ByteOrder, Exif, Ifd, Value, EXIF_IFD_POINTER, GPS_IFD_POINTER},
+        ifd::IfdReader,
+    },
+    image::{grammar::AlphaMode, DynamicImageBuffer, ImageBuffer},
+    tiff::{
+        grammar::{
+            Compression, Photometric, Predictor, Tiff, TiffPage, BITS_PER_SAMPLE, COLOR_MAP,
+            COMPRESSION, EXTRA_SAMPLES, FILL_ORDER, IMAGE_LENGTH, IMAGE_WIDTH,
+            PHOTOMETRIC_INTERPRETATION, PLANAR_CONFIGURATION, PREDICTOR, ROWS_PER_STRIP,
+            SAMPLES_PER_PIXEL, SAMPLE_FORMAT, STRIP_BYTE_COUNTS, STRIP_OFFSETS, TILE_BYTE_COUNTS,
+            TILE_LENGTH, TILE_OFFSETS, TILE_WIDTH,
+        },
+        lzw,
+    },
+};
+use anyhow::{anyhow, ensure, Result};
+use flate2::read::ZlibDecoder;
+use std::{borrow::Cow, collections::HashSet, io::Read};
+
+/// How a page's samples are stored.
+#[derive(Debug)]
+struct SampleLayout {
+    width: usize,
+    height: usize,
+    samples_per_pixel: usize,
+    bits: usize,
+    compression: Compression,
+    predictor: Predictor,
+    byte_order: ByteOrder,
+}
+
+#[derive(Debug)]
+pub struct TiffDecoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> TiffDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    /// Decodes every page in the chain of IFDs.
+    pub fn decode(&self) -> Result<Tiff> {
+        let mut reader = IfdReader::new(self.data)?;
+        let byte_order = reader.byte_order();
+
+        let mut pages = Vec::new();
+        let mut visited = HashSet::new();
+        let mut offset = reader.next_ifd_offset();
+
+        while offset != 0 {
+            ensure!(
+                visited.insert(offset),
+                "TIFF IFDs loop back to offset {offset}."
+            );
+
+            let primary = reader.read_ifd(offset)?;
+            offset = reader.next_ifd_offset();
+
+            let mut read_sub_ifd = |pointer| {
+                primary.get(pointer).and_then(Value::as_u32).map_or_else(
+                    || Ok(Ifd::default()),
+                    |offset| reader.read_ifd(offset as usize),
+                )
+            };
+
+            let exif = read_sub_ifd(EXIF_IFD_POINTER)?;
+            let gps = read_sub_ifd(GPS_IFD_POINTER)?;
+
+            pages.push(self.decode_page(Exif {
+                byte_order,
+                primary,
+                exif,
+                gps,
+            })?);
+        }
+
+        ensure!(!pages.is_empty(), "TIFF file has no images.");
+
+        Ok(Tiff { byte_order, pages })
+    }
+
+    fn decode_page(&self, exif: Exif) -> Result<TiffPage> {
+        let ifd = exif.primary();
+        let field = |tag| ifd.get(tag).and_then(Value::as_u32);
+        let missing = |name: &str| anyhow!("TIFF page is missing {}.", name);
+
+        let width = field(IMAGE_WIDTH).ok_or_else(|| missing("ImageWidth"))?;
+        let height = field(IMAGE_LENGTH).ok_or_else(|| missing("ImageLength"))?;
+        ensure!(
+            width > 0 && height > 0,
+            "Invalid TIFF dimensions: {}x{}",
+            width,
+            height
+        );
+
+        let samples_per_pixel = field(SAMPLES_PER_PIXEL).unwrap_or(1) as usize;
+
+        let bits = ifd
+            .get(BITS_PER_SAMPLE)
+            .and_then(Value::as_u32s)
+            .unwrap_or_else(|| vec![1]);
+        ensure!(
+            bits.iter().all(|&b| b == bits[0]),
+            "Unsupported TIFF bits per sample: {bits:?}"
+        );
+        let bits = bits[0] as usize;
+
+        let compression = Compression::try_from(field(COMPRESSION).unwrap_or(1))?;
+        let predictor = Predictor::try_from(field(PREDICTOR).unwrap_or(1))?;
+
+        // Readers are asked to be lenient with writers that leave this out.
+        let photometric = field(PHOTOMETRIC_INTERPRETATION).map_or_else(
+            || {
+                Ok(if samples_per_pixel >= 3 {
+                    Photometric::Rgb
+                } else {
+                    Photometric::BlackIsZero
+                })
+            },
+            Photometric::try_from,
+        )?;
+
+        ensure!(
+            field(PLANAR_CONFIGURATION).unwrap_or(1) == 1,
+            "Unsupported TIFF planar configuration: samples must be interleaved."
+        );
+        ensure!(
+            field(FILL_ORDER).unwrap_or(1) == 1,
+            "Unsupported TIFF fill order: bits must be packed most significant first."
+        );
+        ensure!(
+            ifd.get(SAMPLE_FORMAT)
+                .and_then(Value::as_u32s)
+                .is_none_or(|formats| formats.iter().all(|&format| format == 1)),
+            "Unsupported TIFF sample format: only unsigned integers are supported."
+        );
+
+        let color_samples = photometric.color_samples();
+        ensure!(
+            samples_per_pixel >= color_samples,
+            "TIFF {photometric:?} pages need {color_samples} samples per pixel, not {samples_per_pixel}."
+        );
+
+        // Of the extra samples, only a first one holding alpha is kept.
+        let alpha_mode = match ifd.get(EXTRA_SAMPLES).and_then(Value::as_u32s).as_deref() {
+            _ if photometric == Photometric::Palette || samples_per_pixel == color_samples => None,
+            Some([1, ..]) => Some(AlphaMode::Premultiplied),
+            Some([2, ..]) => Some(AlphaMode::Straight),
+            _ => None,
+        };
+
+        let supported_bits = match photometric {
+            _ if alpha_mode.is_some() => [8, 16].contains(&bits),
+            Photometric::Rgb => [8, 16].contains(&bits),
+            Photometric::Palette => [1, 2, 4, 8].contains(&bits),
+            Photometric::WhiteIsZero | Photometric::BlackIsZero => [1, 2, 4, 8, 16].contains(&bits),
+        };
+        ensure!(
+            supported_bits,
+            "Unsupported TIFF bits per sample for {photometric:?}: {bits}"
+        );
+        ensure!(
+            predictor == Predictor::None || bits >= 8,
+            "The TIFF horizontal predictor needs 8 or 16-bit samples."
+        );
+
+        let layout = SampleLayout {
+            width: width as usize,
+            height: height as usize,
+            samples_per_pixel,
+            bits,
+            compression,
+            predictor,
+            byte_order: exif.byte_order(),
+        };
+
+        let mut samples = self.read_samples(ifd, &layout)?;
+
+        let channels = color_samples + usize::from(alpha_mode.is_some());
+        if channels < samples_per_pixel {
+            samples = samples
+                .chunks_exact(samples_per_pixel)
+                .flat_map(|pixel| &pixel[..channels])
+                .copied()
+                .collect();
+        }
+
+        let max = u16::MAX >> (16 - bits);
+
+        if photometric == Photometric::WhiteIsZero {
+            for pixel in samples.chunks_exact_mut(channels) {
+                pixel[0] = max - pixel[0];
+            }
+        }
+
+        let image = if photometric == Photometric::Palette {
+            let color_map = ifd
+                .get(COLOR_MAP)
+                .and_then(Value::as_u32s)
+                .ok_or_else(|| missing("ColorMap"))?;
+            ensure!(
+                color_map.len() == 3 << bits,
+                "Invalid TIFF color map length: {}",
+                color_map.len()
+            );
+
+            // All reds come first, then all greens, then all blues, each 16 bits.
+            let rgb = samples
+                .iter()
+                .flat_map(|&index| {
+                    [0, 1, 2].map(|c| {
+                        let value = color_map[(c << bits) + index as usize];
+                        ((value * 255 + 32767) / 65535) as u8
+                    })
+                })
+                .collect();
+
+            DynamicImageBuffer::Rgb8(ImageBuffer::from_raw(width, height, rgb)?)
+        } else if bits == 16 {
+            match channels {
+                1 => DynamicImageBuffer::Luma16(ImageBuffer::from_raw(width, height, samples)?),
+                2 => DynamicImageBuffer::LumaA16(ImageBuffer::from_raw(width, height, samples)?),
+                3 => DynamicImageBuffer::Rgb16(ImageBuffer::from_raw(width, height, samples)?),
+                _ => DynamicImageBuffer::Rgba16(ImageBuffer::from_raw(width, height, samples)?),
+            }
+        } else {
+            let max = max as u32;
+            let samples = samples
+                .into_iter()
+                .map(|sample| ((sample as u32 * 255 + max / 2) / max) as u8)
+                .collect();
+
+            match channels {
+                1 => DynamicImageBuffer::Luma8(ImageBuffer::from_raw(width, height, samples)?),
+                2 => DynamicImageBuffer::LumaA8(ImageBuffer::from_raw(width, height, samples)?),
+                3 => DynamicImageBuffer::Rgb8(ImageBuffer::from_raw(width, height, samples)?),
+                _ => DynamicImageBuffer::Rgba8(ImageBuffer::from_raw(width, height, samples)?),
+            }
+        };
+
+        Ok(TiffPage {
+            compression,
+            photometric,
+            alpha_mode: alpha_mode.unwrap_or_default(),
+            exif,
+            image,
+        })
+    }
+
+    /// Reads every sample of the page, row by row, from its strips or tiles.
+    fn read_samples(&self, ifd: &Ifd, layout: &SampleLayout) -> Result<Vec<u16>> {
+        let &SampleLayout {
+            width,
+            height,
+            samples_per_pixel,
+            bits,
+            ..
+        } = layout;
+
+        let field = |tag| ifd.get(tag).and_then(Value::as_u32);
+        let fields = |tag, name: &str| {
+            ifd.get(tag)
+                .and_then(Value::as_u32s)
+                .ok_or_else(|| anyhow!("TIFF page is missing {}.", name))
+        };
+
+        let tiled = ifd.get(TILE_WIDTH).is_some();
+
+        // Strips are chunks as wide as the image.
+        let (chunk_width, chunk_height, offsets, byte_counts) = if tiled {
+            (
+                field(TILE_WIDTH).unwrap_or(0) as usize,
+                field(TILE_LENGTH).unwrap_or(0) as usize,
+                fields(TILE_OFFSETS, "TileOffsets")?,
+                fields(TILE_BYTE_COUNTS, "TileByteCounts")?,
+            )
+        } else {
+            (
+                width,
+                (field(ROWS_PER_STRIP).unwrap_or(u32::MAX) as usize).min(height),
+                fields(STRIP_OFFSETS, "StripOffsets")?,
+                fields(STRIP_BYTE_COUNTS, "StripByteCounts")?,
+            )
+        };
+
+        ensure!(
+            chunk_width > 0 && chunk_height > 0,
+            "Invalid TIFF chunk size: {}x{}",
+            chunk_width,
+            chunk_height
+        );
+
+        let chunks_across = width.div_ceil(chunk_width);
+        let num_chunks = chunks_across * height.div_ceil(chunk_height);
+        ensure!(
+            offsets.len() >= num_chunks && byte_counts.len() >= num_chunks,
+            "TIFF page has {} chunks, not {}.",
+            offsets.len().min(byte_counts.len()),
+            num_chunks
+        );
+
+        // Rows of chunks are padded to whole bytes.
+        let row_len = (chunk_width * samples_per_pixel * bits).div_ceil(8);
+
+        // The dimensions come straight from the tags, so check that the chunks' data can
+        // decode to every row before allocating. Tiles are always whole.
+        let chunk_rows = if tiled {
+            chunk_height.checked_mul(num_chunks)
+        } else {
+            Some(height)
+        };
+        let decoded_len = chunk_rows.and_then(|rows| row_len.checked_mul(rows));
+        let available = byte_counts[..num_chunks]
+            .iter()
+            .map(|&byte_count| byte_count as u64)
+            .sum::<u64>()
+            .saturating_mul(layout.compression.max_expansion());
+
+        ensure!(
+            decoded_len.is_some_and(|len| len as u64 <= available),
+            "TIFF page is larger than its data allows: {}x{}",
+            width,
+            height
+        );
+
+        let mut samples = vec![0; width * height * samples_per_pixel];
+        let mut row_samples = vec![0; chunk_width * samples_per_pixel];
+
+        for (i, (&offset, &byte_count)) in offsets.iter().zip(&byte_counts).enumerate() {
+            if i == num_chunks {
+                break;
+            }
+
+            let x = i % chunks_across * chunk_width;
+            let y = i / chunks_across * chunk_height;
+
+            // The last strip may end with the image, while tiles are always whole.
+            let rows = if tiled {
+                chunk_height
+            } else {
+                chunk_height.min(height - y)
+            };
+
+            let data = self
+                .data
+                .get(offset as usize..offset as usize + byte_count as usize)
+                .ok_or_else(|| anyhow!("TIFF chunk {i} is out of bounds."))?;
+
+            let chunk = decompress(layout.compression, data, row_len * rows)?;
+            ensure!(
+                chunk.len() >= row_len * rows,
+                "TIFF chunk {i} is truncated."
+            );
+
+            let visible_rows = rows.min(height - y);
+            let visible_samples = chunk_width.min(width - x) * samples_per_pixel;
+
+            for (row, data) in chunk.chunks_exact(row_len).take(visible_rows).enumerate() {
+                unpack_row(data, bits, layout.byte_order, &mut row_samples);
+
+                if layout.predictor == Predictor::Horizontal {
+                    let max = u16::MAX >> (16 - bits);
+
+                    for i in samples_per_pixel..row_samples.len() {
+                        row_samples[i] =
+                            row_samples[i].wrapping_add(row_samples[i - samples_per_pixel]) & max;
+                    }
+                }
+
+                let start = ((y + row) * width + x) * samples_per_pixel;
+                samples[start..start + visible_samples]
+                    .copy_from_slice(&row_samples[..visible_samples]);
+            }
+        }
+
+        Ok(samples)
+    }
+}
+
+/// Unpacks a row of `bits` wide samples, packed most significant bit first.
+fn unpack_row(data: &[u8], bits: usize, byte_order: ByteOrder, samples: &mut [u16]) {
+    match bits {
+        16 => {
+            for (sample, bytes) in samples.iter_mut().zip(data.chunks_exact(2)) {
+                let bytes = [bytes[0], bytes[1]];
+
+                *sample = match byte_order {
+                    ByteOrder::LittleEndian => u16::from_le_bytes(bytes),
+                    ByteOrder::BigEndian => u16::from_be_bytes(bytes),
+                };
+            }
+        }
+        8 => {
+            for (sample, &byte) in samples.iter_mut().zip(data) {
+                *sample = byte as u16;
+            }
+        }
+        _ => {
+            let mask = (1 << bits) - 1;
+
+            for (i, sample) in samples.iter_mut().enumerate() {
+                let bit = i * bits;
+                *sample = ((data[bit / 8] >> (8 - bits - bit % 8)) & mask) as u16;
+            }
+        }
+    }
+}
+
+/// Decompresses a strip or tile, stopping after `max_len` bytes.
+fn decompress(compression: Compression, data: &[u8], max_len: usize) -> Result<Cow<'_, [u8]>> {
+    Ok(match compression {
+        Compression::None => Cow::Borrowed(data),
+        Compression::Lzw => Cow::Owned(lzw::decode(data, max_len)?),
+        Compression::Deflate => {
+            let mut output = Vec::with_capacity(max_len);
+            ZlibDecoder::new(data)
+                .take(max_len as u64)
+                .read_to_end(&mut output)?;
+
+            Cow::Owned(output)
+        }
+        Compression::PackBits => Cow::Owned(unpack_bits(data, max_len)?),
+    })
+}
+
+/// Decodes PackBits: a header byte `n` is followed by `n + 1` literal bytes, or for negative
+/// `n`, by one byte repeated `1 - n` times.
+fn unpack_bits(data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(max_len);
+    let mut cursor = 0;
+
+    while output.len() < max_len && cursor < data.len() {
+        let header = data[cursor] as i8;
+        cursor += 1;
+
+        match header {
+            0.. => {
+                let len = header as usize + 1;
+                let literal = data
+                    .get(cursor..cursor + len)
+                    .ok_or_else(|| anyhow!("PackBits literal run is truncated."))?;
+
+                output.extend_from_slice(literal);
+                cursor += len;
+            }
+            // A no-op.
+            -128 => {}
+            _ => {
+                let &byte = data
+                    .get(cursor)
+                    .ok_or_else(|| anyhow!("PackBits repeat run is truncated."))?;
+
+                output.resize(output.len() + (1 - header as isize) as usize, byte);
+                cursor += 1;
+            }
+        }
+    }
+
+    output.truncate(max_len);
+
+    Ok(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exif::grammar::Orientation,
+        image::{grammar::ImageExt, Rgba16},
+    };
+
+    fn decode(path: &str) -> Result<Tiff> {
+        TiffDecoder::new(&std::fs::read(path)?).decode()
+    }
+
+    /// Decodes `path` and compares its first page against the `image` crate's decoding, at 16
+    /// bits so that 16-bit samples are compared in full.
+    fn compare_tiff(path: &str) -> Result<Tiff> {
+        let reference = image::open(path)?.to_rgba16();
+        let tiff = decode(path)?;
+
+        assert_eq!(tiff.dimensions(), reference.dimensions(), "{path}");
+        assert_eq!(
+            tiff.pages()[0].image().to::<Rgba16>().as_raw(),
+            reference.as_raw(),
+            "{path}"
+        );
+
+        Ok(tiff)
+    }
+
+    #[test]
+    fn test_decode_strips() -> Result<()> {
+        let gray = compare_tiff("./tests/tiff/gray8_strips.tif")?;
+        assert_eq!(gray.byte_order(), ByteOrder::LittleEndian);
+        assert_eq!(gray.pages()[0].compression(), Compression::None);
+
+        let rgb = compare_tiff("./tests/tiff/rgb8_lzw_predictor.tif")?;
+        assert_eq!(rgb.byte_order(), ByteOrder::BigEndian);
+        assert_eq!(rgb.pages()[0].compression(), Compression::Lzw);
+
+        compare_tiff("./tests/tiff/rgb8_lzw_noise.tif")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_tiles() -> Result<()> {
+        let rgba = compare_tiff("./tests/tiff/rgba16_deflate_tiles.tif")?;
+        assert_eq!(rgba.pages()[0].compression(), Compression::Deflate);
+        assert_eq!(rgba.pages()[0].image().bit_depth(), 16);
+
+        let gray = compare_tiff("./tests/tiff/gray16_lzw_tiles.tif")?;
+        assert_eq!(gray.pages()[0].image().bit_depth(), 16);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_palette_and_bilevel() -> Result<()> {
+        // The `image` crate does not read palette images, so these are checked against the color
+        // maps the fixtures were written with: red rises with the index, green falls, and blue
+        // steps by 7.
+        for (path, bits) in [
+            ("./tests/tiff/palette4_packbits.tif", 4),
+            ("./tests/tiff/palette8_lzw.tif", 8),
+        ] {
+            let colors = 1u32 << bits;
+            let scale = 255 / (colors - 1);
+            let expected = (0..21)
+                .flat_map(|y| (0..37).map(move |x| (x / 3 + y / 2) % colors))
+                .map(|i| if bits == 8 { i * 7 % colors } else { i })
+                .flat_map(|i| [i, colors - 1 - i, i * 7 % colors].map(|c| (c * scale) as u8))
+                .collect::<Vec<_>>();
+
+            let palette = decode(path)?;
+            assert_eq!(palette.pages()[0].photometric(), Photometric::Palette);
+            assert_eq!(palette.rgb8().as_ref(), expected.as_slice(), "{path}");
+        }
+
+        // Nor 1-bit images. With WhiteIsZero, set bits are black.
+        let bilevel = decode("./tests/tiff/bilevel_packbits.tif")?;
+        assert_eq!(bilevel.pages()[0].photometric(), Photometric::WhiteIsZero);
+
+        let expected = (0..21)
+            .flat_map(|y| (0..37).map(move |x| (x * 3 + y * 5) % 7 < 3))
+            .map(|set| if set { 0 } else { 255 })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            bilevel.pages()[0].image(),
+            &DynamicImageBuffer::Luma8(ImageBuffer::from_raw(37, 21, expected)?)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_associated_alpha() -> Result<()> {
+        let rgba = compare_tiff("./tests/tiff/rgba8_associated.tif")?;
+        assert_eq!(rgba.alpha_mode(), AlphaMode::Premultiplied);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_pages() -> Result<()> {
+        let tiff = compare_tiff("./tests/tiff/multipage.tif")?;
+        let [rgb, gray] = tiff.pages() else {
+            panic!("Expected two pages, found {}", tiff.pages().len());
+        };
+
+        assert_eq!(rgb.dimensions(), (37, 21));
+        assert_eq!(gray.dimensions(), (20, 10));
+        assert_eq!(gray.compression(), Compression::PackBits);
+
+        let strips = decode("./tests/tiff/gray8_strips.tif")?;
+        assert_eq!(
+            gray.image().to::<Rgba16>().as_raw(),
+            strips.pages()[0]
+                .image()
+                .to::<Rgba16>()
+                .view(0, 0, 20, 10)?
+                .to_image()
+                .as_raw()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_orientation() -> Result<()> {
+        let tiff = decode("./tests/tiff/orientation.tif")?;
+        let exif = tiff.exif().expect("page IFD");
+
+        assert_eq!(exif.orientation(), Orientation::Rotate90);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_oversized() {
+        // A little-endian page of 8-bit grayscale whose tags claim far more pixels than its 16
+        // bytes of data hold.
+        let page = |tags: &[(u16, u32)]| {
+            let mut data = b"II*\0".to_vec();
+            data.extend_from_slice(&8u32.to_le_bytes());
+            data.extend_from_slice(&(tags.len() as u16).to_le_bytes());
+
+            let data_offset = 8 + 2 + tags.len() as u32 * 12 + 4;
+
+            for &(tag, value) in tags {
+                let value = if value == u32::MAX {
+                    data_offset
+                } else {
+                    value
+                };
+
+                data.extend_from_slice(&tag.to_le_bytes());
+                data.extend_from_slice(&4u16.to_le_bytes());
+                data.extend_from_slice(&1u32.to_le_bytes());
+                data.extend_from_slice(&value.to_le_bytes());
+            }
+
+            data.extend_from_slice(&0u32.to_le_bytes());
+            data.extend_from_slice(&[0; 16]);
+            data
+        };
+
+        let base = [
+            (IMAGE_WIDTH, 100_000),
+            (IMAGE_LENGTH, 100_000),
+            (BITS_PER_SAMPLE, 8),
+            (COMPRESSION, 8),
+            (PHOTOMETRIC_INTERPRETATION, 1),
+        ];
+
+        let strips = [
+            (STRIP_OFFSETS, u32::MAX),
+            (ROWS_PER_STRIP, 100_000),
+            (STRIP_BYTE_COUNTS, 16),
+        ];
+        let tiles = [
+            (TILE_WIDTH, u32::MAX - 15),
+            (TILE_LENGTH, 100_000),
+            (TILE_OFFSETS, u32::MAX),
+            (TILE_BYTE_COUNTS, 16),
+        ];
+
+        for chunks in [&strips[..], &tiles[..]] {
+            let mut tags = [&base[..], chunks].concat();
+            tags.sort_unstable_by_key(|&(tag, _)| tag);
+
+            assert!(TiffDecoder::new(&page(&tags)).decode().is_err());
+        }
+    }
+
+    #[test]
+    fn test_unpack_bits() -> Result<()> {
+        // The example from the TIFF 6.0 specification, Section 9.
+        let packed = [
+            0xFE, 0xAA, 0x02, 0x80, 0x00, 0x2A, 0xFD, 0xAA, 0x03, 0x80, 0x00, 0x2A, 0x22, 0xF7,
+            0xAA,
+        ];
+        let unpacked = [
+            0xAA, 0xAA, 0xAA, 0x80, 0x00, 0x2A, 0xAA, 0xAA, 0xAA, 0xAA, 0x80, 0x00, 0x2A, 0x22,
+            0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA, 0xAA,
+        ];
+
+        assert_eq!(unpack_bits(&packed, 1024)?, unpacked);
+        assert_eq!(unpack_bits(&packed, 4)?, unpacked[..4]);
+        assert!(unpack_bits(&packed[..4], 1024).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,241 @@
+use crate::{
+    exif::grammar::{ByteOrder, Exif, Ifd, Tag},
+    image::{
+        grammar::{AlphaMode, ColorType, ImageExt},
+        DynamicImageBuffer,
+    },
+};
+use anyhow::bail;
+use std::borrow::Cow;
+
+pub const NEW_SUBFILE_TYPE: Tag = 0x00FE;
+pub const IMAGE_WIDTH: Tag = 0x0100;
+pub const IMAGE_LENGTH: Tag = 0x0101;
+pub const BITS_PER_SAMPLE: Tag = 0x0102;
+pub const COMPRESSION: Tag = 0x0103;
+pub const PHOTOMETRIC_INTERPRETATION: Tag = 0x0106;
+pub const FILL_ORDER: Tag = 0x010A;
+pub const STRIP_OFFSETS: Tag = 0x0111;
+pub const SAMPLES_PER_PIXEL: Tag = 0x0115;
+pub const ROWS_PER_STRIP: Tag = 0x0116;
+pub const STRIP_BYTE_COUNTS: Tag = 0x0117;
+pub const PLANAR_CONFIGURATION: Tag = 0x011C;
+pub const PREDICTOR: Tag = 0x013D;
+pub const COLOR_MAP: Tag = 0x0140;
+pub const TILE_WIDTH: Tag = 0x0142;
+pub const TILE_LENGTH: Tag = 0x0143;
+pub const TILE_OFFSETS: Tag = 0x0144;
+pub const TILE_BYTE_COUNTS: Tag = 0x0145;
+pub const EXTRA_SAMPLES: Tag = 0x0152;
+pub const SAMPLE_FORMAT: Tag = 0x0153;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Compression {
+    None = 1,
+    Lzw = 5,
+    /// zlib, as registered by Adobe.
+    Deflate = 8,
+    PackBits = 32773,
+}
+
+impl TryFrom<u32> for Compression {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => Self::None,
+            5 => Self::Lzw,
+            // The code Deflate used before Adobe's registration.
+            8 | 32946 => Self::Deflate,
+            32773 => Self::PackBits,
+            _ => bail!("Unsupported TIFF compression: {value}"),
+        })
+    }
+}
+
+impl Compression {
+    /// The most bytes a compressed byte can decode to. An LZW code is at least 9 bits and
+    /// decodes to at most 4096 bytes, Deflate peaks at 1032:1 and a PackBits repeat run turns 2
+    /// bytes into 128.
+    pub const fn max_expansion(&self) -> u64 {
+        match self {
+            Self::None => 1,
+            Self::Lzw => 4096 * 8 / 9 + 1,
+            Self::Deflate => 1032,
+            Self::PackBits => 64,
+        }
+    }
+}
+
+/// How samples map to colors.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Photometric {
+    /// Grayscale where 0 is white.
+    WhiteIsZero = 0,
+    /// Grayscale where 0 is black.
+    BlackIsZero = 1,
+    Rgb = 2,
+    /// Indices into the color map.
+    Palette = 3,
+}
+
+impl Photometric {
+    /// The number of samples per pixel that make up its color, before any extra samples.
+    pub const fn color_samples(&self) -> usize {
+        match self {
+            Self::Rgb => 3,
+            _ => 1,
+        }
+    }
+}
+
+impl TryFrom<u32> for Photometric {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            0 => Self::WhiteIsZero,
+            1 => Self::BlackIsZero,
+            2 => Self::Rgb,
+            3 => Self::Palette,
+            _ => bail!("Unsupported TIFF photometric interpretation: {value}"),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Predictor {
+    None = 1,
+    /// Each sample is stored as the difference from the same sample of the pixel to its left.
+    Horizontal = 2,
+}
+
+impl TryFrom<u32> for Predictor {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u32) -> Result<Self, Self::Error> {
+        Ok(match value {
+            1 => Self::None,
+            2 => Self::Horizontal,
+            _ => bail!("Unsupported TIFF predictor: {value}"),
+        })
+    }
+}
+
+/// An image in a TIFF file, described by its IFD.
+#[derive(Debug)]
+pub struct TiffPage {
+    pub(crate) compression: Compression,
+    pub(crate) photometric: Photometric,
+    pub(crate) alpha_mode: AlphaMode,
+    /// The page's IFD, along with the Exif and GPS IFDs it points to.
+    pub(crate) exif: Exif,
+    pub(crate) image: DynamicImageBuffer,
+}
+
+impl TiffPage {
+    pub const fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    pub const fn photometric(&self) -> Photometric {
+        self.photometric
+    }
+
+    pub const fn ifd(&self) -> &Ifd {
+        self.exif.primary()
+    }
+
+    pub const fn image(&self) -> &DynamicImageBuffer {
+        &self.image
+    }
+}
+
+impl ImageExt for TiffPage {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.image.color_type()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.alpha_mode
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        self.image.rgb8()
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        self.image.rgba8()
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        self.image.bitmap()
+    }
+
+    fn exif(&self) -> Option<&Exif> {
+        Some(&self.exif)
+    }
+}
+
+/// A TIFF file: one or more pages, the first of which is the image.
+#[derive(Debug)]
+pub struct Tiff {
+    pub(crate) byte_order: ByteOrder,
+    pub(crate) pages: Vec<TiffPage>,
+}
+
+impl Tiff {
+    pub const fn byte_order(&self) -> ByteOrder {
+        self.byte_order
+    }
+
+    pub fn pages(&self) -> &[TiffPage] {
+        &self.pages
+    }
+
+    fn first_page(&self) -> &TiffPage {
+        &self.pages[0]
+    }
+}
+
+impl ImageExt for Tiff {
+    fn width(&self) -> u32 {
+        self.first_page().width()
+    }
+
+    fn height(&self) -> u32 {
+        self.first_page().height()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.first_page().color_type()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        self.first_page().alpha_mode()
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        self.first_page().rgb8()
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        self.first_page().rgba8()
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        self.first_page().bitmap()
+    }
+
+    fn exif(&self) -> Option<&Exif> {
+        self.first_page().exif()
+    }
+}
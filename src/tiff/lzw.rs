@@ -0,0 +1,126 @@
+use anyhow::{bail, ensure, Result};
+
+/// Codes are at most 12 bits wide.
+const MAX_CODES: usize = 1 << 12;
+
+const CLEAR_CODE: u16 = 256;
+const END_CODE: u16 = 257;
+
+/// Reads codes packed most significant bit first.
+struct BitReader<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    buffer: u32,
+    bits: u8,
+}
+
+impl<'a> BitReader<'a> {
+    const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            buffer: 0,
+            bits: 0,
+        }
+    }
+
+    fn read(&mut self, width: u8) -> Option<u16> {
+        while self.bits < width {
+            let &byte = self.data.get(self.cursor)?;
+            self.cursor += 1;
+
+            self.buffer = (self.buffer << 8) | byte as u32;
+            self.bits += 8;
+        }
+
+        self.bits -= width;
+        let code = ((self.buffer >> self.bits) & ((1 << width) - 1)) as u16;
+
+        Some(code)
+    }
+}
+
+/// Decodes TIFF's LZW, stopping after `max_len` bytes. Codes widen one code earlier than GIF's,
+/// as the table reaches `2^width - 1` entries.
+pub fn decode(data: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    // Each entry is an earlier entry followed by one byte. Entries below the clear code are the
+    // bytes themselves.
+    let mut prefixes = [0u16; MAX_CODES];
+    let mut suffixes = [0u8; MAX_CODES];
+    let mut lengths = [0u16; MAX_CODES];
+
+    for code in 0..CLEAR_CODE {
+        suffixes[code as usize] = code as u8;
+        lengths[code as usize] = 1;
+    }
+
+    let mut next_code = END_CODE + 1;
+    let mut code_size = 9;
+    let mut previous: Option<u16> = None;
+
+    let mut output = Vec::with_capacity(max_len);
+    let mut reader = BitReader::new(data);
+
+    while output.len() < max_len {
+        let Some(code) = reader.read(code_size) else {
+            break;
+        };
+
+        if code == CLEAR_CODE {
+            next_code = END_CODE + 1;
+            code_size = 9;
+            previous = None;
+            continue;
+        }
+
+        if code == END_CODE {
+            break;
+        }
+
+        let Some(previous_code) = previous else {
+            ensure!(code < CLEAR_CODE, "Invalid first LZW code: {}", code);
+            output.push(code as u8);
+            previous = Some(code);
+            continue;
+        };
+
+        // A code one past the table is the previous entry followed by its own first byte.
+        let entry = match code {
+            code if code < next_code => code,
+            code if code == next_code => previous_code,
+            code => bail!("Invalid LZW code: {}", code),
+        };
+
+        let start = output.len();
+        let length = lengths[entry as usize] as usize;
+        output.resize(start + length, 0);
+
+        let mut walk = entry;
+        for i in (start..start + length).rev() {
+            output[i] = suffixes[walk as usize];
+            walk = prefixes[walk as usize];
+        }
+
+        let first = output[start];
+        if code == next_code {
+            output.push(first);
+        }
+
+        if (next_code as usize) < MAX_CODES {
+            prefixes[next_code as usize] = previous_code;
+            suffixes[next_code as usize] = first;
+            lengths[next_code as usize] = lengths[previous_code as usize] + 1;
+            next_code += 1;
+
+            if next_code + 1 == 1 << code_size && code_size < 12 {
+                code_size += 1;
+            }
+        }
+
+        previous = Some(code);
+    }
+
+    output.truncate(max_len);
+
+    Ok(output)
+}
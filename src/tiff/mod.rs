@@ -0,0 +1,6 @@
+mod decoder;
+mod lzw;
+
+pub mod grammar;
+
+pub use decoder::*;
@@ -0,0 +1,65 @@
+use crate::webp::{
+    grammar::{AlphaCompression, AlphaFiltering, AlphaHeader},
+    lossless::LosslessDecoder,
+};
+use anyhow::{anyhow, ensure, Result};
+
+/// Decodes the alpha plane of an `ALPH` chunk, one byte per pixel in raster order.
+pub fn decode_alpha(data: &[u8], width: u32, height: u32) -> Result<Vec<u8>> {
+    let (&header, data) = data
+        .split_first()
+        .ok_or_else(|| anyhow!("ALPH chunk is empty."))?;
+    let header = AlphaHeader::try_from(header)?;
+
+    let num_pixels = width as usize * height as usize;
+
+    let mut alpha = match header.compression {
+        AlphaCompression::None => {
+            ensure!(data.len() >= num_pixels, "ALPH chunk is truncated.");
+            data[..num_pixels].to_vec()
+        }
+        // The plane is a headerless VP8L image stream, whose green channel holds alpha.
+        AlphaCompression::Lossless => LosslessDecoder::new(data)
+            .decode_image_stream(width, height)?
+            .into_iter()
+            .map(|argb| (argb >> 8) as u8)
+            .collect(),
+    };
+
+    unfilter(&mut alpha, width as usize, header.filtering);
+
+    Ok(alpha)
+}
+
+/// Adds back the prediction each alpha value was stored as the difference from. The first
+/// row always predicts from the left, and the first column from above.
+fn unfilter(alpha: &mut [u8], width: usize, filtering: AlphaFiltering) {
+    if filtering == AlphaFiltering::None {
+        return;
+    }
+
+    for index in 0..alpha.len() {
+        let (x, y) = (index % width, index / width);
+
+        let prediction = match (x, y) {
+            (0, 0) => 0,
+            (_, 0) => alpha[index - 1],
+            (0, _) => alpha[index - width],
+            _ => {
+                let left = alpha[index - 1];
+                let top = alpha[index - width];
+
+                match filtering {
+                    AlphaFiltering::Horizontal => left,
+                    AlphaFiltering::Vertical => top,
+                    _ => {
+                        let top_left = alpha[index - width - 1];
+                        (left as i32 + top as i32 - top_left as i32).clamp(0, 255) as u8
+                    }
+                }
+            }
+        };
+
+        alpha[index] = alpha[index].wrapping_add(prediction);
+    }
+}
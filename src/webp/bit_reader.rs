@@ -0,0 +1,77 @@
+use anyhow::{ensure, Result};
+
+/// Reads bits least significant first, as VP8L packs them.
+#[derive(Debug)]
+pub struct BitReader<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    buffer: u64,
+    bits: u32,
+    /// Zero bits appended past the end of `data`, so that peeks near the end succeed.
+    padding: u32,
+}
+
+impl<'a> BitReader<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            cursor: 0,
+            buffer: 0,
+            bits: 0,
+            padding: 0,
+        }
+    }
+
+    fn fill(&mut self) {
+        while self.bits <= 56 {
+            let byte = match self.data.get(self.cursor) {
+                Some(&byte) => byte,
+                None => {
+                    self.padding += 8;
+                    0
+                }
+            };
+
+            self.cursor += 1;
+            self.buffer |= (byte as u64) << self.bits;
+            self.bits += 8;
+        }
+    }
+
+    /// The next `n` bits, without consuming them.
+    pub fn peek(&mut self, n: u32) -> u32 {
+        if self.bits < n {
+            self.fill();
+        }
+
+        (self.buffer & ((1 << n) - 1)) as u32
+    }
+
+    pub fn consume(&mut self, n: u32) -> Result<()> {
+        if self.bits < n {
+            self.fill();
+        }
+
+        self.buffer >>= n;
+        self.bits -= n;
+
+        ensure!(
+            self.padding <= self.bits,
+            "VP8L bitstream ended unexpectedly."
+        );
+
+        Ok(())
+    }
+
+    /// Reads `n` bits, at most 32.
+    pub fn read_bits(&mut self, n: u32) -> Result<u32> {
+        let value = self.peek(n);
+        self.consume(n)?;
+
+        Ok(value)
+    }
+
+    pub fn read_bit(&mut self) -> Result<bool> {
+        Ok(self.read_bits(1)? == 1)
+    }
+}
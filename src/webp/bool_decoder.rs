@@ -0,0 +1,128 @@
+use anyhow::{ensure, Result};
+
+/// The boolean entropy decoder of VP8 (RFC 6386, Section 7). Each bit is decoded with the
+/// probability, out of 256, that it is zero.
+#[derive(Debug)]
+pub struct BoolDecoder<'a> {
+    data: &'a [u8],
+    cursor: usize,
+    /// The bits of the coded value that are not yet consumed, the next 8 of them in the high
+    /// byte.
+    value: u32,
+    range: u32,
+    /// The number of bits `value` has been shifted by since a byte was last loaded.
+    bit_count: u32,
+    /// Zero bytes appended past the end of `data`.
+    padding: usize,
+}
+
+impl<'a> BoolDecoder<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        let mut decoder = Self {
+            data,
+            cursor: 0,
+            value: 0,
+            range: 255,
+            bit_count: 0,
+            padding: 0,
+        };
+
+        decoder.value = (decoder.next_byte() << 8) | decoder.next_byte();
+
+        decoder
+    }
+
+    fn next_byte(&mut self) -> u32 {
+        match self.data.get(self.cursor) {
+            Some(&byte) => {
+                self.cursor += 1;
+                byte as u32
+            }
+            None => {
+                self.padding += 1;
+                0
+            }
+        }
+    }
+
+    /// Fails if decoding has read well past the end of the data. The decoder reads ahead of the
+    /// bits it has consumed, so a little padding is expected at the end of a partition.
+    pub fn ensure_not_exhausted(&self) -> Result<()> {
+        ensure!(self.padding <= 2, "VP8 partition ended unexpectedly.");
+
+        Ok(())
+    }
+
+    pub fn read_bool(&mut self, probability: u8) -> bool {
+        let split = 1 + (((self.range - 1) * probability as u32) >> 8);
+        let big_split = split << 8;
+
+        let bit = if self.value >= big_split {
+            self.range -= split;
+            self.value -= big_split;
+            true
+        } else {
+            self.range = split;
+            false
+        };
+
+        while self.range < 128 {
+            self.value <<= 1;
+            self.range <<= 1;
+            self.bit_count += 1;
+
+            if self.bit_count == 8 {
+                self.bit_count = 0;
+                self.value |= self.next_byte();
+            }
+        }
+
+        bit
+    }
+
+    /// A bit that is as likely to be one as zero.
+    pub fn read_flag(&mut self) -> bool {
+        self.read_bool(128)
+    }
+
+    /// An unsigned `n`-bit literal, most significant bit first.
+    pub fn read_literal(&mut self, n: u32) -> u32 {
+        (0..n).fold(0, |value, _| (value << 1) | self.read_flag() as u32)
+    }
+
+    /// An `n`-bit magnitude followed by a sign bit.
+    pub fn read_signed_literal(&mut self, n: u32) -> i32 {
+        let magnitude = self.read_literal(n) as i32;
+
+        if self.read_flag() {
+            -magnitude
+        } else {
+            magnitude
+        }
+    }
+
+    /// A signed literal that is only present if a flag says so, and 0 otherwise.
+    pub fn read_optional_signed_literal(&mut self, n: u32) -> i32 {
+        if self.read_flag() {
+            self.read_signed_literal(n)
+        } else {
+            0
+        }
+    }
+
+    /// Walks a tree whose nodes hold the index of their children, and whose leaves hold their
+    /// value negated. Node `i`'s bit is decoded with `probabilities[i / 2]`.
+    pub fn read_tree(&mut self, tree: &[i8], probabilities: &[u8]) -> u8 {
+        let mut index = 0;
+
+        loop {
+            let node = tree[index + self.read_bool(probabilities[index >> 1]) as usize];
+
+            if node <= 0 {
+                return -node as u8;
+            }
+
+            index = node as usize;
+        }
+    }
+}
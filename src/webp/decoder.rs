@@ -0,0 +1,398 @@
+use crate::{
+    exif::ExifDecoder,
+    icc::IccDecoder,
+    impl_read_le_for_datatype, impl_read_slice,
+    webp::{
+        grammar::{AlphaHeader, Bitstream, ExtendedHeader, Webp, WebpInfo},
+        lossless::LosslessDecoder,
+        lossy::LossyDecoder,
+    },
+};
+use anyhow::{anyhow, bail, ensure, Result};
+
+/// The 16-byte header of an `ANMF` chunk that precedes the frame's own chunks.
+const FRAME_HEADER_SIZE: usize = 16;
+
+/// A chunk of the RIFF container, without the padding byte that follows odd-sized chunks.
+#[derive(Debug, Clone, Copy)]
+struct Chunk<'a> {
+    fourcc: [u8; 4],
+    data: &'a [u8],
+}
+
+/// The kind of bitstream, its data, and the `ALPH` chunk that precedes a lossy one.
+type BitstreamChunks<'a> = (Bitstream, &'a [u8], Option<&'a [u8]>);
+
+/// The chunks the image is decoded from, found while reading the container.
+#[derive(Debug)]
+struct Container<'a> {
+    info: WebpInfo,
+    bitstream: &'a [u8],
+    /// The `ALPH` chunk of a lossy image.
+    alpha: Option<&'a [u8]>,
+}
+
+#[derive(Debug)]
+pub struct WebpDecoder<'a> {
+    cursor: usize,
+    data: &'a [u8],
+}
+
+impl<'a> WebpDecoder<'a> {
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { cursor: 0, data }
+    }
+
+    /// Reads the container and the bitstream's header, without decoding any pixels. This
+    /// works for every WebP file, including the ones `decode` does not support.
+    pub fn read_info(&mut self) -> Result<WebpInfo> {
+        Ok(self.read_container()?.info)
+    }
+
+    pub fn decode(&mut self) -> Result<Webp> {
+        let Container {
+            info,
+            bitstream,
+            alpha,
+        } = self.read_container()?;
+
+        if info.extended.is_some_and(|extended| extended.animation) {
+            bail!("Animated WebP images are unsupported.");
+        }
+
+        let image = match info.bitstream {
+            Bitstream::Lossless => LosslessDecoder::new(bitstream).decode()?,
+            Bitstream::Lossy => LossyDecoder::new(bitstream).decode(alpha)?,
+        };
+
+        Ok(Webp { info, image })
+    }
+
+    fn read_container(&mut self) -> Result<Container<'a>> {
+        ensure!(self.read_slice(4)? == b"RIFF", "Expected a RIFF header.");
+        let riff_size = self.read_u32()? as usize;
+        ensure!(self.read_slice(4)? == b"WEBP", "Expected a WEBP form type.");
+
+        // The size counts the form type and the chunks. Anything past them is ignored.
+        ensure!(riff_size >= 4, "RIFF size {riff_size} is too small.");
+        let end = (riff_size + 8).min(self.data.len());
+
+        let mut chunks = Vec::new();
+        while self.cursor + 8 <= end {
+            chunks.push(self.read_chunk()?);
+        }
+
+        let first = chunks
+            .first()
+            .ok_or_else(|| anyhow!("WebP file has no chunks."))?;
+
+        match &first.fourcc {
+            b"VP8 " => Ok(Container {
+                info: read_bitstream_info(Bitstream::Lossy, first.data, None)?,
+                bitstream: first.data,
+                alpha: None,
+            }),
+            b"VP8L" => Ok(Container {
+                info: read_bitstream_info(Bitstream::Lossless, first.data, None)?,
+                bitstream: first.data,
+                alpha: None,
+            }),
+            b"VP8X" => read_extended(ExtendedHeader::from_bytes(first.data)?, &chunks[1..]),
+            fourcc => bail!(
+                "Expected a VP8, VP8L or VP8X chunk, found {:?}",
+                String::from_utf8_lossy(fourcc)
+            ),
+        }
+    }
+
+    fn read_chunk(&mut self) -> Result<Chunk<'a>> {
+        let fourcc = self.read_fixed_array::<4, u8>(Self::read_u8)?;
+        let size = self.read_u32()? as usize;
+        let data = self.read_slice(size)?;
+
+        // Chunks are padded to an even size, though some writers omit the last padding byte.
+        if size % 2 == 1 && self.cursor < self.data.len() {
+            self.cursor += 1;
+        }
+
+        Ok(Chunk { fourcc, data })
+    }
+
+    impl_read_le_for_datatype!(read_u8, u8);
+    impl_read_le_for_datatype!(read_u32, u32);
+
+    impl_read_slice!();
+}
+
+/// Splits chunk data, such as a frame's, into chunks.
+fn split_chunks(mut data: &[u8]) -> Result<Vec<Chunk<'_>>> {
+    let mut chunks = Vec::new();
+
+    while data.len() >= 8 {
+        let fourcc = data[..4].try_into()?;
+        let size = u32::from_le_bytes(data[4..8].try_into()?) as usize;
+        let chunk = data
+            .get(8..8 + size)
+            .ok_or_else(|| anyhow!("WebP chunk is truncated."))?;
+
+        chunks.push(Chunk {
+            fourcc,
+            data: chunk,
+        });
+        data = data.get(8 + size + size % 2..).unwrap_or_default();
+    }
+
+    Ok(chunks)
+}
+
+/// The image chunks among `chunks`: the bitstream, and the `ALPH` chunk before a lossy one.
+fn find_bitstream<'a>(chunks: &[Chunk<'a>]) -> Result<BitstreamChunks<'a>> {
+    let mut alpha = None;
+
+    for chunk in chunks {
+        match &chunk.fourcc {
+            b"ALPH" => alpha = Some(chunk.data),
+            b"VP8 " => return Ok((Bitstream::Lossy, chunk.data, alpha)),
+            // Lossless bitstreams carry their own alpha.
+            b"VP8L" => return Ok((Bitstream::Lossless, chunk.data, None)),
+            _ => {}
+        }
+    }
+
+    bail!("WebP file has no image data.")
+}
+
+fn read_extended<'a>(extended: ExtendedHeader, chunks: &[Chunk<'a>]) -> Result<Container<'a>> {
+    let (bitstream, data, alpha) = if extended.animation {
+        // Describe the first frame, whose chunks follow its header.
+        let frame = chunks
+            .iter()
+            .find(|chunk| &chunk.fourcc == b"ANMF")
+            .ok_or_else(|| anyhow!("Animated WebP file has no frames."))?;
+        let frame_chunks = split_chunks(frame.data.get(FRAME_HEADER_SIZE..).unwrap_or_default())?;
+
+        find_bitstream(&frame_chunks)?
+    } else {
+        find_bitstream(chunks)?
+    };
+
+    let mut info = read_bitstream_info(bitstream, data, alpha)?;
+
+    if !extended.animation {
+        ensure!(
+            (info.width, info.height) == (extended.canvas_width, extended.canvas_height),
+            "WebP image is {}x{}, but its canvas is {}x{}.",
+            info.width,
+            info.height,
+            extended.canvas_width,
+            extended.canvas_height
+        );
+    }
+
+    // Malformed metadata should not prevent displaying the image.
+    for chunk in chunks {
+        match &chunk.fourcc {
+            b"ICCP" => info.icc_profile = IccDecoder::new(chunk.data).decode().ok(),
+            b"EXIF" => {
+                // Some writers keep the identifier of a JPEG APP1 segment.
+                let data = chunk.data.strip_prefix(b"Exif\0\0").unwrap_or(chunk.data);
+                info.exif = ExifDecoder::new(data).decode().ok();
+            }
+            _ => {}
+        }
+    }
+
+    info.width = extended.canvas_width;
+    info.height = extended.canvas_height;
+    info.has_alpha |= extended.alpha;
+    info.extended = Some(extended);
+
+    Ok(Container {
+        info,
+        bitstream: data,
+        alpha,
+    })
+}
+
+/// The dimensions and alpha of the image a bitstream holds, read from its header.
+fn read_bitstream_info(
+    bitstream: Bitstream,
+    data: &[u8],
+    alpha: Option<&[u8]>,
+) -> Result<WebpInfo> {
+    let (width, height, alpha_is_used) = match bitstream {
+        Bitstream::Lossless => {
+            let header = LosslessDecoder::new(data).read_header()?;
+            (header.width, header.height, header.alpha_is_used)
+        }
+        Bitstream::Lossy => {
+            let header = LossyDecoder::new(data).read_header()?;
+            (header.width, header.height, false)
+        }
+    };
+
+    let alpha_header = alpha
+        .and_then(|alpha| alpha.first())
+        .map(|&byte| AlphaHeader::try_from(byte))
+        .transpose()?;
+
+    Ok(WebpInfo {
+        bitstream,
+        width,
+        height,
+        has_alpha: alpha_is_used || alpha_header.is_some(),
+        extended: None,
+        alpha_header,
+        exif: None,
+        icc_profile: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        exif::grammar::Orientation,
+        image::grammar::{ColorType, ImageExt},
+        webp::grammar::{AlphaCompression, AlphaFiltering},
+    };
+
+    /// Decodes `path` and compares it against the `image` crate's decoding.
+    fn compare_webp(path: &str) -> Result<Webp> {
+        let reference = image::open(path)?.to_rgba8();
+        let webp = WebpDecoder::new(&std::fs::read(path)?).decode()?;
+
+        assert_eq!(webp.dimensions(), reference.dimensions(), "{path}");
+        assert_eq!(
+            webp.rgba8().as_ref(),
+            reference.as_raw().as_slice(),
+            "{path}"
+        );
+
+        Ok(webp)
+    }
+
+    #[test]
+    fn test_decode_lossless() -> Result<()> {
+        let rgb = compare_webp("./tests/webp/lossless_rgb.webp")?;
+        assert_eq!(rgb.color_type(), ColorType::RGB);
+        assert_eq!(rgb.info().bitstream, Bitstream::Lossless);
+        assert!(!rgb.info().has_alpha);
+
+        let rgba = compare_webp("./tests/webp/lossless_rgba.webp")?;
+        assert_eq!(rgba.color_type(), ColorType::RGBA);
+        assert!(rgba.info().has_alpha);
+
+        // Large enough for meta prefix codes and the color cache.
+        compare_webp("./tests/webp/lossless_large.webp")?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_color_indexing() -> Result<()> {
+        // These pack 8, 4, 2 and 1 palette indices per pixel.
+        for colors in [2, 4, 16, 200] {
+            compare_webp(&format!("./tests/webp/lossless_palette{colors}.webp"))?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_extended() -> Result<()> {
+        let path = "./tests/webp/extended_lossless.webp";
+        let webp = compare_webp(path)?;
+
+        let extended = webp.info().extended.expect("VP8X chunk");
+        assert!(extended.icc_profile && extended.exif && extended.alpha);
+        assert!(!extended.animation && !extended.xmp);
+        assert_eq!((extended.canvas_width, extended.canvas_height), (67, 43));
+
+        assert!(webp.icc_profile().is_some());
+        assert_eq!(
+            webp.exif().map(|exif| exif.orientation()),
+            Some(Orientation::Rotate90)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_lossy_info() -> Result<()> {
+        let rgb = WebpDecoder::new(&std::fs::read("./tests/webp/lossy_rgb.webp")?).read_info()?;
+        assert_eq!(rgb.bitstream, Bitstream::Lossy);
+        assert_eq!((rgb.width, rgb.height), (67, 43));
+        assert!(!rgb.has_alpha && rgb.extended.is_none());
+
+        let rgba = WebpDecoder::new(&std::fs::read("./tests/webp/lossy_rgba.webp")?).read_info()?;
+        assert_eq!(rgba.bitstream, Bitstream::Lossy);
+        assert_eq!((rgba.width, rgba.height), (67, 43));
+        assert!(rgba.has_alpha);
+
+        let alpha_header = rgba.alpha_header.expect("ALPH chunk");
+        assert_eq!(alpha_header.compression, AlphaCompression::Lossless);
+        assert_ne!(alpha_header.filtering, AlphaFiltering::Gradient);
+
+        Ok(())
+    }
+
+    /// Decodes `path` and compares it against libwebp's decoding of it, stored beside it as a
+    /// PNG. libwebp upsamples chroma with the same interpolation as this decoder, so the two
+    /// should match exactly.
+    fn compare_lossy_webp(path: &str) -> Result<Webp> {
+        let reference = image::open(path.replace(".webp", ".png"))?.to_rgba8();
+        let webp = WebpDecoder::new(&std::fs::read(path)?).decode()?;
+
+        assert_eq!(webp.dimensions(), reference.dimensions(), "{path}");
+        assert_eq!(
+            webp.rgba8().as_ref(),
+            reference.as_raw().as_slice(),
+            "{path}"
+        );
+
+        Ok(webp)
+    }
+
+    #[test]
+    fn test_decode_lossy() -> Result<()> {
+        let rgb = compare_lossy_webp("./tests/webp/lossy_rgb.webp")?;
+        assert_eq!(rgb.color_type(), ColorType::RGB);
+
+        let rgba = compare_lossy_webp("./tests/webp/lossy_rgba.webp")?;
+        assert_eq!(rgba.color_type(), ColorType::RGBA);
+
+        // None of these are a multiple of 16 pixels in either dimension.
+        for name in ["simple_filter", "normal_filter", "segments", "partitions"] {
+            compare_lossy_webp(&format!("./tests/webp/lossy_{name}.webp"))?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_decode_invalid() -> Result<()> {
+        let data = std::fs::read("./tests/webp/lossless_rgb.webp")?;
+
+        assert!(WebpDecoder::new(b"RIFF\x04\0\0\0WEBP").decode().is_err());
+        assert!(WebpDecoder::new(&data[..data.len() / 2]).decode().is_err());
+
+        // A corrupt bitstream.
+        let mut corrupt = data.clone();
+        corrupt[21..].fill(0xFF);
+        assert!(WebpDecoder::new(&corrupt).decode().is_err());
+
+        // A VP8L chunk whose signature is wrong.
+        let mut signature = data;
+        signature[20] = 0;
+        assert!(WebpDecoder::new(&signature).decode().is_err());
+
+        // A VP8 frame cut short.
+        let lossy = std::fs::read("./tests/webp/lossy_rgb.webp")?;
+        assert!(WebpDecoder::new(&lossy[..lossy.len() / 2])
+            .decode()
+            .is_err());
+
+        Ok(())
+    }
+}
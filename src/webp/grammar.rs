@@ -0,0 +1,164 @@
+use crate::{
+    exif::grammar::Exif,
+    icc::grammar::IccProfile,
+    image::{
+        grammar::{ColorType, ImageExt},
+        DynamicImageBuffer,
+    },
+};
+use anyhow::bail;
+use std::borrow::Cow;
+
+/// The kind of bitstream holding the image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bitstream {
+    /// A VP8 keyframe, in a `VP8 ` chunk.
+    Lossy,
+    /// A VP8L image, in a `VP8L` chunk.
+    Lossless,
+}
+
+/// The `VP8X` chunk of the extended format, which announces the chunks that follow.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtendedHeader {
+    pub icc_profile: bool,
+    pub alpha: bool,
+    pub exif: bool,
+    pub xmp: bool,
+    pub animation: bool,
+    pub canvas_width: u32,
+    pub canvas_height: u32,
+}
+
+impl ExtendedHeader {
+    pub(crate) fn from_bytes(data: &[u8]) -> anyhow::Result<Self> {
+        let &[flags, _, _, _, w0, w1, w2, h0, h1, h2, ..] = data else {
+            bail!("VP8X chunk is truncated.");
+        };
+
+        Ok(Self {
+            icc_profile: flags & 0b0010_0000 != 0,
+            alpha: flags & 0b0001_0000 != 0,
+            exif: flags & 0b0000_1000 != 0,
+            xmp: flags & 0b0000_0100 != 0,
+            animation: flags & 0b0000_0010 != 0,
+            // Both are stored minus one, in 24 bits.
+            canvas_width: u32::from_le_bytes([w0, w1, w2, 0]) + 1,
+            canvas_height: u32::from_le_bytes([h0, h1, h2, 0]) + 1,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaCompression {
+    None = 0,
+    /// A VP8L image stream whose green channel holds alpha.
+    Lossless = 1,
+}
+
+/// How alpha values are predicted from their neighbors before they are stored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AlphaFiltering {
+    None = 0,
+    Horizontal = 1,
+    Vertical = 2,
+    Gradient = 3,
+}
+
+/// The first byte of an `ALPH` chunk, describing the alpha data that follows it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AlphaHeader {
+    pub compression: AlphaCompression,
+    pub filtering: AlphaFiltering,
+    /// Whether the encoder reduced the number of alpha levels, a hint for dithering.
+    pub preprocessed: bool,
+}
+
+impl TryFrom<u8> for AlphaHeader {
+    type Error = anyhow::Error;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        let compression = match value & 0b11 {
+            0 => AlphaCompression::None,
+            1 => AlphaCompression::Lossless,
+            foreign => bail!("Invalid ALPH compression: {foreign}"),
+        };
+
+        let filtering = match (value >> 2) & 0b11 {
+            0 => AlphaFiltering::None,
+            1 => AlphaFiltering::Horizontal,
+            2 => AlphaFiltering::Vertical,
+            _ => AlphaFiltering::Gradient,
+        };
+
+        Ok(Self {
+            compression,
+            filtering,
+            preprocessed: (value >> 4) & 0b11 == 1,
+        })
+    }
+}
+
+/// What the RIFF container says about a WebP file, read without decoding its bitstream.
+#[derive(Debug, Clone)]
+pub struct WebpInfo {
+    pub bitstream: Bitstream,
+    pub width: u32,
+    pub height: u32,
+    /// Whether the bitstream or an `ALPH` chunk carries alpha.
+    pub has_alpha: bool,
+    pub extended: Option<ExtendedHeader>,
+    pub alpha_header: Option<AlphaHeader>,
+    pub exif: Option<Exif>,
+    pub icc_profile: Option<IccProfile>,
+}
+
+#[derive(Debug)]
+pub struct Webp {
+    pub(crate) info: WebpInfo,
+    pub(crate) image: DynamicImageBuffer,
+}
+
+impl Webp {
+    pub const fn info(&self) -> &WebpInfo {
+        &self.info
+    }
+
+    pub const fn image(&self) -> &DynamicImageBuffer {
+        &self.image
+    }
+}
+
+impl ImageExt for Webp {
+    fn width(&self) -> u32 {
+        self.image.width()
+    }
+
+    fn height(&self) -> u32 {
+        self.image.height()
+    }
+
+    fn color_type(&self) -> ColorType {
+        self.image.color_type()
+    }
+
+    fn rgb8(&self) -> Cow<'_, [u8]> {
+        self.image.rgb8()
+    }
+
+    fn rgba8(&self) -> Cow<'_, [u8]> {
+        self.image.rgba8()
+    }
+
+    fn bitmap(&self) -> Cow<'_, [u32]> {
+        self.image.bitmap()
+    }
+
+    fn exif(&self) -> Option<&Exif> {
+        self.info.exif.as_ref()
+    }
+
+    fn icc_profile(&self) -> Option<&IccProfile> {
+        self.info.icc_profile.as_ref()
+    }
+}
@@ -0,0 +1,100 @@
+//! The inverse transforms of VP8 (RFC 6386, Section 14), in the exact integer arithmetic the
+//! encoder predicts with.
+
+/// `x * sqrt(2) * cos(π / 8)`, as `x + x * 20091 / 65536`.
+const fn mul_cos(x: i32) -> i32 {
+    x + ((x * 20091) >> 16)
+}
+
+/// `x * sqrt(2) * sin(π / 8)`, as `x * 35468 / 65536`.
+const fn mul_sin(x: i32) -> i32 {
+    (x * 35468) >> 16
+}
+
+/// Computes the residual of a dequantized 4x4 block in raster order.
+pub fn idct_4x4(block: &[i32; 16]) -> [i32; 16] {
+    let mut intermediate = [0; 16];
+
+    for x in 0..4 {
+        let a = block[x] + block[8 + x];
+        let b = block[x] - block[8 + x];
+        let c = mul_sin(block[4 + x]) - mul_cos(block[12 + x]);
+        let d = mul_cos(block[4 + x]) + mul_sin(block[12 + x]);
+
+        intermediate[x] = a + d;
+        intermediate[4 + x] = b + c;
+        intermediate[8 + x] = b - c;
+        intermediate[12 + x] = a - d;
+    }
+
+    let mut residual = [0; 16];
+
+    for (row, output) in intermediate
+        .chunks_exact(4)
+        .zip(residual.chunks_exact_mut(4))
+    {
+        let a = row[0] + row[2];
+        let b = row[0] - row[2];
+        let c = mul_sin(row[1]) - mul_cos(row[3]);
+        let d = mul_cos(row[1]) + mul_sin(row[3]);
+
+        output[0] = (a + d + 4) >> 3;
+        output[1] = (b + c + 4) >> 3;
+        output[2] = (b - c + 4) >> 3;
+        output[3] = (a - d + 4) >> 3;
+    }
+
+    residual
+}
+
+/// Computes the DC coefficients of a macroblock's 16 luma blocks, in raster order, from its Y2
+/// block.
+pub fn iwht_4x4(block: &[i32; 16]) -> [i32; 16] {
+    let mut intermediate = [0; 16];
+
+    for x in 0..4 {
+        let a = block[x] + block[12 + x];
+        let b = block[4 + x] + block[8 + x];
+        let c = block[4 + x] - block[8 + x];
+        let d = block[x] - block[12 + x];
+
+        intermediate[x] = a + b;
+        intermediate[4 + x] = c + d;
+        intermediate[8 + x] = a - b;
+        intermediate[12 + x] = d - c;
+    }
+
+    let mut dc = [0; 16];
+
+    for (row, output) in intermediate.chunks_exact(4).zip(dc.chunks_exact_mut(4)) {
+        let a = row[0] + row[3];
+        let b = row[1] + row[2];
+        let c = row[1] - row[2];
+        let d = row[0] - row[3];
+
+        output[0] = (a + b + 3) >> 3;
+        output[1] = (c + d + 3) >> 3;
+        output[2] = (a - b + 3) >> 3;
+        output[3] = (d - c + 3) >> 3;
+    }
+
+    dc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dc_only_blocks() {
+        let mut block = [0; 16];
+        block[0] = 100;
+
+        // A lone DC coefficient spreads evenly. The two transforms round differently.
+        assert_eq!(idct_4x4(&block), [13; 16]);
+        assert_eq!(iwht_4x4(&block), [12; 16]);
+
+        block[0] = -100;
+        assert_eq!(idct_4x4(&block), [-12; 16]);
+    }
+}
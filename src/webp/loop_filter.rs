@@ -0,0 +1,238 @@
+//! The in-loop deblocking filter of VP8 (RFC 6386, Section 15), which smooths the edges between
+//! blocks where the difference across them is small enough to be a compression artifact.
+
+/// How strongly a macroblock's edges are filtered, derived from its filter level.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FilterStrength {
+    /// The largest difference across a subblock edge that is still filtered. Macroblock edges
+    /// allow 4 more.
+    pub edge_limit: i32,
+    /// The largest difference between neighboring samples on either side of an edge.
+    pub interior_limit: i32,
+    /// Edges with a larger difference next to them have high edge variance, and only the two
+    /// samples nearest the edge are adjusted.
+    pub hev_threshold: i32,
+}
+
+impl FilterStrength {
+    /// The strength of a filter `level` from 0 to 63, or `None` if the level turns the filter
+    /// off.
+    pub const fn new(level: i32, sharpness: i32) -> Option<Self> {
+        if level == 0 {
+            return None;
+        }
+
+        let mut interior_limit = level;
+
+        if sharpness > 0 {
+            interior_limit >>= if sharpness > 4 { 2 } else { 1 };
+
+            if interior_limit > 9 - sharpness {
+                interior_limit = 9 - sharpness;
+            }
+        }
+
+        if interior_limit < 1 {
+            interior_limit = 1;
+        }
+
+        let hev_threshold = if level >= 40 {
+            2
+        } else if level >= 15 {
+            1
+        } else {
+            0
+        };
+
+        Some(Self {
+            edge_limit: 2 * level + interior_limit,
+            interior_limit,
+            hev_threshold,
+        })
+    }
+}
+
+/// A plane of samples, with the position and step across the edge being filtered.
+struct Edge<'a> {
+    samples: &'a mut [u8],
+    /// The first sample past the edge, `q0`.
+    position: usize,
+    step: usize,
+}
+
+impl Edge<'_> {
+    /// The sample `offset` steps from `q0`, so -1 is `p0`.
+    fn get(&self, offset: isize) -> i32 {
+        self.samples[(self.position as isize + offset * self.step as isize) as usize] as i32
+    }
+
+    fn set(&mut self, offset: isize, value: i32) {
+        let index = (self.position as isize + offset * self.step as isize) as usize;
+        self.samples[index] = value.clamp(0, 255) as u8;
+    }
+
+    /// Whether the difference across the edge is small enough to filter.
+    fn is_filtered(&self, edge_limit: i32) -> bool {
+        let (p1, p0, q0, q1) = (self.get(-2), self.get(-1), self.get(0), self.get(1));
+        4 * (p0 - q0).abs() + (p1 - q1).abs() <= 2 * edge_limit + 1
+    }
+
+    /// Whether the edge is filtered by the normal filter, which also requires the samples on
+    /// either side to be smooth.
+    fn is_filtered_normally(&self, strength: FilterStrength, edge_limit: i32) -> bool {
+        self.is_filtered(edge_limit)
+            && (-4..3).all(|offset| {
+                offset == -1
+                    || (self.get(offset) - self.get(offset + 1)).abs() <= strength.interior_limit
+            })
+    }
+
+    fn has_high_edge_variance(&self, threshold: i32) -> bool {
+        (self.get(-2) - self.get(-1)).abs() > threshold
+            || (self.get(1) - self.get(0)).abs() > threshold
+    }
+
+    /// Adjusts `p0` and `q0` towards each other, taking `p1` and `q1` into account.
+    fn filter_common(&mut self) {
+        let (p1, p0, q0, q1) = (self.get(-2), self.get(-1), self.get(0), self.get(1));
+        let a = 3 * (q0 - p0) + clamp_signed(p1 - q1);
+
+        self.set(-1, p0 + (clamp_signed(a + 3) >> 3));
+        self.set(0, q0 - (clamp_signed(a + 4) >> 3));
+    }
+
+    /// Adjusts the two samples on either side of a subblock edge.
+    fn filter_subblock(&mut self) {
+        let (p1, p0, q0, q1) = (self.get(-2), self.get(-1), self.get(0), self.get(1));
+        let a = 3 * (q0 - p0);
+
+        let a1 = clamp_signed(a + 4) >> 3;
+        let a2 = clamp_signed(a + 3) >> 3;
+        let a3 = (a1 + 1) >> 1;
+
+        self.set(-2, p1 + a3);
+        self.set(-1, p0 + a2);
+        self.set(0, q0 - a1);
+        self.set(1, q1 - a3);
+    }
+
+    /// Adjusts the three samples on either side of a macroblock edge, tapering off with
+    /// distance from it.
+    fn filter_macroblock(&mut self) {
+        let (p2, p1, p0) = (self.get(-3), self.get(-2), self.get(-1));
+        let (q0, q1, q2) = (self.get(0), self.get(1), self.get(2));
+        let a = clamp_signed(3 * (q0 - p0) + clamp_signed(p1 - q1));
+
+        let a1 = (27 * a + 63) >> 7;
+        let a2 = (18 * a + 63) >> 7;
+        let a3 = (9 * a + 63) >> 7;
+
+        self.set(-3, p2 + a3);
+        self.set(-2, p1 + a2);
+        self.set(-1, p0 + a1);
+        self.set(0, q0 - a1);
+        self.set(1, q1 - a2);
+        self.set(2, q2 - a3);
+    }
+}
+
+/// Clamps to the range of an `i8`.
+const fn clamp_signed(value: i32) -> i32 {
+    if value < -128 {
+        -128
+    } else if value > 127 {
+        127
+    } else {
+        value
+    }
+}
+
+/// Which filter applies to a frame: the simple filter only adjusts luma, and only the two
+/// samples nearest each edge.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    Normal,
+    Simple,
+}
+
+/// A square block of samples within a plane, whose edges are filtered.
+pub struct Block<'a> {
+    pub samples: &'a mut [u8],
+    pub stride: usize,
+    /// The index of the block's top-left sample.
+    pub origin: usize,
+    pub size: usize,
+}
+
+impl Block<'_> {
+    /// Filters the edges of a macroblock's block in one plane, in the order the specification
+    /// prescribes: the left edge, the vertical subblock edges, the top edge, then the horizontal
+    /// subblock edges. The left and top edges are skipped at the frame's borders, and subblock
+    /// edges unless `inner`.
+    pub fn filter(
+        &mut self,
+        filter_type: FilterType,
+        strength: FilterStrength,
+        (has_left, has_above, inner): (bool, bool, bool),
+    ) {
+        // Subblock edges are 4 samples apart.
+        let inner_distances = (4..self.size).step_by(4);
+
+        for (across, along, has_edge) in [(1, self.stride, has_left), (self.stride, 1, has_above)] {
+            if has_edge {
+                self.filter_edge(filter_type, strength, (across, along, 0), true);
+            }
+
+            if inner {
+                for distance in inner_distances.clone() {
+                    self.filter_edge(filter_type, strength, (across, along, distance), false);
+                }
+            }
+        }
+    }
+
+    /// Filters the edge `distance` samples into the block, across which samples are `across`
+    /// apart, and along which they are `along` apart.
+    fn filter_edge(
+        &mut self,
+        filter_type: FilterType,
+        strength: FilterStrength,
+        (across, along, distance): (usize, usize, usize),
+        is_macroblock_edge: bool,
+    ) {
+        let edge_limit = if is_macroblock_edge {
+            strength.edge_limit + 4
+        } else {
+            strength.edge_limit
+        };
+
+        for i in 0..self.size {
+            let mut edge = Edge {
+                samples: &mut *self.samples,
+                position: self.origin + distance * across + i * along,
+                step: across,
+            };
+
+            match filter_type {
+                FilterType::Simple => {
+                    if edge.is_filtered(edge_limit) {
+                        edge.filter_common();
+                    }
+                }
+                FilterType::Normal => {
+                    if !edge.is_filtered_normally(strength, edge_limit) {
+                        continue;
+                    }
+
+                    if edge.has_high_edge_variance(strength.hev_threshold) {
+                        edge.filter_common();
+                    } else if is_macroblock_edge {
+                        edge.filter_macroblock();
+                    } else {
+                        edge.filter_subblock();
+                    }
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,397 @@
+//! The VP8L bitstream, as described in the WebP Lossless Bitstream Specification (RFC 9649,
+//! Section 3).
+
+use crate::{
+    image::{DynamicImageBuffer, ImageBuffer},
+    webp::{
+        bit_reader::BitReader,
+        prefix_code::PrefixCode,
+        transform::{add_pixels, Transform},
+    },
+};
+use anyhow::{bail, ensure, Result};
+
+const VP8L_SIGNATURE: u8 = 0x2F;
+
+/// Green codes past the 256 literals are 24 backward reference lengths, then color cache
+/// indices.
+const NUM_LENGTH_CODES: usize = 24;
+const NUM_DISTANCE_CODES: usize = 40;
+
+const MAX_COLOR_CACHE_BITS: u32 = 11;
+
+/// The `(x, y)` offsets of the first 120 distance codes, nearest neighbors first.
+#[rustfmt::skip]
+const DISTANCE_MAP: [(i8, i8); 120] = [
+    (0, 1),  (1, 0),  (1, 1),  (-1, 1), (0, 2),  (2, 0),  (1, 2),  (-1, 2),
+    (2, 1),  (-2, 1), (2, 2),  (-2, 2), (0, 3),  (3, 0),  (1, 3),  (-1, 3),
+    (3, 1),  (-3, 1), (2, 3),  (-2, 3), (3, 2),  (-3, 2), (0, 4),  (4, 0),
+    (1, 4),  (-1, 4), (4, 1),  (-4, 1), (3, 3),  (-3, 3), (2, 4),  (-2, 4),
+    (4, 2),  (-4, 2), (0, 5),  (3, 4),  (-3, 4), (4, 3),  (-4, 3), (5, 0),
+    (1, 5),  (-1, 5), (5, 1),  (-5, 1), (2, 5),  (-2, 5), (5, 2),  (-5, 2),
+    (4, 4),  (-4, 4), (3, 5),  (-3, 5), (5, 3),  (-5, 3), (0, 6),  (6, 0),
+    (1, 6),  (-1, 6), (6, 1),  (-6, 1), (2, 6),  (-2, 6), (6, 2),  (-6, 2),
+    (4, 5),  (-4, 5), (5, 4),  (-5, 4), (3, 6),  (-3, 6), (6, 3),  (-6, 3),
+    (0, 7),  (7, 0),  (1, 7),  (-1, 7), (5, 5),  (-5, 5), (7, 1),  (-7, 1),
+    (4, 6),  (-4, 6), (6, 4),  (-6, 4), (2, 7),  (-2, 7), (7, 2),  (-7, 2),
+    (3, 7),  (-3, 7), (7, 3),  (-7, 3), (5, 6),  (-5, 6), (6, 5),  (-6, 5),
+    (8, 0),  (4, 7),  (-4, 7), (7, 4),  (-7, 4), (8, 1),  (8, 2),  (6, 6),
+    (-6, 6), (8, 3),  (5, 7),  (-5, 7), (7, 5),  (-7, 5), (8, 4),  (6, 7),
+    (-6, 7), (7, 6),  (-7, 6), (8, 5),  (7, 7),  (-7, 7), (8, 6),  (8, 7),
+];
+
+/// The five-byte header that opens a VP8L bitstream.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LosslessHeader {
+    pub width: u32,
+    pub height: u32,
+    /// A hint that some pixel is not opaque.
+    pub alpha_is_used: bool,
+}
+
+/// The prefix codes for the green (with lengths and cache indices), red, blue, alpha and
+/// distance symbols of the pixels in one group.
+#[derive(Debug)]
+struct PrefixCodeGroup {
+    green: PrefixCode,
+    red: PrefixCode,
+    blue: PrefixCode,
+    alpha: PrefixCode,
+    distance: PrefixCode,
+}
+
+impl PrefixCodeGroup {
+    fn read(reader: &mut BitReader, color_cache_size: usize) -> Result<Self> {
+        Ok(Self {
+            green: PrefixCode::read(reader, 256 + NUM_LENGTH_CODES + color_cache_size)?,
+            red: PrefixCode::read(reader, 256)?,
+            blue: PrefixCode::read(reader, 256)?,
+            alpha: PrefixCode::read(reader, 256)?,
+            distance: PrefixCode::read(reader, NUM_DISTANCE_CODES)?,
+        })
+    }
+}
+
+/// Recently seen colors, indexed by a hash of their value.
+#[derive(Debug)]
+struct ColorCache {
+    bits: u32,
+    colors: Vec<u32>,
+}
+
+impl ColorCache {
+    fn new(bits: u32) -> Self {
+        Self {
+            bits,
+            colors: vec![0; 1 << bits],
+        }
+    }
+
+    const fn index(&self, color: u32) -> usize {
+        (0x1E35_A7BD_u32.wrapping_mul(color) >> (32 - self.bits)) as usize
+    }
+
+    fn insert(&mut self, color: u32) {
+        let index = self.index(color);
+        self.colors[index] = color;
+    }
+}
+
+pub struct LosslessDecoder<'a> {
+    reader: BitReader<'a>,
+}
+
+impl<'a> LosslessDecoder<'a> {
+    /// A decoder over the contents of a `VP8L` chunk, or of an `ALPH` chunk after its header
+    /// byte.
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self {
+            reader: BitReader::new(data),
+        }
+    }
+
+    pub fn read_header(&mut self) -> Result<LosslessHeader> {
+        let signature = self.reader.read_bits(8)? as u8;
+        ensure!(
+            signature == VP8L_SIGNATURE,
+            "Invalid VP8L signature: {:#04x}",
+            signature
+        );
+
+        let width = self.reader.read_bits(14)? + 1;
+        let height = self.reader.read_bits(14)? + 1;
+        let alpha_is_used = self.reader.read_bit()?;
+
+        let version = self.reader.read_bits(3)?;
+        ensure!(version == 0, "Unsupported VP8L version: {}", version);
+
+        Ok(LosslessHeader {
+            width,
+            height,
+            alpha_is_used,
+        })
+    }
+
+    /// Decodes a whole VP8L bitstream, as RGBA if its header says alpha is used and RGB
+    /// otherwise.
+    pub fn decode(&mut self) -> Result<DynamicImageBuffer> {
+        let LosslessHeader {
+            width,
+            height,
+            alpha_is_used,
+        } = self.read_header()?;
+
+        let pixels = self.decode_image_stream(width, height)?;
+
+        let image = if alpha_is_used {
+            DynamicImageBuffer::Rgba8(ImageBuffer::from_raw(
+                width,
+                height,
+                pixels
+                    .iter()
+                    .flat_map(|&argb| {
+                        let [a, r, g, b] = argb.to_be_bytes();
+                        [r, g, b, a]
+                    })
+                    .collect(),
+            )?)
+        } else {
+            DynamicImageBuffer::Rgb8(ImageBuffer::from_raw(
+                width,
+                height,
+                pixels
+                    .iter()
+                    .flat_map(|&argb| {
+                        let [_, r, g, b] = argb.to_be_bytes();
+                        [r, g, b]
+                    })
+                    .collect(),
+            )?)
+        };
+
+        Ok(image)
+    }
+
+    /// Decodes the image stream that follows the header, with its transforms undone. Alpha
+    /// planes are stored as such a stream without a header, with the dimensions of the image.
+    pub fn decode_image_stream(&mut self, width: u32, height: u32) -> Result<Vec<u32>> {
+        let height = height as usize;
+        let mut width = width as usize;
+
+        let mut transforms = Vec::new();
+        let mut seen = 0u8;
+
+        while self.reader.read_bit()? {
+            let kind = self.reader.read_bits(2)?;
+            ensure!(
+                seen & (1 << kind) == 0,
+                "VP8L transform {kind} is applied twice."
+            );
+            seen |= 1 << kind;
+
+            let transform = match kind {
+                0 | 1 => {
+                    let size_bits = self.reader.read_bits(3)? + 2;
+                    let elements = self.decode_entropy_image(
+                        width.div_ceil(1 << size_bits),
+                        height.div_ceil(1 << size_bits),
+                    )?;
+
+                    if kind == 0 {
+                        Transform::Predictor {
+                            size_bits,
+                            width,
+                            modes: elements,
+                        }
+                    } else {
+                        Transform::Color {
+                            size_bits,
+                            width,
+                            elements,
+                        }
+                    }
+                }
+                2 => Transform::SubtractGreen,
+                _ => {
+                    let palette_size = self.reader.read_bits(8)? as usize + 1;
+                    let mut palette = self.decode_entropy_image(palette_size, 1)?;
+
+                    // Each color is stored as its difference from the previous one.
+                    for index in 1..palette.len() {
+                        palette[index] = add_pixels(palette[index], palette[index - 1]);
+                    }
+
+                    // Small palettes pack several indices into each pixel.
+                    let width_bits = match palette_size {
+                        0..=2 => 3,
+                        3..=4 => 2,
+                        5..=16 => 1,
+                        _ => 0,
+                    };
+
+                    let transform = Transform::ColorIndexing {
+                        width_bits,
+                        width,
+                        palette,
+                    };
+                    width = width.div_ceil(1 << width_bits);
+
+                    transform
+                }
+            };
+
+            transforms.push(transform);
+        }
+
+        let pixels = self.decode_entropy_coded_image(width, height, true)?;
+
+        Ok(transforms.iter().rev().fold(pixels, |pixels, transform| {
+            transform.apply_inverse(pixels, height)
+        }))
+    }
+
+    /// Decodes one of the sub-images that transforms and meta prefix codes store their data
+    /// in. These have no transforms and a single group of prefix codes.
+    fn decode_entropy_image(&mut self, width: usize, height: usize) -> Result<Vec<u32>> {
+        self.decode_entropy_coded_image(width, height, false)
+    }
+
+    fn decode_entropy_coded_image(
+        &mut self,
+        width: usize,
+        height: usize,
+        is_main_image: bool,
+    ) -> Result<Vec<u32>> {
+        let mut color_cache = if self.reader.read_bit()? {
+            let bits = self.reader.read_bits(4)?;
+            ensure!(
+                (1..=MAX_COLOR_CACHE_BITS).contains(&bits),
+                "Invalid VP8L color cache size: {} bits",
+                bits
+            );
+
+            Some(ColorCache::new(bits))
+        } else {
+            None
+        };
+
+        let color_cache_size = color_cache
+            .as_ref()
+            .map_or(0, |color_cache| color_cache.colors.len());
+
+        // Each block of `1 << prefix_bits` pixels picks its group in the green and red channels
+        // of an entropy image.
+        let (prefix_bits, entropy_image) = if is_main_image && self.reader.read_bit()? {
+            let prefix_bits = self.reader.read_bits(3)? + 2;
+            let entropy_image = self.decode_entropy_image(
+                width.div_ceil(1 << prefix_bits),
+                height.div_ceil(1 << prefix_bits),
+            )?;
+
+            (
+                prefix_bits,
+                entropy_image
+                    .into_iter()
+                    .map(|pixel| ((pixel >> 8) & 0xFFFF) as usize)
+                    .collect(),
+            )
+        } else {
+            (0, Vec::new())
+        };
+
+        let num_groups = entropy_image.iter().max().map_or(1, |&group| group + 1);
+        let groups = (0..num_groups)
+            .map(|_| PrefixCodeGroup::read(&mut self.reader, color_cache_size))
+            .collect::<Result<Vec<_>>>()?;
+
+        let entropy_width = width.div_ceil(1 << prefix_bits);
+        let num_pixels = width * height;
+
+        let mut pixels = vec![0u32; num_pixels];
+        let mut index = 0;
+        let mut cached = 0;
+
+        while index < num_pixels {
+            let group = if entropy_image.is_empty() {
+                &groups[0]
+            } else {
+                let (x, y) = (index % width, index / width);
+                &groups[entropy_image[(y >> prefix_bits) * entropy_width + (x >> prefix_bits)]]
+            };
+
+            let green = group.green.decode(&mut self.reader)? as usize;
+
+            match green {
+                0..=255 => {
+                    let red = group.red.decode(&mut self.reader)? as u32;
+                    let blue = group.blue.decode(&mut self.reader)? as u32;
+                    let alpha = group.alpha.decode(&mut self.reader)? as u32;
+
+                    pixels[index] = alpha << 24 | red << 16 | (green as u32) << 8 | blue;
+                    index += 1;
+                }
+                256..=279 => {
+                    let length = self.read_lz77_value(green - 256)?;
+                    let distance_code = group.distance.decode(&mut self.reader)? as usize;
+                    let distance =
+                        plane_code_to_distance(width, self.read_lz77_value(distance_code)?);
+
+                    ensure!(
+                        distance <= index && length <= num_pixels - index,
+                        "VP8L backward reference is out of bounds."
+                    );
+
+                    // The copy may overlap what it writes, repeating a pattern.
+                    for offset in index..index + length {
+                        pixels[offset] = pixels[offset - distance];
+                    }
+                    index += length;
+                }
+                _ => {
+                    let Some(color_cache) = color_cache.as_ref() else {
+                        bail!("VP8L color cache index without a color cache.");
+                    };
+
+                    pixels[index] = color_cache.colors[green - 256 - NUM_LENGTH_CODES];
+                    index += 1;
+                }
+            }
+
+            // Every pixel enters the cache, whichever way it was coded.
+            if let Some(color_cache) = color_cache.as_mut() {
+                for &pixel in &pixels[cached..index] {
+                    color_cache.insert(pixel);
+                }
+                cached = index;
+            }
+        }
+
+        Ok(pixels)
+    }
+
+    /// Reads a backward reference length or distance: the prefix code picks a range, and extra
+    /// bits the value within it.
+    fn read_lz77_value(&mut self, prefix_code: usize) -> Result<usize> {
+        if prefix_code < 4 {
+            return Ok(prefix_code + 1);
+        }
+
+        let extra_bits = (prefix_code as u32 - 2) >> 1;
+        let offset = (2 + (prefix_code & 1)) << extra_bits;
+
+        Ok(offset + self.reader.read_bits(extra_bits)? as usize + 1)
+    }
+}
+
+/// Maps a distance code to a distance in pixels. The first 120 codes name nearby pixels in
+/// the two dimensions of the image; the rest count back linearly.
+fn plane_code_to_distance(width: usize, code: usize) -> usize {
+    if code > DISTANCE_MAP.len() {
+        return code - DISTANCE_MAP.len();
+    }
+
+    let (x, y) = DISTANCE_MAP[code - 1];
+    let distance = x as isize + y as isize * width as isize;
+
+    distance.max(1) as usize
+}
@@ -0,0 +1,894 @@
+//! The VP8 key frame bitstream, as described in RFC 6386.
+
+use crate::{
+    image::{DynamicImageBuffer, ImageBuffer},
+    webp::{
+        alpha::decode_alpha,
+        bool_decoder::BoolDecoder,
+        idct::{idct_4x4, iwht_4x4},
+        loop_filter::{Block, FilterStrength, FilterType},
+        prediction::{MacroblockMode, SubblockMode, Workspace},
+        tables::{
+            CoefficientProbabilities, AC_QUANTIZATION, CATEGORY_PROBABILITIES, COEFFICIENT_BANDS,
+            COEFFICIENT_UPDATE_PROBABILITIES, DC_QUANTIZATION, DEFAULT_COEFFICIENT_PROBABILITIES,
+            NUM_TOKEN_PROBABILITIES, SUBBLOCK_MODE_PROBABILITIES, SUBBLOCK_MODE_TREE, ZIGZAG,
+        },
+    },
+};
+use anyhow::{bail, ensure, Result};
+
+const START_CODE: [u8; 3] = [0x9D, 0x01, 0x2A];
+
+const NUM_SEGMENTS: usize = 4;
+
+/// The dimensions a key frame's header gives.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+    pub width: u32,
+    pub height: u32,
+    /// The size of the first partition, which holds the frame parameters and macroblock modes.
+    pub first_partition_size: usize,
+}
+
+/// The quantizer steps of a segment's blocks, as `[dc, ac]`.
+#[derive(Debug, Clone, Copy)]
+struct Quantizers {
+    y: [i32; 2],
+    y2: [i32; 2],
+    uv: [i32; 2],
+}
+
+impl Quantizers {
+    /// The steps for quantizer index `base`, adjusted by the frame's per-block deltas.
+    fn new(base: i32, [y_dc, y2_dc, y2_ac, uv_dc, uv_ac]: [i32; 5]) -> Self {
+        let dc = |delta: i32| DC_QUANTIZATION[(base + delta).clamp(0, 127) as usize] as i32;
+        let ac = |delta: i32| AC_QUANTIZATION[(base + delta).clamp(0, 127) as usize] as i32;
+
+        Self {
+            y: [dc(y_dc), ac(0)],
+            // x * 155 / 100, exactly, for every AC step.
+            y2: [dc(y2_dc) * 2, ((ac(y2_ac) * 101_581) >> 16).max(8)],
+            uv: [dc(uv_dc).min(132), ac(uv_ac)],
+        }
+    }
+}
+
+/// How the luma of a macroblock is predicted.
+#[derive(Debug, Clone, Copy)]
+enum LumaPrediction {
+    /// All 16x16 samples at once. The DC coefficients of its blocks are coded together in a
+    /// separate Y2 block.
+    Whole(MacroblockMode),
+    /// Each 4x4 subblock on its own, in raster order.
+    Subblocks([SubblockMode; 16]),
+}
+
+#[derive(Debug, Clone, Copy)]
+struct MacroblockHeader {
+    segment: usize,
+    /// Whether the macroblock has no coefficients coded.
+    skip: bool,
+    luma: LumaPrediction,
+    chroma: MacroblockMode,
+}
+
+/// Whether the neighboring blocks of each plane had non-zero coefficients, which is the context
+/// their first coefficient is coded in.
+#[derive(Debug, Clone, Copy, Default)]
+struct NonZeroContext {
+    y: [bool; 4],
+    u: [bool; 2],
+    v: [bool; 2],
+    y2: bool,
+}
+
+/// The kinds of block, which select their coefficient probabilities.
+#[derive(Debug, Clone, Copy)]
+enum Plane {
+    /// Luma blocks whose DC coefficient is coded in the Y2 block.
+    YAfterY2 = 0,
+    Y2 = 1,
+    Chroma = 2,
+    /// Luma blocks of subblock-predicted macroblocks.
+    YWithDC = 3,
+}
+
+/// What the first partition says about the whole frame.
+#[derive(Debug)]
+struct FrameParameters {
+    /// The probabilities of the segment tree, if macroblocks say which segment they are in.
+    segment_probabilities: Option<[u8; 3]>,
+    quantizers: [Quantizers; NUM_SEGMENTS],
+    /// The filter of each segment, for macroblocks predicted as a whole and by subblock.
+    filter_strengths: [[Option<FilterStrength>; 2]; NUM_SEGMENTS],
+    filter_type: FilterType,
+    num_partitions: usize,
+    coefficient_probabilities: Box<CoefficientProbabilities>,
+    skip_probability: Option<u8>,
+}
+
+/// A decoded frame in YUV 4:2:0, with planes covering whole macroblocks.
+#[derive(Debug)]
+pub struct Frame {
+    pub width: usize,
+    pub height: usize,
+    pub y: Vec<u8>,
+    pub u: Vec<u8>,
+    pub v: Vec<u8>,
+    pub y_stride: usize,
+    pub uv_stride: usize,
+}
+
+impl Frame {
+    /// Converts the frame to RGB, interpolating chroma between the nearest samples like the JPEG
+    /// decoder does.
+    pub fn rgb_pixels(&self) -> impl Iterator<Item = [u8; 3]> + '_ {
+        let chroma_width = self.width.div_ceil(2);
+        let chroma_height = self.height.div_ceil(2);
+
+        // Chroma samples sit between pairs of luma samples. The nearer one is weighed by 3/4.
+        let neighbors = |i: usize, length: usize| {
+            let near = i / 2;
+            let far = if i.is_multiple_of(2) {
+                near.saturating_sub(1)
+            } else {
+                (near + 1).min(length - 1)
+            };
+
+            (near, far)
+        };
+
+        (0..self.height).flat_map(move |y| {
+            let (near_y, far_y) = neighbors(y, chroma_height);
+
+            (0..self.width).map(move |x| {
+                let (near_x, far_x) = neighbors(x, chroma_width);
+
+                let upsample = |plane: &[u8]| {
+                    let sample = |x: usize, y: usize| plane[y * self.uv_stride + x] as u32;
+
+                    let value = 9 * sample(near_x, near_y)
+                        + 3 * sample(far_x, near_y)
+                        + 3 * sample(near_x, far_y)
+                        + sample(far_x, far_y);
+
+                    ((value + 8) >> 4) as u8
+                };
+
+                yuv_to_rgb(
+                    self.y[y * self.y_stride + x],
+                    upsample(&self.u),
+                    upsample(&self.v),
+                )
+            })
+        })
+    }
+}
+
+/// Converts a BT.601 limited range sample to RGB, in the 14-bit fixed point libwebp uses.
+fn yuv_to_rgb(y: u8, u: u8, v: u8) -> [u8; 3] {
+    let multiply = |sample: u8, coefficient: i32| (sample as i32 * coefficient) >> 8;
+    let clip = |value: i32| (value >> 6).clamp(0, 255) as u8;
+
+    let luma = multiply(y, 19077);
+
+    [
+        clip(luma + multiply(v, 26149) - 14234),
+        clip(luma - multiply(u, 6419) - multiply(v, 13320) + 8708),
+        clip(luma + multiply(u, 33050) - 17685),
+    ]
+}
+
+pub struct LossyDecoder<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> LossyDecoder<'a> {
+    /// A decoder over the contents of a `VP8 ` chunk.
+    pub const fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+
+    pub fn read_header(&self) -> Result<FrameHeader> {
+        let &[tag0, tag1, tag2, s0, s1, s2, w0, w1, h0, h1, ..] = self.data else {
+            bail!("VP8 frame header is truncated.");
+        };
+
+        let tag = u32::from_le_bytes([tag0, tag1, tag2, 0]);
+        ensure!(tag & 1 == 0, "Expected a VP8 key frame.");
+
+        let version = (tag >> 1) & 0b111;
+        ensure!(version <= 3, "Unsupported VP8 version: {}", version);
+
+        ensure!([s0, s1, s2] == START_CODE, "Invalid VP8 start code.");
+
+        // The top two bits of each dimension are an upscaling hint, which this decoder ignores.
+        let width = (u16::from_le_bytes([w0, w1]) & 0x3FFF) as u32;
+        let height = (u16::from_le_bytes([h0, h1]) & 0x3FFF) as u32;
+        ensure!(width > 0 && height > 0, "VP8 frame has no pixels.");
+
+        Ok(FrameHeader {
+            width,
+            height,
+            first_partition_size: (tag >> 5) as usize,
+        })
+    }
+
+    /// Decodes the frame to RGB, or to RGBA with the alpha plane of an `ALPH` chunk.
+    pub fn decode(&self, alpha: Option<&[u8]>) -> Result<DynamicImageBuffer> {
+        let frame = self.decode_frame()?;
+        let (width, height) = (frame.width as u32, frame.height as u32);
+
+        let image = match alpha {
+            Some(alpha) => DynamicImageBuffer::Rgba8(ImageBuffer::from_raw(
+                width,
+                height,
+                frame
+                    .rgb_pixels()
+                    .zip(decode_alpha(alpha, width, height)?)
+                    .flat_map(|([r, g, b], a)| [r, g, b, a])
+                    .collect(),
+            )?),
+            None => DynamicImageBuffer::Rgb8(ImageBuffer::from_raw(
+                width,
+                height,
+                frame.rgb_pixels().flatten().collect(),
+            )?),
+        };
+
+        Ok(image)
+    }
+
+    pub fn decode_frame(&self) -> Result<Frame> {
+        let FrameHeader {
+            width,
+            height,
+            first_partition_size,
+        } = self.read_header()?;
+
+        let partitions = &self.data[10..];
+        ensure!(
+            first_partition_size <= partitions.len(),
+            "VP8 first partition is truncated."
+        );
+        let (first_partition, partitions) = partitions.split_at(first_partition_size);
+
+        let mut header_decoder = BoolDecoder::new(first_partition);
+        let parameters = read_frame_parameters(&mut header_decoder)?;
+        let mut token_decoders = split_partitions(partitions, parameters.num_partitions)?;
+
+        let mb_width = width.div_ceil(16) as usize;
+        let mb_height = height.div_ceil(16) as usize;
+
+        let mut frame = Frame {
+            width: width as usize,
+            height: height as usize,
+            y: vec![0; mb_width * 16 * mb_height * 16],
+            u: vec![0; mb_width * 8 * mb_height * 8],
+            v: vec![0; mb_width * 8 * mb_height * 8],
+            y_stride: mb_width * 16,
+            uv_stride: mb_width * 8,
+        };
+
+        // The subblock modes and non-zero flags along the bottom of the macroblock row above.
+        let mut above_modes = vec![[SubblockMode::DC; 4]; mb_width];
+        let mut above_non_zero = vec![NonZeroContext::default(); mb_width];
+
+        // The filter of each macroblock, and whether its subblock edges are filtered.
+        let mut filters = Vec::with_capacity(mb_width * mb_height);
+
+        for mb_y in 0..mb_height {
+            let mut left_modes = [SubblockMode::DC; 4];
+            let mut left_non_zero = NonZeroContext::default();
+
+            let token_decoder = &mut token_decoders[mb_y % parameters.num_partitions];
+
+            for mb_x in 0..mb_width {
+                let macroblock = read_macroblock_header(
+                    &mut header_decoder,
+                    &parameters,
+                    &mut above_modes[mb_x],
+                    &mut left_modes,
+                );
+
+                let mut coefficients = [[0; 16]; 24];
+
+                if macroblock.skip {
+                    let (above, left) = (&mut above_non_zero[mb_x], &mut left_non_zero);
+                    let y2 = (above.y2, left.y2);
+
+                    *above = NonZeroContext::default();
+                    *left = NonZeroContext::default();
+
+                    // Skipped subblock-predicted macroblocks have no Y2 block to reset.
+                    if let LumaPrediction::Subblocks(_) = macroblock.luma {
+                        (above.y2, left.y2) = y2;
+                    }
+                } else {
+                    read_residuals(
+                        token_decoder,
+                        &parameters,
+                        &macroblock,
+                        (&mut above_non_zero[mb_x], &mut left_non_zero),
+                        &mut coefficients,
+                    );
+                }
+
+                reconstruct_macroblock(&mut frame, (mb_x, mb_y), &macroblock, &coefficients);
+
+                let is_subblock_predicted = matches!(macroblock.luma, LumaPrediction::Subblocks(_));
+                let has_coefficients = coefficients.iter().flatten().any(|&c| c != 0);
+
+                filters.push((
+                    parameters.filter_strengths[macroblock.segment][is_subblock_predicted as usize],
+                    is_subblock_predicted || has_coefficients,
+                ));
+
+                header_decoder.ensure_not_exhausted()?;
+                token_decoder.ensure_not_exhausted()?;
+            }
+        }
+
+        // Prediction reads unfiltered samples, so the frame is filtered once it is whole.
+        for (index, &(strength, inner)) in filters.iter().enumerate() {
+            let Some(strength) = strength else {
+                continue;
+            };
+
+            let (mb_x, mb_y) = (index % mb_width, index / mb_width);
+            let edges = (mb_x > 0, mb_y > 0, inner);
+
+            Block {
+                samples: &mut frame.y,
+                stride: frame.y_stride,
+                origin: mb_y * 16 * frame.y_stride + mb_x * 16,
+                size: 16,
+            }
+            .filter(parameters.filter_type, strength, edges);
+
+            if parameters.filter_type == FilterType::Normal {
+                for plane in [&mut frame.u, &mut frame.v] {
+                    Block {
+                        samples: plane,
+                        stride: frame.uv_stride,
+                        origin: mb_y * 8 * frame.uv_stride + mb_x * 8,
+                        size: 8,
+                    }
+                    .filter(parameters.filter_type, strength, edges);
+                }
+            }
+        }
+
+        Ok(frame)
+    }
+}
+
+/// Reads the frame parameters that follow the dimensions, at the start of the first partition
+/// (Section 9.2 to 9.11).
+fn read_frame_parameters(decoder: &mut BoolDecoder) -> Result<FrameParameters> {
+    // The color space and clamping type. Decoders always clamp.
+    decoder.read_literal(2);
+
+    let mut segment_probabilities = None;
+    let mut absolute_segment_values = true;
+    let mut segment_quantizers = [0; NUM_SEGMENTS];
+    let mut segment_filter_levels = [0; NUM_SEGMENTS];
+
+    let segmentation_enabled = decoder.read_flag();
+
+    if segmentation_enabled {
+        let update_map = decoder.read_flag();
+
+        if decoder.read_flag() {
+            absolute_segment_values = decoder.read_flag();
+
+            for quantizer in &mut segment_quantizers {
+                *quantizer = decoder.read_optional_signed_literal(7);
+            }
+
+            for level in &mut segment_filter_levels {
+                *level = decoder.read_optional_signed_literal(6);
+            }
+        }
+
+        if update_map {
+            segment_probabilities = Some(std::array::from_fn(|_| {
+                if decoder.read_flag() {
+                    decoder.read_literal(8) as u8
+                } else {
+                    255
+                }
+            }));
+        }
+    }
+
+    let is_simple_filter = decoder.read_flag();
+    let filter_level = decoder.read_literal(6) as i32;
+    let sharpness = decoder.read_literal(3) as i32;
+
+    // Adjustments to the filter level by reference frame and prediction mode. Key frames only
+    // use those of the current frame, and of subblock prediction.
+    let mut intra_frame_delta = 0;
+    let mut subblock_delta = 0;
+
+    let use_filter_deltas = decoder.read_flag();
+
+    if use_filter_deltas && decoder.read_flag() {
+        let mut reference_deltas = [0; 4];
+        let mut mode_deltas = [0; 4];
+
+        for delta in reference_deltas.iter_mut().chain(&mut mode_deltas) {
+            if decoder.read_flag() {
+                *delta = decoder.read_signed_literal(6);
+            }
+        }
+
+        intra_frame_delta = reference_deltas[0];
+        subblock_delta = mode_deltas[0];
+    }
+
+    let num_partitions = 1 << decoder.read_literal(2);
+
+    let base_quantizer = decoder.read_literal(7) as i32;
+    let quantizer_deltas = std::array::from_fn(|_| decoder.read_optional_signed_literal(4));
+
+    // Whether to keep the probabilities for the next frame, which does not apply to still images.
+    decoder.read_flag();
+
+    let mut coefficient_probabilities = Box::new(DEFAULT_COEFFICIENT_PROBABILITIES);
+
+    for (probabilities, update_probabilities) in coefficient_probabilities
+        .iter_mut()
+        .flatten()
+        .flatten()
+        .zip(COEFFICIENT_UPDATE_PROBABILITIES.iter().flatten().flatten())
+    {
+        for (probability, &update_probability) in probabilities.iter_mut().zip(update_probabilities)
+        {
+            if decoder.read_bool(update_probability) {
+                *probability = decoder.read_literal(8) as u8;
+            }
+        }
+    }
+
+    let skip_probability = decoder.read_flag().then(|| decoder.read_literal(8) as u8);
+
+    let segment_value = |segment: usize, base: i32, values: &[i32; NUM_SEGMENTS]| match (
+        segmentation_enabled,
+        absolute_segment_values,
+    ) {
+        (false, _) => base,
+        (true, true) => values[segment],
+        (true, false) => base + values[segment],
+    };
+
+    let quantizers = std::array::from_fn(|segment| {
+        Quantizers::new(
+            segment_value(segment, base_quantizer, &segment_quantizers),
+            quantizer_deltas,
+        )
+    });
+
+    let filter_strengths = std::array::from_fn(|segment| {
+        let level = segment_value(segment, filter_level, &segment_filter_levels);
+
+        [false, true].map(|is_subblock_predicted| {
+            // A frame level of 0 turns the filter off, whatever the segments say.
+            if filter_level == 0 {
+                return None;
+            }
+
+            let mut level = level;
+
+            if use_filter_deltas {
+                level += intra_frame_delta;
+
+                if is_subblock_predicted {
+                    level += subblock_delta;
+                }
+            }
+
+            FilterStrength::new(level.clamp(0, 63), sharpness)
+        })
+    });
+
+    Ok(FrameParameters {
+        segment_probabilities,
+        quantizers,
+        filter_strengths,
+        filter_type: if is_simple_filter {
+            FilterType::Simple
+        } else {
+            FilterType::Normal
+        },
+        num_partitions,
+        coefficient_probabilities,
+        skip_probability,
+    })
+}
+
+/// Splits the data after the first partition into the token partitions, which are preceded by
+/// the 3-byte sizes of all but the last.
+fn split_partitions(data: &[u8], num_partitions: usize) -> Result<Vec<BoolDecoder<'_>>> {
+    let sizes_length = 3 * (num_partitions - 1);
+    ensure!(
+        data.len() >= sizes_length,
+        "VP8 partition sizes are truncated."
+    );
+
+    let (sizes, mut data) = data.split_at(sizes_length);
+    let mut decoders = Vec::with_capacity(num_partitions);
+
+    for size in sizes.chunks_exact(3) {
+        let size = u32::from_le_bytes([size[0], size[1], size[2], 0]) as usize;
+        ensure!(size <= data.len(), "VP8 partition is truncated.");
+
+        let (partition, rest) = data.split_at(size);
+        decoders.push(BoolDecoder::new(partition));
+        data = rest;
+    }
+
+    decoders.push(BoolDecoder::new(data));
+
+    Ok(decoders)
+}
+
+/// Reads a macroblock's segment, skip flag and prediction modes from the first partition
+/// (Section 19.3). The subblock modes along its bottom and right edges become the context of
+/// the macroblocks below and to the right.
+fn read_macroblock_header(
+    decoder: &mut BoolDecoder,
+    parameters: &FrameParameters,
+    above_modes: &mut [SubblockMode; 4],
+    left_modes: &mut [SubblockMode; 4],
+) -> MacroblockHeader {
+    let segment = match parameters.segment_probabilities {
+        Some([p0, p1, p2]) => {
+            if decoder.read_bool(p0) {
+                2 + decoder.read_bool(p2) as usize
+            } else {
+                decoder.read_bool(p1) as usize
+            }
+        }
+        None => 0,
+    };
+
+    let skip = parameters
+        .skip_probability
+        .is_some_and(|probability| decoder.read_bool(probability));
+
+    let luma = if !decoder.read_bool(145) {
+        let mut modes = [SubblockMode::DC; 16];
+
+        for y in 0..4 {
+            for x in 0..4 {
+                let probabilities =
+                    &SUBBLOCK_MODE_PROBABILITIES[above_modes[x] as usize][left_modes[y] as usize];
+                let mode =
+                    SubblockMode::from_index(decoder.read_tree(&SUBBLOCK_MODE_TREE, probabilities));
+
+                modes[y * 4 + x] = mode;
+                above_modes[x] = mode;
+                left_modes[y] = mode;
+            }
+        }
+
+        LumaPrediction::Subblocks(modes)
+    } else {
+        let mode = if decoder.read_bool(156) {
+            if decoder.read_bool(128) {
+                MacroblockMode::TrueMotion
+            } else {
+                MacroblockMode::Horizontal
+            }
+        } else if decoder.read_bool(163) {
+            MacroblockMode::Vertical
+        } else {
+            MacroblockMode::DC
+        };
+
+        *above_modes = [mode.subblock_mode(); 4];
+        *left_modes = [mode.subblock_mode(); 4];
+
+        LumaPrediction::Whole(mode)
+    };
+
+    let chroma = if !decoder.read_bool(142) {
+        MacroblockMode::DC
+    } else if !decoder.read_bool(114) {
+        MacroblockMode::Vertical
+    } else if decoder.read_bool(183) {
+        MacroblockMode::TrueMotion
+    } else {
+        MacroblockMode::Horizontal
+    };
+
+    MacroblockHeader {
+        segment,
+        skip,
+        luma,
+        chroma,
+    }
+}
+
+/// Reads a macroblock's dequantized coefficients from its token partition (Section 13): 16 luma
+/// blocks, then 4 for each chroma plane. The DC coefficients of wholly predicted macroblocks come
+/// from their Y2 block.
+fn read_residuals(
+    decoder: &mut BoolDecoder,
+    parameters: &FrameParameters,
+    macroblock: &MacroblockHeader,
+    (above, left): (&mut NonZeroContext, &mut NonZeroContext),
+    coefficients: &mut [[i32; 16]; 24],
+) {
+    let probabilities = &parameters.coefficient_probabilities;
+    let quantizers = &parameters.quantizers[macroblock.segment];
+
+    let (luma_plane, first) = match macroblock.luma {
+        LumaPrediction::Whole(_) => {
+            let mut y2 = [0; 16];
+            let context = above.y2 as usize + left.y2 as usize;
+
+            let end = read_coefficients(
+                decoder,
+                &probabilities[Plane::Y2 as usize],
+                context,
+                0,
+                quantizers.y2,
+                &mut y2,
+            );
+            above.y2 = end > 0;
+            left.y2 = end > 0;
+
+            for (block, dc) in coefficients.iter_mut().zip(iwht_4x4(&y2)) {
+                block[0] = dc;
+            }
+
+            (Plane::YAfterY2, 1)
+        }
+        LumaPrediction::Subblocks(_) => (Plane::YWithDC, 0),
+    };
+
+    for y in 0..4 {
+        for x in 0..4 {
+            let context = above.y[x] as usize + left.y[y] as usize;
+            let end = read_coefficients(
+                decoder,
+                &probabilities[luma_plane as usize],
+                context,
+                first,
+                quantizers.y,
+                &mut coefficients[y * 4 + x],
+            );
+
+            above.y[x] = end > first;
+            left.y[y] = end > first;
+        }
+    }
+
+    for (offset, above, left) in [
+        (16, &mut above.u, &mut left.u),
+        (20, &mut above.v, &mut left.v),
+    ] {
+        for y in 0..2 {
+            for x in 0..2 {
+                let context = above[x] as usize + left[y] as usize;
+                let end = read_coefficients(
+                    decoder,
+                    &probabilities[Plane::Chroma as usize],
+                    context,
+                    0,
+                    quantizers.uv,
+                    &mut coefficients[offset + y * 2 + x],
+                );
+
+                above[x] = end > 0;
+                left[y] = end > 0;
+            }
+        }
+    }
+}
+
+/// Reads the tokens of one block from coefficient `first` on, storing the dequantized
+/// coefficients in raster order. Returns the index past the last coefficient read.
+fn read_coefficients(
+    decoder: &mut BoolDecoder,
+    probabilities: &[[[u8; NUM_TOKEN_PROBABILITIES]; 3]; 8],
+    context: usize,
+    first: usize,
+    [dc_step, ac_step]: [i32; 2],
+    block: &mut [i32; 16],
+) -> usize {
+    let mut n = first;
+    let mut p = &probabilities[COEFFICIENT_BANDS[n]][context];
+
+    while n < 16 {
+        // The end of block token. It cannot follow a zero, so the check is skipped after one.
+        if !decoder.read_bool(p[0]) {
+            return n;
+        }
+
+        while !decoder.read_bool(p[1]) {
+            n += 1;
+
+            if n == 16 {
+                return 16;
+            }
+
+            p = &probabilities[COEFFICIENT_BANDS[n]][0];
+        }
+
+        // The magnitude also picks the context of the next coefficient.
+        let (magnitude, context) = if !decoder.read_bool(p[2]) {
+            (1, 1)
+        } else {
+            (read_large_magnitude(decoder, p), 2)
+        };
+
+        let value = if decoder.read_flag() {
+            -magnitude
+        } else {
+            magnitude
+        };
+
+        block[ZIGZAG[n]] = value * if n == 0 { dc_step } else { ac_step };
+        n += 1;
+
+        if n < 16 {
+            p = &probabilities[COEFFICIENT_BANDS[n]][context];
+        }
+    }
+
+    16
+}
+
+/// Reads the magnitude of a token greater than one: the `DCT_2` to `DCT_4` literals, or a
+/// category whose extra bits give the offset from its base.
+fn read_large_magnitude(decoder: &mut BoolDecoder, p: &[u8; NUM_TOKEN_PROBABILITIES]) -> i32 {
+    if !decoder.read_bool(p[3]) {
+        if !decoder.read_bool(p[4]) {
+            2
+        } else {
+            3 + decoder.read_bool(p[5]) as i32
+        }
+    } else if !decoder.read_bool(p[6]) {
+        if !decoder.read_bool(p[7]) {
+            5 + decoder.read_bool(159) as i32
+        } else {
+            7 + 2 * decoder.read_bool(165) as i32 + decoder.read_bool(145) as i32
+        }
+    } else {
+        let high = decoder.read_bool(p[8]) as usize;
+        let low = decoder.read_bool(p[9 + high]) as usize;
+        let category = 2 * high + low;
+
+        let extra = CATEGORY_PROBABILITIES[category]
+            .iter()
+            .fold(0, |extra, &probability| {
+                (extra << 1) | decoder.read_bool(probability) as i32
+            });
+
+        // `DCT_CAT3` starts at 11, and each category after it covers twice as many values.
+        3 + (8 << category) + extra
+    }
+}
+
+/// Loads the edges prediction reads for a macroblock's block in one plane. Edges outside the
+/// frame are 127 above and 129 to the left.
+fn load_workspace(
+    plane: &[u8],
+    stride: usize,
+    (mb_x, mb_y): (usize, usize),
+    size: usize,
+) -> Workspace {
+    let mut workspace = Workspace::new();
+    let (x0, y0) = (mb_x * size, mb_y * size);
+    let is_last_column = x0 + size == stride;
+
+    if mb_y == 0 {
+        for x in -1..(size + 4) as isize {
+            workspace.set(x, -1, 127);
+        }
+    } else {
+        let row = &plane[(y0 - 1) * stride..y0 * stride];
+
+        workspace.set(-1, -1, if mb_x > 0 { row[x0 - 1] } else { 129 });
+
+        for x in 0..size + 4 {
+            // Past the right of the frame, the last sample above is repeated.
+            let sample = if x >= size && is_last_column {
+                row[x0 + size - 1]
+            } else {
+                row[x0 + x]
+            };
+
+            workspace.set(x as isize, -1, sample);
+        }
+    }
+
+    for y in 0..size {
+        let sample = if mb_x > 0 {
+            plane[(y0 + y) * stride + x0 - 1]
+        } else {
+            129
+        };
+
+        workspace.set(-1, y as isize, sample);
+    }
+
+    workspace
+}
+
+fn store_workspace(
+    workspace: &Workspace,
+    plane: &mut [u8],
+    stride: usize,
+    (mb_x, mb_y): (usize, usize),
+    size: usize,
+) {
+    for y in 0..size {
+        for x in 0..size {
+            plane[(mb_y * size + y) * stride + mb_x * size + x] =
+                workspace.get(x as isize, y as isize);
+        }
+    }
+}
+
+/// Predicts a macroblock and adds its residual.
+fn reconstruct_macroblock(
+    frame: &mut Frame,
+    position: (usize, usize),
+    macroblock: &MacroblockHeader,
+    coefficients: &[[i32; 16]; 24],
+) {
+    let (mb_x, mb_y) = position;
+    let add_residual = |workspace: &mut Workspace, x: usize, y: usize, block: &[i32; 16]| {
+        if block.iter().any(|&c| c != 0) {
+            workspace.add_residual(x, y, &idct_4x4(block));
+        }
+    };
+
+    let mut workspace = load_workspace(&frame.y, frame.y_stride, position, 16);
+
+    match macroblock.luma {
+        LumaPrediction::Whole(mode) => {
+            workspace.predict_macroblock(16, mode, mb_y > 0, mb_x > 0);
+
+            for (index, block) in coefficients[..16].iter().enumerate() {
+                add_residual(&mut workspace, index % 4 * 4, index / 4 * 4, block);
+            }
+        }
+        LumaPrediction::Subblocks(modes) => {
+            // Subblocks on the right edge take the samples past the macroblock's top-right, as
+            // those to their own top-right are not decoded yet.
+            for y in [3, 7, 11] {
+                for x in 16..20 {
+                    workspace.set(x, y, workspace.get(x, -1));
+                }
+            }
+
+            for (index, (&mode, block)) in modes.iter().zip(&coefficients[..16]).enumerate() {
+                let (x, y) = (index % 4 * 4, index / 4 * 4);
+
+                workspace.predict_subblock(x, y, mode);
+                add_residual(&mut workspace, x, y, block);
+            }
+        }
+    }
+
+    store_workspace(&workspace, &mut frame.y, frame.y_stride, position, 16);
+
+    for (plane, blocks) in [
+        (&mut frame.u, &coefficients[16..20]),
+        (&mut frame.v, &coefficients[20..24]),
+    ] {
+        let mut workspace = load_workspace(plane, frame.uv_stride, position, 8);
+        workspace.predict_macroblock(8, macroblock.chroma, mb_y > 0, mb_x > 0);
+
+        for (index, block) in blocks.iter().enumerate() {
+            add_residual(&mut workspace, index % 2 * 4, index / 2 * 4, block);
+        }
+
+        store_workspace(&workspace, plane, frame.uv_stride, position, 8);
+    }
+}
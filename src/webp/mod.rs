@@ -0,0 +1,16 @@
+mod alpha;
+mod bit_reader;
+mod bool_decoder;
+mod decoder;
+mod idct;
+mod loop_filter;
+mod lossless;
+mod lossy;
+mod prediction;
+mod prefix_code;
+mod tables;
+mod transform;
+
+pub mod grammar;
+
+pub use decoder::*;
@@ -0,0 +1,330 @@
+//! Intra prediction of VP8 key frames (RFC 6386, Section 12).
+
+/// How a whole macroblock, or the chroma of any macroblock, is predicted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MacroblockMode {
+    /// The average of the row above and the column to the left.
+    DC,
+    /// Copies the row above down.
+    Vertical,
+    /// Copies the column to the left across.
+    Horizontal,
+    /// `left + above - top_left`, the "TrueMotion" predictor.
+    TrueMotion,
+}
+
+impl MacroblockMode {
+    /// The subblock mode a macroblock predicted as a whole implies, which neighboring subblocks
+    /// take as context.
+    pub const fn subblock_mode(self) -> SubblockMode {
+        match self {
+            Self::DC => SubblockMode::DC,
+            Self::Vertical => SubblockMode::VE,
+            Self::Horizontal => SubblockMode::HE,
+            Self::TrueMotion => SubblockMode::TM,
+        }
+    }
+}
+
+/// How one of the 16 luma subblocks of a `B_PRED` macroblock is predicted, in the order of
+/// Section 11.2. The diagonal modes are named for the direction they extend edges in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SubblockMode {
+    DC = 0,
+    TM = 1,
+    VE = 2,
+    HE = 3,
+    LD = 4,
+    RD = 5,
+    VR = 6,
+    VL = 7,
+    HD = 8,
+    HU = 9,
+}
+
+impl SubblockMode {
+    pub const fn from_index(index: u8) -> Self {
+        match index {
+            0 => Self::DC,
+            1 => Self::TM,
+            2 => Self::VE,
+            3 => Self::HE,
+            4 => Self::LD,
+            5 => Self::RD,
+            6 => Self::VR,
+            7 => Self::VL,
+            8 => Self::HD,
+            _ => Self::HU,
+        }
+    }
+}
+
+/// Samples up to the right of a macroblock: the row above extends four past it.
+pub const STRIDE: usize = 1 + 16 + 4;
+
+/// A macroblock's samples in one plane, surrounded by what prediction reads: the row above
+/// (with the top-left corner and the four samples past the top-right) and the column to the
+/// left.
+#[derive(Debug, Clone)]
+pub struct Workspace {
+    samples: [u8; STRIDE * 17],
+}
+
+impl Workspace {
+    pub const fn new() -> Self {
+        Self {
+            samples: [0; STRIDE * 17],
+        }
+    }
+
+    /// The index of the sample at `(x, y)` relative to the macroblock, where -1 addresses the
+    /// row above or the column to the left.
+    const fn index(x: isize, y: isize) -> usize {
+        ((y + 1) as usize) * STRIDE + (x + 1) as usize
+    }
+
+    pub const fn get(&self, x: isize, y: isize) -> u8 {
+        self.samples[Self::index(x, y)]
+    }
+
+    pub const fn set(&mut self, x: isize, y: isize, value: u8) {
+        self.samples[Self::index(x, y)] = value;
+    }
+
+    /// Adds the residual of the 4x4 block whose top-left sample is at `(x, y)`.
+    pub fn add_residual(&mut self, x: usize, y: usize, residual: &[i32; 16]) {
+        for (dy, row) in residual.chunks_exact(4).enumerate() {
+            for (dx, &delta) in row.iter().enumerate() {
+                let (x, y) = ((x + dx) as isize, (y + dy) as isize);
+                let sample = self.get(x, y) as i32 + delta;
+                self.set(x, y, sample.clamp(0, 255) as u8);
+            }
+        }
+    }
+
+    /// Predicts the top-left `size` by `size` samples as a whole. DC prediction only averages
+    /// the edges that lie inside the frame.
+    pub fn predict_macroblock(
+        &mut self,
+        size: usize,
+        mode: MacroblockMode,
+        has_above: bool,
+        has_left: bool,
+    ) {
+        let size = size as isize;
+
+        match mode {
+            MacroblockMode::DC => {
+                let above = (0..size).map(|x| self.get(x, -1) as u32).sum::<u32>();
+                let left = (0..size).map(|y| self.get(-1, y) as u32).sum::<u32>();
+                let shift = size.trailing_zeros();
+
+                let dc = match (has_above, has_left) {
+                    (true, true) => (above + left + size as u32) >> (shift + 1),
+                    (true, false) => (above + (size as u32 >> 1)) >> shift,
+                    (false, true) => (left + (size as u32 >> 1)) >> shift,
+                    (false, false) => 128,
+                };
+
+                self.fill(size, |_, _, _| dc as u8);
+            }
+            MacroblockMode::Vertical => self.fill(size, |workspace, x, _| workspace.get(x, -1)),
+            MacroblockMode::Horizontal => self.fill(size, |workspace, _, y| workspace.get(-1, y)),
+            MacroblockMode::TrueMotion => self.fill(size, Self::true_motion),
+        }
+    }
+
+    fn fill(&mut self, size: isize, predict: impl Fn(&Self, isize, isize) -> u8) {
+        for y in 0..size {
+            for x in 0..size {
+                let value = predict(self, x, y);
+                self.set(x, y, value);
+            }
+        }
+    }
+
+    fn true_motion(&self, x: isize, y: isize) -> u8 {
+        let value = self.get(-1, y) as i32 + self.get(x, -1) as i32 - self.get(-1, -1) as i32;
+        value.clamp(0, 255) as u8
+    }
+
+    /// Predicts the 4x4 luma subblock whose top-left sample is at `(x, y)`, from the samples
+    /// reconstructed around it.
+    pub fn predict_subblock(&mut self, x: usize, y: usize, mode: SubblockMode) {
+        let (x0, y0) = (x as isize, y as isize);
+
+        // The edges, named as in the specification: `above[0]` is the top-left corner, followed
+        // by the four samples above and the four past the top-right. `left` runs downwards.
+        let above: [i32; 9] = std::array::from_fn(|i| self.get(x0 + i as isize - 1, y0 - 1) as i32);
+        let left: [i32; 4] = std::array::from_fn(|i| self.get(x0 - 1, y0 + i as isize) as i32);
+
+        let [p, a, b, c, d, e, f, g, h] = above;
+        let [i, j, k, l] = left;
+
+        let average2 = |x: i32, y: i32| ((x + y + 1) >> 1) as u8;
+        let average3 = |x: i32, y: i32, z: i32| ((x + 2 * y + z + 2) >> 2) as u8;
+
+        let mut block = [[0u8; 4]; 4];
+
+        match mode {
+            SubblockMode::DC => {
+                let dc = ((a + b + c + d + i + j + k + l + 4) >> 3) as u8;
+                block = [[dc; 4]; 4];
+            }
+            SubblockMode::TM => {
+                for (row, left) in block.iter_mut().zip(left) {
+                    for (value, above) in row.iter_mut().zip(&above[1..5]) {
+                        *value = (left + above - p).clamp(0, 255) as u8;
+                    }
+                }
+            }
+            SubblockMode::VE => {
+                block = [[
+                    average3(p, a, b),
+                    average3(a, b, c),
+                    average3(b, c, d),
+                    average3(c, d, e),
+                ]; 4];
+            }
+            SubblockMode::HE => {
+                block = [
+                    [average3(p, i, j); 4],
+                    [average3(i, j, k); 4],
+                    [average3(j, k, l); 4],
+                    [average3(k, l, l); 4],
+                ];
+            }
+            SubblockMode::LD => {
+                // Each antidiagonal is the smoothed edge above and past the top-right.
+                let edge = [a, b, c, d, e, f, g, h, h];
+
+                for (dy, row) in block.iter_mut().enumerate() {
+                    for (dx, value) in row.iter_mut().enumerate() {
+                        let n = dx + dy;
+                        *value = average3(edge[n], edge[n + 1], edge[n + 2]);
+                    }
+                }
+            }
+            SubblockMode::RD => {
+                // Each diagonal is the smoothed edge from the bottom-left, around the corner, to
+                // the top-right.
+                let edge = [l, k, j, i, p, a, b, c, d];
+
+                for (dy, row) in block.iter_mut().enumerate() {
+                    for (dx, value) in row.iter_mut().enumerate() {
+                        let n = 3 + dx - dy;
+                        *value = average3(edge[n], edge[n + 1], edge[n + 2]);
+                    }
+                }
+            }
+            SubblockMode::VR => {
+                block = [
+                    [
+                        average2(p, a),
+                        average2(a, b),
+                        average2(b, c),
+                        average2(c, d),
+                    ],
+                    [
+                        average3(i, p, a),
+                        average3(p, a, b),
+                        average3(a, b, c),
+                        average3(b, c, d),
+                    ],
+                    [
+                        average3(j, i, p),
+                        average2(p, a),
+                        average2(a, b),
+                        average2(b, c),
+                    ],
+                    [
+                        average3(k, j, i),
+                        average3(i, p, a),
+                        average3(p, a, b),
+                        average3(a, b, c),
+                    ],
+                ];
+            }
+            SubblockMode::VL => {
+                block = [
+                    [
+                        average2(a, b),
+                        average2(b, c),
+                        average2(c, d),
+                        average2(d, e),
+                    ],
+                    [
+                        average3(a, b, c),
+                        average3(b, c, d),
+                        average3(c, d, e),
+                        average3(d, e, f),
+                    ],
+                    [
+                        average2(b, c),
+                        average2(c, d),
+                        average2(d, e),
+                        average3(e, f, g),
+                    ],
+                    [
+                        average3(b, c, d),
+                        average3(c, d, e),
+                        average3(d, e, f),
+                        average3(f, g, h),
+                    ],
+                ];
+            }
+            SubblockMode::HD => {
+                block = [
+                    [
+                        average2(i, p),
+                        average3(i, p, a),
+                        average3(p, a, b),
+                        average3(a, b, c),
+                    ],
+                    [
+                        average2(j, i),
+                        average3(j, i, p),
+                        average2(i, p),
+                        average3(i, p, a),
+                    ],
+                    [
+                        average2(k, j),
+                        average3(k, j, i),
+                        average2(j, i),
+                        average3(j, i, p),
+                    ],
+                    [
+                        average2(l, k),
+                        average3(l, k, j),
+                        average2(k, j),
+                        average3(k, j, i),
+                    ],
+                ];
+            }
+            SubblockMode::HU => {
+                block = [
+                    [
+                        average2(i, j),
+                        average3(i, j, k),
+                        average2(j, k),
+                        average3(j, k, l),
+                    ],
+                    [
+                        average2(j, k),
+                        average3(j, k, l),
+                        average2(k, l),
+                        average3(k, l, l),
+                    ],
+                    [average2(k, l), average3(k, l, l), l as u8, l as u8],
+                    [l as u8; 4],
+                ];
+            }
+        }
+
+        for (dy, row) in block.iter().enumerate() {
+            for (dx, &value) in row.iter().enumerate() {
+                self.set(x0 + dx as isize, y0 + dy as isize, value);
+            }
+        }
+    }
+}
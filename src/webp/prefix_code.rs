@@ -0,0 +1,228 @@
+use crate::webp::bit_reader::BitReader;
+use anyhow::{bail, ensure, Result};
+
+/// Codes are at most 15 bits long.
+const MAX_CODE_LENGTH: usize = 15;
+
+/// Codes up to this long are decoded with a single table lookup.
+const TABLE_BITS: u32 = 8;
+
+/// A canonical prefix code, as in Deflate: shorter codes come first, and codes of the same
+/// length are ordered by symbol. Codes are packed most significant bit first.
+#[derive(Debug, Clone)]
+pub struct PrefixCode {
+    /// The number of codes of each length.
+    counts: [u16; MAX_CODE_LENGTH + 1],
+    /// Symbols in code order.
+    symbols: Vec<u16>,
+    /// The symbol and length of codes up to `TABLE_BITS` long, indexed by the next
+    /// `TABLE_BITS` bits of the stream. Entries of length 0 fall back to `decode_slow`.
+    table: Vec<(u16, u8)>,
+    /// A code with one symbol takes no bits to read.
+    single: Option<u16>,
+}
+
+impl PrefixCode {
+    /// Builds the code from the length of each symbol's code, 0 for absent symbols.
+    pub fn from_lengths(lengths: &[u8]) -> Result<Self> {
+        let mut counts = [0u16; MAX_CODE_LENGTH + 1];
+        for &length in lengths {
+            ensure!(
+                length as usize <= MAX_CODE_LENGTH,
+                "Invalid prefix code length: {}",
+                length
+            );
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+
+        let num_symbols = counts.iter().sum::<u16>();
+
+        if num_symbols == 1 {
+            let symbol = lengths.iter().position(|&length| length > 0).unwrap_or(0);
+
+            return Ok(Self {
+                counts,
+                symbols: vec![symbol as u16],
+                table: Vec::new(),
+                single: Some(symbol as u16),
+            });
+        }
+
+        // Every code must be used: the lengths must fill the code space exactly.
+        let mut open = 1i32;
+        for &count in &counts[1..] {
+            open = open * 2 - count as i32;
+            ensure!(open >= 0, "Prefix code is over-subscribed.");
+        }
+        ensure!(open == 0, "Prefix code is incomplete.");
+
+        let mut next_code = [0u16; MAX_CODE_LENGTH + 2];
+        for (length, &count) in counts.iter().enumerate().skip(1) {
+            next_code[length + 1] = (next_code[length] + count) << 1;
+        }
+
+        let mut symbols = Vec::with_capacity(num_symbols as usize);
+        let mut table = vec![(0, 0); 1 << TABLE_BITS];
+
+        for (length, next) in next_code
+            .iter_mut()
+            .enumerate()
+            .take(MAX_CODE_LENGTH + 1)
+            .skip(1)
+        {
+            for (symbol, _) in lengths
+                .iter()
+                .enumerate()
+                .filter(|&(_, &l)| l as usize == length)
+            {
+                symbols.push(symbol as u16);
+
+                let code = *next;
+                *next += 1;
+
+                if length as u32 <= TABLE_BITS {
+                    let reversed = code.reverse_bits() >> (16 - length);
+
+                    for index in (reversed as usize..table.len()).step_by(1 << length) {
+                        table[index] = (symbol as u16, length as u8);
+                    }
+                }
+            }
+        }
+
+        Ok(Self {
+            counts,
+            symbols,
+            table,
+            single: None,
+        })
+    }
+
+    /// Reads a code as described in the VP8L specification, Section 3.7.2.1: either up to two
+    /// symbols listed directly, or code lengths that are themselves prefix coded.
+    pub fn read(reader: &mut BitReader, alphabet_size: usize) -> Result<Self> {
+        let mut lengths = vec![0; alphabet_size];
+
+        if reader.read_bit()? {
+            let num_symbols = reader.read_bits(1)? + 1;
+            let first_symbol_bits = if reader.read_bit()? { 8 } else { 1 };
+
+            for bits in [first_symbol_bits, 8]
+                .into_iter()
+                .take(num_symbols as usize)
+            {
+                let symbol = reader.read_bits(bits)? as usize;
+                ensure!(
+                    symbol < alphabet_size,
+                    "Prefix code symbol {symbol} is out of range."
+                );
+
+                lengths[symbol] = 1;
+            }
+
+            return Self::from_lengths(&lengths);
+        }
+
+        const CODE_LENGTH_ORDER: [usize; 19] = [
+            17, 18, 0, 1, 2, 3, 4, 5, 16, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15,
+        ];
+
+        let mut code_length_lengths = [0; 19];
+        let num_code_lengths = reader.read_bits(4)? as usize + 4;
+
+        for &symbol in &CODE_LENGTH_ORDER[..num_code_lengths] {
+            code_length_lengths[symbol] = reader.read_bits(3)? as u8;
+        }
+
+        let code_length_code = Self::from_lengths(&code_length_lengths)?;
+
+        let mut max_symbol = if reader.read_bit()? {
+            let length_bits = 2 + 2 * reader.read_bits(3)?;
+            let max_symbol = 2 + reader.read_bits(length_bits)? as usize;
+            ensure!(
+                max_symbol <= alphabet_size,
+                "Prefix code max symbol {max_symbol} exceeds the alphabet."
+            );
+
+            max_symbol
+        } else {
+            alphabet_size
+        };
+
+        let mut symbol = 0;
+        let mut previous_length = 8;
+
+        while symbol < alphabet_size && max_symbol > 0 {
+            max_symbol -= 1;
+
+            match code_length_code.decode(reader)? {
+                length @ 0..=15 => {
+                    lengths[symbol] = length as u8;
+                    symbol += 1;
+
+                    if length != 0 {
+                        previous_length = length as u8;
+                    }
+                }
+                code => {
+                    // 16 repeats the previous nonzero length, 17 and 18 repeat zeros.
+                    let (extra_bits, offset, length) = match code {
+                        16 => (2, 3, previous_length),
+                        17 => (3, 3, 0),
+                        _ => (7, 11, 0),
+                    };
+
+                    let repeat = reader.read_bits(extra_bits)? as usize + offset;
+                    ensure!(
+                        symbol + repeat <= alphabet_size,
+                        "Prefix code lengths overflow the alphabet."
+                    );
+
+                    lengths[symbol..symbol + repeat].fill(length);
+                    symbol += repeat;
+                }
+            }
+        }
+
+        Self::from_lengths(&lengths)
+    }
+
+    pub fn decode(&self, reader: &mut BitReader) -> Result<u16> {
+        if let Some(symbol) = self.single {
+            return Ok(symbol);
+        }
+
+        let (symbol, length) = self.table[reader.peek(TABLE_BITS) as usize];
+
+        if length == 0 {
+            return self.decode_slow(reader);
+        }
+
+        reader.consume(length as u32)?;
+
+        Ok(symbol)
+    }
+
+    /// Decodes one bit at a time, for codes longer than the table covers.
+    fn decode_slow(&self, reader: &mut BitReader) -> Result<u16> {
+        let mut code = 0i32;
+        let mut first = 0i32;
+        let mut index = 0i32;
+
+        for &count in &self.counts[1..] {
+            code |= reader.read_bits(1)? as i32;
+            let count = count as i32;
+
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+
+        bail!("Invalid prefix code.")
+    }
+}
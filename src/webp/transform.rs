@@ -0,0 +1,218 @@
+//! The inverse of the transforms a VP8L encoder applies to decorrelate pixels (see the VP8L
+//! specification, Section 4). Pixels are ARGB packed in a `u32`.
+
+/// Transforms in the order they were read, with the width of the image they apply to.
+#[derive(Debug)]
+pub enum Transform {
+    /// Each block of `1 << size_bits` pixels is predicted from its neighbors by the mode in
+    /// the green channel of its `modes` entry.
+    Predictor {
+        size_bits: u32,
+        width: usize,
+        modes: Vec<u32>,
+    },
+    /// Red and blue are offset by multiples of green, and blue by a multiple of red.
+    Color {
+        size_bits: u32,
+        width: usize,
+        elements: Vec<u32>,
+    },
+    SubtractGreen,
+    /// Pixels are indices into a palette, several of them packed in each stored pixel when the
+    /// palette is small.
+    ColorIndexing {
+        width_bits: u32,
+        width: usize,
+        palette: Vec<u32>,
+    },
+}
+
+impl Transform {
+    /// Undoes the transform on `pixels`, returning the pixels it was computed from.
+    pub fn apply_inverse(&self, pixels: Vec<u32>, height: usize) -> Vec<u32> {
+        match self {
+            Self::Predictor {
+                size_bits,
+                width,
+                modes,
+            } => inverse_predictor(pixels, *width, *size_bits, modes),
+            Self::Color {
+                size_bits,
+                width,
+                elements,
+            } => inverse_color(pixels, *width, *size_bits, elements),
+            Self::SubtractGreen => pixels
+                .into_iter()
+                .map(|pixel| {
+                    let green = (pixel >> 8) & 0xFF;
+                    let red = ((pixel >> 16) + green) & 0xFF;
+                    let blue = (pixel + green) & 0xFF;
+
+                    (pixel & 0xFF00_FF00) | red << 16 | blue
+                })
+                .collect(),
+            Self::ColorIndexing {
+                width_bits,
+                width,
+                palette,
+            } => inverse_color_indexing(&pixels, *width, height, *width_bits, palette),
+        }
+    }
+}
+
+/// The alpha, red, green and blue channels of a pixel.
+const fn channels(pixel: u32) -> [i32; 4] {
+    [
+        (pixel >> 24) as i32,
+        ((pixel >> 16) & 0xFF) as i32,
+        ((pixel >> 8) & 0xFF) as i32,
+        (pixel & 0xFF) as i32,
+    ]
+}
+
+/// Packs channels back into a pixel, keeping the low 8 bits of each.
+const fn pack([a, r, g, b]: [i32; 4]) -> u32 {
+    ((a as u32 & 0xFF) << 24)
+        | ((r as u32 & 0xFF) << 16)
+        | ((g as u32 & 0xFF) << 8)
+        | (b as u32 & 0xFF)
+}
+
+fn average2(a: u32, b: u32) -> u32 {
+    let (a, b) = (channels(a), channels(b));
+    pack([0, 1, 2, 3].map(|c| (a[c] + b[c]) / 2))
+}
+
+/// Whichever of the left and top pixels is closer to the gradient estimate `L + T - TL`.
+fn select(left: u32, top: u32, top_left: u32) -> u32 {
+    let distance = |a: u32, b: u32| {
+        let (a, b) = (channels(a), channels(b));
+        (0..4).map(|c| (a[c] - b[c]).abs()).sum::<i32>()
+    };
+
+    // The distance of the estimate from left is that of top from top-left, and vice versa.
+    if distance(top, top_left) < distance(left, top_left) {
+        left
+    } else {
+        top
+    }
+}
+
+fn clamp_add_subtract_full(a: u32, b: u32, c: u32) -> u32 {
+    let (a, b, c) = (channels(a), channels(b), channels(c));
+    pack([0, 1, 2, 3].map(|i| (a[i] + b[i] - c[i]).clamp(0, 255)))
+}
+
+fn clamp_add_subtract_half(a: u32, b: u32) -> u32 {
+    let (a, b) = (channels(a), channels(b));
+    pack([0, 1, 2, 3].map(|i| (a[i] + (a[i] - b[i]) / 2).clamp(0, 255)))
+}
+
+/// Adds two pixels channel by channel, modulo 256.
+pub fn add_pixels(a: u32, b: u32) -> u32 {
+    let (a, b) = (channels(a), channels(b));
+    pack([0, 1, 2, 3].map(|i| a[i] + b[i]))
+}
+
+fn inverse_predictor(
+    mut pixels: Vec<u32>,
+    width: usize,
+    size_bits: u32,
+    modes: &[u32],
+) -> Vec<u32> {
+    let blocks_per_row = width.div_ceil(1 << size_bits);
+
+    for index in 0..pixels.len() {
+        let (x, y) = (index % width, index / width);
+
+        // The top row predicts from the left, and the left column from the top.
+        let prediction = match (x, y) {
+            (0, 0) => 0xFF00_0000,
+            (_, 0) => pixels[index - 1],
+            (0, _) => pixels[index - width],
+            _ => {
+                let block = (y >> size_bits) * blocks_per_row + (x >> size_bits);
+                let left = pixels[index - 1];
+                let top = pixels[index - width];
+                let top_left = pixels[index - width - 1];
+                // The rightmost pixel's top-right is the leftmost pixel of its own row.
+                let top_right = pixels[index - width + 1];
+
+                match (modes[block] >> 8) & 0xF {
+                    1 => left,
+                    2 => top,
+                    3 => top_right,
+                    4 => top_left,
+                    5 => average2(average2(left, top_right), top),
+                    6 => average2(left, top_left),
+                    7 => average2(left, top),
+                    8 => average2(top_left, top),
+                    9 => average2(top, top_right),
+                    10 => average2(average2(left, top_left), average2(top, top_right)),
+                    11 => select(left, top, top_left),
+                    12 => clamp_add_subtract_full(left, top, top_left),
+                    13 => clamp_add_subtract_half(average2(left, top), top_left),
+                    // 0, and 14 and 15, which decoders treat like it.
+                    _ => 0xFF00_0000,
+                }
+            }
+        };
+
+        pixels[index] = add_pixels(pixels[index], prediction);
+    }
+
+    pixels
+}
+
+/// `(t * c) >> 5`, with both treated as signed.
+const fn color_transform_delta(t: u32, c: u32) -> i32 {
+    ((t as u8 as i8 as i32) * (c as u8 as i8 as i32)) >> 5
+}
+
+fn inverse_color(mut pixels: Vec<u32>, width: usize, size_bits: u32, elements: &[u32]) -> Vec<u32> {
+    let blocks_per_row = width.div_ceil(1 << size_bits);
+
+    for (index, pixel) in pixels.iter_mut().enumerate() {
+        let (x, y) = (index % width, index / width);
+        let element = elements[(y >> size_bits) * blocks_per_row + (x >> size_bits)];
+
+        // Stored as ARGB (255, red_to_blue, green_to_blue, green_to_red).
+        let green_to_red = element & 0xFF;
+        let green_to_blue = (element >> 8) & 0xFF;
+        let red_to_blue = (element >> 16) & 0xFF;
+
+        let [alpha, red, green, blue] = channels(*pixel);
+        let red = (red + color_transform_delta(green_to_red, green as u32)) & 0xFF;
+        let blue = blue
+            + color_transform_delta(green_to_blue, green as u32)
+            + color_transform_delta(red_to_blue, red as u32);
+
+        *pixel = pack([alpha, red, green, blue]);
+    }
+
+    pixels
+}
+
+fn inverse_color_indexing(
+    packed: &[u32],
+    width: usize,
+    height: usize,
+    width_bits: u32,
+    palette: &[u32],
+) -> Vec<u32> {
+    let packed_width = width.div_ceil(1 << width_bits);
+    let bits_per_index = 8 >> width_bits;
+    let mask = (1 << bits_per_index) - 1;
+
+    (0..height)
+        .flat_map(|y| (0..width).map(move |x| (x, y)))
+        .map(|(x, y)| {
+            let green = (packed[y * packed_width + (x >> width_bits)] >> 8) & 0xFF;
+            let shift = (x & ((1 << width_bits) - 1)) as u32 * bits_per_index;
+            let index = (green >> shift) & mask;
+
+            // Indices past the palette are transparent black.
+            palette.get(index as usize).copied().unwrap_or(0)
+        })
+        .collect()
+}